@@ -1,7 +1,7 @@
 use criterion::{criterion_group, criterion_main, Criterion, Throughput};
 use shuttle::scheduler::{PctScheduler, RandomScheduler, Scheduler};
 use shuttle::sync::atomic::{AtomicUsize, Ordering};
-use shuttle::{asynch, thread, Runner};
+use shuttle::{future, thread, Runner};
 use std::sync::Arc;
 
 const NUM_TASKS: usize = 10;
@@ -9,20 +9,20 @@ const ITERATIONS: usize = 100;
 
 /// A toy benchmark that runs a bunch of tasks that just increment a counter
 fn counter_async(scheduler: impl Scheduler + 'static) {
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(|| {
         let counter = Arc::new(AtomicUsize::new(0usize));
 
         let tasks: Vec<_> = (0..NUM_TASKS)
             .map(|_| {
                 let counter = Arc::clone(&counter);
-                asynch::spawn(async move {
+                future::spawn(async move {
                     counter.fetch_add(1, Ordering::SeqCst);
                 })
             })
             .collect();
 
-        asynch::block_on(async move {
+        future::block_on(async move {
             for t in tasks {
                 t.await.unwrap();
             }
@@ -32,7 +32,7 @@ fn counter_async(scheduler: impl Scheduler + 'static) {
 
 /// A toy benchmark that runs a bunch of threads that just increment a counter
 fn counter_sync(scheduler: impl Scheduler + 'static) {
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(|| {
         let counter = Arc::new(AtomicUsize::new(0usize));
 
@@ -0,0 +1,137 @@
+//! Shuttle's implementation of [`std::cell::UnsafeCell`], which detects data races.
+
+use crate::runtime::execution::ExecutionState;
+use crate::runtime::task::clock::VectorClock;
+use crate::runtime::task::TaskId;
+use std::cell::RefCell;
+use tracing::trace;
+
+/// A version of [`std::cell::UnsafeCell`] that checks that accesses to its contents don't race.
+///
+/// Unlike the standard library's `UnsafeCell`, the contents can only be accessed inside a closure
+/// passed to [`UnsafeCell::with`] (for reads) or [`UnsafeCell::with_mut`] (for writes), the same
+/// API as [Loom]'s `UnsafeCell`. Shuttle records the vector clock (see
+/// [`current::clock`](crate::current::clock)) of the task making each access, and checks it
+/// against earlier conflicting accesses from other tasks: a read conflicts with the last write, and
+/// a write conflicts with the last write and every read since then. If a conflicting access didn't
+/// happen before the new one, the two accesses are unsynchronized, and the test fails with a data
+/// race that names both tasks.
+///
+/// This catches races that modeling shared state with locks alone would miss, such as a missing
+/// lock around a write. Only the accesses in the interleavings Shuttle explores are checked, and
+/// raw pointers that escape the access closures aren't tracked.
+///
+/// [Loom]: https://github.com/tokio-rs/loom
+#[derive(Debug)]
+pub struct UnsafeCell<T: ?Sized> {
+    accesses: RefCell<Accesses>,
+    data: std::cell::UnsafeCell<T>,
+}
+
+#[derive(Debug, Default)]
+struct Accesses {
+    last_write: Option<Access>,
+    // The reads since the last write
+    reads: Vec<Access>,
+}
+
+#[derive(Debug)]
+struct Access {
+    task: TaskId,
+    clock: VectorClock,
+}
+
+impl<T> UnsafeCell<T> {
+    /// Constructs a new instance of `UnsafeCell` which will wrap the specified value.
+    pub fn new(data: T) -> Self {
+        Self {
+            accesses: RefCell::new(Accesses::default()),
+            data: std::cell::UnsafeCell::new(data),
+        }
+    }
+
+    /// Unwraps the value, consuming the cell.
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> UnsafeCell<T> {
+    /// Get an immutable pointer to the wrapped value, checking that this read doesn't race with an
+    /// earlier write.
+    pub fn with<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(*const T) -> R,
+    {
+        let access = Access::current();
+        let mut accesses = self.accesses.borrow_mut();
+        trace!(task = ?access.task, "read of UnsafeCell {:p}", self);
+        if let Some(write) = &accesses.last_write {
+            write.check(&access, "read", "write");
+        }
+        accesses.reads.push(access);
+        drop(accesses);
+        f(self.data.get())
+    }
+
+    /// Get a mutable pointer to the wrapped value, checking that this write doesn't race with an
+    /// earlier read or write.
+    pub fn with_mut<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(*mut T) -> R,
+    {
+        let access = Access::current();
+        let mut accesses = self.accesses.borrow_mut();
+        trace!(task = ?access.task, "write of UnsafeCell {:p}", self);
+        if let Some(write) = &accesses.last_write {
+            write.check(&access, "write", "write");
+        }
+        for read in &accesses.reads {
+            read.check(&access, "write", "read");
+        }
+        accesses.reads.clear();
+        accesses.last_write = Some(access);
+        drop(accesses);
+        f(self.data.get())
+    }
+
+    /// Returns a mutable reference to the underlying data. No checks are needed, as the mutable
+    /// borrow statically guarantees no other task is accessing the cell.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl Access {
+    fn current() -> Self {
+        ExecutionState::with(|s| {
+            let clock = s.increment_clock().clone();
+            Self {
+                task: s.current().id(),
+                clock,
+            }
+        })
+    }
+
+    /// Fail the test if `later` isn't ordered after this access by happens-before
+    fn check(&self, later: &Access, later_kind: &str, kind: &str) {
+        if self.clock > later.clock || self.clock.partial_cmp(&later.clock).is_none() {
+            panic!(
+                "data race on UnsafeCell: {} by task {:?} races with an earlier {} by task {:?}",
+                later_kind, later.task, kind, self.task
+            );
+        }
+    }
+}
+
+impl<T: Default> Default for UnsafeCell<T> {
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl<T> From<T> for UnsafeCell<T> {
+    fn from(data: T) -> Self {
+        Self::new(data)
+    }
+}
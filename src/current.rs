@@ -20,6 +20,20 @@ pub fn context_switches() -> usize {
     ExecutionState::context_switches()
 }
 
+/// Label the code the current thread runs from now on for coverage tracking, or remove the label if
+/// `label` is `None`.
+///
+/// If [`Config::record_coverage`](crate::Config::record_coverage) is enabled, every label that was
+/// set on a runnable thread when the scheduler had a choice to make is reported in
+/// [`RunStats::coverage`](crate::RunStats::coverage). Labels should be stable across versions of
+/// the code under test (e.g., naming the critical section they mark), so that the coverage of
+/// different runs can be compared.
+///
+/// Panics if called outside of a Shuttle execution.
+pub fn set_coverage_label(label: Option<&'static str>) {
+    ExecutionState::with(|state| state.current_mut().coverage_label = label);
+}
+
 /// Get the current thread's vector clock
 pub fn clock() -> VectorClock {
     crate::runtime::execution::ExecutionState::with(|state| {
@@ -1,10 +1,22 @@
 //! Shuttle's implementation of an async executor, roughly equivalent to [`futures::executor`].
 //!
 //! The [spawn] method spawns a new asynchronous task that the executor will run to completion. The
-//! [block_on] method blocks the current thread on the completion of a future.
+//! [block_on] method blocks the current thread on the completion of a future. The [Mutex] type is
+//! an async mutex for sharing data between async tasks, and the [select!] macro waits on several
+//! futures at once.
 //!
 //! [`futures::executor`]: https://docs.rs/futures/0.3.13/futures/executor/index.html
 
+mod mutex;
+mod select;
+
+pub use mutex::{Mutex, MutexGuard};
+
+#[doc(inline)]
+pub use crate::__select as select;
+#[doc(hidden)]
+pub use select::{__poll_fn, __select_poll};
+
 use crate::runtime::execution::ExecutionState;
 use crate::runtime::task::TaskId;
 use crate::runtime::thread;
@@ -14,6 +26,13 @@ use std::result::Result;
 use std::task::{Context, Poll};
 
 /// Spawn a new async task that the executor will run to completion.
+///
+/// Spawning a task is a yield point, and so is every `.await` inside it that returns `Pending`, so
+/// the scheduler explores interleavings of the new task with every other task and thread. The
+/// returned [`JoinHandle`] can be awaited to get the task's result. Dropping the `JoinHandle`
+/// detaches the task rather than cancelling it: the task keeps running, but the execution won't
+/// wait for it to finish, so it can be abandoned partway through once every attached task and
+/// thread has finished.
 pub fn spawn<T, F>(fut: F) -> JoinHandle<T>
 where
     F: Future<Output = T> + Send + 'static,
@@ -29,6 +48,8 @@ where
 }
 
 /// An owned permission to join on an async task (await its termination).
+///
+/// Dropping the handle detaches the task; see [`spawn`] for what that means.
 #[derive(Debug)]
 pub struct JoinHandle<T> {
     task_id: TaskId,
@@ -55,6 +76,7 @@ impl<T> Drop for JoinHandle<T> {
     fn drop(&mut self) {
         ExecutionState::try_with(|state| {
             if !state.is_finished() {
+                state.record_unknown_access();
                 let task = state.get_mut(self.task_id);
                 task.detach();
             }
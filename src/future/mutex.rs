@@ -0,0 +1,248 @@
+use crate::runtime::execution::ExecutionState;
+use crate::runtime::task::clock::VectorClock;
+use crate::runtime::task::{LockId, TaskId};
+use crate::runtime::thread;
+use crate::scheduler::ObjectId;
+use std::cell::{RefCell, UnsafeCell};
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+use tracing::trace;
+
+/// An async mutex, roughly equivalent to [`futures::lock::Mutex`].
+///
+/// Unlike [`sync::Mutex`](crate::sync::Mutex), a task waiting to acquire this mutex doesn't block
+/// its thread. Instead, [`Mutex::lock`] returns a future that stays pending until the lock is
+/// available, so the async executor can run other tasks in the meantime. Acquiring and releasing
+/// the lock are both yield points, so Shuttle explores the different orders in which contending
+/// tasks acquire it.
+///
+/// [`futures::lock::Mutex`]: https://docs.rs/futures/0.3.13/futures/lock/struct.Mutex.html
+pub struct Mutex<T: ?Sized> {
+    state: Rc<RefCell<MutexState>>,
+    data: UnsafeCell<T>,
+}
+
+/// A guard that releases the lock on an async [`Mutex`] when dropped.
+pub struct MutexGuard<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+}
+
+#[derive(Debug)]
+struct MutexState {
+    // Assigned lazily if the mutex was created outside of an execution
+    id: Option<LockId>,
+    holder: Option<TaskId>,
+    // The tasks waiting for the lock, and the wakers to call when it's released
+    waiters: Vec<(TaskId, Waker)>,
+    clock: VectorClock,
+}
+
+impl MutexState {
+    fn id(&mut self) -> LockId {
+        *self
+            .id
+            .get_or_insert_with(|| ExecutionState::with(|s| s.new_lock_id(None)))
+    }
+}
+
+impl<T> Mutex<T> {
+    /// Creates a new async mutex in an unlocked state ready for use.
+    pub fn new(value: T) -> Self {
+        let state = MutexState {
+            id: ExecutionState::try_with(|s| s.new_lock_id(None)),
+            holder: None,
+            waiters: Vec::new(),
+            clock: VectorClock::new(),
+        };
+
+        Self {
+            state: Rc::new(RefCell::new(state)),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Consumes this mutex, returning the underlying data.
+    pub fn into_inner(self) -> T {
+        let state = self.state.borrow();
+        assert!(state.holder.is_none());
+        // Update the receiver's clock with the Mutex clock
+        ExecutionState::with(|s| s.update_clock(&state.clock));
+        drop(state);
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    /// Acquires the mutex, returning a future that resolves to a guard once the lock is available.
+    pub async fn lock(&self) -> MutexGuard<'_, T> {
+        let id = self.state.borrow_mut().id();
+        // Acquiring a lock is a yield point
+        thread::switch_on(ObjectId::lock(id));
+        Acquire { mutex: self, id }.await
+    }
+
+    /// Attempts to acquire the mutex without waiting. Returns `None` if the lock is held by another
+    /// task.
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        let id = self.state.borrow_mut().id();
+        // Acquiring a lock is a yield point, even if we fail to acquire it
+        thread::switch_on(ObjectId::lock(id));
+        if self.acquire(id) {
+            Some(MutexGuard { mutex: self })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the underlying data. No locking is needed, as the mutable
+    /// borrow statically guarantees no other task holds the lock.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+
+    /// Take the lock for the current task if nobody holds it, and return whether we succeeded.
+    fn acquire(&self, id: LockId) -> bool {
+        let me = ExecutionState::me();
+        let mut state = self.state.borrow_mut();
+        if state.holder.is_some() {
+            return false;
+        }
+
+        trace!(waiters=?state.waiters.iter().map(|(tid, _)| *tid).collect::<Vec<_>>(), "acquired async mutex {:p}", self.state);
+        state.holder = Some(me);
+        state.waiters.retain(|(tid, _)| *tid != me);
+        // Update acquiring task's clock with the clock stored in the Mutex
+        ExecutionState::with(|s| {
+            s.update_clock(&state.clock);
+            s.current_mut().acquire_lock(id);
+        });
+        true
+    }
+
+    fn release(&self) {
+        let mut state = self.state.borrow_mut();
+
+        trace!(waiters=?state.waiters.iter().map(|(tid, _)| *tid).collect::<Vec<_>>(), "releasing async mutex {:p}", self.state);
+
+        state.holder = None;
+
+        let id = state.id();
+        ExecutionState::release_lock(id);
+
+        if ExecutionState::should_stop() {
+            return;
+        }
+
+        // Update the Mutex clock with the owning task's clock
+        ExecutionState::with(|s| {
+            let clock = s.increment_clock();
+            state.clock.update(clock);
+        });
+
+        // Wake every task waiting on this lock. The scheduler will choose one of them to win the
+        // race to this lock, and the others will go back to waiting.
+        let waiters = std::mem::take(&mut state.waiters);
+        drop(state);
+        for (_, waker) in waiters {
+            waker.wake();
+        }
+
+        // Releasing a lock is a yield point
+        thread::switch_on(ObjectId::lock(id));
+    }
+}
+
+/// The future returned by [`Mutex::lock`], which resolves once the current task holds the lock
+struct Acquire<'a, T: ?Sized> {
+    mutex: &'a Mutex<T>,
+    id: LockId,
+}
+
+impl<'a, T: ?Sized> Future for Acquire<'a, T> {
+    type Output = MutexGuard<'a, T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.mutex.acquire(self.id) {
+            return Poll::Ready(MutexGuard { mutex: self.mutex });
+        }
+
+        let me = ExecutionState::me();
+        let mut state = self.mutex.state.borrow_mut();
+        trace!(holder=?state.holder, "waiting to acquire async mutex {:p}", self.mutex.state);
+        match state.waiters.iter_mut().find(|(tid, _)| *tid == me) {
+            Some((_, waker)) => *waker = cx.waker().clone(),
+            None => state.waiters.push((me, cx.waker().clone())),
+        }
+        let id = self.id;
+        ExecutionState::with(|s| s.current_mut().set_waiting_lock(Some(id)));
+        Poll::Pending
+    }
+}
+
+impl<T: ?Sized> Drop for Acquire<'_, T> {
+    fn drop(&mut self) {
+        // If we're dropped before acquiring the lock, stop waiting for it. There's nothing to do
+        // if the execution has already ended and is tearing down a detached task that was waiting.
+        ExecutionState::try_with(|s| {
+            let me = match s.try_current() {
+                Some(task) => task.id(),
+                None => return,
+            };
+            let mut state = self.mutex.state.borrow_mut();
+            if state.holder != Some(me) {
+                state.waiters.retain(|(tid, _)| *tid != me);
+                s.current_mut().set_waiting_lock(None);
+            }
+        });
+    }
+}
+
+// Safety: Mutex is never actually passed across true threads, only across continuations. The
+// Rc<RefCell<_>> type therefore can't be preempted mid-bookkeeping-operation, and the data is only
+// accessed through a guard, of which there is at most one at a time.
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> {}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl<T: ?Sized + Debug> Debug for Mutex<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Mutex").field("state", &self.state).finish()
+    }
+}
+
+impl<T: ?Sized> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.release();
+    }
+}
+
+impl<T: ?Sized> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // Safety: we hold the lock, so nobody else has access to the data
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Safety: we hold the lock, so nobody else has access to the data
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T: ?Sized + Debug> Debug for MutexGuard<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MutexGuard").field("data", &&**self).finish()
+    }
+}
@@ -0,0 +1,120 @@
+//! Support code for the [`select!`](crate::future::select) macro.
+
+use crate::runtime::execution::ExecutionState;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Wait on multiple concurrent branches, running the body of the first branch whose future
+/// completes.
+///
+/// Each branch has the form `<pattern> = <future> => <body>`. The futures are evaluated up front
+/// and polled until one of them is ready, at which point its output is bound to the pattern and
+/// the branch's body runs. The other futures are then dropped. The patterns must be irrefutable.
+///
+/// When more than one future is ready, which branch wins is a nondeterministic choice made by the
+/// scheduler, so Shuttle explores every ready branch being chosen. Each time the `select!` is
+/// polled, the scheduler picks which branch to poll first, and the first ready branch from there on
+/// wins.
+///
+/// This macro can only be used inside an async function or block.
+///
+/// # Example
+///
+/// ```
+/// use shuttle::future::{self, select};
+///
+/// shuttle::check_dfs(
+///     || {
+///         let winner = future::block_on(async {
+///             select! {
+///                 a = async { 1 } => a,
+///                 b = async { 2 } => b,
+///             }
+///         });
+///         assert!(winner == 1 || winner == 2);
+///     },
+///     None,
+/// );
+/// ```
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __select {
+    ($($pat:pat = $fut:expr => $body:expr),+ $(,)?) => {
+        $crate::__select_inner!(@bind [] $($pat = $fut => $body,)+)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __select_inner {
+    // Bind the next branch's future and output slot to fresh identifiers. Each expansion of this
+    // macro gets its own hygiene context, so the names don't collide across branches.
+    (@bind [$($bound:tt)*] $pat:pat = $fut:expr => $body:expr, $($rest:tt)*) => {{
+        let mut fut = ::std::boxed::Box::pin($fut);
+        let mut out = ::std::option::Option::None;
+        $crate::__select_inner!(@bind [$($bound)* (fut, out, $pat, $body)] $($rest)*)
+    }};
+
+    // Every branch is bound: poll them until one is ready, then run that branch's body
+    (@bind [$(($fut:ident, $out:ident, $pat:pat, $body:expr))*]) => {{
+        $crate::future::__poll_fn(|cx: &mut ::std::task::Context<'_>| {
+            let mut branches: [&mut dyn FnMut(&mut ::std::task::Context<'_>) -> bool; $crate::__select_inner!(@count $($fut)*)] = [
+                $(&mut |cx: &mut ::std::task::Context<'_>| {
+                    match ::std::future::Future::poll(::std::pin::Pin::as_mut(&mut $fut), cx) {
+                        ::std::task::Poll::Ready(v) => {
+                            $out = ::std::option::Option::Some(v);
+                            true
+                        }
+                        ::std::task::Poll::Pending => false,
+                    }
+                }),*
+            ];
+            $crate::future::__select_poll(&mut branches, cx)
+        })
+        .await;
+        $(
+            if let ::std::option::Option::Some(v) = $out {
+                let $pat = v;
+                $body
+            } else
+        )* {
+            ::std::unreachable!("select! completed without a ready branch")
+        }
+    }};
+
+    (@count) => { 0 };
+    (@count $head:ident $($tail:ident)*) => { 1 + $crate::__select_inner!(@count $($tail)*) };
+}
+
+/// Poll each branch of a `select!`, starting from one chosen by the scheduler, until one of them is
+/// ready. Returns `Ready` once a branch has stored its output.
+#[doc(hidden)]
+pub fn __select_poll(branches: &mut [&mut dyn FnMut(&mut Context<'_>) -> bool], cx: &mut Context<'_>) -> Poll<()> {
+    let start = ExecutionState::choose(branches.len());
+    for i in 0..branches.len() {
+        let branch = &mut branches[(start + i) % branches.len()];
+        if branch(cx) {
+            return Poll::Ready(());
+        }
+    }
+    Poll::Pending
+}
+
+/// A future that calls a closure each time it's polled
+#[doc(hidden)]
+pub fn __poll_fn<F: FnMut(&mut Context<'_>) -> Poll<()>>(f: F) -> impl Future<Output = ()> {
+    struct PollFn<F>(F);
+
+    impl<F> Unpin for PollFn<F> {}
+
+    impl<F: FnMut(&mut Context<'_>) -> Poll<()>> Future for PollFn<F> {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            (self.0)(cx)
+        }
+    }
+
+    PollFn(f)
+}
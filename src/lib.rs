@@ -150,6 +150,9 @@
 //!
 //! and be executed by running `cargo test --features shuttle`.
 //!
+//! Tests already written against [Loom]'s API can instead enable Shuttle's `loom-compat` feature
+//! and import from `shuttle::loom`, which provides Shuttle's primitives under Loom's paths.
+//!
 //! ### Choosing a scheduler and running a test
 //!
 //! Shuttle tests need to choose a *scheduler* to use to direct the execution. The scheduler
@@ -161,6 +164,11 @@
 //! [`Scheduler`](crate::scheduler::Scheduler) trait. They are most easily accessed via convenience
 //! methods:
 //! - [`check_random`] runs a test using a random scheduler for a chosen number of executions.
+//!   If the test fails, `check_random` prints the random seed it used, which can be passed to
+//!   [`check_random_with_seed`] to rerun the same schedules. [`check_random_parallel`] spreads the
+//!   executions across several OS threads, and likewise prints a seed that can be passed to
+//!   [`check_random_parallel_with_seed`]. [`check_no_deadlock`] also uses a random scheduler, but
+//!   fails if any task is left blocked at the end of an execution, even a detached one.
 //! - [`check_pct`] runs a test using the [Probabilistic Concurrency Testing][pct] (PCT) algorithm.
 //!   PCT bounds the number of preemptions a test explores; empirically, most concurrency bugs can
 //!   be detected with very few preemptions, and so PCT increases the probability of finding such
@@ -170,27 +178,62 @@
 //!   testing is intractable for all but the very simplest programs, and so using this scheduler is
 //!   not recommended, but it can be useful to thoroughly test small concurrency primitives. The DFS
 //!   scheduler can be configured with a bound on the depth of schedules to explore.
+//!   [`check_dfs_bounded`] instead bounds the number of preemptions in each schedule, which keeps
+//!   the search tractable for larger programs while still finding most bugs.
+//! - [`check_dpor`] runs a test with an exhaustive scheduler that uses dynamic partial-order
+//!   reduction to skip schedules that only reorder independent operations (like operations on
+//!   different locks). It explores the same behaviors as [`check_dfs`], but often in far fewer
+//!   executions.
 //!
 //! When these convenience methods do not provide enough control, Shuttle provides a [`Runner`]
-//! object for executing a test. A runner is constructed from a chosen [scheduler](scheduler), and
-//! then invoked with the [`Runner::run`] method. Shuttle also provides a [`PortfolioRunner`] object
+//! object for executing a test. A runner is constructed from a chosen [scheduler](scheduler) (any
+//! implementation of the [`Scheduler`](scheduler::Scheduler) trait, including your own) and a
+//! [`Config`] given with [`Runner::with_config`], and then invoked with the [`Runner::run`] method,
+//! which returns [`RunStats`] about the test. The convenience methods above are all shorthands for
+//! constructing a runner this way. Shuttle also provides a [`PortfolioRunner`] object
 //! for running multiple schedulers, using parallelism to increase the number of test executions
 //! explored.
 //!
+//! ### Inspecting test executions
+//!
+//! Shuttle emits [`tracing`](https://docs.rs/tracing) events as it runs a test: when a task is
+//! spawned, blocked, or unblocked, when the scheduler makes a decision, and when a task acquires or
+//! releases a synchronization primitive. Each step of a task runs inside a `step` span whose `task`
+//! field is the ID of the running task, and each iteration runs inside an `execution` span, so
+//! installing a [`tracing` subscriber](https://docs.rs/tracing-subscriber) shows how the tasks of a
+//! failing execution interleaved. The events are at the `TRACE` level, so they cost almost nothing
+//! when no subscriber is listening for them, and can be compiled out entirely with `tracing`'s
+//! `max_level_*` features. [`Schedule::display_trace`](crate::scheduler::Schedule::display_trace)
+//! uses these events to produce a step-by-step listing of a single schedule.
+//!
 //! [Loom]: https://github.com/tokio-rs/loom
 //! [pct]: https://www.microsoft.com/en-us/research/wp-content/uploads/2016/02/asplos277-pct.pdf
 
-pub mod asynch;
+pub mod cell;
+pub mod future;
+pub mod nondet;
 pub mod rand;
 pub mod sync;
 pub mod thread;
+pub mod time;
+pub mod tokio;
+
+#[cfg(feature = "loom-compat")]
+pub mod loom;
 
 pub mod current;
 pub mod scheduler;
 
 mod runtime;
 
-pub use runtime::runner::{PortfolioRunner, Runner};
+/// Shuttle's async executor, now available as [`future`](crate::future).
+#[deprecated(note = "the `asynch` module has been renamed to `future`")]
+#[doc(hidden)]
+pub mod asynch {
+    pub use crate::future::*;
+}
+
+pub use runtime::runner::{LockContention, PortfolioRunner, ProgressInterval, RunStats, Runner};
 
 /// Configuration parameters for Shuttle
 #[derive(Clone, Debug)]
@@ -199,33 +242,127 @@ pub struct Config {
     /// Stack size allocated for each thread
     pub stack_size: usize,
 
-    /// How to persist schedules when a test fails
+    /// How to persist schedules when a test fails. Defaults to [`FailurePersistence::Print`], unless
+    /// the `SHUTTLE_FAILURE_PERSISTENCE_DIR` environment variable is set, in which case it defaults
+    /// to persisting schedules as files in that directory.
     pub failure_persistence: FailurePersistence,
 
     /// Maximum number of steps a single iteration of a test can take, and how to react when the
     /// limit is reached
     pub max_steps: MaxSteps,
 
+    /// Maximum number of tasks (threads and futures, including the main thread) that can be alive
+    /// at once in a single iteration of a test. Spawning a task that would exceed the limit fails
+    /// the test, which can catch code that spawns tasks without bound. Finished tasks don't count
+    /// towards the limit. Defaults to no limit.
+    pub max_tasks: Option<usize>,
+
     /// Time limit for an entire test. If set, calls to [`Runner::run`] will return when the time
     /// limit is exceeded or the [`Scheduler`](crate::scheduler::Scheduler) chooses to stop (e.g.,
-    /// by hitting its maximum number of iterations), whichever comes first. This time limit will
-    /// not abort a currently running test iteration; the limit is only checked between iterations.
+    /// by hitting its maximum number of iterations), whichever comes first.
+    ///
+    /// The limit is checked at every scheduling decision, so an iteration that is still running
+    /// when the limit is exceeded (e.g., because it is stuck in a spin loop) is stopped early. In
+    /// that case, Shuttle prints the schedule of that iteration so it can be replayed, unless
+    /// [`Config::failure_persistence`] is [`FailurePersistence::None`]. Exceeding the time limit is
+    /// not a test failure.
     pub max_time: Option<std::time::Duration>,
 
     /// Whether to enable warnings about [Shuttle's unsound implementation of
     /// `atomic`](crate::sync::atomic#warning-about-relaxed-behaviors).
     pub silence_atomic_ordering_warning: bool,
+
+    /// Whether to allow [`Condvar::wait`](crate::sync::Condvar::wait) to wake up spuriously, without
+    /// a notification. Real condition variables can wake up spuriously, so enabling this option can
+    /// find bugs in code that doesn't re-check its condition after waking up, but it also makes the
+    /// search space larger, as the scheduler can choose to wake a waiting thread at any point.
+    ///
+    /// This option also allows [`thread::park`](crate::thread::park) to return without being
+    /// unparked, as the standard library's `park` can.
+    ///
+    /// This option also allows `compare_exchange_weak` on [atomics](crate::sync::atomic) to fail
+    /// spuriously, even if the comparison succeeds.
+    pub spurious_wakeups: bool,
+
+    /// Whether to model [store buffering](crate::sync::atomic#store-buffering) for atomic stores
+    /// that use an ordering weaker than `SeqCst`. Enabling this option lets Shuttle find bugs in code
+    /// that relies on `SeqCst` to stop a store from being reordered with a later load.
+    pub store_buffering: bool,
+
+    /// Whether to capture a backtrace of each task every time it yields, so that when a deadlock
+    /// is detected, the failure message can show where each blocked task is waiting. Capturing
+    /// backtraces is slow, so this is off by default.
+    pub capture_backtraces: bool,
+
+    /// Whether to count how often each [`Mutex`](crate::sync::Mutex) and
+    /// [`RwLock`](crate::sync::RwLock) is contended, and report it in
+    /// [`RunStats::lock_contention`]. This can help find scalability problems in the code under
+    /// test.
+    pub record_lock_contention: bool,
+
+    /// Whether releasing an [`RwLock`](crate::sync::RwLock) that no other thread is waiting for
+    /// should skip the yield point that releasing a lock normally is. For code that takes
+    /// uncontended locks often, this can make the search space much smaller.
+    ///
+    /// This option is unsound in general, which is why it's off by default. Skipping the yield
+    /// means no other thread can run between the release and the releasing thread's next yield
+    /// point, so any operation the releasing thread performs before it yields is treated as
+    /// happening at the same time as the release. Some operations take effect before they yield,
+    /// like [`RwLock::try_write`](crate::sync::RwLock::try_write), so Shuttle will never explore
+    /// another thread taking the lock in between a release and an immediate `try_write` by the
+    /// same thread.
+    pub skip_uncontended_release_yields: bool,
+
+    /// Whether an execution that ends with [detached](crate::future::spawn) tasks still blocked
+    /// should fail as a deadlock. By default, Shuttle only reports a deadlock if some attached task
+    /// can never finish, because detached tasks are often meant to wait forever (e.g., a background
+    /// task waiting for more work). Enabling this option catches deadlocks between detached tasks
+    /// too. A detached task that is only waiting for a timer is never reported.
+    pub report_detached_deadlocks: bool,
+
+    /// Whether to tell the [`Scheduler`](crate::scheduler::Scheduler) which tasks are blocked
+    /// waiting for locks held by other tasks, so that priority-aware schedulers like
+    /// [`WeightedRandomScheduler`](crate::scheduler::WeightedRandomScheduler) can apply priority
+    /// inheritance. While a task holds a lock that a higher-priority task is blocked on, the holder
+    /// is treated as if it had the higher priority, so that a low-priority lock holder can't hold
+    /// up a high-priority task indefinitely while medium-priority tasks run (priority inversion).
+    pub priority_inheritance: bool,
+
+    /// Whether to record which [coverage labels](crate::current::set_coverage_label) took part in
+    /// scheduling decisions, and report them in [`RunStats::coverage`]. Comparing the coverage of
+    /// two runs can detect when a code change stops a test from exercising an interleaving.
+    pub record_coverage: bool,
+
+    /// Whether the locks a thread holds when it is [aborted](crate::thread::JoinHandle::abort)
+    /// should be poisoned as they are released, as if the thread had panicked. By default, an
+    /// aborted thread's locks are released without being poisoned.
+    pub poison_on_abort: bool,
 }
 
 impl Config {
     /// Create a new default configuration
     pub fn new() -> Self {
+        let failure_persistence = match std::env::var_os("SHUTTLE_FAILURE_PERSISTENCE_DIR") {
+            Some(dir) => FailurePersistence::File(Some(dir.into())),
+            None => FailurePersistence::Print,
+        };
+
         Self {
             stack_size: 0x8000,
-            failure_persistence: FailurePersistence::Print,
+            failure_persistence,
             max_steps: MaxSteps::FailAfter(1_000_000),
+            max_tasks: None,
             max_time: None,
             silence_atomic_ordering_warning: false,
+            spurious_wakeups: false,
+            store_buffering: false,
+            capture_backtraces: false,
+            record_lock_contention: false,
+            skip_uncontended_release_yields: false,
+            report_detached_deadlocks: false,
+            priority_inheritance: false,
+            record_coverage: false,
+            poison_on_abort: false,
         }
     }
 }
@@ -241,7 +378,11 @@ impl Default for Config {
 /// By default, schedules are printed to stdout/stderr, and can be replayed using [`replay`].
 /// Optionally, they can instead be persisted to a file and replayed using [`replay_from_file`],
 /// which can be useful if the schedule is too large to conveniently include in a call to
-/// [`replay`].
+/// [`replay`]. Setting the `SHUTTLE_FAILURE_PERSISTENCE_DIR` environment variable makes that the
+/// default for every [`Config`], which is handy for collecting failing schedules in CI.
+///
+/// A persisted schedule is a short hex string that starts with a format version, so a schedule can
+/// only be replayed by a version of Shuttle that understands its format.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
 pub enum FailurePersistence {
@@ -265,6 +406,8 @@ pub enum FailurePersistence {
 /// The steps bound can be used to protect against livelock and fairness issues. For example, if a
 /// thread is waiting for another thread to make progress, but the chosen [`Scheduler`] never
 /// schedules that thread, a livelock occurs and the test will not terminate without a step bound.
+/// Like any other failure, a test that fails because it hit the bound prints the schedule that hit
+/// it, so the livelock can be replayed.
 ///
 /// By default, Shuttle fails a test after 1,000,000 steps.
 ///
@@ -291,20 +434,112 @@ where
 {
     use crate::scheduler::RoundRobinScheduler;
 
-    let runner = Runner::new(RoundRobinScheduler::new(), Default::default());
+    let runner = Runner::new(RoundRobinScheduler::new());
     runner.run(f);
 }
 
 /// Run the given function under a randomized concurrency scheduler for some number of iterations.
 /// Each iteration will run a (potentially) different randomized schedule.
+///
+/// The scheduler is seeded with a fresh random seed. If the test fails, the seed is printed, and
+/// passing it to [`check_random_with_seed`] reruns exactly the same schedules.
 pub fn check_random<F>(f: F, iterations: usize)
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    use ::rand::{rngs::OsRng, RngCore};
+
+    let seed = OsRng.next_u64();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        check_random_with_seed(f, iterations, seed)
+    }));
+    if let Err(e) = result {
+        eprintln!(
+            "test failed with random seed {}\npass that seed to `shuttle::check_random_with_seed` to rerun the same schedules",
+            seed
+        );
+        std::panic::resume_unwind(e);
+    }
+}
+
+/// Run the given function under a randomized concurrency scheduler for some number of iterations,
+/// like [`check_random`], but with the scheduler's RNG seeded with `seed`. The seed fully
+/// determines the schedules that are explored, so two runs with the same seed explore the same
+/// schedules in the same order.
+pub fn check_random_with_seed<F>(f: F, iterations: usize, seed: u64)
 where
     F: Fn() + Send + Sync + 'static,
 {
     use crate::scheduler::RandomScheduler;
 
+    let scheduler = RandomScheduler::new_from_seed(seed, iterations);
+    let runner = Runner::new(scheduler);
+    runner.run(f);
+}
+
+/// Run the given function under a randomized concurrency scheduler for some number of iterations,
+/// like [`check_random`], but spread the iterations across `threads` OS threads.
+///
+/// Each thread runs its own [`RandomScheduler`](crate::scheduler::RandomScheduler), seeded from a
+/// single fresh seed using [`RandomScheduler::split`](crate::scheduler::RandomScheduler::split).
+/// As soon as any iteration fails, the other threads stop, and the test panics with the failing
+/// iteration's schedule. The seed is also printed, and passing it to
+/// [`check_random_parallel_with_seed`] reruns the same schedules.
+pub fn check_random_parallel<F>(f: F, iterations: usize, threads: usize)
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    use ::rand::{rngs::OsRng, RngCore};
+
+    let seed = OsRng.next_u64();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        check_random_parallel_with_seed(f, iterations, threads, seed)
+    }));
+    if let Err(e) = result {
+        eprintln!(
+            "test failed with random seed {}\npass that seed to `shuttle::check_random_parallel_with_seed` to rerun the same schedules",
+            seed
+        );
+        std::panic::resume_unwind(e);
+    }
+}
+
+/// Run the given function under a randomized concurrency scheduler for some number of iterations,
+/// like [`check_random_parallel`], but with the schedulers seeded from `seed`. Two runs with the
+/// same seed and number of threads explore the same set of schedules, although the threads may
+/// finish them in a different order.
+pub fn check_random_parallel_with_seed<F>(f: F, iterations: usize, threads: usize, seed: u64)
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    use crate::scheduler::RandomScheduler;
+
+    let mut runner = PortfolioRunner::new(true, Default::default());
+    for scheduler in RandomScheduler::split(seed, iterations, threads) {
+        runner.add(scheduler);
+    }
+    runner.run(f);
+}
+
+/// Run the given function under a randomized concurrency scheduler for some number of iterations,
+/// like [`check_random`], and fail if any execution ends with a task that can never finish.
+///
+/// Shuttle always fails an execution that deadlocks, but by default it ignores
+/// [detached](crate::future::spawn) tasks that are still blocked when every other task has
+/// finished. This function enables [`Config::report_detached_deadlocks`] so that those are
+/// reported too, which makes it a quick way to sanity-check that a data structure can never
+/// deadlock, whatever the interleaving, without writing any assertions. The failure message lists
+/// the blocked tasks and, if they are waiting for each other's locks, the lock cycle.
+pub fn check_no_deadlock<F>(f: F, iterations: usize)
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    use crate::scheduler::RandomScheduler;
+
+    let mut config = Config::new();
+    config.report_detached_deadlocks = true;
     let scheduler = RandomScheduler::new(iterations);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler).with_config(config);
     runner.run(f);
 }
 
@@ -317,7 +552,7 @@ where
     use crate::scheduler::PctScheduler;
 
     let scheduler = PctScheduler::new(depth, iterations);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(f);
 }
 
@@ -330,7 +565,35 @@ where
     use crate::scheduler::DfsScheduler;
 
     let scheduler = DfsScheduler::new(max_iterations, false);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
+    runner.run(f);
+}
+
+/// Run the given function under a depth-first-search scheduler that only explores schedules with at
+/// most `max_preemptions` preemptions, using iterative context bounding. See
+/// [`DfsScheduler`](scheduler::DfsScheduler) for details.
+pub fn check_dfs_bounded<F>(f: F, max_preemptions: usize)
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    use crate::scheduler::DfsScheduler;
+
+    let scheduler = DfsScheduler::new_with_preemption_bound(None, max_preemptions, false);
+    let runner = Runner::new(scheduler);
+    runner.run(f);
+}
+
+/// Run the given function under a DPOR scheduler until all interleavings have been explored, up to
+/// reordering of independent steps (but if the max_iterations bound is provided, stop after that
+/// many iterations). See [`DporScheduler`](scheduler::DporScheduler) for details.
+pub fn check_dpor<F>(f: F, max_iterations: Option<usize>)
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    use crate::scheduler::DporScheduler;
+
+    let scheduler = DporScheduler::new(max_iterations, false);
+    let runner = Runner::new(scheduler);
     runner.run(f);
 }
 
@@ -342,6 +605,8 @@ where
 ///
 /// This is a convenience function for constructing a [`Runner`] that uses
 /// [`ReplayScheduler::new_from_encoded`](scheduler::ReplayScheduler::new_from_encoded).
+/// To replay a schedule without changing the test, set the `SHUTTLE_REPLAY` environment variable
+/// instead (see [`Runner::run`]).
 pub fn replay<F>(f: F, encoded_schedule: &str)
 where
     F: Fn() + Send + Sync + 'static,
@@ -349,7 +614,7 @@ where
     use crate::scheduler::ReplayScheduler;
 
     let scheduler = ReplayScheduler::new_from_encoded(encoded_schedule);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(f);
 }
 
@@ -369,11 +634,62 @@ where
     use crate::scheduler::ReplayScheduler;
 
     let scheduler = ReplayScheduler::new_from_file(path).expect("could not load schedule from file");
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(f);
 }
 
+/// Search for a shorter version of a schedule that makes the given function fail, and return the
+/// shortest failing schedule found.
+///
+/// Schedules found by randomized or exhaustive schedulers are often long and full of context
+/// switches that have nothing to do with the bug. This function repeatedly removes scheduling
+/// decisions from the schedule and replays the function to check that it still panics, in the
+/// style of delta debugging. The resulting schedule can be replayed with [`replay`] (after
+/// [encoding it](scheduler::Schedule::encode)) or a
+/// [`ReplayScheduler`](scheduler::ReplayScheduler). It's not guaranteed to be the shortest failing
+/// schedule, and it might fail with a different panic than the original schedule.
+///
+/// Panics if `schedule` doesn't make `f` fail. Like replay, minimization relies on `f` containing
+/// no non-determinism other than that introduced by scheduling.
+pub fn minimize_schedule<F>(f: F, schedule: scheduler::Schedule) -> scheduler::Schedule
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    scheduler::minimize::minimize_schedule(f, schedule)
+}
+
+/// Register an invariant that must hold at every scheduling point for the rest of the current
+/// execution.
+///
+/// Shuttle calls `invariant` every time a task reaches a yield point, and fails the test as soon as
+/// it returns false, printing the schedule so far so the failure can be replayed. This catches an
+/// invariant that is broken only for a while, and repaired before the test's own assertions at the
+/// end of the execution can notice.
+///
+/// The invariant runs outside of any task, so it can't block or yield: it should inspect shared
+/// state directly, for example with standard library atomics or
+/// [`RwLock::reader_count`](crate::sync::RwLock::reader_count), rather than by acquiring a Shuttle
+/// lock. Invariants only apply to the execution that registers them, so a test should register
+/// them at the start of the closure it passes to Shuttle.
+///
+/// Panics if called outside of a Shuttle execution.
+#[track_caller]
+pub fn assert_invariant<F>(invariant: F)
+where
+    F: Fn() -> bool + 'static,
+{
+    let location = std::panic::Location::caller();
+    runtime::execution::ExecutionState::register_invariant(std::rc::Rc::new(invariant), location);
+}
+
 /// Declare a new thread local storage key of type [`LocalKey`](crate::thread::LocalKey).
+///
+/// Shuttle runs every thread in a test on the same operating system thread, so the standard
+/// library's `thread_local!` would share its value between all threads in the test, and across
+/// executions. Values declared with this macro are instead stored per Shuttle thread: each thread
+/// lazily initializes its own copy on first access, and the copy is destroyed when the thread
+/// finishes. Every execution runs with fresh threads, so no value carries over from one execution
+/// to the next.
 #[macro_export]
 macro_rules! thread_local {
     // empty (base case for the recursion)
@@ -0,0 +1,117 @@
+//! A compatibility layer that exposes Shuttle's primitives under the same paths as [Loom]'s, so
+//! tests written against Loom can run under Shuttle by swapping `loom::` for `shuttle::loom::`.
+//!
+//! This module is only available with the `loom-compat` feature enabled.
+//!
+//! [`model`](fn@model) runs a test under Shuttle's [`DfsScheduler`], which, like Loom, explores
+//! every interleaving of the test, and [`model::Builder`] supports the subset of Loom's options
+//! that have a Shuttle equivalent. The primitives in [`sync`] and [`thread`] are Shuttle's own.
+//!
+//! Shuttle and Loom don't check exactly the same things, so some differences remain:
+//! * Loom explores weak memory behaviors of atomics. Shuttle only models store buffering, and only
+//!   if [`Config::store_buffering`](crate::Config::store_buffering) is enabled, which
+//!   [`model`](fn@model) doesn't do. A test that relies on other weak memory behaviors won't see
+//!   them under Shuttle.
+//! * Loom tracks every access to a [`cell::UnsafeCell`] and reports accesses that aren't ordered by
+//!   happens-before as data races. Shuttle's [`UnsafeCell`](crate::cell::UnsafeCell) does the same
+//!   using vector clocks, but it only catches races that happen in the interleavings Shuttle
+//!   explores, and doesn't check raw pointers that escape the closure.
+//! * Like Loom, [`model`](fn@model) explores spurious failures of `compare_exchange_weak` and
+//!   spurious wakeups of [`Condvar`](sync::Condvar) and [`park`](thread::park), by enabling
+//!   [`Config::spurious_wakeups`](crate::Config::spurious_wakeups).
+//!
+//! [Loom]: https://github.com/tokio-rs/loom
+//! [`DfsScheduler`]: crate::scheduler::DfsScheduler
+
+/// Loom-compatible versions of the types in [`std::cell`].
+pub mod cell {
+    pub use crate::cell::UnsafeCell;
+}
+
+/// Loom-compatible versions of the primitives in [`std::sync`].
+pub mod sync {
+    pub use crate::sync::{
+        atomic, mpsc, Arc, Barrier, BarrierWaitResult, Condvar, Mutex, MutexGuard, RwLock, RwLockReadGuard,
+        RwLockWriteGuard, WaitTimeoutResult,
+    };
+}
+
+/// Loom-compatible versions of the functions and types in [`std::thread`].
+pub mod thread {
+    pub use crate::thread::*;
+}
+
+/// Loom-compatible versions of the functions in [`std::hint`].
+pub mod hint {
+    /// Signals that the current thread is in a spin loop. Shuttle treats this as a yield point, so
+    /// that a spinning thread gives other threads a chance to make progress.
+    pub fn spin_loop() {
+        crate::thread::yield_now();
+    }
+}
+
+/// Loom-compatible version of Loom's `future` module.
+pub mod future {
+    pub use crate::future::block_on;
+}
+
+pub use crate::thread_local;
+
+/// Run the given function under Shuttle's DFS scheduler until every interleaving has been
+/// explored, like Loom's `model`.
+pub fn model<F>(f: F)
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    model::Builder::new().check(f)
+}
+
+/// Loom-compatible configuration for [`model`](fn@model).
+pub mod model {
+    use crate::scheduler::DfsScheduler;
+    use crate::{Config, Runner};
+    use std::time::Duration;
+
+    /// Configures how a test is explored, like Loom's `model::Builder`.
+    ///
+    /// Only the options that have a Shuttle equivalent are supported.
+    #[derive(Clone, Debug, Default)]
+    #[non_exhaustive]
+    pub struct Builder {
+        /// The maximum number of preemptions to explore in each execution. If `None`, the number of
+        /// preemptions is unbounded.
+        pub preemption_bound: Option<usize>,
+
+        /// The maximum number of executions (permutations) to explore. If `None`, every execution
+        /// is explored.
+        pub max_permutations: Option<usize>,
+
+        /// The maximum amount of time to spend exploring executions. If `None`, there's no time
+        /// limit.
+        pub max_duration: Option<Duration>,
+    }
+
+    impl Builder {
+        /// Create a new `Builder` with no bounds on the exploration.
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Run the given function under Shuttle's DFS scheduler with this configuration. Like Loom,
+        /// this explores spurious wakeups and spurious `compare_exchange_weak` failures.
+        pub fn check<F>(&self, f: F)
+        where
+            F: Fn() + Send + Sync + 'static,
+        {
+            let scheduler = match self.preemption_bound {
+                Some(bound) => DfsScheduler::new_with_preemption_bound(self.max_permutations, bound, false),
+                None => DfsScheduler::new(self.max_permutations, false),
+            };
+            let mut config = Config::new();
+            config.max_time = self.max_duration;
+            config.spurious_wakeups = true;
+            let runner = Runner::new(scheduler).with_config(config);
+            runner.run(f);
+        }
+    }
+}
@@ -0,0 +1,26 @@
+//! Nondeterministic choices made by the test harness itself.
+//!
+//! A test can use these functions to explore different behaviors of its own harness, like the order
+//! in which it sends two messages. Each choice is made by the scheduler and recorded in the
+//! schedule, just like a context switch: the DFS and DPOR schedulers explore every option, the
+//! random, PCT, and weighted random schedulers choose uniformly at random (regardless of task
+//! priorities or weights), and replaying a schedule makes the same choices again.
+//!
+//! Unlike [`rand`](crate::rand), which produces random data that the DFS scheduler doesn't explore,
+//! these choices are part of the search space, so they should only be used for choices with a
+//! small number of options.
+
+use crate::runtime::execution::ExecutionState;
+
+/// Ask the scheduler to choose one of `values`, and return the chosen value.
+///
+/// Panics if `values` is empty.
+pub fn choose<T>(values: &[T]) -> &T {
+    assert!(!values.is_empty(), "must have at least one value to choose from");
+    &values[ExecutionState::choose(values.len())]
+}
+
+/// Ask the scheduler to choose a boolean.
+pub fn bool() -> bool {
+    *choose(&[false, true])
+}
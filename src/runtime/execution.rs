@@ -1,17 +1,21 @@
-use crate::runtime::failure::{init_panic_hook, persist_failure, persist_task_failure};
+use crate::runtime::failure::{init_panic_hook, persist_failure, persist_task_failure, FailureKind};
+use crate::runtime::runner::LockContention;
 use crate::runtime::storage::{StorageKey, StorageMap};
 use crate::runtime::task::clock::VectorClock;
-use crate::runtime::task::{Task, TaskId, DEFAULT_INLINE_TASKS};
+use crate::runtime::task::{LockId, Task, TaskId, TaskSet, DEFAULT_INLINE_TASKS};
+use crate::runtime::thread;
 use crate::runtime::thread::continuation::PooledContinuation;
-use crate::scheduler::{Schedule, Scheduler};
-use crate::{Config, MaxSteps};
+use crate::scheduler::{Accesses, ObjectId, Schedule, Scheduler};
+use crate::{Config, FailurePersistence, MaxSteps};
 use scoped_tls::scoped_thread_local;
 use smallvec::SmallVec;
 use std::any::Any;
 use std::cell::RefCell;
+use std::collections::BTreeSet;
 use std::future::Future;
-use std::panic;
+use std::panic::{self, Location};
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 use tracing::span::Entered;
 use tracing::{span, trace, Level, Span};
 
@@ -31,15 +35,45 @@ scoped_thread_local! {
 pub(crate) struct Execution {
     scheduler: Rc<RefCell<dyn Scheduler>>,
     initial_schedule: Schedule,
+    deadline: Option<Instant>,
+}
+
+/// The reason an execution stopped before all its tasks finished, other than the scheduler asking
+/// to stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StopReason {
+    /// The execution reached the [`MaxSteps::ContinueAfter`] bound
+    MaxSteps,
+    /// The execution was still running when the deadline for the whole test passed
+    MaxTime,
+}
+
+/// What happened during an execution, for the `Runner` to report.
+#[derive(Debug)]
+pub(crate) struct ExecutionOutcome {
+    /// The reason the execution stopped early, if it stopped because of a step or time bound
+    pub(crate) stop_reason: Option<StopReason>,
+    /// Contention on each lock the execution used, indexed by `LockId`, if
+    /// `Config::record_lock_contention` is enabled
+    pub(crate) lock_contention: Vec<LockContention>,
+    /// The coverage labels that took part in scheduling decisions, if `Config::record_coverage` is
+    /// enabled
+    pub(crate) coverage: BTreeSet<&'static str>,
 }
 
 impl Execution {
     /// Construct a new execution that will use the given scheduler. The execution should then be
-    /// invoked via its `run` method, which takes as input the closure for task 0.
-    pub(crate) fn new(scheduler: Rc<RefCell<dyn Scheduler>>, initial_schedule: Schedule) -> Self {
+    /// invoked via its `run` method, which takes as input the closure for task 0. If a `deadline` is
+    /// given, the execution stops early if it is still running at that time.
+    pub(crate) fn new(
+        scheduler: Rc<RefCell<dyn Scheduler>>,
+        initial_schedule: Schedule,
+        deadline: Option<Instant>,
+    ) -> Self {
         Self {
             scheduler,
             initial_schedule,
+            deadline,
         }
     }
 }
@@ -48,7 +82,10 @@ impl Execution {
     /// Run a function to be tested, taking control of scheduling it and any tasks it might spawn.
     /// This function runs until `f` and all tasks spawned by `f` have terminated, or until the
     /// scheduler returns `None`, indicating the execution should not be explored any further.
-    pub(crate) fn run<F>(mut self, config: &Config, f: F)
+    ///
+    /// Returns what happened during the execution, like whether it stopped early because of a step
+    /// or time bound.
+    pub(crate) fn run<F>(mut self, config: &Config, f: F) -> ExecutionOutcome
     where
         F: FnOnce() + Send + 'static,
     {
@@ -56,6 +93,7 @@ impl Execution {
             config.clone(),
             Rc::clone(&self.scheduler),
             self.initial_schedule.clone(),
+            self.deadline,
         ));
 
         let _guard = init_panic_hook(config.clone());
@@ -75,6 +113,13 @@ impl Execution {
             // Cleanup the state before it goes out of `EXECUTION_STATE` scope
             ExecutionState::cleanup();
         });
+
+        let mut state = state.borrow_mut();
+        ExecutionOutcome {
+            stop_reason: state.stop_reason,
+            lock_contention: std::mem::take(&mut state.lock_contention),
+            coverage: std::mem::take(&mut state.coverage),
+        }
     }
 
     /// Execute a single step of the scheduler. Returns true if the execution should continue.
@@ -82,13 +127,13 @@ impl Execution {
     fn step(&mut self, config: &Config) -> bool {
         enum NextStep {
             Task(Rc<RefCell<PooledContinuation>>),
-            Failure(String, Schedule),
+            Failure(FailureKind, String, Schedule),
             Finished,
         }
 
         let next_step = ExecutionState::with(|state| {
-            if let Err(msg) = state.schedule() {
-                return NextStep::Failure(msg, state.current_schedule.clone());
+            if let Err((kind, msg)) = state.schedule() {
+                return NextStep::Failure(kind, msg, state.current_schedule.clone());
             }
             state.advance_to_next_task();
 
@@ -100,8 +145,9 @@ impl Execution {
                 ScheduledTask::Finished => {
                     // The scheduler decided we're finished, so there are either no runnable tasks,
                     // or all runnable tasks are detached and there are no unfinished attached
-                    // tasks. Therefore, it's a deadlock if there are unfinished attached tasks.
-                    if state.tasks.iter().any(|t| !t.finished() && !t.detached) {
+                    // tasks. Therefore, it's a deadlock if there are unfinished attached tasks
+                    // (or stuck detached tasks, if we were asked to report those).
+                    if state.is_deadlocked() {
                         let blocked_tasks = state
                             .tasks
                             .iter()
@@ -116,18 +162,36 @@ impl Execution {
                                 )
                             })
                             .collect::<Vec<_>>();
-                        NextStep::Failure(
-                            format!("deadlock! blocked tasks: [{}]", blocked_tasks.join(", ")),
-                            state.current_schedule.clone(),
-                        )
+                        let mut msg = format!("deadlock! blocked tasks: [{}]", blocked_tasks.join(", "));
+                        if let Some(cycle) = state.lock_cycle() {
+                            msg.push_str(&format!("\nlock cycle: {}", cycle));
+                        }
+                        for t in state.tasks.iter().filter(|t| !t.finished()) {
+                            if let Some(backtrace) = &t.backtrace {
+                                msg.push_str(&format!(
+                                    "\n\n{} (task {}) is blocked at:\n{}",
+                                    t.name().unwrap_or_else(|| "<unknown>".to_string()),
+                                    t.id().0,
+                                    backtrace
+                                ));
+                            }
+                        }
+                        NextStep::Failure(FailureKind::Deadlock, msg, state.current_schedule.clone())
+                    } else if let Some(leaks) = state.leaked_locks() {
+                        // A task can only hold a lock after it finishes if it never dropped the
+                        // guard, which would leave the lock held when the next execution starts
+                        let msg = format!("leaked lock guard! finished tasks still hold locks: {}", leaks);
+                        NextStep::Failure(FailureKind::LogicBug, msg, state.current_schedule.clone())
                     } else {
                         NextStep::Finished
                     }
                 }
                 ScheduledTask::Stopped => NextStep::Finished,
-                ScheduledTask::None => {
-                    NextStep::Failure("no task was scheduled".to_string(), state.current_schedule.clone())
-                }
+                ScheduledTask::None => NextStep::Failure(
+                    FailureKind::LogicBug,
+                    "no task was scheduled".to_string(),
+                    state.current_schedule.clone(),
+                ),
             }
         });
 
@@ -136,10 +200,10 @@ impl Execution {
             NextStep::Task(continuation) => {
                 panic::catch_unwind(panic::AssertUnwindSafe(|| continuation.borrow_mut().resume()))
             }
-            NextStep::Failure(msg, schedule) => {
+            NextStep::Failure(kind, msg, schedule) => {
                 // Because we're creating the panic here, we don't need `persist_failure` to print
                 // as the failure message will be part of the panic payload.
-                let message = persist_failure(&schedule, msg, config, false);
+                let message = persist_failure(&schedule, kind, msg, config, false);
                 panic!("{}", message);
             }
             NextStep::Finished => return false,
@@ -165,6 +229,14 @@ impl Execution {
             }
         }
 
+        // The task has reached a yield point, so check that it left the invariants intact
+        if let Some(location) = ExecutionState::violated_invariant() {
+            let schedule = ExecutionState::with(|state| state.current_schedule.clone());
+            let msg = format!("invariant registered at {} was violated", location);
+            let message = persist_failure(&schedule, FailureKind::LogicBug, msg, config, false);
+            panic!("{}", message);
+        }
+
         true
     }
 }
@@ -184,9 +256,29 @@ pub(crate) struct ExecutionState {
     has_yielded: bool,
     // the number of scheduling decisions made so far
     context_switches: usize,
+    // the name of each lock that has been assigned a `LockId` so far, indexed by `LockId`
+    lock_names: Vec<Option<&'static str>>,
+    // contention on each lock, indexed by `LockId`, if `Config::record_lock_contention` is enabled
+    lock_contention: Vec<LockContention>,
+    // the coverage labels of tasks that were runnable when the scheduler had a choice to make, if
+    // `Config::record_coverage` is enabled
+    coverage: BTreeSet<&'static str>,
+    // the shared objects the current task has accessed since it was last scheduled
+    step_accesses: Accesses,
+    // when to stop the execution if it's still running, and why it stopped early, if it did
+    deadline: Option<Instant>,
+    stop_reason: Option<StopReason>,
+    // a failure detected while running a task (e.g., exceeding `Config::max_tasks`), to report at
+    // the next scheduling decision
+    failure: Option<String>,
+    // the logical time since the execution started, and the tasks sleeping until a later time
+    now: Duration,
+    timers: Vec<(Duration, TaskId)>,
 
     // static values for the current execution
     storage: StorageMap,
+    // the invariants registered with `assert_invariant`, and where each was registered
+    invariants: Vec<(Invariant, &'static Location<'static>)>,
 
     scheduler: Rc<RefCell<dyn Scheduler>>,
     current_schedule: Schedule,
@@ -200,6 +292,9 @@ pub(crate) struct ExecutionState {
     has_cleaned_up: bool,
 }
 
+/// An invariant registered with `assert_invariant`, which returns false if it doesn't hold
+type Invariant = Rc<dyn Fn() -> bool>;
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 enum ScheduledTask {
     None,         // no task has ever been scheduled
@@ -222,7 +317,12 @@ impl ScheduledTask {
 }
 
 impl ExecutionState {
-    fn new(config: Config, scheduler: Rc<RefCell<dyn Scheduler>>, initial_schedule: Schedule) -> Self {
+    fn new(
+        config: Config,
+        scheduler: Rc<RefCell<dyn Scheduler>>,
+        initial_schedule: Schedule,
+        deadline: Option<Instant>,
+    ) -> Self {
         Self {
             config,
             tasks: SmallVec::new(),
@@ -230,7 +330,17 @@ impl ExecutionState {
             next_task: ScheduledTask::None,
             has_yielded: false,
             context_switches: 0,
+            lock_names: Vec::new(),
+            lock_contention: Vec::new(),
+            coverage: BTreeSet::new(),
+            step_accesses: Accesses::default(),
+            deadline,
+            stop_reason: None,
+            failure: None,
+            now: Duration::ZERO,
+            timers: Vec::new(),
             storage: StorageMap::new(),
+            invariants: Vec::new(),
             scheduler,
             current_schedule: initial_schedule,
             current_span_entered: None,
@@ -282,8 +392,11 @@ impl ExecutionState {
     where
         F: Future<Output = ()> + Send + 'static,
     {
+        Self::check_max_tasks();
+
         Self::with(|state| {
             let task_id = TaskId(state.tasks.len());
+            trace!(task = task_id.0, name = name.as_deref(), "spawned future");
             let clock = state.increment_clock_mut(); // Increment the parent's clock
             clock.extend(task_id); // and extend it with an entry for the new task
             let task = Task::from_future(future, stack_size, task_id, name, clock.clone());
@@ -301,8 +414,11 @@ impl ExecutionState {
     where
         F: FnOnce() + Send + 'static,
     {
+        Self::check_max_tasks();
+
         Self::with(|state| {
             let task_id = TaskId(state.tasks.len());
+            trace!(task = task_id.0, name = name.as_deref(), "spawned");
             let clock = if let Some(ref mut clock) = initial_clock {
                 clock
             } else {
@@ -316,6 +432,32 @@ impl ExecutionState {
         })
     }
 
+    /// Fail the execution if the current task spawning another task would exceed
+    /// [`Config::max_tasks`]. The main thread is spawned before there is a current task, so it can
+    /// always be spawned.
+    fn check_max_tasks() {
+        let exceeded = Self::with(|state| match (state.config.max_tasks, state.try_current()) {
+            (Some(max_tasks), Some(current)) => {
+                let live = state.tasks.iter().filter(|t| !t.finished()).count();
+                if live >= max_tasks {
+                    state.failure = Some(format!(
+                        "exceeded max_tasks bound {}: task {} tried to spawn a new task while {} tasks were alive",
+                        max_tasks,
+                        current.id().0,
+                        live
+                    ));
+                }
+                live >= max_tasks
+            }
+            _ => false,
+        });
+        // Fail the execution directly rather than panicking, so the task can't catch the failure
+        // (e.g., by joining the spawning thread). The execution stops at this yield point.
+        if exceeded {
+            thread::switch();
+        }
+    }
+
     /// Prepare this ExecutionState to be dropped. Call this before dropping so that the tasks have
     /// a chance to run their drop handlers while `EXECUTION_STATE` is still in scope.
     fn cleanup() {
@@ -328,7 +470,9 @@ impl ExecutionState {
             (std::mem::replace(&mut state.tasks, SmallVec::new()), state.current_task)
         });
 
-        for task in tasks.drain(..) {
+        // Drop the tasks in reverse order of creation, so that the continuations of scoped threads
+        // are unwound before those of the threads whose stacks they borrow from
+        for task in tasks.drain(..).rev() {
             assert!(
                 final_state == ScheduledTask::Stopped || task.finished() || task.detached,
                 "execution finished but task is not"
@@ -338,6 +482,10 @@ impl ExecutionState {
                 .expect("couldn't cleanup a future");
         }
 
+        // Invariants might capture Shuttle primitives, so drop them while we still have a state
+        let invariants = Self::with(|state| std::mem::take(&mut state.invariants));
+        drop(invariants);
+
         while Self::with(|state| state.storage.pop()).is_some() {}
 
         #[cfg(debug_assertions)]
@@ -359,6 +507,14 @@ impl ExecutionState {
                 "we're inside a task and scheduler should not yet have run"
             );
 
+            // Inside a `sync::atomic::critical` region, a task that can keep running isn't
+            // preempted, so the yield point doesn't count as a scheduling decision
+            let current = state.current();
+            if current.critical_depth > 0 && current.runnable() && state.failure.is_none() {
+                state.has_yielded = false;
+                return false;
+            }
+
             let result = state.schedule();
             // If scheduling failed, yield so that the outer scheduling loop can handle it.
             if result.is_err() {
@@ -384,6 +540,17 @@ impl ExecutionState {
         });
     }
 
+    /// Record that the current task has released the given lock. Unlike most bookkeeping, this
+    /// happens even while the task is panicking, so that a task that unwinds while holding a lock
+    /// isn't reported as leaking it. It does nothing once the execution has stopped.
+    pub(crate) fn release_lock(lock: LockId) {
+        Self::try_with(|state| {
+            if let ScheduledTask::Some(tid) = state.current_task {
+                state.get_mut(tid).release_lock(lock);
+            }
+        });
+    }
+
     /// Check whether the current execution has stopped. Call from `Drop` handlers to early exit if
     /// they are being invoked because an execution has stopped.
     ///
@@ -399,6 +566,14 @@ impl ExecutionState {
             })
     }
 
+    /// Whether a lock released now should be poisoned. As in std, releasing a lock while panicking
+    /// poisons it, except when an aborted thread is unwinding and `Config::poison_on_abort` is off.
+    pub(crate) fn should_poison() -> bool {
+        std::thread::panicking()
+            && Self::try_with(|s| s.config.poison_on_abort || !matches!(s.try_current(), Some(t) if t.aborted))
+                .unwrap_or(true)
+    }
+
     /// Generate some diagnostic information used when persisting failures.
     ///
     /// Because this method may be called from a panic hook, it must not panic.
@@ -422,6 +597,35 @@ impl ExecutionState {
         })
     }
 
+    /// Ask the scheduler to choose one of `num_choices` options for the currently running task, and
+    /// return the index of the chosen option.
+    ///
+    /// The choice is recorded in the schedule as if the chosen option were a task, so that it can
+    /// be replayed just like a context switch.
+    pub(crate) fn choose(num_choices: usize) -> usize {
+        assert!(num_choices > 0, "must have at least one option to choose from");
+        if num_choices == 1 {
+            return 0;
+        }
+
+        Self::with(|state| {
+            let choice = state
+                .scheduler
+                .borrow_mut()
+                .next_choice(num_choices, state.current_task.id());
+            trace!(num_choices, ?choice, "nondeterministic choice");
+            match choice {
+                Some(choice) => {
+                    state.current_schedule.push_task(TaskId::from(choice));
+                    choice
+                }
+                // If the scheduler wants to stop, it doesn't matter which option we take; the
+                // execution will stop at the running task's next context switch
+                None => 0,
+            }
+        })
+    }
+
     pub(crate) fn current(&self) -> &Task {
         self.get(self.current_task.id().unwrap())
     }
@@ -449,6 +653,23 @@ impl ExecutionState {
         Self::with(|state| state.context_switches)
     }
 
+    /// Register an invariant to check every time a task reaches a yield point, for the rest of the
+    /// execution
+    pub(crate) fn register_invariant(invariant: Invariant, location: &'static Location<'static>) {
+        Self::with(|state| state.invariants.push((invariant, location)));
+    }
+
+    /// Check the registered invariants, and return where the first one that doesn't hold was
+    /// registered. The invariants are called without the `ExecutionState` borrowed, as they may
+    /// inspect Shuttle primitives.
+    fn violated_invariant() -> Option<&'static Location<'static>> {
+        let invariants = Self::with(|state| state.invariants.clone());
+        invariants
+            .into_iter()
+            .find(|(invariant, _)| !invariant())
+            .map(|(_, location)| location)
+    }
+
     pub(crate) fn get_storage<K: Into<StorageKey>, T: 'static>(&self, key: K) -> Option<&T> {
         self.storage
             .get(key.into())
@@ -459,6 +680,212 @@ impl ExecutionState {
         self.storage.init(key.into(), value);
     }
 
+    pub(crate) fn remove_storage<K: Into<StorageKey>>(&mut self, key: K) -> Option<Box<dyn Any>> {
+        self.storage.remove(key.into())
+    }
+
+    /// Record that the current task accessed the given shared object during its current step.
+    ///
+    /// With store buffering enabled, any yield point can make buffered stores visible to other
+    /// threads, so we can't tell which objects a step affected.
+    pub(crate) fn record_access(&mut self, object: ObjectId) {
+        if self.config.store_buffering {
+            self.step_accesses = Accesses::Unknown;
+        } else {
+            self.step_accesses.record(object);
+        }
+    }
+
+    /// Record that the current task might have accessed any shared object during its current step.
+    /// Primitives that don't report the specific objects they access must call this (directly, or
+    /// by using `thread::switch`) whenever they access shared state.
+    pub(crate) fn record_unknown_access(&mut self) {
+        self.step_accesses = Accesses::Unknown;
+    }
+
+    /// The current logical time of this execution, as a duration since the execution started
+    pub(crate) fn now(&self) -> Duration {
+        self.now
+    }
+
+    /// Block the current task until the logical clock reaches `deadline`. The clock only advances
+    /// when no task can run, so the task wakes once every other task is blocked or sleeping until
+    /// later.
+    pub(crate) fn sleep_until(&mut self, deadline: Duration) {
+        let me = self.current().id();
+        trace!(?deadline, now = ?self.now, "sleeping");
+        self.timers.push((deadline, me));
+        self.current_mut().block();
+    }
+
+    /// Wake every task whose timer has expired
+    fn fire_timers(&mut self) {
+        let now = self.now;
+        let mut expired = Vec::new();
+        self.timers.retain(|(deadline, tid)| {
+            if *deadline <= now {
+                expired.push(*tid);
+                false
+            } else {
+                true
+            }
+        });
+        for tid in expired {
+            self.get_mut(tid).unblock();
+        }
+    }
+
+    /// Allocate a new identifier for a lock, unique within this execution, and remember the lock's
+    /// name (if it has one) for diagnostics
+    pub(crate) fn new_lock_id(&mut self, name: Option<&'static str>) -> LockId {
+        let id = LockId(self.lock_names.len());
+        self.lock_names.push(name);
+        id
+    }
+
+    /// Describe a lock for diagnostics, using its name if it was given one
+    fn describe_lock(&self, lock: LockId) -> String {
+        match self.lock_names[lock.0] {
+            Some(name) => format!("{} ({})", name, lock),
+            None => lock.to_string(),
+        }
+    }
+
+    /// Record that the current task has started trying to acquire `lock`, for the lock contention
+    /// metrics. `waiters` are the tasks waiting to acquire the lock, including the current task.
+    /// Call this after deciding whether the current task must block.
+    pub(crate) fn record_lock_attempt(&mut self, lock: LockId, waiters: impl Iterator<Item = TaskId>) {
+        if !self.config.record_lock_contention {
+            return;
+        }
+        let blocked = self.current().blocked();
+        let blocked_waiters = waiters.filter(|tid| self.get(*tid).blocked()).count();
+        if self.lock_contention.len() <= lock.0 {
+            self.lock_contention.resize(lock.0 + 1, LockContention::default());
+        }
+        let contention = &mut self.lock_contention[lock.0];
+        contention.name = self.lock_names[lock.0];
+        contention.acquisitions += 1;
+        if blocked {
+            contention.blocked_acquisitions += 1;
+        }
+        contention.max_waiters = contention.max_waiters.max(blocked_waiters);
+    }
+
+    /// Look for a cycle of tasks that are each blocked waiting for a lock held by the next task in
+    /// the cycle, and describe it if there is one. Only meaningful once the execution has
+    /// deadlocked.
+    fn lock_cycle(&self) -> Option<String> {
+        // Report the cycle starting from its lowest task ID, so the report is deterministic
+        for task in self.tasks.iter().filter(|t| !t.finished()) {
+            let mut path = Vec::new();
+            let mut visited = TaskSet::new();
+            if self.find_lock_cycle(task.id(), task.id(), &mut path, &mut visited) {
+                let edges = path
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (tid, waiting))| {
+                        // Each task holds the lock the previous task in the cycle is waiting for
+                        let held = path[(i + path.len() - 1) % path.len()].1;
+                        format!(
+                            "{} (task {}) holds {} waiting {}",
+                            self.get(*tid).name().unwrap_or_else(|| "<unknown>".to_string()),
+                            tid.0,
+                            self.describe_lock(held),
+                            self.describe_lock(*waiting)
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                return Some(edges.join("; "));
+            }
+        }
+        None
+    }
+
+    /// Whether the execution has ended with tasks that can never finish. Unfinished attached tasks
+    /// always count. Detached tasks only count if [`Config::report_detached_deadlocks`] is set and
+    /// they are blocked or sleeping without a pending timer; a detached task waiting for a timer
+    /// isn't stuck, the execution just ended before the timer fired.
+    fn is_deadlocked(&self) -> bool {
+        self.tasks.iter().any(|t| {
+            if t.finished() {
+                false
+            } else if !t.detached {
+                true
+            } else {
+                self.config.report_detached_deadlocks
+                    && !t.runnable()
+                    && !self.timers.iter().any(|(_, tid)| *tid == t.id())
+            }
+        })
+    }
+
+    /// Describe the locks still held by tasks that have finished, if there are any. A finished
+    /// task only holds a lock if it leaked the guard, for example by storing it somewhere that
+    /// outlives the task or passing it to `std::mem::forget`.
+    fn leaked_locks(&self) -> Option<String> {
+        let leaks = self
+            .tasks
+            .iter()
+            .filter(|t| t.finished() && !t.held_locks().is_empty())
+            .map(|t| {
+                let mut locks = t.held_locks().to_vec();
+                locks.sort();
+                format!(
+                    "{} (task {}) holds {}",
+                    t.name().unwrap_or_else(|| "<unknown>".to_string()),
+                    t.id().0,
+                    locks
+                        .iter()
+                        .map(|lock| self.describe_lock(*lock))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+            .collect::<Vec<_>>();
+        if leaks.is_empty() {
+            None
+        } else {
+            Some(leaks.join("; "))
+        }
+    }
+
+    /// Depth-first search of the wait-for graph from `current`, looking for a path back to `start`
+    /// through tasks with higher IDs than `start`. On success, `path` contains each task in the
+    /// cycle along with the lock it's waiting for.
+    fn find_lock_cycle(
+        &self,
+        start: TaskId,
+        current: TaskId,
+        path: &mut Vec<(TaskId, LockId)>,
+        visited: &mut TaskSet,
+    ) -> bool {
+        let waiting = match self.get(current).waiting_lock() {
+            Some(lock) => lock,
+            None => return false,
+        };
+        path.push((current, waiting));
+        let holders = self
+            .tasks
+            .iter()
+            .filter(|t| t.id() != current && !t.finished() && t.holds_lock(waiting))
+            .map(|t| t.id())
+            .collect::<Vec<_>>();
+        for holder in holders {
+            if holder == start {
+                return true;
+            }
+            if holder > start && !visited.contains(holder) {
+                visited.insert(holder);
+                if self.find_lock_cycle(start, holder, path, visited) {
+                    return true;
+                }
+            }
+        }
+        path.pop();
+        false
+    }
+
     pub(crate) fn get_clock(&self, id: TaskId) -> &VectorClock {
         &self.tasks.get(id.0).unwrap().clock
     }
@@ -490,39 +917,89 @@ impl ExecutionState {
 
     /// Run the scheduler to choose the next task to run. `has_yielded` should be false if the
     /// scheduler is being invoked from within a running task. If scheduling fails, returns an Err
-    /// with a String describing the failure.
-    fn schedule(&mut self) -> Result<(), String> {
+    /// with the kind of failure and a String describing it.
+    fn schedule(&mut self) -> Result<(), (FailureKind, String)> {
         // Don't schedule twice. If `maybe_yield` ran the scheduler, we don't want to run it
         // again at the top of `step`.
         if self.next_task != ScheduledTask::None {
             return Ok(());
         }
 
+        // The current task's step is over, so tell the scheduler what it accessed
+        if let ScheduledTask::Some(tid) = self.current_task {
+            self.scheduler.borrow_mut().record_accesses(tid, &self.step_accesses);
+        }
+        self.step_accesses.clear();
+
         self.context_switches += 1;
 
+        if let Some(msg) = &self.failure {
+            return Err((FailureKind::LogicBug, msg.clone()));
+        }
+
         match self.config.max_steps {
             MaxSteps::FailAfter(n) if self.current_schedule.len() >= n => {
-                let msg = format!(
-                    "exceeded max_steps bound {}. this might be caused by an unfair schedule (e.g., a spin loop)?",
-                    n
-                );
-                return Err(msg);
+                // If no task can run, the execution is about to finish or deadlock, so let the
+                // checks below report that instead of blaming the step bound
+                let runnable = self.runnable_tasks().0;
+                if !runnable.is_empty() {
+                    let runnable = runnable
+                        .iter()
+                        .map(|id| {
+                            let t = self.get(*id);
+                            format!(
+                                "{} (task {})",
+                                t.name().unwrap_or_else(|| "<unknown>".to_string()),
+                                id.0
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    let msg = format!(
+                        "exceeded max_steps bound {} while tasks were still runnable: [{}]. this is likely a livelock, or might be caused by an unfair schedule (e.g., a spin loop)?",
+                        n,
+                        runnable.join(", ")
+                    );
+                    return Err((FailureKind::Livelock, msg));
+                }
             }
             MaxSteps::ContinueAfter(n) if self.current_schedule.len() >= n => {
                 self.next_task = ScheduledTask::Stopped;
+                self.stop_reason = Some(StopReason::MaxSteps);
                 return Ok(());
             }
             _ => {}
         }
 
-        let mut unfinished_attached = false;
-        let runnable = self
-            .tasks
-            .iter()
-            .inspect(|t| unfinished_attached = unfinished_attached || (!t.finished() && !t.detached))
-            .filter(|t| t.runnable())
-            .map(|t| t.id)
-            .collect::<SmallVec<[_; DEFAULT_INLINE_TASKS]>>();
+        if self
+            .deadline
+            .map(|deadline| Instant::now() >= deadline)
+            .unwrap_or(false)
+        {
+            if self.config.failure_persistence != FailurePersistence::None {
+                eprintln!(
+                    "test exceeded max_time after {} steps of the current iteration, stopping. this might be caused by a livelock (e.g., a spin loop)?\nlast schedule: \"{}\"\npass that string to `shuttle::replay` to replay it",
+                    self.current_schedule.len(),
+                    self.current_schedule.encode()
+                );
+            }
+            self.next_task = ScheduledTask::Stopped;
+            self.stop_reason = Some(StopReason::MaxTime);
+            return Ok(());
+        }
+
+        self.fire_timers();
+        let (mut runnable, unfinished_attached) = self.runnable_tasks();
+
+        // If every task is blocked but some are sleeping, advance the logical clock to the earliest
+        // timer
+        if runnable.is_empty() && unfinished_attached {
+            if let Some(next) = self.timers.iter().map(|(deadline, _)| *deadline).min() {
+                trace!(from = ?self.now, to = ?next, "advancing clock");
+                self.now = next;
+                self.fire_timers();
+                runnable = self.runnable_tasks().0;
+            }
+        }
 
         // We should finish execution when either
         // (1) There are no runnable tasks, or
@@ -534,6 +1011,28 @@ impl ExecutionState {
             return Ok(());
         }
 
+        // Setup tasks run before any other task, and don't preempt each other until they yield
+        if runnable.iter().any(|id| self.get(*id).setup) {
+            runnable.retain(|id| self.get(*id).setup);
+            if let Some(current) = self.current_task.id().filter(|id| runnable.contains(id)) {
+                runnable.retain(|id| *id == current);
+            }
+        }
+
+        // Only decisions with more than one choice can lead to different interleavings
+        if self.config.record_coverage && runnable.len() > 1 {
+            for id in runnable.iter() {
+                if let Some(label) = self.get(*id).coverage_label {
+                    self.coverage.insert(label);
+                }
+            }
+        }
+
+        if self.config.priority_inheritance {
+            let waits = self.lock_waits();
+            self.scheduler.borrow_mut().record_lock_waits(&waits);
+        }
+
         let is_yielding = std::mem::replace(&mut self.has_yielded, false);
 
         self.next_task = self
@@ -543,11 +1042,39 @@ impl ExecutionState {
             .map(ScheduledTask::Some)
             .unwrap_or(ScheduledTask::Stopped);
 
-        trace!(?runnable, next_task=?self.next_task);
+        trace!(?runnable, next_task=?self.next_task, "scheduling decision");
 
         Ok(())
     }
 
+    /// Each task that is blocked waiting for a lock, paired with each task that holds that lock
+    fn lock_waits(&self) -> Vec<(TaskId, TaskId)> {
+        let mut waits = Vec::new();
+        for waiter in self.tasks.iter().filter(|t| t.blocked()) {
+            if let Some(lock) = waiter.waiting_lock() {
+                for holder in self.tasks.iter() {
+                    if holder.id() != waiter.id() && !holder.finished() && holder.holds_lock(lock) {
+                        waits.push((waiter.id(), holder.id()));
+                    }
+                }
+            }
+        }
+        waits
+    }
+
+    /// The tasks that can run, and whether any attached task is unfinished
+    fn runnable_tasks(&self) -> (SmallVec<[TaskId; DEFAULT_INLINE_TASKS]>, bool) {
+        let mut unfinished_attached = false;
+        let runnable = self
+            .tasks
+            .iter()
+            .inspect(|t| unfinished_attached = unfinished_attached || (!t.finished() && !t.detached))
+            .filter(|t| t.runnable())
+            .map(|t| t.id)
+            .collect();
+        (runnable, unfinished_attached)
+    }
+
     /// Set the next task as the current task, and update our tracing span
     fn advance_to_next_task(&mut self) {
         debug_assert_ne!(self.next_task, ScheduledTask::None);
@@ -563,7 +1090,14 @@ impl ExecutionState {
         // `self.current_span_entered` before dropping the `self.current_span` it points to.
         self.current_span_entered.take();
         if let ScheduledTask::Some(tid) = self.current_task {
-            self.current_span = span!(Level::INFO, "step", i = self.current_schedule.len() - 1, task = tid.0);
+            let name = self.get(tid).name();
+            self.current_span = span!(
+                Level::INFO,
+                "step",
+                i = self.current_schedule.len() - 1,
+                task = tid.0,
+                name = name.as_deref()
+            );
             self.current_span_entered = Some(unsafe { extend_span_entered_lt(self.current_span.enter()) });
         }
     }
@@ -30,17 +30,52 @@ use crate::scheduler::serialization::serialize_schedule;
 use crate::scheduler::Schedule;
 use crate::{Config, FailurePersistence};
 
+/// The category a test failure falls into, which determines the advice we print alongside it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FailureKind {
+    /// Every unfinished task was blocked, so none of them could make progress
+    Deadlock,
+    /// The step bound was hit while some tasks were still runnable
+    Livelock,
+    /// The test itself reported a failure, e.g. by panicking or violating an invariant
+    LogicBug,
+}
+
+impl FailureKind {
+    fn label(self) -> &'static str {
+        match self {
+            FailureKind::Deadlock => "deadlock",
+            FailureKind::Livelock => "probable livelock",
+            FailureKind::LogicBug => "logic bug",
+        }
+    }
+
+    fn hint(self) -> &'static str {
+        match self {
+            FailureKind::Deadlock => "look for a lock ordering cycle, a missing notify or unpark, or a join or receive that can never complete",
+            FailureKind::Livelock => "look for a spin or retry loop waiting on a condition no other task establishes, or raise `max_steps` if the test is just long",
+            FailureKind::LogicBug => "the test panicked or failed an assertion, so start from the panic message above",
+        }
+    }
+}
+
 /// Produce a message describing how to replay a failing schedule.
 ///
 /// If `print_if_fresh` is true, the message will also be printed to stderr if this is the first
 /// time `persist_failure` has been called.
-pub fn persist_failure(schedule: &Schedule, message: String, config: &Config, print_if_fresh: bool) -> String {
+pub fn persist_failure(
+    schedule: &Schedule,
+    kind: FailureKind,
+    message: String,
+    config: &Config,
+    print_if_fresh: bool,
+) -> String {
     // Disarm the panic hook so that we don't print the failure twice
     if let PanicHookState::Persisted(persisted_message) = PANIC_HOOK.with(|lock| lock.lock().unwrap().clone()) {
         return persisted_message;
     }
 
-    let persisted_message = persist_failure_inner(schedule, message, config);
+    let persisted_message = persist_failure_inner(schedule, kind, message, config);
     PANIC_HOOK.with(|lock| *lock.lock().unwrap() = PanicHookState::Persisted(persisted_message.clone()));
     if print_if_fresh {
         eprintln!("{}", persisted_message);
@@ -52,13 +87,15 @@ pub fn persist_failure(schedule: &Schedule, message: String, config: &Config, pr
 pub fn persist_task_failure(schedule: &Schedule, task_name: String, config: &Config, print_if_fresh: bool) -> String {
     persist_failure(
         schedule,
+        FailureKind::LogicBug,
         format!("test panicked in task '{}'", task_name),
         config,
         print_if_fresh,
     )
 }
 
-fn persist_failure_inner(schedule: &Schedule, message: String, config: &Config) -> String {
+fn persist_failure_inner(schedule: &Schedule, kind: FailureKind, message: String, config: &Config) -> String {
+    let message = format!("{}\nfailure kind: {}\nhint: {}", message, kind.label(), kind.hint());
     if config.failure_persistence == FailurePersistence::None {
         return message;
     }
@@ -139,6 +176,12 @@ pub fn init_panic_hook(config: Config) -> PanicHookGuard {
     INIT.call_once(|| {
         let original_hook = panic::take_hook();
         panic::set_hook(Box::new(move |panic_info| {
+            // A panic that will be caught and returned to a `JoinHandle` isn't a test failure (yet),
+            // so leave the hook armed
+            if crate::thread::can_catch_panic() {
+                original_hook(panic_info);
+                return;
+            }
             let state = PANIC_HOOK.with(|lock| std::mem::replace(&mut *lock.lock().unwrap(), PanicHookState::Disarmed));
             // The hook is armed if this is the first time it's fired
             if let PanicHookState::Armed(config) = state {
@@ -4,3 +4,4 @@ pub(crate) mod runner;
 pub(crate) mod storage;
 pub(crate) mod task;
 pub(crate) mod thread;
+pub(crate) mod trace;
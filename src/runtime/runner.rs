@@ -1,17 +1,19 @@
-use crate::runtime::execution::Execution;
+use crate::runtime::execution::{Execution, StopReason};
 use crate::runtime::task::TaskId;
 use crate::runtime::thread::continuation::{ContinuationPool, CONTINUATION_POOL};
 use crate::scheduler::metrics::MetricsScheduler;
-use crate::scheduler::{Schedule, Scheduler};
+use crate::scheduler::{Accesses, ReplayScheduler, Schedule, Scheduler};
 use crate::Config;
 use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fmt::Debug;
 use std::panic;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
 use std::sync::Arc;
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tracing::{span, Level};
 
 /// A `Runner` is the entry-point for testing concurrent code.
@@ -19,26 +21,119 @@ use tracing::{span, Level};
 /// It takes as input a function to test and a `Scheduler` to run it under. It then executes that
 /// function as many times as dictated by the scheduler; each execution has its scheduling decisions
 /// resolved by the scheduler, which can make different choices for each execution.
+///
+/// A runner uses the default [`Config`] unless one is given with [`Runner::with_config`]:
+///
+/// ```
+/// use shuttle::scheduler::RandomScheduler;
+/// use shuttle::{Config, MaxSteps, Runner};
+///
+/// let mut config = Config::new();
+/// config.max_steps = MaxSteps::FailAfter(1000);
+///
+/// let stats = Runner::new(RandomScheduler::new(10)).with_config(config).run(|| {
+///     shuttle::thread::spawn(|| ()).join().unwrap();
+/// });
+/// assert_eq!(stats.iterations, 10);
+/// ```
 #[derive(Debug)]
 pub struct Runner<S: ?Sized + Scheduler> {
     scheduler: Rc<RefCell<MetricsScheduler<S>>>,
     config: Config,
+    progress: Option<ProgressCallback>,
+}
+
+/// How often a [`Runner`] should invoke its progress callback.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProgressInterval {
+    /// Invoke the callback after every `n` iterations
+    Iterations(usize),
+    /// Invoke the callback after the first iteration that ends at least the given amount of time
+    /// after the previous invocation (or the start of the test)
+    Time(Duration),
+}
+
+struct ProgressCallback {
+    interval: ProgressInterval,
+    callback: Box<dyn FnMut(&RunStats)>,
+}
+
+impl Debug for ProgressCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProgressCallback")
+            .field("interval", &self.interval)
+            .finish()
+    }
 }
 
 impl<S: Scheduler + 'static> Runner<S> {
-    /// Construct a new `Runner` that will use the given `Scheduler` to control the test.
-    pub fn new(scheduler: S, config: Config) -> Self {
+    /// Construct a new `Runner` that will use the given `Scheduler` to control the test, with the
+    /// default [`Config`].
+    pub fn new(scheduler: S) -> Self {
         let metrics_scheduler = MetricsScheduler::new(scheduler);
 
         Self {
             scheduler: Rc::new(RefCell::new(metrics_scheduler)),
-            config,
+            config: Config::new(),
+            progress: None,
         }
     }
 
-    /// Test the given function and return the number of times the function was invoked during the
-    /// test (i.e., the number of iterations run).
-    pub fn run<F>(self, f: F) -> usize
+    /// Use the given `Config` for the test instead of the default one.
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Invoke `callback` with the statistics of the test so far as it runs, as often as `interval`
+    /// dictates. This can be used to report the progress of long-running tests.
+    ///
+    /// The callback is only invoked between iterations, and has no influence on the scheduler, so
+    /// it does not affect which schedules the test explores.
+    pub fn set_progress_callback<C>(&mut self, interval: ProgressInterval, callback: C)
+    where
+        C: FnMut(&RunStats) + 'static,
+    {
+        if let ProgressInterval::Iterations(n) = interval {
+            assert!(n > 0, "progress interval must be at least one iteration");
+        }
+        self.progress = Some(ProgressCallback {
+            interval,
+            callback: Box::new(callback),
+        });
+    }
+
+    /// Test the given function and return statistics about the test, like how many iterations
+    /// were run and how long they took.
+    ///
+    /// If the `SHUTTLE_REPLAY` environment variable is set to an [encoded
+    /// schedule](Schedule::encode), like the one Shuttle prints when a test fails, this ignores the
+    /// runner's scheduler and instead replays that schedule once. This makes it easy to replay a
+    /// failure without changing the test, e.g. with `SHUTTLE_REPLAY=<schedule> cargo test my_test`.
+    pub fn run<F>(self, f: F) -> RunStats
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        if let Ok(encoded) = std::env::var("SHUTTLE_REPLAY") {
+            let schedule = Schedule::decode(&encoded).expect("SHUTTLE_REPLAY is not a valid schedule");
+            let mut runner = Runner::new(ReplayScheduler::new_from_schedule(schedule)).with_config(self.config);
+            runner.progress = self.progress;
+            return runner.run_inner(f);
+        }
+        self.run_inner(f)
+    }
+
+    /// Test the given function and return statistics about the test.
+    #[deprecated(note = "`Runner::run` now returns the statistics")]
+    pub fn run_with_stats<F>(self, f: F) -> RunStats
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.run(f)
+    }
+
+    pub(crate) fn run_inner<F>(mut self, f: F) -> RunStats
     where
         F: Fn() + Send + Sync + 'static,
     {
@@ -51,8 +146,15 @@ impl<S: Scheduler + 'static> Runner<S> {
             let start = Instant::now();
 
             let mut i = 0;
+            let mut hit_time_limit = false;
+            let mut hit_max_steps = false;
+            let deadline = self.config.max_time.map(|t| start + t);
+            let mut last_progress = start;
+            let mut lock_contention = Vec::<LockContention>::new();
+            let mut coverage = BTreeSet::new();
             loop {
-                if self.config.max_time.map(|t| start.elapsed() > t).unwrap_or(false) {
+                if deadline.map(|deadline| Instant::now() > deadline).unwrap_or(false) {
+                    hit_time_limit = true;
                     break;
                 }
 
@@ -61,17 +163,125 @@ impl<S: Scheduler + 'static> Runner<S> {
                     Some(s) => s,
                 };
 
-                let execution = Execution::new(self.scheduler.clone(), schedule);
+                let execution = Execution::new(self.scheduler.clone(), schedule, deadline);
                 let f = Arc::clone(&f);
-                span!(Level::INFO, "execution", i).in_scope(|| execution.run(&self.config, move || f()));
+                let outcome = span!(Level::INFO, "execution", i).in_scope(|| execution.run(&self.config, move || f()));
 
                 i += 1;
+
+                if lock_contention.len() < outcome.lock_contention.len() {
+                    lock_contention.resize(outcome.lock_contention.len(), LockContention::default());
+                }
+                for (total, contention) in lock_contention.iter_mut().zip(outcome.lock_contention) {
+                    total.name = total.name.or(contention.name);
+                    total.acquisitions += contention.acquisitions;
+                    total.blocked_acquisitions += contention.blocked_acquisitions;
+                    total.max_waiters = total.max_waiters.max(contention.max_waiters);
+                }
+                coverage.extend(outcome.coverage);
+
+                if let Some(progress) = self.progress.as_mut() {
+                    let due = match progress.interval {
+                        ProgressInterval::Iterations(n) => i % n == 0,
+                        ProgressInterval::Time(t) => last_progress.elapsed() >= t,
+                    };
+                    if due {
+                        last_progress = Instant::now();
+                        let (steps, max_steps) = self.scheduler.borrow().steps();
+                        (progress.callback)(&RunStats {
+                            iterations: i,
+                            steps,
+                            max_steps,
+                            elapsed: start.elapsed(),
+                            hit_time_limit: false,
+                            exhausted: false,
+                            lock_contention: lock_contention.clone(),
+                            coverage: coverage.clone(),
+                        });
+                    }
+                }
+
+                match outcome.stop_reason {
+                    Some(StopReason::MaxSteps) => hit_max_steps = true,
+                    Some(StopReason::MaxTime) => {
+                        hit_time_limit = true;
+                        break;
+                    }
+                    None => {}
+                }
+            }
+
+            let (steps, max_steps) = self.scheduler.borrow().steps();
+            let exhausted = !hit_time_limit && !hit_max_steps && self.scheduler.borrow().exhausted();
+            RunStats {
+                iterations: i,
+                steps,
+                max_steps,
+                elapsed: start.elapsed(),
+                hit_time_limit,
+                exhausted,
+                lock_contention,
+                coverage,
             }
-            i
         })
     }
 }
 
+/// Statistics about a test run by [`Runner::run`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RunStats {
+    /// The number of iterations (executions of the test) that were run
+    pub iterations: usize,
+    /// The total number of scheduling decisions, including random choices, made across all
+    /// iterations
+    pub steps: usize,
+    /// The largest number of scheduling decisions made in any one iteration
+    pub max_steps: usize,
+    /// The wall-clock time the test took
+    pub elapsed: Duration,
+    /// Whether the test stopped because it exceeded [`Config::max_time`], rather than because the
+    /// scheduler chose to stop (e.g., because it explored every schedule or ran its maximum number
+    /// of iterations)
+    pub hit_time_limit: bool,
+    /// Whether the test explored every possible schedule, so that the absence of a failure means no
+    /// schedule can fail. Only exhaustive schedulers like
+    /// [`DfsScheduler`](crate::scheduler::DfsScheduler) can set this, and only when no execution was
+    /// cut short by [`Config::max_steps`](crate::Config::max_steps) or [`Config::max_time`].
+    pub exhausted: bool,
+    /// How contended each lock was, if [`Config::record_lock_contention`] is enabled (and empty
+    /// otherwise). Locks are numbered in the order each execution first used them, so for most
+    /// tests, the same index refers to the same lock in every execution.
+    pub lock_contention: Vec<LockContention>,
+    coverage: BTreeSet<&'static str>,
+}
+
+impl RunStats {
+    /// The [coverage labels](crate::current::set_coverage_label) that took part in a scheduling
+    /// decision in any iteration, if [`Config::record_coverage`] is enabled (and empty otherwise).
+    /// A label takes part in a decision if some task was runnable with that label set while the
+    /// scheduler was choosing between more than one runnable task.
+    pub fn coverage(&self) -> &BTreeSet<&'static str> {
+        &self.coverage
+    }
+}
+
+/// How contended a single lock (a [`Mutex`](crate::sync::Mutex) or
+/// [`RwLock`](crate::sync::RwLock)) was across all the iterations of a test.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LockContention {
+    /// The lock's name, if it was created with a name (like
+    /// [`Mutex::new_named`](crate::sync::Mutex::new_named))
+    pub name: Option<&'static str>,
+    /// The number of times a task tried to acquire the lock
+    pub acquisitions: usize,
+    /// The number of those attempts that had to block because the lock wasn't available
+    pub blocked_acquisitions: usize,
+    /// The largest number of tasks that were blocked waiting for the lock at once
+    pub max_waiters: usize,
+}
+
 /// A `PortfolioRunner` is the same as a `Runner`, except that it can run multiple different
 /// schedulers (a "portfolio" of schedulers) in parallel. If any of the schedulers finds a failing
 /// execution of the test, the entire run fails.
@@ -129,7 +339,7 @@ impl PortfolioRunner {
                 thread::spawn(move || {
                     let scheduler = PortfolioStoppableScheduler { scheduler, stop_signal };
 
-                    let runner = Runner::new(scheduler, config);
+                    let runner = Runner::new(scheduler).with_config(config);
 
                     span!(Level::INFO, "job", i).in_scope(|| {
                         let ret = panic::catch_unwind(panic::AssertUnwindSafe(|| runner.run(move || f())));
@@ -199,7 +409,27 @@ impl<S: Scheduler> Scheduler for PortfolioStoppableScheduler<S> {
         }
     }
 
+    fn next_choice(&mut self, num_choices: usize, current_task: Option<TaskId>) -> Option<usize> {
+        if self.stop_signal.load(Ordering::SeqCst) {
+            None
+        } else {
+            self.scheduler.next_choice(num_choices, current_task)
+        }
+    }
+
+    fn record_accesses(&mut self, task: TaskId, accesses: &Accesses) {
+        self.scheduler.record_accesses(task, accesses)
+    }
+
+    fn record_lock_waits(&mut self, waits: &[(TaskId, TaskId)]) {
+        self.scheduler.record_lock_waits(waits)
+    }
+
     fn next_u64(&mut self) -> u64 {
         self.scheduler.next_u64()
     }
+
+    fn exhausted(&self) -> bool {
+        self.scheduler.exhausted()
+    }
 }
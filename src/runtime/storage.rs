@@ -43,6 +43,13 @@ impl StorageMap {
         self.order.push_back(key);
     }
 
+    /// Remove a storage slot, returning ownership of its value if it was still initialized.
+    pub fn remove(&mut self, key: StorageKey) -> Option<Box<dyn Any>> {
+        let value = self.locals.remove(&key)?;
+        self.order.retain(|k| *k != key);
+        value
+    }
+
     /// Return ownership of the next still-initialized storage slot.
     pub fn pop(&mut self) -> Option<Box<dyn Any>> {
         let key = self.order.pop_front()?;
@@ -6,11 +6,13 @@ use crate::runtime::thread::continuation::{ContinuationPool, PooledContinuation}
 use crate::thread::LocalKey;
 use bitvec::prelude::*;
 use std::any::Any;
+use std::backtrace::Backtrace;
 use std::cell::RefCell;
-use std::fmt::Debug;
+use std::fmt::{Debug, Display};
 use std::future::Future;
 use std::rc::Rc;
 use std::task::{Context, Waker};
+use tracing::trace;
 
 pub(crate) mod clock;
 pub(crate) mod waker;
@@ -47,6 +49,11 @@ pub(crate) struct Task {
     pub(super) continuation: Rc<RefCell<PooledContinuation>>,
 
     pub(crate) clock: VectorClock,
+    // The clock as of this task's last release fence, which relaxed atomic stores publish
+    pub(crate) release_fence_clock: VectorClock,
+    // The clocks of the stores this task's relaxed atomic loads have read from, which its next
+    // acquire fence synchronizes with
+    pub(crate) acquire_fence_clock: VectorClock,
 
     waiter: Option<TaskId>,
 
@@ -55,8 +62,42 @@ pub(crate) struct Task {
     woken: bool,
 
     name: Option<String>,
+    // The label set by `current::set_coverage_label`, if any
+    pub(crate) coverage_label: Option<&'static str>,
 
     local_storage: StorageMap,
+
+    // The locks this task currently holds, and the lock it's currently blocked waiting for (if any),
+    // so that we can report lock-ordering cycles when a deadlock is detected
+    held_locks: Vec<LockId>,
+    waiting_lock: Option<LockId>,
+
+    // Where this task was when it last yielded, if `Config::capture_backtraces` is enabled, so that
+    // we can report where each task is blocked when a deadlock is detected
+    pub(crate) backtrace: Option<Backtrace>,
+
+    // Incremented every time this task drains its store buffer, so that atomics can tell whether a
+    // store this task buffered has become visible to other tasks
+    store_buffer_epoch: usize,
+    // Whether this task's last weak compare-and-exchange failed spuriously, in which case its next
+    // one can't (see `Atomic::compare_exchange_weak`)
+    pub(crate) failed_weak_cas_spuriously: bool,
+    // Whether a panic in this task is caught and returned from `JoinHandle::join`, rather than
+    // failing the test. True for spawned threads whose `JoinHandle` is still alive.
+    pub(crate) catch_panic: bool,
+    // The unpark token for `thread::park`, holding the joined clocks of the `unpark` calls that set
+    // it, and whether this task is currently blocked in `park` waiting for the token
+    park_token: Option<VectorClock>,
+    parked: bool,
+    // Whether `JoinHandle::abort` was called for this thread, so that it unwinds at its next
+    // cancellation point
+    pub(crate) aborted: bool,
+    // How many `sync::atomic::critical` regions this task is inside. While this is non-zero, the
+    // task isn't preempted at yield points as long as it can keep running.
+    pub(crate) critical_depth: usize,
+    // Whether this thread was spawned by `thread::spawn_setup` and hasn't yielded yet. While it
+    // can run, no other task is scheduled.
+    pub(crate) setup: bool,
 }
 
 impl Task {
@@ -75,12 +116,26 @@ impl Task {
             state: TaskState::Runnable,
             continuation,
             clock,
+            release_fence_clock: VectorClock::new(),
+            acquire_fence_clock: VectorClock::new(),
             waiter: None,
             waker,
             woken: false,
             detached: false,
             name,
+            coverage_label: None,
             local_storage: StorageMap::new(),
+            held_locks: Vec::new(),
+            store_buffer_epoch: 0,
+            failed_weak_cas_spuriously: false,
+            catch_panic: false,
+            park_token: None,
+            parked: false,
+            aborted: false,
+            critical_depth: 0,
+            setup: false,
+            waiting_lock: None,
+            backtrace: None,
         }
     }
 
@@ -104,7 +159,12 @@ impl Task {
         let mut future = Box::pin(future);
         Self::new(
             move || {
-                let waker = ExecutionState::with(|state| state.current_mut().waker());
+                // We don't track which objects a future accesses, so every step of an async task
+                // might access any object
+                let waker = ExecutionState::with(|state| {
+                    state.record_unknown_access();
+                    state.current_mut().waker()
+                });
                 let cx = &mut Context::from_waker(&waker);
                 while future.as_mut().poll(cx).is_pending() {
                     ExecutionState::with(|state| state.current_mut().sleep_unless_woken());
@@ -148,6 +208,9 @@ impl Task {
 
     pub(crate) fn block(&mut self) {
         assert!(self.state != TaskState::Finished);
+        if self.state != TaskState::Blocked {
+            trace!(task = self.id.0, "blocked");
+        }
         self.state = TaskState::Blocked;
     }
 
@@ -160,6 +223,9 @@ impl Task {
         // Note we don't assert the task is blocked here. For example, a task invoking its own waker
         // will not be blocked when this is called.
         assert!(self.state != TaskState::Finished);
+        if self.state != TaskState::Runnable {
+            trace!(task = self.id.0, "unblocked");
+        }
         self.state = TaskState::Runnable;
     }
 
@@ -213,6 +279,78 @@ impl Task {
         self.name.clone()
     }
 
+    /// Block this task in `thread::park` unless its unpark token is already set.
+    pub(crate) fn park(&mut self) {
+        if self.park_token.is_none() {
+            self.parked = true;
+            self.block();
+        }
+    }
+
+    /// Set this task's unpark token, recording the clock of the unparking task, and unblock it if
+    /// it's blocked in `thread::park`. Does nothing if the task has already finished.
+    pub(crate) fn unpark(&mut self, clock: &VectorClock) {
+        if self.finished() {
+            return;
+        }
+        self.park_token.get_or_insert_with(VectorClock::new).update(clock);
+        if std::mem::replace(&mut self.parked, false) {
+            self.unblock();
+        }
+    }
+
+    /// Consume this task's unpark token, returning the clock it recorded.
+    pub(crate) fn take_park_token(&mut self) -> Option<VectorClock> {
+        self.park_token.take()
+    }
+
+    /// Record that this task is waiting to acquire the given lock (or is no longer waiting for any
+    /// lock, if `None`).
+    pub(crate) fn set_waiting_lock(&mut self, lock: Option<LockId>) {
+        self.waiting_lock = lock;
+    }
+
+    /// Record that this task has acquired the given lock, and so is no longer waiting for it.
+    pub(crate) fn acquire_lock(&mut self, lock: LockId) {
+        if self.waiting_lock == Some(lock) {
+            self.waiting_lock = None;
+        }
+        self.held_locks.push(lock);
+    }
+
+    /// Record that this task has released the given lock.
+    pub(crate) fn release_lock(&mut self, lock: LockId) {
+        let index = self
+            .held_locks
+            .iter()
+            .position(|held| *held == lock)
+            .expect("released a lock that wasn't held");
+        self.held_locks.swap_remove(index);
+    }
+
+    pub(crate) fn holds_lock(&self, lock: LockId) -> bool {
+        self.held_locks.contains(&lock)
+    }
+
+    /// The locks this task currently holds, in no particular order.
+    pub(crate) fn held_locks(&self) -> &[LockId] {
+        &self.held_locks
+    }
+
+    pub(crate) fn waiting_lock(&self) -> Option<LockId> {
+        self.waiting_lock
+    }
+
+    /// The number of times this task has drained its store buffer.
+    pub(crate) fn store_buffer_epoch(&self) -> usize {
+        self.store_buffer_epoch
+    }
+
+    /// Drain this task's store buffer, making any atomic store it buffered visible to other tasks.
+    pub(crate) fn drain_store_buffer(&mut self) {
+        self.store_buffer_epoch += 1;
+    }
+
     /// Retrieve a reference to the given thread-local storage slot.
     ///
     /// Returns Some(Err(_)) if the slot has already been destructed. Returns None if the slot has
@@ -276,6 +414,17 @@ impl From<TaskId> for usize {
     }
 }
 
+/// A `LockId` is a unique identifier for a lock (a `Mutex` or `RwLock`), used to describe the
+/// locks involved in a deadlock. `LockId`s are never reused within a single execution.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord, Debug)]
+pub(crate) struct LockId(pub(super) usize);
+
+impl Display for LockId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "L{}", self.0)
+    }
+}
+
 /// A `TaskSet` is a set of `TaskId`s but implemented efficiently as an array of bools.
 // TODO this probably won't work well with large numbers of tasks -- maybe a BitVec?
 #[derive(PartialEq, Eq)]
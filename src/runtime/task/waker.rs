@@ -1,5 +1,6 @@
 use crate::runtime::execution::ExecutionState;
 use crate::runtime::task::TaskId;
+use crate::scheduler::ObjectId;
 use std::task::{RawWaker, RawWakerVTable, Waker};
 
 // Safety: the `RawWaker` interface is unsafe because it requires manually enforcing resource
@@ -39,6 +40,7 @@ unsafe fn raw_waker_wake(data: *const ()) {
         }
 
         waiter.wake();
+        state.record_access(ObjectId::task(task_id));
     });
 }
 
@@ -5,8 +5,10 @@
 #![allow(deprecated)]
 
 use crate::runtime::execution::ExecutionState;
+use crate::scheduler::ObjectId;
 use generator::{Generator, Gn};
 use scoped_tls::scoped_thread_local;
+use std::backtrace::Backtrace;
 use std::cell::{Cell, RefCell};
 use std::collections::VecDeque;
 use std::ops::Deref;
@@ -257,10 +259,58 @@ impl std::fmt::Debug for PooledContinuation {
 unsafe impl Send for PooledContinuation {}
 
 /// Possibly yield back to the executor to perform a context switch.
+///
+/// Every yield point other than an atomic operation drains the current task's store buffer, as
+/// other synchronization primitives make all prior stores visible.
+///
+/// The caller doesn't say which shared object it's operating on, so the steps on either side of
+/// the yield point are treated as possibly accessing any shared object. Primitives that know which
+/// object they operate on should prefer [`switch_on`].
 pub(crate) fn switch() {
+    ExecutionState::with(|s| {
+        s.current_mut().drain_store_buffer();
+        s.record_unknown_access();
+    });
+    switch_atomic();
+    ExecutionState::with(|s| s.record_unknown_access());
+}
+
+/// Like [`switch`], but for a primitive that only accesses the given shared object on either side
+/// of the yield point.
+pub(crate) fn switch_on(object: ObjectId) {
+    ExecutionState::with(|s| {
+        s.current_mut().drain_store_buffer();
+        s.record_access(object);
+    });
+    switch_atomic();
+    ExecutionState::with(|s| s.record_access(object));
+}
+
+/// Like [`switch_on`], but for a primitive that only accesses the given shared object before the
+/// yield point, and none after it.
+pub(crate) fn switch_after(object: ObjectId) {
+    ExecutionState::with(|s| {
+        s.current_mut().drain_store_buffer();
+        s.record_access(object);
+    });
+    switch_atomic();
+}
+
+/// Possibly yield back to the executor to perform a context switch, without draining the current
+/// task's store buffer. Atomic operations use this, and manage the store buffer themselves.
+///
+/// Returns true if the current task was switched out, so other tasks might have run.
+pub(crate) fn switch_atomic() -> bool {
     if ExecutionState::maybe_yield() {
+        if ExecutionState::with(|s| s.config.capture_backtraces) {
+            let backtrace = Backtrace::force_capture();
+            ExecutionState::with(|s| s.current_mut().backtrace = Some(backtrace));
+        }
         let r = generator::yield_(ContinuationOutput::Yielded).unwrap();
         assert!(matches!(r, ContinuationInput::Resume));
+        true
+    } else {
+        false
     }
 }
 
@@ -1,3 +1,3 @@
 pub(crate) mod continuation;
 
-pub(crate) use continuation::switch;
+pub(crate) use continuation::{switch, switch_after, switch_atomic, switch_on};
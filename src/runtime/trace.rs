@@ -0,0 +1,160 @@
+//! This module renders a schedule as a human-readable listing of what each step of an execution
+//! did. It replays the schedule while listening to the `tracing` events that the runtime and the
+//! synchronization primitives emit, and labels each step with the events that happened during it.
+
+use crate::scheduler::{ReplayScheduler, Schedule};
+use crate::{Config, FailurePersistence, Runner};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Record};
+use tracing::{Event, Id, Metadata, Subscriber};
+
+pub(crate) fn display_trace<F>(f: F, schedule: &Schedule) -> String
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let subscriber = TraceSubscriber::default();
+    let result = {
+        let _guard = tracing::subscriber::set_default(subscriber.clone());
+        let mut config = Config::new();
+        config.failure_persistence = FailurePersistence::None;
+        let runner = Runner::new(ReplayScheduler::new_from_schedule(schedule.clone())).with_config(config);
+        panic::catch_unwind(AssertUnwindSafe(move || runner.run_inner(f)))
+    };
+
+    let mut trace = String::new();
+    for step in subscriber.steps.lock().unwrap().iter() {
+        if step.events.is_empty() {
+            writeln!(&mut trace, "step {}: task {}", step.index, step.task).unwrap();
+        }
+        for event in &step.events {
+            write!(&mut trace, "step {}: task {} {}", step.index, step.task, event.message).unwrap();
+            // Events about another task, like waking it up, name that task too
+            match event.task {
+                Some(task) if task != step.task => writeln!(&mut trace, " task {}", task).unwrap(),
+                _ => writeln!(&mut trace).unwrap(),
+            }
+        }
+    }
+    if let Err(e) = result {
+        let message = e
+            .downcast_ref::<String>()
+            .map(String::as_str)
+            .or_else(|| e.downcast_ref::<&str>().copied())
+            .unwrap_or("<unknown panic>");
+        writeln!(&mut trace, "execution failed: {}", message).unwrap();
+    }
+    trace
+}
+
+#[derive(Debug)]
+struct Step {
+    index: u64,
+    task: u64,
+    events: Vec<TraceEvent>,
+}
+
+#[derive(Debug)]
+struct TraceEvent {
+    message: String,
+    task: Option<u64>,
+}
+
+/// A `Subscriber` that groups the events emitted during each `step` span of an execution
+#[derive(Clone, Default)]
+struct TraceSubscriber {
+    next_id: Arc<AtomicU64>,
+    // For each span we've seen, the index into `steps` of the step it's for, if it's a step span
+    spans: Arc<Mutex<HashMap<u64, Option<usize>>>>,
+    entered: Arc<Mutex<Vec<u64>>>,
+    steps: Arc<Mutex<Vec<Step>>>,
+}
+
+#[derive(Default)]
+struct FieldVisitor {
+    message: Option<String>,
+    index: Option<u64>,
+    task: Option<u64>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = Some(format!("{:?}", value));
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "i" => self.index = Some(value),
+            "task" => self.task = Some(value),
+            _ => {}
+        }
+    }
+}
+
+impl Subscriber for TraceSubscriber {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let mut visitor = FieldVisitor::default();
+        span.record(&mut visitor);
+        let step = match (span.metadata().name(), visitor.index, visitor.task) {
+            ("step", Some(index), Some(task)) => {
+                let mut steps = self.steps.lock().unwrap();
+                steps.push(Step {
+                    index,
+                    task,
+                    events: vec![],
+                });
+                Some(steps.len() - 1)
+            }
+            _ => None,
+        };
+        self.spans.lock().unwrap().insert(id, step);
+        Id::from_u64(id)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let message = match visitor.message {
+            // Scheduling decisions are already implied by which task runs each step
+            Some(message) if message != "scheduling decision" => message,
+            _ => return,
+        };
+
+        let step = self
+            .entered
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find_map(|id| self.spans.lock().unwrap().get(id).copied().flatten());
+        if let Some(step) = step {
+            self.steps.lock().unwrap()[step].events.push(TraceEvent {
+                message,
+                task: visitor.task,
+            });
+        }
+    }
+
+    fn enter(&self, span: &Id) {
+        self.entered.lock().unwrap().push(span.into_u64());
+    }
+
+    fn exit(&self, _span: &Id) {
+        self.entered.lock().unwrap().pop();
+    }
+}
@@ -6,15 +6,34 @@ use crate::scheduler::{Schedule, Scheduler};
 const DFS_RANDOM_SEED: u64 = 0x12345678;
 
 /// A scheduler that performs an exhaustive, depth-first enumeration of all possible schedules.
+///
+/// A DFS scheduler can optionally bound the number of preemptions in each schedule, using
+/// iterative context bounding (as in [CHESS]). A preemption is a context switch away from a task
+/// that could have kept running; switching away from a task that blocked, finished, or asked to
+/// yield (e.g., with [`thread::yield_now`](crate::thread::yield_now)) is not a preemption. The
+/// scheduler first explores every schedule with no preemptions, then every schedule with at most
+/// one, and so on up to the bound. Each round re-explores the schedules of the rounds before it,
+/// but most concurrency bugs need only a few preemptions, so small bounds find them quickly.
+///
+/// [CHESS]: https://www.microsoft.com/en-us/research/publication/iterative-context-bounding-for-systematic-testing-of-multithreaded-programs/
 #[derive(Debug)]
 pub struct DfsScheduler {
     max_iterations: Option<usize>,
     allow_random_data: bool,
+    max_preemptions: Option<usize>,
 
     iterations: usize,
     // Vec<(previous choice, was that the last choice at that level)>
     levels: Vec<(TaskId, bool)>,
     steps: usize,
+    // The preemption bound for the current round of the search, and the number of preemptions in
+    // the current execution so far
+    preemption_bound: usize,
+    preemptions: usize,
+    // Whether the search finished every schedule, and whether any execution used random data, which
+    // the search doesn't enumerate
+    exhausted: bool,
+    used_random_data: bool,
 
     data_source: FixedDataSource,
 }
@@ -26,6 +45,21 @@ impl DfsScheduler {
     /// explore all possible values for the random choices. To ensure determinism, each execution
     /// will use the same sequence of random choices.
     pub fn new(max_iterations: Option<usize>, allow_random_data: bool) -> Self {
+        Self::new_inner(max_iterations, None, allow_random_data)
+    }
+
+    /// Construct a new DFSScheduler that only explores schedules with at most `max_preemptions`
+    /// preemptions, using iterative context bounding. Like [`DfsScheduler::new`], the search will
+    /// stop after `max_iterations` iterations if that bound is provided.
+    pub fn new_with_preemption_bound(
+        max_iterations: Option<usize>,
+        max_preemptions: usize,
+        allow_random_data: bool,
+    ) -> Self {
+        Self::new_inner(max_iterations, Some(max_preemptions), allow_random_data)
+    }
+
+    fn new_inner(max_iterations: Option<usize>, max_preemptions: Option<usize>, allow_random_data: bool) -> Self {
         let data_source = FixedDataSource::initialize(DFS_RANDOM_SEED);
 
         Self {
@@ -33,7 +67,12 @@ impl DfsScheduler {
             iterations: 0,
             levels: vec![],
             steps: 0,
+            preemption_bound: 0,
+            preemptions: 0,
+            exhausted: false,
+            used_random_data: false,
             allow_random_data,
+            max_preemptions,
             data_source,
         }
     }
@@ -45,33 +84,15 @@ impl DfsScheduler {
     fn has_more_choices(&self, index: usize) -> bool {
         self.levels[index..].iter().any(|(_, last)| !*last)
     }
-}
-
-impl Scheduler for DfsScheduler {
-    fn new_execution(&mut self) -> Option<Schedule> {
-        if self.max_iterations.map(|mi| self.iterations >= mi).unwrap_or(false) {
-            return None;
-        }
-
-        // If there are no more choices to make at any level, we're done
-        if self.iterations > 0 && !self.has_more_choices(0) {
-            return None;
-        }
-
-        self.iterations += 1;
-        self.steps = 0;
-
-        Some(Schedule::new(self.data_source.reinitialize()))
-    }
 
-    // TODO should we respect `is_yielding` by not allowing `current` to be scheduled next? That
-    // TODO would be unsound but perhaps useful for validating some code
-    fn next_task(&mut self, runnable: &[TaskId], _current: Option<TaskId>, _is_yielding: bool) -> Option<TaskId> {
+    /// Choose one of the `options` at the current level, picking up the search where the previous
+    /// execution left off.
+    fn choose(&mut self, options: &[TaskId]) -> TaskId {
         let next = if self.steps >= self.levels.len() {
             // First time we've reached this level
             assert_eq!(self.steps, self.levels.len());
-            let to_run = runnable.first().unwrap();
-            self.levels.push((*to_run, runnable.len() == 1));
+            let to_run = options.first().unwrap();
+            self.levels.push((*to_run, options.len() == 1));
             *to_run
         } else {
             let (last_choice, was_last) = self.levels[self.steps];
@@ -84,23 +105,83 @@ impl Scheduler for DfsScheduler {
                     !was_last,
                     "if we are making a change, there should be another available option"
                 );
-                let next_idx = runnable.iter().position(|tid| *tid == last_choice).unwrap() + 1;
-                let next = runnable[next_idx];
+                let next_idx = options.iter().position(|tid| *tid == last_choice).unwrap() + 1;
+                let next = options[next_idx];
                 self.levels.drain(self.steps..);
-                self.levels.push((next, next_idx == runnable.len() - 1));
+                self.levels.push((next, next_idx == options.len() - 1));
                 next
             }
         };
 
         self.steps += 1;
 
+        next
+    }
+}
+
+impl Scheduler for DfsScheduler {
+    fn new_execution(&mut self) -> Option<Schedule> {
+        if self.max_iterations.map(|mi| self.iterations >= mi).unwrap_or(false) {
+            return None;
+        }
+
+        // If there are no more choices to make at any level, we're done with this round, and can
+        // start the next round with a larger preemption bound if there is one
+        if self.iterations > 0 && !self.has_more_choices(0) {
+            match self.max_preemptions {
+                Some(max_preemptions) if self.preemption_bound < max_preemptions => {
+                    self.preemption_bound += 1;
+                    self.levels.clear();
+                }
+                Some(_) => return None,
+                None => {
+                    self.exhausted = !self.used_random_data;
+                    return None;
+                }
+            }
+        }
+
+        self.iterations += 1;
+        self.steps = 0;
+        self.preemptions = 0;
+
+        Some(Schedule::new(self.data_source.reinitialize()))
+    }
+
+    // TODO should we respect `is_yielding` by not allowing `current` to be scheduled next? That
+    // TODO would be unsound but perhaps useful for validating some code
+    fn next_task(&mut self, runnable: &[TaskId], current: Option<TaskId>, is_yielding: bool) -> Option<TaskId> {
+        // Switching away from the current task is a preemption if it could have kept running
+        let preemptible = current.filter(|tid| !is_yielding && runnable.contains(tid));
+        let next = match (preemptible, self.max_preemptions) {
+            (Some(current), Some(_)) if self.preemptions >= self.preemption_bound => self.choose(&[current]),
+            _ => self.choose(runnable),
+        };
+
+        if preemptible.map(|current| current != next).unwrap_or(false) {
+            self.preemptions += 1;
+        }
+
         Some(next)
     }
 
+    // Choices aren't context switches, so they never count as preemptions
+    fn next_choice(&mut self, num_choices: usize, _current: Option<TaskId>) -> Option<usize> {
+        let options = (0..num_choices).map(TaskId::from).collect::<Vec<_>>();
+        Some(usize::from(self.choose(&options)))
+    }
+
     fn next_u64(&mut self) -> u64 {
         if !self.allow_random_data {
             panic!("requested random data from DFS scheduler with allow_random_data = false");
         }
+        self.used_random_data = true;
         self.data_source.next_u64()
     }
+
+    // A preemption-bounded search deliberately skips schedules, and random data is always the same
+    // sequence of values, so only an unbounded search that never used random data is exhaustive
+    fn exhausted(&self) -> bool {
+        self.exhausted
+    }
 }
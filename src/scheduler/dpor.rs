@@ -0,0 +1,485 @@
+use crate::runtime::task::clock::VectorClock;
+use crate::runtime::task::TaskId;
+use crate::scheduler::data::fixed::FixedDataSource;
+use crate::scheduler::data::DataSource;
+use crate::scheduler::{Accesses, ObjectId, ObjectKind, Schedule, Scheduler};
+use std::collections::HashMap;
+
+const DPOR_RANDOM_SEED: u64 = 0x12345678;
+
+/// A scheduler that performs an exhaustive enumeration of schedules, like [`DfsScheduler`], but
+/// uses dynamic partial-order reduction (DPOR) to skip schedules that only differ from an already
+/// explored one in the order of independent steps.
+///
+/// Two steps are independent if they didn't access any of the same shared objects (like a
+/// [`Mutex`](crate::sync::Mutex), an atomic, or a thread's join state), in which case running them
+/// in either order leads to the same state. Steps that only took or released read access to the
+/// same [`RwLock`](crate::sync::RwLock) are independent too, as readers share the lock, so the
+/// scheduler explores one order of a group of concurrent readers instead of all of them (unless the
+/// lock limits how many readers it allows). After each execution, the scheduler looks for pairs of
+/// dependent steps that could have run in the opposite order, and only schedules executions that
+/// reverse one of those races (following Flanagan and Godefroid's algorithm, with sleep sets). For
+/// programs whose threads mostly access disjoint objects, this explores far fewer executions than
+/// DFS, while still finding every bug DFS would find. Some executions are stopped early, once the
+/// scheduler can tell they are equivalent to one it already explored.
+///
+/// Steps that use primitives that don't report which objects they access (like a
+/// [`Condvar`](crate::sync::Condvar), a channel, or an async task) are treated as dependent on
+/// every other step, so they get no reduction but are still explored soundly. The same goes for
+/// every step while [`Config::store_buffering`](crate::Config::store_buffering) is enabled, as
+/// buffered stores can become visible at any yield point. Threads that communicate through state
+/// Shuttle doesn't control (like `std` atomics) are invisible to the scheduler, so DPOR may miss
+/// bugs that depend on the order of those accesses.
+///
+/// [`DfsScheduler`]: crate::scheduler::DfsScheduler
+#[derive(Debug)]
+pub struct DporScheduler {
+    max_iterations: Option<usize>,
+    allow_random_data: bool,
+
+    iterations: usize,
+    // The scheduling decisions of the current execution, and the alternatives still to explore at
+    // each of them. Persists across executions, as each execution replays a prefix of the last.
+    nodes: Vec<Node>,
+    // The index into `nodes` of the next decision to make in the current execution
+    position: usize,
+    // The index into `nodes` of the decision that chose the currently running task
+    current_node: Option<usize>,
+    // The steps of the current execution so far
+    trace: Vec<Step>,
+    // Happens-before tracking for the current execution
+    clocks: Clocks,
+    // The sleep set at the current point of the current execution: tasks whose next step we
+    // already explored from an earlier point, with what that step accessed. Running them now would
+    // only lead to equivalent schedules, until some step that conflicts with them runs.
+    sleep: Vec<(TaskId, Accesses)>,
+    // Whether we stopped the current execution early because every runnable task was asleep
+    sleep_blocked: bool,
+    // Whether the search finished every equivalence class of schedules, and whether any execution
+    // used random data, which the search doesn't enumerate
+    exhausted: bool,
+    used_random_data: bool,
+
+    data_source: FixedDataSource,
+}
+
+#[derive(Debug)]
+enum Node {
+    /// A choice of which task to run next
+    Schedule {
+        enabled: Vec<TaskId>,
+        chosen: TaskId,
+        // Tasks that must be explored at this node, including the ones already explored
+        backtrack: Vec<TaskId>,
+        // Tasks already explored at this node, in order, with what their step accessed
+        done: Vec<(TaskId, Accesses)>,
+        // Tasks we don't need to explore at this node, because running them here leads to schedules
+        // equivalent to ones we've explored from an earlier node
+        sleep: Vec<TaskId>,
+    },
+    /// A nondeterministic choice made by the running task, which we enumerate exhaustively
+    Choice { num_choices: usize, chosen: usize },
+}
+
+#[derive(Debug)]
+struct Step {
+    task: TaskId,
+    // The index into `nodes` of the decision that chose to run this step
+    node: usize,
+    accesses: Accesses,
+    // The step's vector clock, counting each task's steps
+    clock: VectorClock,
+}
+
+#[derive(Debug)]
+struct Clocks {
+    // The clock of each task's latest step, or of the step that spawned it if it hasn't run yet
+    tasks: HashMap<TaskId, VectorClock>,
+    // The clock of the latest step to access each object. For shared lock accesses, which don't
+    // depend on each other, this is instead the join of the clocks of every such step so far.
+    objects: HashMap<ObjectId, VectorClock>,
+    // The clock of the latest step that might have accessed any object
+    unknown: VectorClock,
+    // The join of the clocks of every step so far
+    all: VectorClock,
+}
+
+impl Clocks {
+    fn new() -> Self {
+        Self {
+            tasks: HashMap::new(),
+            objects: HashMap::new(),
+            unknown: VectorClock::new(),
+            all: VectorClock::new(),
+        }
+    }
+
+    /// Update `clock` so that it happens after every earlier step whose access conflicts with an
+    /// access to `object`
+    fn depend_on(&self, clock: &mut VectorClock, object: ObjectId) {
+        let mut update = |object| {
+            if let Some(object_clock) = self.objects.get(&object) {
+                clock.update(object_clock);
+            }
+        };
+        match object.0 {
+            // Shared accesses only depend on the exclusive accesses to the same lock
+            ObjectKind::SharedLock(lock) => update(ObjectId::lock(lock)),
+            ObjectKind::Lock(lock) => {
+                update(object);
+                update(ObjectId::shared_lock(lock));
+            }
+            _ => update(object),
+        }
+    }
+
+    /// Record that a step with the given clock accessed `object`
+    fn access(&mut self, object: ObjectId, clock: &VectorClock) {
+        match object.0 {
+            ObjectKind::SharedLock(_) => self
+                .objects
+                .entry(object)
+                .or_insert_with(VectorClock::new)
+                .update(clock),
+            _ => {
+                self.objects.insert(object, clock.clone());
+            }
+        }
+    }
+}
+
+impl DporScheduler {
+    /// Construct a new DporScheduler with an optional bound on how many iterations to run. Like a
+    /// [`DfsScheduler`](crate::scheduler::DfsScheduler), a DporScheduler can optionally allow
+    /// random data to be generated by the test (using `shuttle::rand`), which makes the search
+    /// incomplete, as each execution uses the same sequence of random choices.
+    pub fn new(max_iterations: Option<usize>, allow_random_data: bool) -> Self {
+        let data_source = FixedDataSource::initialize(DPOR_RANDOM_SEED);
+
+        Self {
+            max_iterations,
+            allow_random_data,
+            iterations: 0,
+            nodes: vec![],
+            position: 0,
+            current_node: None,
+            trace: vec![],
+            clocks: Clocks::new(),
+            sleep: vec![],
+            sleep_blocked: false,
+            exhausted: false,
+            used_random_data: false,
+            data_source,
+        }
+    }
+
+    /// Change the deepest decision that still has an alternative to explore, and discard all the
+    /// decisions after it. Returns false if there are no alternatives left anywhere.
+    fn advance(&mut self) -> bool {
+        while let Some(node) = self.nodes.last_mut() {
+            match node {
+                Node::Schedule {
+                    chosen,
+                    backtrack,
+                    done,
+                    sleep,
+                    ..
+                } => {
+                    let next = backtrack
+                        .iter()
+                        .find(|tid| !sleep.contains(tid) && !done.iter().any(|(done, _)| done == *tid));
+                    if let Some(next) = next {
+                        *chosen = *next;
+                        done.push((*next, Accesses::Unknown));
+                        return true;
+                    }
+                }
+                Node::Choice { num_choices, chosen } => {
+                    if *chosen + 1 < *num_choices {
+                        *chosen += 1;
+                        return true;
+                    }
+                }
+            }
+            self.nodes.pop();
+        }
+        false
+    }
+
+    /// Check whether step `j` of the trace happens before a step with the given clock.
+    fn happens_before(&self, j: usize, clock: &VectorClock) -> bool {
+        let step = &self.trace[j];
+        get(clock, step.task) >= get(&step.clock, step.task)
+    }
+
+    /// Look for earlier steps that race with a step by `task` with the given accesses and pre-step
+    /// clock, and make sure we'll explore running `task` (or something that leads to it) before
+    /// them.
+    ///
+    /// Usually only the latest race matters, as reversing it leads to an execution where we'll
+    /// find the earlier ones. But if `task` wasn't enabled before the latest racing step, we can't
+    /// run it there, so we keep looking back until we find a race where it was.
+    fn add_backtrack_points(&mut self, task: TaskId, accesses: &Accesses, clock: &VectorClock) {
+        for j in (0..self.trace.len()).rev() {
+            let step = &self.trace[j];
+            if step.task == task || !step.accesses.conflicts_with(accesses) || self.happens_before(j, clock) {
+                continue;
+            }
+            if self.add_backtrack_point(j, task, clock) {
+                break;
+            }
+        }
+    }
+
+    /// Reverse the race between step `j` and a step by `task` with the given pre-step clock.
+    /// Returns true if `task` itself was enabled before step `j`.
+    fn add_backtrack_point(&mut self, j: usize, task: TaskId, clock: &VectorClock) -> bool {
+        // To reverse the race, we need to run either `task` itself at the racing step's node, or
+        // some other task whose later steps lead to this one
+        let node = self.trace[j].node;
+        let Node::Schedule { enabled, .. } = &self.nodes[node] else {
+            unreachable!("steps are always chosen by a scheduling decision");
+        };
+        let candidates = enabled
+            .iter()
+            .copied()
+            .filter(|&tid| {
+                tid == task
+                    || (j + 1..self.trace.len()).any(|m| self.trace[m].task == tid && self.happens_before(m, clock))
+            })
+            .collect::<Vec<_>>();
+
+        let Node::Schedule { enabled, backtrack, .. } = &mut self.nodes[node] else {
+            unreachable!();
+        };
+        if candidates.is_empty() {
+            for tid in enabled.iter() {
+                if !backtrack.contains(tid) {
+                    backtrack.push(*tid);
+                }
+            }
+        } else if !candidates.iter().any(|tid| backtrack.contains(tid)) {
+            let tid = if candidates.contains(&task) {
+                task
+            } else {
+                candidates[0]
+            };
+            backtrack.push(tid);
+        }
+        enabled.contains(&task)
+    }
+
+    /// At the end of an execution, make sure we'll explore running each task that was still
+    /// enabled but never got to run again (for example, a detached task still runnable when the
+    /// main thread finished). We never saw what its next step would have accessed, so it might race
+    /// with any step.
+    fn add_abandoned_backtrack_points(&mut self) {
+        let mut last_step = HashMap::new();
+        for step in &self.trace {
+            last_step.insert(step.task, step.node);
+        }
+
+        let mut abandoned = Vec::new();
+        for (i, node) in self.nodes[..self.position].iter().enumerate() {
+            if let Node::Schedule { enabled, .. } = node {
+                for &tid in enabled {
+                    if !abandoned.contains(&tid) && last_step.get(&tid).map(|&node| node < i).unwrap_or(true) {
+                        abandoned.push(tid);
+                    }
+                }
+            }
+        }
+
+        for tid in abandoned {
+            let clock = self.clocks.tasks.get(&tid).cloned().unwrap_or_else(VectorClock::new);
+            self.add_backtrack_points(tid, &Accesses::Unknown, &clock);
+        }
+    }
+}
+
+impl Scheduler for DporScheduler {
+    fn new_execution(&mut self) -> Option<Schedule> {
+        if self.max_iterations.map(|mi| self.iterations >= mi).unwrap_or(false) {
+            return None;
+        }
+
+        if self.iterations > 0 {
+            // If the last execution was cut short, it was equivalent to one we already explored
+            if !self.sleep_blocked {
+                self.add_abandoned_backtrack_points();
+            }
+            if !self.advance() {
+                self.exhausted = !self.used_random_data;
+                return None;
+            }
+        }
+
+        self.iterations += 1;
+        self.position = 0;
+        self.trace.clear();
+        self.clocks = Clocks::new();
+        self.current_node = None;
+        self.sleep.clear();
+        self.sleep_blocked = false;
+
+        Some(Schedule::new(self.data_source.reinitialize()))
+    }
+
+    fn next_task(&mut self, runnable: &[TaskId], _current: Option<TaskId>, _is_yielding: bool) -> Option<TaskId> {
+        // Any task we haven't seen before was spawned by the step that just ended
+        let spawn_clock = self
+            .trace
+            .last()
+            .map(|step| step.clock.clone())
+            .unwrap_or_else(VectorClock::new);
+        for tid in runnable {
+            self.clocks.tasks.entry(*tid).or_insert_with(|| spawn_clock.clone());
+        }
+
+        let next = if self.position < self.nodes.len() {
+            let Node::Schedule { chosen, .. } = &self.nodes[self.position] else {
+                panic!("DPOR scheduler expected a scheduling decision while replaying; is the test deterministic?");
+            };
+            assert!(
+                runnable.contains(chosen),
+                "DPOR scheduler replayed a task that isn't runnable; is the test deterministic?"
+            );
+            *chosen
+        } else {
+            let sleep = self.sleep.iter().map(|(tid, _)| *tid).collect::<Vec<_>>();
+            let awake = runnable.iter().filter(|tid| !sleep.contains(tid)).collect::<Vec<_>>();
+            if awake.is_empty() {
+                self.sleep_blocked = true;
+                return None;
+            }
+            // Like DFS, start with the lowest-numbered task, so that a task spinning on some
+            // condition doesn't keep running forever before the tasks that would satisfy it
+            let chosen = *awake[0];
+            self.nodes.push(Node::Schedule {
+                enabled: runnable.to_vec(),
+                chosen,
+                backtrack: vec![chosen],
+                done: vec![(chosen, Accesses::Unknown)],
+                sleep,
+            });
+            chosen
+        };
+
+        self.current_node = Some(self.position);
+        self.position += 1;
+
+        Some(next)
+    }
+
+    fn next_choice(&mut self, num_choices: usize, _current: Option<TaskId>) -> Option<usize> {
+        let choice = if self.position < self.nodes.len() {
+            let Node::Choice { chosen, .. } = &self.nodes[self.position] else {
+                panic!("DPOR scheduler expected a nondeterministic choice while replaying; is the test deterministic?");
+            };
+            *chosen
+        } else {
+            self.nodes.push(Node::Choice { num_choices, chosen: 0 });
+            0
+        };
+
+        self.position += 1;
+
+        Some(choice)
+    }
+
+    fn record_accesses(&mut self, task: TaskId, accesses: &Accesses) {
+        let node = self.current_node.expect("accesses should be for a task we scheduled");
+
+        // The tasks we explored at this node before this one go to sleep, and any sleeping task
+        // whose next step conflicts with this one wakes up
+        let Node::Schedule { done, .. } = &mut self.nodes[node] else {
+            unreachable!("steps are always chosen by a scheduling decision");
+        };
+        let (last, earlier) = done.split_last_mut().expect("the chosen task is always done");
+        debug_assert_eq!(last.0, task);
+        last.1 = accesses.clone();
+        for (tid, accesses) in earlier.iter() {
+            if !self.sleep.iter().any(|(sleeping, _)| sleeping == tid) {
+                self.sleep.push((*tid, accesses.clone()));
+            }
+        }
+        self.sleep
+            .retain(|(tid, sleeping)| *tid != task && !conflicts_across_executions(sleeping, accesses));
+
+        let mut clock = self.clocks.tasks.get(&task).cloned().unwrap_or_else(VectorClock::new);
+        self.add_backtrack_points(task, accesses, &clock);
+
+        // The step happens after every earlier step it depends on
+        clock.update(&self.clocks.unknown);
+        match accesses {
+            Accesses::Objects(objects) => {
+                for object in objects {
+                    self.clocks.depend_on(&mut clock, *object);
+                }
+            }
+            Accesses::Unknown => clock.update(&self.clocks.all),
+        }
+        if clock.len() <= usize::from(task) {
+            clock.extend(task);
+        }
+        clock.increment(task);
+
+        match accesses {
+            Accesses::Objects(objects) => {
+                for object in objects {
+                    self.clocks.access(*object, &clock);
+                }
+            }
+            Accesses::Unknown => self.clocks.unknown = clock.clone(),
+        }
+        self.clocks.all.update(&clock);
+        self.clocks.tasks.insert(task, clock.clone());
+
+        self.trace.push(Step {
+            task,
+            node,
+            accesses: accesses.clone(),
+            clock,
+        });
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        if !self.allow_random_data {
+            panic!("requested random data from DPOR scheduler with allow_random_data = false");
+        }
+        self.used_random_data = true;
+        self.data_source.next_u64()
+    }
+
+    fn exhausted(&self) -> bool {
+        self.exhausted
+    }
+}
+
+// A clock is implicitly zero for tasks beyond its length
+fn get(clock: &VectorClock, task: TaskId) -> u32 {
+    clock[..].get(usize::from(task)).copied().unwrap_or(0)
+}
+
+// Whether a step from an earlier execution might conflict with a step of the current one. Locks and
+// atomics are identified by their id or address, which can differ between executions (locks get
+// their id the first time they're used, and atomics can be allocated at a different address), so
+// any two lock or atomic accesses might be to the same object. Task ids are stable, as a task
+// that the earlier step accessed must exist in the prefix the two executions share.
+fn conflicts_across_executions(earlier: &Accesses, accesses: &Accesses) -> bool {
+    match (earlier, accesses) {
+        (Accesses::Objects(earlier), Accesses::Objects(objects)) => earlier.iter().any(|e| {
+            objects.iter().any(|o| match (e.0, o.0) {
+                (ObjectKind::Task(e), ObjectKind::Task(o)) => e == o,
+                // Shared accesses commute even if they're to the same lock
+                (ObjectKind::SharedLock(_), ObjectKind::SharedLock(_)) => false,
+                (ObjectKind::Lock(_) | ObjectKind::SharedLock(_), ObjectKind::Lock(_) | ObjectKind::SharedLock(_)) => {
+                    true
+                }
+                (ObjectKind::Atomic(_), ObjectKind::Atomic(_)) => true,
+                _ => false,
+            })
+        }),
+        _ => true,
+    }
+}
@@ -0,0 +1,94 @@
+use crate::runtime::task::TaskId;
+use crate::scheduler::{Accesses, Schedule, Scheduler};
+
+/// A scheduler that wraps another scheduler and guarantees that no runnable task is starved
+/// forever.
+///
+/// The scheduler tracks how many consecutive scheduling decisions each runnable task has been
+/// passed over for. Most decisions are left to the inner scheduler, but once a runnable task has
+/// waited for `max_wait` decisions, the scheduler overrides the inner scheduler and runs the task
+/// that has waited longest. A task's waiting time resets whenever it runs or blocks, as a blocked
+/// task couldn't have been scheduled anyway.
+///
+/// This makes it possible to test liveness properties: under an unfair scheduler, a spin loop can
+/// run forever while the task it's waiting for never gets to run, which looks just like a genuine
+/// livelock. Under a `FairScheduler`, an execution that still fails to make progress is a real bug.
+///
+/// The inner scheduler must tolerate having its choices overridden, so this is best suited to
+/// schedulers that don't rely on their own past choices, like
+/// [`RandomScheduler`](crate::scheduler::RandomScheduler) or
+/// [`PctScheduler`](crate::scheduler::PctScheduler), rather than exhaustive schedulers.
+#[derive(Debug)]
+pub struct FairScheduler<S: ?Sized + Scheduler> {
+    max_wait: usize,
+    // How many consecutive decisions each task has been runnable but not chosen, indexed by task id
+    waits: Vec<usize>,
+    inner: S,
+}
+
+impl<S: Scheduler> FairScheduler<S> {
+    /// Construct a new FairScheduler that wraps `inner`, and runs any task that has been runnable
+    /// but not scheduled for `max_wait` consecutive decisions.
+    pub fn new(inner: S, max_wait: usize) -> Self {
+        assert!(max_wait > 0, "max_wait must be at least 1");
+        Self {
+            max_wait,
+            waits: Vec::new(),
+            inner,
+        }
+    }
+}
+
+impl<S: Scheduler> Scheduler for FairScheduler<S> {
+    fn new_execution(&mut self) -> Option<Schedule> {
+        self.waits.clear();
+        self.inner.new_execution()
+    }
+
+    fn next_task(&mut self, runnable: &[TaskId], current: Option<TaskId>, is_yielding: bool) -> Option<TaskId> {
+        let choice = self.inner.next_task(runnable, current, is_yielding)?;
+
+        // Run the longest-waiting task instead if it has waited too long, preferring the lowest task
+        // id among ties
+        let wait = |tid: &TaskId| self.waits.get(usize::from(*tid)).copied().unwrap_or(0);
+        let next = runnable
+            .iter()
+            .filter(|tid| wait(tid) >= self.max_wait)
+            .max_by_key(|tid| (wait(tid), std::cmp::Reverse(**tid)))
+            .copied()
+            .unwrap_or(choice);
+
+        if let Some(max) = runnable.iter().map(|tid| usize::from(*tid)).max() {
+            if self.waits.len() <= max {
+                self.waits.resize(max + 1, 0);
+            }
+        }
+        for (tid, wait) in self.waits.iter_mut().enumerate() {
+            let tid = TaskId::from(tid);
+            if tid != next && runnable.contains(&tid) {
+                *wait += 1;
+            } else {
+                *wait = 0;
+            }
+        }
+
+        Some(next)
+    }
+
+    // Choices aren't context switches, so they leave every task's waiting time alone
+    fn next_choice(&mut self, num_choices: usize, current: Option<TaskId>) -> Option<usize> {
+        self.inner.next_choice(num_choices, current)
+    }
+
+    fn record_accesses(&mut self, task: TaskId, accesses: &Accesses) {
+        self.inner.record_accesses(task, accesses);
+    }
+
+    fn record_lock_waits(&mut self, waits: &[(TaskId, TaskId)]) {
+        self.inner.record_lock_waits(waits);
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.inner.next_u64()
+    }
+}
@@ -1,5 +1,5 @@
 use crate::runtime::task::TaskId;
-use crate::scheduler::{Schedule, Scheduler};
+use crate::scheduler::{Accesses, Schedule, Scheduler};
 use tracing::info;
 
 /// A `MetricsScheduler` wraps an inner `Scheduler` and collects metrics about the schedules it's
@@ -51,6 +51,15 @@ impl<S: Scheduler> MetricsScheduler<S> {
 }
 
 impl<S: ?Sized + Scheduler> MetricsScheduler<S> {
+    /// The total number of steps taken so far across all executions, and the largest number of
+    /// steps taken in any one execution
+    pub(crate) fn steps(&self) -> (usize, usize) {
+        (
+            self.steps_metric.sum + self.steps,
+            self.steps_metric.max.max(self.steps),
+        )
+    }
+
     fn record_and_reset_metrics(&mut self) {
         self.steps_metric.record(self.steps);
         self.steps = 0;
@@ -103,11 +112,29 @@ impl<S: Scheduler> Scheduler for MetricsScheduler<S> {
         Some(choice)
     }
 
+    fn next_choice(&mut self, num_choices: usize, current_task: Option<TaskId>) -> Option<usize> {
+        self.steps += 1;
+        self.random_choices += 1;
+        self.inner.next_choice(num_choices, current_task)
+    }
+
+    fn record_accesses(&mut self, task: TaskId, accesses: &Accesses) {
+        self.inner.record_accesses(task, accesses)
+    }
+
+    fn record_lock_waits(&mut self, waits: &[(TaskId, TaskId)]) {
+        self.inner.record_lock_waits(waits)
+    }
+
     fn next_u64(&mut self) -> u64 {
         self.steps += 1;
         self.random_choices += 1;
         self.inner.next_u64()
     }
+
+    fn exhausted(&self) -> bool {
+        self.inner.exhausted()
+    }
 }
 
 impl<S: ?Sized + Scheduler> Drop for MetricsScheduler<S> {
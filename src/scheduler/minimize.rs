@@ -0,0 +1,133 @@
+//! This module implements schedule minimization: given a schedule that makes a test fail, search for
+//! a shorter schedule that still makes it fail, by repeatedly removing scheduling decisions and
+//! replaying the test (in the style of delta debugging).
+
+use crate::runtime::task::TaskId;
+use crate::scheduler::data::random::RandomDataSource;
+use crate::scheduler::data::DataSource;
+use crate::scheduler::{Schedule, ScheduleStep, Scheduler};
+use crate::{Config, FailurePersistence, Runner};
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::rc::Rc;
+use std::sync::Arc;
+
+pub(crate) fn minimize_schedule<F>(f: F, schedule: Schedule) -> Schedule
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let f = Arc::new(f);
+    let mut schedule = reproduce(&f, schedule).expect("the schedule to minimize should make the test fail");
+
+    // Try removing chunks of decisions, starting with big chunks and halving the chunk size whenever
+    // no chunk of the current size can be removed
+    let mut chunk = schedule.len() / 2;
+    while chunk > 0 {
+        let mut removed = false;
+        let mut start = 0;
+        while start < schedule.len() {
+            let mut candidate = schedule.clone();
+            candidate.steps.drain(start..(start + chunk).min(schedule.len()));
+            match reproduce(&f, candidate) {
+                Some(reduced) => {
+                    schedule = reduced;
+                    removed = true;
+                }
+                None => start += chunk,
+            }
+        }
+        if !removed {
+            chunk /= 2;
+        }
+    }
+
+    schedule
+}
+
+/// Replay a candidate schedule, and if the test fails before the execution diverges from it, return
+/// the prefix of the candidate that the failing execution used.
+fn reproduce<F>(f: &Arc<F>, candidate: Schedule) -> Option<Schedule>
+where
+    F: Fn() + Send + Sync + 'static,
+{
+    let outcome = Rc::new(RefCell::new(Outcome {
+        used: Schedule::new(candidate.seed),
+        diverged: false,
+    }));
+    let scheduler = MinimizeScheduler {
+        data_source: RandomDataSource::initialize(candidate.seed),
+        candidate,
+        started: false,
+        outcome: Rc::clone(&outcome),
+    };
+
+    let mut config = Config::new();
+    config.failure_persistence = FailurePersistence::None;
+    let runner = Runner::new(scheduler).with_config(config);
+    let f = Arc::clone(f);
+    let failed = panic::catch_unwind(AssertUnwindSafe(move || runner.run_inner(move || f()))).is_err();
+
+    let outcome = Rc::try_unwrap(outcome).ok()?.into_inner();
+    if failed && !outcome.diverged {
+        Some(outcome.used)
+    } else {
+        None
+    }
+}
+
+#[derive(Debug)]
+struct Outcome {
+    // The steps of the candidate that the execution has used so far
+    used: Schedule,
+    // Whether the execution stopped following the candidate, in which case a failure doesn't count
+    diverged: bool,
+}
+
+/// A scheduler that follows a candidate schedule like a `ReplayScheduler`, but instead of panicking
+/// when the candidate doesn't fit the execution (because a step was removed), stops the execution
+/// and records that it diverged.
+#[derive(Debug)]
+struct MinimizeScheduler {
+    candidate: Schedule,
+    started: bool,
+    outcome: Rc<RefCell<Outcome>>,
+    data_source: RandomDataSource,
+}
+
+impl Scheduler for MinimizeScheduler {
+    fn new_execution(&mut self) -> Option<Schedule> {
+        if self.started {
+            None
+        } else {
+            self.started = true;
+            Some(Schedule::new(self.data_source.reinitialize()))
+        }
+    }
+
+    fn next_task(&mut self, runnable: &[TaskId], _current: Option<TaskId>, _is_yielding: bool) -> Option<TaskId> {
+        let mut outcome = self.outcome.borrow_mut();
+        if outcome.diverged {
+            return None;
+        }
+        match self.candidate.steps.get(outcome.used.len()) {
+            Some(ScheduleStep::Task(next)) if runnable.contains(next) => {
+                outcome.used.push_task(*next);
+                Some(*next)
+            }
+            _ => {
+                outcome.diverged = true;
+                None
+            }
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        // We can't stop the execution here, so keep going until the next scheduling decision
+        let mut outcome = self.outcome.borrow_mut();
+        match self.candidate.steps.get(outcome.used.len()) {
+            Some(ScheduleStep::Random) if !outcome.diverged => outcome.used.push_random(),
+            _ => outcome.diverged = true,
+        }
+        self.data_source.next_u64()
+    }
+}
@@ -3,21 +3,29 @@ use std::fmt::Debug;
 
 mod data;
 mod dfs;
+mod dpor;
+mod fair;
 mod pct;
 mod random;
 mod replay;
 mod round_robin;
+mod weighted_random;
 
 pub(crate) mod metrics;
+pub(crate) mod minimize;
 pub(crate) mod serialization;
 
+use crate::runtime::task::LockId;
 pub use crate::runtime::task::TaskId;
 
 pub use dfs::DfsScheduler;
+pub use dpor::DporScheduler;
+pub use fair::FairScheduler;
 pub use pct::PctScheduler;
 pub use random::RandomScheduler;
 pub use replay::ReplayScheduler;
 pub use round_robin::RoundRobinScheduler;
+pub use weighted_random::WeightedRandomScheduler;
 
 /// A `Schedule` determines the order in which tasks are to be executed
 // TODO would be nice to make this generic in the type of `seed`, but for now all our seeds are u64s
@@ -70,6 +78,140 @@ impl Schedule {
     pub fn is_empty(&self) -> bool {
         self.steps.is_empty()
     }
+
+    /// Encode the schedule as a short printable string, suitable for passing to
+    /// [`replay`](crate::replay) or the `SHUTTLE_REPLAY` environment variable (see
+    /// [`Runner::run`](crate::Runner::run)). This is the same encoding Shuttle prints when a test
+    /// fails. Encoded schedules start with a version tag, so a schedule encoded by one version of
+    /// Shuttle either decodes to the same schedule in another version, or fails to decode.
+    pub fn encode(&self) -> String {
+        serialization::serialize_schedule(self)
+    }
+
+    /// Decode a schedule produced by [`Schedule::encode`], ignoring any surrounding whitespace.
+    /// Returns `None` if the string isn't a valid encoded schedule, or was encoded by an
+    /// incompatible version of Shuttle.
+    pub fn decode(encoded: &str) -> Option<Self> {
+        serialization::deserialize_schedule(encoded.trim())
+    }
+
+    /// Replay this schedule against the test `f`, and return a human-readable listing of what each
+    /// step of the execution did, like:
+    ///
+    /// ```text
+    /// step 1: task 0 acquired mutex 0x7f2510009d80
+    /// step 1: task 0 spawned task 1
+    /// step 2: task 1 waiting to acquire mutex 0x7f2510009d80
+    /// step 2: task 1 blocked
+    /// step 3: task 0
+    /// step 4: task 0 releasing mutex 0x7f2510009d80
+    /// step 4: task 0 unblocked task 1
+    /// ```
+    ///
+    /// Each line names the step, the task that ran it, and an operation that task performed, like
+    /// acquiring a lock or waking up another task, or just the task if the step performed no
+    /// operation Shuttle knows how to describe. If the execution fails, the last line describes
+    /// the failure. The operations are recovered from the [`tracing`](https://docs.rs/tracing)
+    /// events Shuttle emits, so the listing is empty if those events are compiled out.
+    pub fn display_trace<F>(&self, f: F) -> String
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        crate::runtime::trace::display_trace(f, self)
+    }
+}
+
+/// An identifier for a shared object, like a lock or an atomic, that tasks can synchronize or
+/// communicate through. Identifiers are unique within an execution, but the same object might have
+/// a different identifier in a different execution.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjectId(pub(crate) ObjectKind);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum ObjectKind {
+    /// The state of a task that other tasks can observe or change, like whether it has finished
+    /// (for `join`) or its unpark token
+    Task(TaskId),
+    Lock(LockId),
+    /// Shared (read) access to a lock. Shared accesses to the same lock commute with each other,
+    /// but not with any other access to that lock.
+    SharedLock(LockId),
+    /// An atomic, identified by its address
+    Atomic(usize),
+}
+
+impl ObjectId {
+    pub(crate) fn task(task: TaskId) -> Self {
+        Self(ObjectKind::Task(task))
+    }
+
+    pub(crate) fn lock(lock: LockId) -> Self {
+        Self(ObjectKind::Lock(lock))
+    }
+
+    pub(crate) fn shared_lock(lock: LockId) -> Self {
+        Self(ObjectKind::SharedLock(lock))
+    }
+
+    pub(crate) fn atomic<T>(atomic: &T) -> Self {
+        Self(ObjectKind::Atomic(atomic as *const T as usize))
+    }
+
+    /// Check whether accessing this object might not commute with accessing the `other` one
+    pub(crate) fn conflicts_with(&self, other: &ObjectId) -> bool {
+        match (self.0, other.0) {
+            (ObjectKind::SharedLock(_), ObjectKind::SharedLock(_)) => false,
+            (ObjectKind::SharedLock(lock), ObjectKind::Lock(other))
+            | (ObjectKind::Lock(lock), ObjectKind::SharedLock(other)) => lock == other,
+            _ => self == other,
+        }
+    }
+}
+
+/// The shared objects that a task accessed during a single step of an execution (i.e., between two
+/// consecutive scheduling decisions), as reported to [`Scheduler::record_accesses`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Accesses {
+    /// The step accessed only these shared objects, and so is independent of any other step that
+    /// accessed none of them, or only shared the same locks for reading
+    Objects(Vec<ObjectId>),
+    /// The step might have accessed any shared object, because it used a synchronization
+    /// primitive that doesn't report the objects it accesses
+    Unknown,
+}
+
+impl Accesses {
+    /// Check whether a step with these accesses might not commute with a step with the `other`
+    /// accesses, because they might have accessed the same shared object.
+    pub fn conflicts_with(&self, other: &Accesses) -> bool {
+        match (self, other) {
+            (Accesses::Objects(objects), Accesses::Objects(others)) => objects
+                .iter()
+                .any(|o| others.iter().any(|other| o.conflicts_with(other))),
+            _ => true,
+        }
+    }
+
+    pub(crate) fn record(&mut self, object: ObjectId) {
+        if let Accesses::Objects(objects) = self {
+            if !objects.contains(&object) {
+                objects.push(object);
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        match self {
+            Accesses::Objects(objects) => objects.clear(),
+            Accesses::Unknown => *self = Accesses::default(),
+        }
+    }
+}
+
+impl Default for Accesses {
+    fn default() -> Self {
+        Accesses::Objects(Vec::new())
+    }
 }
 
 /// A `Scheduler` is an oracle that decides the order in which to execute concurrent tasks and the
@@ -79,6 +221,67 @@ impl Schedule {
 /// state and strategically explore different schedules. At the start of each test execution, the
 /// executor calls `new_execution()` to inform the scheduler that a new execution is starting. Then,
 /// for each scheduling decision, the executor calls `next_task` to determine which task to run.
+///
+/// # Implementing a scheduler
+///
+/// Users can implement their own scheduling strategies and run tests with them by passing them to a
+/// [`Runner`](crate::Runner). Within an execution, the executor calls the scheduler's methods in
+/// the order the test makes decisions: `next_task` at every yield point (including once before the
+/// first task runs, with `current_task` set to `None`), `next_choice` when the running task picks
+/// between several options, and `next_u64` when the test asks for random data (e.g., through
+/// [`shuttle::rand`](crate::rand)). The executor records every decision in the [`Schedule`]
+/// returned by `new_execution`, so that a failing execution can be replayed with a
+/// [`ReplayScheduler`]. The replay generates random data from the seed of that `Schedule` using
+/// Shuttle's own random number generator, so executions that use random data from a custom
+/// scheduler might not replay faithfully; a scheduler that doesn't support random data can just
+/// panic in `next_u64`, like [`DfsScheduler`] does by default.
+///
+/// A scheduler must be deterministic given the same sequence of calls, and must only return tasks
+/// from the runnable list it was given. Returning `None` from `next_task` stops the current
+/// execution without failing the test.
+///
+/// For example, a scheduler that always runs the lowest-numbered runnable task, and runs any test
+/// only once:
+///
+/// ```
+/// use shuttle::scheduler::{Schedule, Scheduler, TaskId};
+/// use shuttle::{thread, Runner};
+///
+/// #[derive(Debug, Default)]
+/// struct LowestTaskScheduler {
+///     started: bool,
+/// }
+///
+/// impl Scheduler for LowestTaskScheduler {
+///     fn new_execution(&mut self) -> Option<Schedule> {
+///         if self.started {
+///             None
+///         } else {
+///             self.started = true;
+///             Some(Schedule::new(0))
+///         }
+///     }
+///
+///     fn next_task(
+///         &mut self,
+///         runnable_tasks: &[TaskId],
+///         _current_task: Option<TaskId>,
+///         _is_yielding: bool,
+///     ) -> Option<TaskId> {
+///         runnable_tasks.iter().min().copied()
+///     }
+///
+///     fn next_u64(&mut self) -> u64 {
+///         panic!("this scheduler doesn't support random data")
+///     }
+/// }
+///
+/// let runner = Runner::new(LowestTaskScheduler::default());
+/// let stats = runner.run(|| {
+///     thread::spawn(|| {}).join().unwrap();
+/// });
+/// assert_eq!(stats.iterations, 1);
+/// ```
 pub trait Scheduler: Debug {
     /// Inform the `Scheduler` that a new execution is about to begin. If this function returns
     /// None, the test will end rather than performing another execution. If it returns
@@ -103,8 +306,51 @@ pub trait Scheduler: Debug {
         is_yielding: bool,
     ) -> Option<TaskId>;
 
+    /// Choose one of `num_choices` options for the currently running task to take, like which of
+    /// several ready channels an [`mpsc::Select`](crate::sync::mpsc::Select) receives from. This
+    /// method returns `Some(index)` of the chosen option, or `None` to stop exploring the current
+    /// schedule.
+    ///
+    /// The default implementation presents the options to [`Scheduler::next_task`] as if they were
+    /// runnable tasks, so schedulers explore choices the same way they explore context switches.
+    /// Schedulers whose `next_task` treats tasks differently based on their ids or on how often
+    /// they're scheduled, like by weighting or prioritizing them, should override this, as the
+    /// options aren't real tasks.
+    fn next_choice(&mut self, num_choices: usize, current_task: Option<TaskId>) -> Option<usize> {
+        let options = (0..num_choices).map(TaskId::from).collect::<Vec<_>>();
+        self.next_task(&options, current_task, false).map(usize::from)
+    }
+
+    /// Inform the `Scheduler` which shared objects `task`, the task it last chose to run, accessed
+    /// during the step that just ended. This is called at the end of every step, before the next
+    /// call to [`Scheduler::next_task`] or the end of the execution.
+    ///
+    /// Schedulers can use this to avoid exploring schedules that only differ in the order of
+    /// independent steps, like [`DporScheduler`] does. The default implementation ignores it.
+    fn record_accesses(&mut self, _task: TaskId, _accesses: &Accesses) {}
+
+    /// Inform the `Scheduler` which tasks are blocked waiting for a lock that another task holds,
+    /// as `(waiter, holder)` pairs. This is called before every call to [`Scheduler::next_task`],
+    /// but only if [`Config::priority_inheritance`](crate::Config::priority_inheritance) is
+    /// enabled.
+    ///
+    /// Schedulers that prioritize some tasks over others can use this to implement priority
+    /// inheritance, treating a lock holder as if it had the priority of the tasks waiting for it,
+    /// like [`WeightedRandomScheduler`] does. The default implementation ignores it.
+    fn record_lock_waits(&mut self, _waits: &[(TaskId, TaskId)]) {}
+
     /// Choose the next u64 value to return to the currently running task.
     fn next_u64(&mut self) -> u64;
+
+    /// Whether the `Scheduler` has explored every possible schedule of the test, so that no
+    /// unexplored schedule could reveal a failure. This is only meaningful once
+    /// [`Scheduler::new_execution`] has returned `None`.
+    ///
+    /// The default implementation returns false, which is the right answer for any scheduler that
+    /// doesn't systematically enumerate schedules.
+    fn exhausted(&self) -> bool {
+        false
+    }
 }
 
 impl Scheduler for Box<dyn Scheduler + Send> {
@@ -121,7 +367,23 @@ impl Scheduler for Box<dyn Scheduler + Send> {
         self.as_mut().next_task(runnable_tasks, current_task, is_yielding)
     }
 
+    fn next_choice(&mut self, num_choices: usize, current_task: Option<TaskId>) -> Option<usize> {
+        self.as_mut().next_choice(num_choices, current_task)
+    }
+
+    fn record_accesses(&mut self, task: TaskId, accesses: &Accesses) {
+        self.as_mut().record_accesses(task, accesses)
+    }
+
+    fn record_lock_waits(&mut self, waits: &[(TaskId, TaskId)]) {
+        self.as_mut().record_lock_waits(waits)
+    }
+
     fn next_u64(&mut self) -> u64 {
         self.as_mut().next_u64()
     }
+
+    fn exhausted(&self) -> bool {
+        self.as_ref().exhausted()
+    }
 }
@@ -67,18 +67,18 @@ impl Scheduler for PctScheduler {
         // On the first iteration, we run a simple oldest-task-first scheduler to determine a
         // bound on the maximum number of steps. Once we have that, we can initialize PCT.
         if self.iterations > 0 {
-            assert!(self.max_steps > 0);
-
             // Initialize priorities by shuffling the task IDs
             self.priority_queue.shuffle(&mut self.rng);
 
             // Initialize change points by sampling from the current max_steps. We skip step 0
             // because there's no point making a priority change before any tasks have run; the
-            // random priority initialization takes care of that.
-            let num_points = std::cmp::min(self.max_depth - 1, self.max_steps - 1);
+            // random priority initialization takes care of that. A test that never had a choice of
+            // which task to run (e.g., one with a single thread) has no steps to change at all.
+            let num_steps = self.max_steps.saturating_sub(1);
+            let num_points = std::cmp::min(self.max_depth - 1, num_steps);
             // sample(R, L, n) returns n distinct values in the range [0, L)
             // but we want values in range [1, self.max_steps] so we offset by 1
-            self.change_points = sample(&mut self.rng, self.max_steps - 1, num_points)
+            self.change_points = sample(&mut self.rng, num_steps, num_points)
                 .iter()
                 .map(|v| v + 1)
                 .collect::<Vec<_>>();
@@ -127,6 +127,12 @@ impl Scheduler for PctScheduler {
         Some(*self.priority_queue.iter().find(|tid| runnable.contains(tid)).unwrap())
     }
 
+    // Choices aren't context switches, so they're uniformly random, and don't count as steps that
+    // can trigger a change point
+    fn next_choice(&mut self, num_choices: usize, _current: Option<TaskId>) -> Option<usize> {
+        Some(self.rng.gen_range(0, num_choices))
+    }
+
     fn next_u64(&mut self) -> u64 {
         self.data_source.next_u64()
     }
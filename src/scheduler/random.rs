@@ -4,7 +4,7 @@ use crate::scheduler::data::DataSource;
 use crate::scheduler::{Schedule, Scheduler};
 use rand::rngs::OsRng;
 use rand::seq::SliceRandom;
-use rand::{RngCore, SeedableRng};
+use rand::{Rng, RngCore, SeedableRng};
 use rand_pcg::Pcg64Mcg;
 
 /// A scheduler that randomly chooses a runnable task at each context switch.
@@ -15,6 +15,7 @@ use rand_pcg::Pcg64Mcg;
 #[derive(Debug)]
 pub struct RandomScheduler {
     max_iterations: usize,
+    seed: u64,
     rng: Pcg64Mcg,
     iterations: usize,
     data_source: RandomDataSource,
@@ -34,11 +35,39 @@ impl RandomScheduler {
         let rng = Pcg64Mcg::seed_from_u64(seed);
         Self {
             max_iterations,
+            seed,
             rng,
             iterations: 0,
             data_source: RandomDataSource::initialize(seed),
         }
     }
+
+    /// The seed this scheduler was constructed with. Passing it to
+    /// [`RandomScheduler::new_from_seed`] produces a scheduler that makes the same scheduling
+    /// decisions.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Construct `count` RandomSchedulers that together run `max_iterations` iterations, with seeds
+    /// derived deterministically from `seed`. This is useful to spread a random test across
+    /// several OS threads with a [`PortfolioRunner`](crate::PortfolioRunner).
+    ///
+    /// The first scheduler uses `seed` itself, so splitting into a single scheduler is the same as
+    /// calling [`RandomScheduler::new_from_seed`]. Splitting the same seed into the same number of
+    /// schedulers always produces the same schedulers.
+    pub fn split(seed: u64, max_iterations: usize, count: usize) -> Vec<Self> {
+        assert!(count > 0, "must split into at least one scheduler");
+        let mut seeds = Pcg64Mcg::seed_from_u64(seed);
+        (0..count)
+            .map(|i| {
+                let seed = if i == 0 { seed } else { seeds.next_u64() };
+                // Spread any remainder across the first few schedulers
+                let iterations = max_iterations / count + if i < max_iterations % count { 1 } else { 0 };
+                Self::new_from_seed(seed, iterations)
+            })
+            .collect()
+    }
 }
 
 impl Scheduler for RandomScheduler {
@@ -55,6 +84,10 @@ impl Scheduler for RandomScheduler {
         Some(*runnable.choose(&mut self.rng).unwrap())
     }
 
+    fn next_choice(&mut self, num_choices: usize, _current: Option<TaskId>) -> Option<usize> {
+        Some(self.rng.gen_range(0, num_choices))
+    }
+
     fn next_u64(&mut self) -> u64 {
         self.data_source.next_u64()
     }
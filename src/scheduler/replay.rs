@@ -1,19 +1,33 @@
 use crate::runtime::task::TaskId;
 use crate::scheduler::data::random::RandomDataSource;
 use crate::scheduler::data::DataSource;
-use crate::scheduler::serialization::deserialize_schedule;
 use crate::scheduler::{Schedule, ScheduleStep, Scheduler};
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_pcg::Pcg64Mcg;
 use std::fs::OpenOptions;
 use std::io::Read;
 use std::path::Path;
+use tracing::warn;
 
 /// A scheduler that can replay a chosen schedule deserialized from a string.
+///
+/// If the test no longer makes the same decisions as when the schedule was recorded (for example,
+/// because its code changed), the replay diverges from the schedule: the next recorded decision
+/// names a task that isn't runnable, is the wrong kind of decision, or is missing because the
+/// schedule already ended. By default, the replay then fails with a "schedule diverged at step N"
+/// message, where N counts the schedule's decisions from 0. [`ReplayScheduler::set_random_fallback`]
+/// instead continues the execution with random decisions from that point on.
 #[derive(Debug)]
 pub struct ReplayScheduler {
     schedule: Schedule,
     steps: usize,
     started: bool,
     allow_incomplete: bool,
+    random_fallback: bool,
+    // Whether the replay diverged from the schedule and is now making random decisions
+    diverged: bool,
+    rng: Pcg64Mcg,
     data_source: RandomDataSource,
 }
 
@@ -21,7 +35,7 @@ impl ReplayScheduler {
     /// Given an encoded schedule, construct a new [`ReplayScheduler`] that will execute threads in
     /// the order specified in the schedule.
     pub fn new_from_encoded(encoded_schedule: &str) -> Self {
-        let schedule = deserialize_schedule(encoded_schedule).expect("invalid schedule");
+        let schedule = Schedule::decode(encoded_schedule).expect("invalid schedule");
         Self::new_from_schedule(schedule)
     }
 
@@ -38,12 +52,16 @@ impl ReplayScheduler {
     /// specified in the schedule.
     pub fn new_from_schedule(schedule: Schedule) -> Self {
         let data_source = RandomDataSource::initialize(schedule.seed);
+        let rng = Pcg64Mcg::seed_from_u64(schedule.seed);
 
         Self {
             schedule,
             steps: 0,
             started: false,
             allow_incomplete: false,
+            random_fallback: false,
+            diverged: false,
+            rng,
             data_source,
         }
     }
@@ -52,6 +70,29 @@ impl ReplayScheduler {
     pub fn set_allow_incomplete(&mut self) {
         self.allow_incomplete = true;
     }
+
+    /// Set flag to continue with random decisions, seeded from the schedule's seed, if the replay
+    /// diverges from the schedule, rather than failing. A warning names the step where the replay
+    /// diverged. This takes precedence over [`ReplayScheduler::set_allow_incomplete`].
+    pub fn set_random_fallback(&mut self) {
+        self.random_fallback = true;
+    }
+
+    /// Handle the replay diverging from the schedule, for the given reason. Returns true if we
+    /// should continue with random decisions, or false if we should stop the execution early,
+    /// which is only allowed if `can_stop` is true.
+    fn diverge(&mut self, reason: String, can_stop: bool) -> bool {
+        let message = format!("schedule diverged at step {}: {}", self.steps, reason);
+        if self.random_fallback {
+            warn!("{}; continuing with random decisions", message);
+            self.diverged = true;
+            true
+        } else if self.allow_incomplete && can_stop {
+            false
+        } else {
+            panic!("{}", message);
+        }
+    }
 }
 
 impl Scheduler for ReplayScheduler {
@@ -65,39 +106,44 @@ impl Scheduler for ReplayScheduler {
     }
 
     fn next_task(&mut self, runnable: &[TaskId], _current: Option<TaskId>, _is_yielding: bool) -> Option<TaskId> {
-        if self.steps >= self.schedule.steps.len() {
-            assert!(self.allow_incomplete, "schedule ended early");
-            return None;
-        }
-        match self.schedule.steps[self.steps] {
-            ScheduleStep::Random => {
-                panic!("expected context switch but next schedule step is random choice");
-            }
-            ScheduleStep::Task(next) => {
-                if !runnable.contains(&next) {
-                    assert!(
-                        self.allow_incomplete,
-                        "scheduled task is not runnable, expected to run {:?}, but choices were {:?}",
-                        next, runnable
-                    );
-                    None
-                } else {
+        if !self.diverged {
+            let reason = match self.schedule.steps.get(self.steps) {
+                None => format!("the schedule ended, but tasks {:?} were still runnable", runnable),
+                Some(ScheduleStep::Random) => {
+                    "expected a context switch, but the schedule has a random choice".to_string()
+                }
+                Some(ScheduleStep::Task(next)) if !runnable.contains(next) => format!(
+                    "the schedule runs {:?}, but the runnable tasks were {:?}",
+                    next, runnable
+                ),
+                Some(ScheduleStep::Task(next)) => {
                     self.steps += 1;
-                    Some(next)
+                    return Some(*next);
                 }
+            };
+            if !self.diverge(reason, true) {
+                return None;
             }
         }
+        Some(*runnable.choose(&mut self.rng).unwrap())
     }
 
     fn next_u64(&mut self) -> u64 {
-        match self.schedule.steps[self.steps] {
-            ScheduleStep::Random => {
-                self.steps += 1;
-                self.data_source.next_u64()
-            }
-            ScheduleStep::Task(_) => {
-                panic!("expected random choice but next schedule step is context switch");
-            }
+        if !self.diverged {
+            let reason = match self.schedule.steps.get(self.steps) {
+                None => "the schedule ended, but the test asked for a random value".to_string(),
+                Some(ScheduleStep::Task(_)) => {
+                    "expected a random choice, but the schedule has a context switch".to_string()
+                }
+                Some(ScheduleStep::Random) => {
+                    self.steps += 1;
+                    return self.data_source.next_u64();
+                }
+            };
+            // We can't stop the execution from here, so this fails even if the schedule is allowed
+            // to be incomplete
+            self.diverge(reason, false);
         }
+        self.data_source.next_u64()
     }
 }
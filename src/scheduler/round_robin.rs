@@ -4,6 +4,10 @@ use crate::scheduler::data::DataSource;
 use crate::scheduler::{Schedule, Scheduler};
 
 /// A round robin scheduler that chooses the next available runnable task at each context switch.
+///
+/// Tasks take turns in order of their task IDs, wrapping around, and tasks that aren't runnable
+/// (e.g., because they're blocked on a lock) are skipped. The scheduler is deterministic, so it's
+/// mostly useful as a smoke test, rather than for finding bugs.
 #[derive(Debug)]
 pub struct RoundRobinScheduler {
     iterations: usize,
@@ -63,25 +63,28 @@ pub(crate) fn serialize_schedule(schedule: &Schedule) -> String {
 pub(crate) fn deserialize_schedule(str: &str) -> Option<Schedule> {
     let bytes = hex::decode(str).ok()?;
 
-    let version = bytes[0];
+    let (&version, mut bytes) = bytes.split_first()?;
     if version != SCHEDULE_MAGIC_V2 {
         return None;
     }
-    let mut bytes = &bytes[1..];
 
     let task_id_bits = bytes.read_usize_varint().ok()?;
+    if task_id_bits == 0 || task_id_bits > usize::BITS as usize {
+        return None;
+    }
     let schedule_len = bytes.read_usize_varint().ok()?;
     let seed = bytes.read_u64_varint().ok()?;
 
     let encoded = BitSlice::<Lsb0, _>::from_slice(bytes).unwrap();
     let mut offset = 0usize;
-    let mut steps = Vec::with_capacity(schedule_len);
+    // Don't trust the encoded length to size the allocation, as every step takes at least one bit
+    let mut steps = Vec::with_capacity(schedule_len.min(encoded.len()));
     while steps.len() < schedule_len {
-        if *encoded.get(offset).unwrap() {
+        if *encoded.get(offset)? {
             steps.push(ScheduleStep::Random);
             offset += 1;
         } else {
-            let tid = encoded[offset + 1..offset + 1 + task_id_bits].load::<usize>();
+            let tid = encoded.get(offset + 1..offset + 1 + task_id_bits)?.load::<usize>();
             steps.push(ScheduleStep::Task(TaskId::from(tid)));
             offset += 1 + task_id_bits;
         }
@@ -123,6 +126,19 @@ mod test {
         });
     }
 
+    #[test]
+    fn deserialization_rejects_malformed() {
+        let encoded = serialize_schedule(&Schedule {
+            seed: 10,
+            steps: vec![ScheduleStep::Task(TaskId::from(3)); 20],
+        });
+        assert!(deserialize_schedule("").is_none());
+        assert!(deserialize_schedule("not hex").is_none());
+        assert!(deserialize_schedule(&encoded[..encoded.len() - 2]).is_none());
+        // An unknown version tag
+        assert!(deserialize_schedule(&format!("92{}", &encoded[2..])).is_none());
+    }
+
     proptest! {
         #[test]
         fn serialization_roundtrip_proptest(schedule in schedule_strategy()) {
@@ -0,0 +1,135 @@
+use crate::runtime::task::TaskId;
+use crate::scheduler::data::random::RandomDataSource;
+use crate::scheduler::data::DataSource;
+use crate::scheduler::{Schedule, Scheduler};
+use rand::rngs::OsRng;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_pcg::Pcg64Mcg;
+use std::fmt::Debug;
+
+/// A scheduler that randomly chooses a runnable task at each context switch, with each task's
+/// chance of being chosen proportional to a user-supplied weight.
+///
+/// Weighting some tasks more heavily than others biases exploration towards interleavings where
+/// those tasks run first or more often, which can help to stress a suspected-problematic part of a
+/// test. Like [`RandomScheduler`](crate::scheduler::RandomScheduler), the scheduler only ever
+/// chooses among runnable tasks. A task with weight zero is only chosen if every runnable task has
+/// weight zero, in which case the choice is uniform.
+///
+/// If [`Config::priority_inheritance`](crate::Config::priority_inheritance) is enabled, a task that
+/// holds a lock is treated as having the weight of the heaviest task blocked waiting for it, if
+/// that's more than its own weight.
+pub struct WeightedRandomScheduler {
+    max_iterations: usize,
+    rng: Pcg64Mcg,
+    iterations: usize,
+    weight: Box<dyn Fn(TaskId) -> u32 + Send>,
+    // The (waiter, holder) pairs of tasks blocked on locks, for priority inheritance
+    lock_waits: Vec<(TaskId, TaskId)>,
+    data_source: RandomDataSource,
+}
+
+impl WeightedRandomScheduler {
+    /// Construct a new WeightedRandomScheduler with a freshly seeded RNG, where `weight` gives the
+    /// weight of each task.
+    pub fn new<W>(max_iterations: usize, weight: W) -> Self
+    where
+        W: Fn(TaskId) -> u32 + Send + 'static,
+    {
+        Self::new_from_seed(OsRng.next_u64(), max_iterations, weight)
+    }
+
+    /// Construct a new WeightedRandomScheduler with a given seed, where `weight` gives the weight
+    /// of each task.
+    ///
+    /// Two WeightedRandomSchedulers initialized with the same seed and weights will make the same
+    /// scheduling decisions when executing the same workloads.
+    pub fn new_from_seed<W>(seed: u64, max_iterations: usize, weight: W) -> Self
+    where
+        W: Fn(TaskId) -> u32 + Send + 'static,
+    {
+        let rng = Pcg64Mcg::seed_from_u64(seed);
+        Self {
+            max_iterations,
+            rng,
+            iterations: 0,
+            weight: Box::new(weight),
+            lock_waits: Vec::new(),
+            data_source: RandomDataSource::initialize(seed),
+        }
+    }
+}
+
+impl WeightedRandomScheduler {
+    /// The weight of the given task, raised to the weight of any task blocked waiting for a lock
+    /// it holds, either directly or through a chain of lock holders
+    fn inherited_weight(&self, tid: TaskId) -> u64 {
+        let mut weight = (self.weight)(tid) as u64;
+        let mut visited = vec![tid];
+        let mut holders = vec![tid];
+        while let Some(holder) = holders.pop() {
+            for (waiter, _) in self.lock_waits.iter().filter(|(_, h)| *h == holder) {
+                if !visited.contains(waiter) {
+                    visited.push(*waiter);
+                    holders.push(*waiter);
+                    weight = weight.max((self.weight)(*waiter) as u64);
+                }
+            }
+        }
+        weight
+    }
+}
+
+impl Debug for WeightedRandomScheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WeightedRandomScheduler")
+            .field("max_iterations", &self.max_iterations)
+            .field("iterations", &self.iterations)
+            .finish()
+    }
+}
+
+impl Scheduler for WeightedRandomScheduler {
+    fn new_execution(&mut self) -> Option<Schedule> {
+        if self.iterations >= self.max_iterations {
+            None
+        } else {
+            self.iterations += 1;
+            self.lock_waits.clear();
+            Some(Schedule::new(self.data_source.reinitialize()))
+        }
+    }
+
+    fn next_task(&mut self, runnable: &[TaskId], _current: Option<TaskId>, _is_yielding: bool) -> Option<TaskId> {
+        let weights = runnable
+            .iter()
+            .map(|tid| self.inherited_weight(*tid))
+            .collect::<Vec<_>>();
+        let total = weights.iter().sum::<u64>();
+        if total == 0 {
+            return Some(runnable[self.rng.gen_range(0, runnable.len())]);
+        }
+
+        let mut target = self.rng.gen_range(0, total);
+        for (tid, weight) in runnable.iter().zip(weights) {
+            if target < weight {
+                return Some(*tid);
+            }
+            target -= weight;
+        }
+        unreachable!("target is less than the total weight")
+    }
+
+    // Weights apply to tasks, not to a task's choices, so choices are uniformly random
+    fn next_choice(&mut self, num_choices: usize, _current: Option<TaskId>) -> Option<usize> {
+        Some(self.rng.gen_range(0, num_choices))
+    }
+
+    fn record_lock_waits(&mut self, waits: &[(TaskId, TaskId)]) {
+        self.lock_waits = waits.to_vec();
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.data_source.next_u64()
+    }
+}
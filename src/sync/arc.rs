@@ -0,0 +1,303 @@
+use crate::runtime::execution::ExecutionState;
+use crate::sync::atomic::{AtomicUsize, Ordering};
+use std::borrow::Borrow;
+use std::cell::UnsafeCell;
+use std::cmp::Ordering as CmpOrdering;
+use std::fmt::{Debug, Display};
+use std::hash::{Hash, Hasher};
+use std::mem::ManuallyDrop;
+use std::ops::Deref;
+
+/// A thread-safe reference-counting pointer, like [`std::sync::Arc`], whose reference counts are
+/// visible to Shuttle.
+///
+/// The strong and weak counts are Shuttle atomics, so cloning or dropping an `Arc` and upgrading a
+/// [`Weak`] are all yield points. In particular, if a [`Weak::upgrade`] races with the drop of the
+/// last strong reference, Shuttle explores both the schedule where the upgrade succeeds (and so the
+/// value lives on) and the one where it fails.
+///
+/// Unlike the standard library's `Arc`, this type can't hold unsized values like `Arc<dyn Trait>`,
+/// because that requires unsizing coercions that only the standard library can implement.
+pub struct Arc<T> {
+    inner: std::sync::Arc<ArcInner<T>>,
+}
+
+/// A version of [`Arc`] that holds a non-owning reference to the managed value, like
+/// [`std::sync::Weak`]. The value is accessed by calling [`upgrade`](Weak::upgrade).
+pub struct Weak<T> {
+    // None for a `Weak` created by `Weak::new`, which never upgrades
+    inner: Option<std::sync::Arc<ArcInner<T>>>,
+}
+
+// The allocation itself is managed by the `std` `Arc`, which keeps it alive while any `Arc` or
+// `Weak` points to it. The modeled counts only decide when the value is dropped: the thread that
+// drops the last strong reference drops the value in place.
+struct ArcInner<T> {
+    strong: AtomicUsize,
+    weak: AtomicUsize, // number of `Weak`s, not counting `Weak::new()`s
+    // Only accessed while the strong count is non-zero, except by the thread that brings it to zero
+    data: UnsafeCell<ManuallyDrop<T>>,
+}
+
+// All our atomic operations are SeqCst, both because Shuttle treats all orderings as SeqCst anyway
+// and to avoid printing the ordering warning for code that never uses atomics itself. This is
+// stronger than the standard library's `Arc`, but still gives the drop of the value the right
+// happens-before relationship with every use of it.
+const ORDERING: Ordering = Ordering::SeqCst;
+
+impl<T> Arc<T> {
+    /// Constructs a new `Arc<T>`.
+    pub fn new(data: T) -> Self {
+        let inner = ArcInner {
+            strong: AtomicUsize::new(1),
+            weak: AtomicUsize::new(0),
+            data: UnsafeCell::new(ManuallyDrop::new(data)),
+        };
+        Self {
+            inner: std::sync::Arc::new(inner),
+        }
+    }
+
+    /// Returns the inner value, if the `Arc` has exactly one strong reference. Otherwise, an
+    /// [`Err`] is returned with the same `Arc` that was passed in.
+    pub fn try_unwrap(this: Self) -> Result<T, Self> {
+        if this.inner.strong.compare_exchange(1, 0, ORDERING, ORDERING).is_err() {
+            return Err(this);
+        }
+
+        // We don't want to run our `Drop` impl, as we've already released our strong reference
+        let this = ManuallyDrop::new(this);
+        // Safety: we just took the strong count to zero, so no other thread can access the value,
+        // and we read `this.inner` exactly once because `this` is never dropped
+        unsafe {
+            let data = ManuallyDrop::take(&mut *this.inner.data.get());
+            drop(std::ptr::read(&this.inner));
+            Ok(data)
+        }
+    }
+
+    /// Creates a new [`Weak`] pointer to this allocation.
+    pub fn downgrade(this: &Self) -> Weak<T> {
+        this.inner.weak.fetch_add(1, ORDERING);
+        Weak {
+            inner: Some(std::sync::Arc::clone(&this.inner)),
+        }
+    }
+
+    /// Gets the number of [`Weak`] pointers to this allocation.
+    pub fn weak_count(this: &Self) -> usize {
+        this.inner.weak.load(ORDERING)
+    }
+
+    /// Gets the number of strong (`Arc`) pointers to this allocation.
+    pub fn strong_count(this: &Self) -> usize {
+        this.inner.strong.load(ORDERING)
+    }
+
+    /// Returns a mutable reference into the given `Arc`, if there are no other `Arc` or [`Weak`]
+    /// pointers to the same allocation.
+    pub fn get_mut(this: &mut Self) -> Option<&mut T> {
+        // Other threads can only create new `Arc`s or `Weak`s from existing ones, so if we hold
+        // the only one, nobody can race with us
+        if this.inner.strong.load(ORDERING) == 1 && this.inner.weak.load(ORDERING) == 0 {
+            // Safety: ours is the only pointer to the allocation
+            unsafe { Some(&mut *this.inner.data.get()) }
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if the two `Arc`s point to the same allocation.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        std::sync::Arc::ptr_eq(&this.inner, &other.inner)
+    }
+
+    /// Provides a raw pointer to the data.
+    pub fn as_ptr(this: &Self) -> *const T {
+        Self::deref(this)
+    }
+}
+
+impl<T> Clone for Arc<T> {
+    fn clone(&self) -> Self {
+        self.inner.strong.fetch_add(1, ORDERING);
+        Self {
+            inner: std::sync::Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<T> Drop for Arc<T> {
+    fn drop(&mut self) {
+        // If the execution has stopped, we can't touch the modeled counts, so we leak the value
+        if ExecutionState::should_stop() {
+            return;
+        }
+        if self.inner.strong.fetch_sub(1, ORDERING) == 1 {
+            // Safety: we dropped the last strong reference, so no other thread can access the value
+            unsafe { ManuallyDrop::drop(&mut *self.inner.data.get()) }
+        }
+    }
+}
+
+impl<T> Deref for Arc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // Safety: we hold a strong reference, so the value hasn't been dropped
+        unsafe { &*self.inner.data.get() }
+    }
+}
+
+impl<T> AsRef<T> for Arc<T> {
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+impl<T> Borrow<T> for Arc<T> {
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<T: Default> Default for Arc<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> From<T> for Arc<T> {
+    fn from(t: T) -> Self {
+        Self::new(t)
+    }
+}
+
+impl<T: Debug> Debug for Arc<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl<T: Display> Display for Arc<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
+
+impl<T: PartialEq> PartialEq for Arc<T> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<T: Eq> Eq for Arc<T> {}
+
+impl<T: PartialOrd> PartialOrd for Arc<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<T: Ord> Ord for Arc<T> {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<T: Hash> Hash for Arc<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl<T> Weak<T> {
+    /// Constructs a new `Weak<T>` that doesn't point to any allocation. Calling
+    /// [`upgrade`](Weak::upgrade) on it always returns `None`.
+    pub fn new() -> Self {
+        Self { inner: None }
+    }
+
+    /// Attempts to upgrade the `Weak` pointer to an [`Arc`], returning `None` if the value has
+    /// already been dropped.
+    pub fn upgrade(&self) -> Option<Arc<T>> {
+        let inner = self.inner.as_ref()?;
+        // Only take a new strong reference if there's still an existing one keeping the value alive
+        inner
+            .strong
+            .fetch_update(ORDERING, ORDERING, |n| if n == 0 { None } else { Some(n + 1) })
+            .ok()?;
+        Some(Arc {
+            inner: std::sync::Arc::clone(inner),
+        })
+    }
+
+    /// Gets the number of strong (`Arc`) pointers to this allocation, or 0 if there are none.
+    pub fn strong_count(&self) -> usize {
+        self.inner
+            .as_ref()
+            .map(|inner| inner.strong.load(ORDERING))
+            .unwrap_or(0)
+    }
+
+    /// Gets the number of `Weak` pointers to this allocation, or 0 if there are no remaining
+    /// strong pointers.
+    pub fn weak_count(&self) -> usize {
+        match &self.inner {
+            Some(inner) if inner.strong.load(ORDERING) > 0 => inner.weak.load(ORDERING),
+            _ => 0,
+        }
+    }
+
+    /// Returns `true` if the two `Weak`s point to the same allocation, or if both don't point to
+    /// any allocation.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        match (&self.inner, &other.inner) {
+            (Some(inner), Some(other)) => std::sync::Arc::ptr_eq(inner, other),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl<T> Clone for Weak<T> {
+    fn clone(&self) -> Self {
+        if let Some(inner) = &self.inner {
+            inner.weak.fetch_add(1, ORDERING);
+        }
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Weak<T> {
+    fn drop(&mut self) {
+        if ExecutionState::should_stop() {
+            return;
+        }
+        if let Some(inner) = &self.inner {
+            inner.weak.fetch_sub(1, ORDERING);
+        }
+    }
+}
+
+impl<T> Default for Weak<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Debug for Weak<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(Weak)")
+    }
+}
+
+// Safety: like `std::sync::Arc`, an `Arc` or `Weak` gives shared access to the value from any
+// thread that holds one, and the value can be dropped by any of those threads. The counts are only
+// ever accessed through Shuttle atomics.
+unsafe impl<T: Send + Sync> Send for Arc<T> {}
+unsafe impl<T: Send + Sync> Sync for Arc<T> {}
+unsafe impl<T: Send + Sync> Send for Weak<T> {}
+unsafe impl<T: Send + Sync> Sync for Weak<T> {}
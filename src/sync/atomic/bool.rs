@@ -58,11 +58,15 @@ impl AtomicBool {
     /// Fetches the value, and applies a function to it that returns an optional new value.
     /// Returns a `Result` of `Ok(previous_value)` if the function returned `Some(_)`, else
     /// `Err(previous_value)`.
+    ///
+    /// Like the standard library, this is implemented as a loop around a load and
+    /// `compare_exchange_weak`, so other threads can modify the value between each attempt's
+    /// load and store, and `f` may be called more than once.
     pub fn fetch_update<F>(&self, set_order: Ordering, fetch_order: Ordering, f: F) -> Result<bool, bool>
     where
         F: FnMut(bool) -> Option<bool>,
     {
-        self.inner.fetch_update(set_order, fetch_order, f)
+        self.inner.fetch_update_loop(set_order, fetch_order, f)
     }
 
     /// Stores a value into the atomic boolean if the current value is the same as the
@@ -88,7 +92,8 @@ impl AtomicBool {
         success: Ordering,
         failure: Ordering,
     ) -> Result<bool, bool> {
-        self.fetch_update(success, failure, |val| (val == current).then(|| new))
+        self.inner
+            .fetch_update(success, failure, |val| (val == current).then(|| new))
     }
 
     /// Stores a value into the atomic boolean if the current value is the same as the
@@ -98,7 +103,10 @@ impl AtomicBool {
     /// even when the comparison succeeds, which can result in more efficient code on some
     /// platforms. The return value is a result indicating whether the new value was written
     /// and containing the previous value.
-    // TODO actually produce spurious failures
+    ///
+    /// If [`Config::spurious_wakeups`](crate::Config::spurious_wakeups) is enabled, Shuttle will
+    /// explore executions where this function fails spuriously because another thread ran
+    /// between its load and its store.
     pub fn compare_exchange_weak(
         &self,
         current: bool,
@@ -106,27 +114,27 @@ impl AtomicBool {
         success: Ordering,
         failure: Ordering,
     ) -> Result<bool, bool> {
-        self.compare_exchange(current, new, success, failure)
+        self.inner.compare_exchange_weak(current, new, success, failure)
     }
 
     /// Logical "and" with the current value. Returns the previous value.
     pub fn fetch_and(&self, val: bool, order: Ordering) -> bool {
-        self.fetch_update(order, order, |old| Some(old & val)).unwrap()
+        self.inner.fetch_update(order, order, |old| Some(old & val)).unwrap()
     }
 
     /// Logical "nand" with the current value. Returns the previous value.
     pub fn fetch_nand(&self, val: bool, order: Ordering) -> bool {
-        self.fetch_update(order, order, |old| Some(!(old & val))).unwrap()
+        self.inner.fetch_update(order, order, |old| Some(!(old & val))).unwrap()
     }
 
     /// Logical "or" with the current value. Returns the previous value.
     pub fn fetch_or(&self, val: bool, order: Ordering) -> bool {
-        self.fetch_update(order, order, |old| Some(old | val)).unwrap()
+        self.inner.fetch_update(order, order, |old| Some(old | val)).unwrap()
     }
 
     /// Logical "xor" with the current value. Returns the previous value.
     pub fn fetch_xor(&self, val: bool, order: Ordering) -> bool {
-        self.fetch_update(order, order, |old| Some(old ^ val)).unwrap()
+        self.inner.fetch_update(order, order, |old| Some(old ^ val)).unwrap()
     }
 
     /// Load the atomic value directly without triggering any Shuttle context switches.
@@ -62,6 +62,10 @@ macro_rules! atomic_int {
             /// Fetches the value, and applies a function to it that returns an optional new value.
             /// Returns a `Result` of `Ok(previous_value)` if the function returned `Some(_)`, else
             /// `Err(previous_value)`.
+            ///
+            /// Like the standard library, this is implemented as a loop around a load and
+            /// `compare_exchange_weak`, so other threads can modify the value between each attempt's
+            /// load and store, and `f` may be called more than once.
             pub fn fetch_update<F>(
                 &self,
                 set_order: Ordering,
@@ -71,7 +75,7 @@ macro_rules! atomic_int {
             where
                 F: FnMut($int_type) -> Option<$int_type>,
             {
-                self.inner.fetch_update(set_order, fetch_order, f)
+                self.inner.fetch_update_loop(set_order, fetch_order, f)
             }
 
             /// Stores a value into the atomic integer if the current value is the same as the
@@ -100,7 +104,8 @@ macro_rules! atomic_int {
                 success: Ordering,
                 failure: Ordering,
             ) -> Result<$int_type, $int_type> {
-                self.fetch_update(success, failure, |val| (val == current).then(|| new))
+                self.inner
+                    .fetch_update(success, failure, |val| (val == current).then(|| new))
             }
 
             /// Stores a value into the atomic integer if the current value is the same as the
@@ -110,7 +115,10 @@ macro_rules! atomic_int {
             /// the comparison succeeds, which can result in more efficient code on some platforms.
             /// The return value is a result indicating whether the new value was written and
             /// containing the previous value.
-            // TODO actually produce spurious failures
+            ///
+            /// If [`Config::spurious_wakeups`](crate::Config::spurious_wakeups) is enabled, Shuttle will
+            /// explore executions where this function fails spuriously because another thread ran
+            /// between its load and its store.
             pub fn compare_exchange_weak(
                 &self,
                 current: $int_type,
@@ -118,14 +126,15 @@ macro_rules! atomic_int {
                 success: Ordering,
                 failure: Ordering,
             ) -> Result<$int_type, $int_type> {
-                self.compare_exchange(current, new, success, failure)
+                self.inner.compare_exchange_weak(current, new, success, failure)
             }
 
             /// Adds to the current value, returning the previous value.
             ///
             /// This operation wraps around on overflow.
             pub fn fetch_add(&self, val: $int_type, order: Ordering) -> $int_type {
-                self.fetch_update(order, order, |old| Some(old.wrapping_add(val)))
+                self.inner
+                    .fetch_update(order, order, |old| Some(old.wrapping_add(val)))
                     .unwrap()
             }
 
@@ -133,38 +142,51 @@ macro_rules! atomic_int {
             ///
             /// This operation wraps around on overflow.
             pub fn fetch_sub(&self, val: $int_type, order: Ordering) -> $int_type {
-                self.fetch_update(order, order, |old| Some(old.wrapping_sub(val)))
+                self.inner
+                    .fetch_update(order, order, |old| Some(old.wrapping_sub(val)))
                     .unwrap()
             }
 
             /// Bitwise "and" with the current value. Returns the previous value.
             pub fn fetch_and(&self, val: $int_type, order: Ordering) -> $int_type {
-                self.fetch_update(order, order, |old| Some(old & val)).unwrap()
+                self.inner
+                    .fetch_update(order, order, |old| Some(old & val))
+                    .unwrap()
             }
 
             /// Bitwise "nand" with the current value. Returns the previous value.
             pub fn fetch_nand(&self, val: $int_type, order: Ordering) -> $int_type {
-                self.fetch_update(order, order, |old| Some(!(old & val))).unwrap()
+                self.inner
+                    .fetch_update(order, order, |old| Some(!(old & val)))
+                    .unwrap()
             }
 
             /// Bitwise "or" with the current value. Returns the previous value.
             pub fn fetch_or(&self, val: $int_type, order: Ordering) -> $int_type {
-                self.fetch_update(order, order, |old| Some(old | val)).unwrap()
+                self.inner
+                    .fetch_update(order, order, |old| Some(old | val))
+                    .unwrap()
             }
 
             /// Bitwise "xor" with the current value. Returns the previous value.
             pub fn fetch_xor(&self, val: $int_type, order: Ordering) -> $int_type {
-                self.fetch_update(order, order, |old| Some(old ^ val)).unwrap()
+                self.inner
+                    .fetch_update(order, order, |old| Some(old ^ val))
+                    .unwrap()
             }
 
             /// Maximum with the current value. Returns the previous value.
             pub fn fetch_max(&self, val: $int_type, order: Ordering) -> $int_type {
-                self.fetch_update(order, order, |old| Some(old.max(val))).unwrap()
+                self.inner
+                    .fetch_update(order, order, |old| Some(old.max(val)))
+                    .unwrap()
             }
 
             /// Minimum with the current value. Returns the previous value.
             pub fn fetch_min(&self, val: $int_type, order: Ordering) -> $int_type {
-                self.fetch_update(order, order, |old| Some(old.min(val))).unwrap()
+                self.inner
+                    .fetch_update(order, order, |old| Some(old.min(val)))
+                    .unwrap()
             }
 
             /// Load the atomic value directly without triggering any Shuttle context switches.
@@ -191,3 +213,7 @@ atomic_int!(AtomicU16, u16);
 atomic_int!(AtomicU32, u32);
 atomic_int!(AtomicU64, u64);
 atomic_int!(AtomicUsize, usize);
+// The standard library's 128-bit atomics are unstable, as not every platform supports them, but
+// since ours don't need hardware support we can provide them anyway
+atomic_int!(AtomicI128, i128);
+atomic_int!(AtomicU128, u128);
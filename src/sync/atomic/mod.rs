@@ -49,6 +49,40 @@
 //! [`silence_atomic_ordering_warning`](crate::Config::silence_atomic_ordering_warning) field of
 //! [`Config`](crate::Config) to true.
 //!
+//! ## Happens-before
+//!
+//! While Shuttle treats the *values* of all atomic operations as if they were SeqCst, the vector
+//! clocks it uses to track causality (see [`current::clock`](crate::current::clock)) do respect
+//! orderings. An Acquire load synchronizes with the Release store it reads from, but a Relaxed
+//! load or store creates no happens-before relationship on its own. Fences work as they do in the
+//! standard library: a Relaxed store after a Release [`fence`] publishes everything before the
+//! fence, and an Acquire [`fence`] after a Relaxed load synchronizes with the store that load read
+//! from.
+//!
+//! ## Store buffering
+//!
+//! Setting the [`store_buffering`](crate::Config::store_buffering) field of
+//! [`Config`](crate::Config) to true makes Shuttle model one relaxed behavior: an atomic store that
+//! uses an ordering weaker than SeqCst can be held in the storing thread's store buffer, so that
+//! other threads don't see it until after that thread's next atomic load. This is the reordering
+//! that breaks Dekker-style mutual exclusion, where each thread sets its own flag and then checks
+//! the others':
+//! ```
+//! # use std::sync::atomic::{AtomicBool, Ordering};
+//! # let (my_flag, other_flag) = (AtomicBool::new(false), AtomicBool::new(false));
+//! my_flag.store(true, Ordering::Release);
+//! if !other_flag.load(Ordering::Acquire) {
+//!     // critical section
+//! }
+//! ```
+//! With store buffering, both threads can read false and enter the critical section together. The
+//! code is only correct if both the store and the load are SeqCst.
+//!
+//! A thread always sees its own buffered store, and drains its buffer at any yield point other than
+//! an atomic load: another atomic store or read-modify-write operation, a fence, or an operation on
+//! any other synchronization primitive. This is stronger than the C++ memory model, so this option
+//! still does not make Shuttle sound for relaxed orderings.
+//!
 //! [Loom]: https://crates.io/crates/loom
 
 mod bool;
@@ -62,7 +96,9 @@ pub use std::sync::atomic::Ordering;
 
 use crate::runtime::execution::ExecutionState;
 use crate::runtime::task::clock::VectorClock;
+use crate::runtime::task::TaskId;
 use crate::runtime::thread;
+use crate::scheduler::ObjectId;
 use std::cell::RefCell;
 
 static PRINTED_ORDERING_WARNING: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
@@ -98,6 +134,9 @@ fn maybe_warn_about_ordering(order: Ordering) {
 }
 
 /// An atomic fence, like the standard library's [std::sync::atomic::fence].
+///
+/// A fence is a yield point. See the [module documentation](self#happens-before) for how fences
+/// affect causality.
 pub fn fence(order: Ordering) {
     if order == Ordering::Relaxed {
         panic!("there is no such thing as a relaxed fence");
@@ -105,7 +144,59 @@ pub fn fence(order: Ordering) {
 
     maybe_warn_about_ordering(order);
 
-    // SeqCst fences are no-ops in our execution model
+    // A fence drains this thread's store buffer (see `Config::store_buffering`)
+    ExecutionState::with(|s| s.current_mut().drain_store_buffer());
+    thread::switch_atomic();
+
+    ExecutionState::with(|s| {
+        // An acquire fence synchronizes with the stores that earlier relaxed loads read from
+        if is_acquire(order) {
+            let clock = s.current().acquire_fence_clock.clone();
+            s.update_clock(&clock);
+        }
+        // A release fence publishes this thread's clock to later relaxed stores
+        if is_release(order) {
+            let clock = s.increment_clock().clone();
+            s.current_mut().release_fence_clock = clock;
+        }
+    });
+}
+
+/// Runs `f` without preempting the current thread at the yield points inside it, so that the
+/// operations in `f` (e.g., a sequence of atomic operations) behave as a single step of the test.
+///
+/// This is an escape hatch for shrinking the search space: a test that performs several operations
+/// that can't race with any other thread (e.g., because they only touch state no other thread uses
+/// yet) can wrap them in `critical` to avoid exploring the interleavings between them. The thread
+/// still yields if it blocks inside `f` (e.g., waiting for a lock), and other threads can be
+/// preempted as usual. Regions can be nested.
+///
+/// # Warning
+///
+/// This is unsound if misused. Shuttle will never explore another thread running in the middle of
+/// `f`, so wrapping operations that *can* race with other threads hides any bug that race could
+/// cause. A thread that spins inside `f` waiting for another thread will never let that thread
+/// run, and will hang the test.
+pub fn critical<F, T>(f: F) -> T
+where
+    F: FnOnce() -> T,
+{
+    // Leave the region even if `f` panics
+    struct CriticalGuard;
+
+    impl Drop for CriticalGuard {
+        fn drop(&mut self) {
+            ExecutionState::try_with(|s| {
+                if s.try_current().is_some() {
+                    s.current_mut().critical_depth -= 1;
+                }
+            });
+        }
+    }
+
+    ExecutionState::with(|s| s.current_mut().critical_depth += 1);
+    let _guard = CriticalGuard;
+    f()
 }
 
 // We can just reuse the standard library's compiler fence, as they have no visible run-time
@@ -119,6 +210,23 @@ pub use std::sync::atomic::compiler_fence;
 struct Atomic<T> {
     inner: RefCell<T>,
     clock: RefCell<Option<VectorClock>>, // wrapped in option to support the const new()
+    // A store to this atomic that is still sitting in its thread's store buffer, if any
+    pending: RefCell<Option<PendingStore<T>>>,
+}
+
+/// A buffered store (see `Config::store_buffering`). The store becomes visible to other threads
+/// once its thread drains its store buffer, which is tracked by the thread's store buffer epoch.
+///
+/// Each thread has at most one buffered store at a time, because every atomic operation other than
+/// a load drains the store buffer first. That means it's always correct to drain a buffered store
+/// early, which lets us keep at most one buffered store per atomic: a store, swap, or update to an
+/// atomic commits any store that another thread has buffered for it first.
+#[derive(Debug)]
+struct PendingStore<T> {
+    task: TaskId,
+    value: T,
+    epoch: usize,
+    clock: VectorClock,
 }
 
 // Safety: Atomic is never actually passed across true threads, only across continuations. The
@@ -137,49 +245,95 @@ impl<T> Atomic<T> {
         Self {
             inner: RefCell::new(v),
             clock: RefCell::new(None),
+            pending: RefCell::new(None),
         }
     }
 }
 
 impl<T: Copy + Eq> Atomic<T> {
+    /// Possibly yield back to the executor, as part of an operation on this atomic. Returns true if
+    /// the current task was switched out.
+    fn switch(&self) -> bool {
+        ExecutionState::with(|s| s.record_access(ObjectId::atomic(self)));
+        let preempted = thread::switch_atomic();
+        ExecutionState::with(|s| s.record_access(ObjectId::atomic(self)));
+        preempted
+    }
+
     fn get_mut(&mut self) -> &mut T {
-        self.exhale_clock();
+        self.commit_pending(true);
+        // This is often called from a `Drop` impl, which might run while a stopped execution is
+        // being torn down, when there's no current task to inherit the clock
+        if !ExecutionState::should_stop() {
+            self.exhale_clock(Ordering::SeqCst);
+        }
         self.inner.get_mut()
     }
 
     fn into_inner(self) -> T {
-        self.exhale_clock();
+        self.commit_pending(true);
+        if !ExecutionState::should_stop() {
+            self.exhale_clock(Ordering::SeqCst);
+        }
         self.inner.into_inner()
     }
 
     fn load(&self, order: Ordering) -> T {
         maybe_warn_about_ordering(order);
 
-        thread::switch();
-        self.exhale_clock();
-        let value = *self.inner.borrow();
-        thread::switch();
+        self.switch();
+        let me = ExecutionState::me();
+        // A thread can always see its own buffered store
+        let value = match &*self.pending.borrow() {
+            Some(pending) if pending.task == me => Some(pending.value),
+            _ => None,
+        };
+        let value = value.unwrap_or_else(|| {
+            self.commit_pending(false);
+            self.exhale_clock(order);
+            *self.inner.borrow()
+        });
+        self.switch();
+        // A load can be reordered before an earlier buffered store, but only one: once the load is
+        // done, the store becomes visible. Draining here (rather than at some later step) keeps
+        // the delay bounded, so that spin loops waiting on a buffered store still terminate.
+        ExecutionState::with(|s| s.current_mut().drain_store_buffer());
         value
     }
 
     fn store(&self, val: T, order: Ordering) {
         maybe_warn_about_ordering(order);
 
-        thread::switch();
-        self.inhale_clock();
-        *self.inner.borrow_mut() = val;
-        thread::switch();
+        ExecutionState::with(|s| s.current_mut().drain_store_buffer());
+        self.switch();
+        self.commit_pending(true);
+        let buffered = order != Ordering::SeqCst && ExecutionState::with(|s| s.config.store_buffering);
+        if buffered {
+            let pending = ExecutionState::with(|s| PendingStore {
+                task: s.current().id(),
+                value: val,
+                epoch: s.current().store_buffer_epoch(),
+                clock: store_clock(s, order).clone(),
+            });
+            *self.pending.borrow_mut() = Some(pending);
+        } else {
+            self.inhale_clock(order);
+            *self.inner.borrow_mut() = val;
+        }
+        self.switch();
     }
 
     fn swap(&self, mut val: T, order: Ordering) -> T {
         maybe_warn_about_ordering(order);
 
         // swap behaves like { let x = load() ; store(val) ; x }
-        thread::switch();
-        self.exhale_clock(); // for the load
-        self.inhale_clock(); // for the store
+        ExecutionState::with(|s| s.current_mut().drain_store_buffer());
+        self.switch();
+        self.commit_pending(true);
+        self.exhale_clock(order); // for the load
+        self.inhale_clock(order); // for the store
         std::mem::swap(&mut *self.inner.borrow_mut(), &mut val);
-        thread::switch();
+        self.switch();
         val
     }
 
@@ -192,20 +346,98 @@ impl<T: Copy + Eq> Atomic<T> {
 
         // fetch_update behaves like (ignoring error): { let x = load() ; store(f(x)); x }
         // in the error case, there is no store, so the register does not inherit the clock of the caller
-        thread::switch();
-        self.exhale_clock(); // for the load()
+        ExecutionState::with(|s| s.current_mut().drain_store_buffer());
+        self.switch();
+        self.commit_pending(true);
         let current = *self.inner.borrow();
         let ret = if let Some(new) = f(current) {
             *self.inner.borrow_mut() = new;
-            self.inhale_clock(); // for the store()
+            self.exhale_clock(set_order); // for the load()
+            self.inhale_clock(set_order); // for the store()
             Ok(current)
         } else {
+            self.exhale_clock(fetch_order); // for the load()
             Err(current)
         };
-        thread::switch();
+        self.switch();
         ret
     }
 
+    fn compare_exchange_weak(&self, current: T, new: T, success: Ordering, failure: Ordering) -> Result<T, T> {
+        if !ExecutionState::with(|s| s.config.spurious_wakeups) {
+            return self.fetch_update(success, failure, |val| (val == current).then_some(new));
+        }
+
+        maybe_warn_about_ordering(success);
+        maybe_warn_about_ordering(failure);
+
+        // We model a weak compare-and-exchange like a load-linked/store-conditional pair: the load
+        // happens in one step and the store in the next, and the store fails spuriously if any other
+        // thread was scheduled in between, even if that thread didn't touch this atomic. To avoid
+        // livelocks where two threads' CAS loops keep failing each other forever, a thread can't
+        // fail spuriously twice in a row.
+        // Whether the store fails spuriously depends on whether any other thread ran in between,
+        // not just on the threads that touched this atomic
+        ExecutionState::with(|s| {
+            s.current_mut().drain_store_buffer();
+            s.record_unknown_access();
+        });
+        // The load-linked step
+        self.switch();
+        // The store-conditional step. Other threads might have modified the atomic while we were
+        // switched out, so we read it here rather than in the previous step.
+        let preempted = self.switch();
+        self.commit_pending(true);
+        let value = *self.inner.borrow();
+        let spurious =
+            value == current && preempted && ExecutionState::with(|s| !s.current().failed_weak_cas_spuriously);
+        ExecutionState::with(|s| s.current_mut().failed_weak_cas_spuriously = spurious);
+        if value != current || spurious {
+            self.exhale_clock(failure); // for the load
+            return Err(value);
+        }
+        *self.inner.borrow_mut() = new;
+        self.exhale_clock(success); // for the load
+        self.inhale_clock(success); // for the store
+        Ok(value)
+    }
+
+    // The standard library's `fetch_update`, which (unlike our `fetch_update`, which is a single
+    // atomic read-modify-write) retries a weak compare-and-exchange until it succeeds
+    fn fetch_update_loop<F>(&self, set_order: Ordering, fetch_order: Ordering, mut f: F) -> Result<T, T>
+    where
+        F: FnMut(T) -> Option<T>,
+    {
+        let mut prev = self.load(fetch_order);
+        while let Some(next) = f(prev) {
+            match self.compare_exchange_weak(prev, next, set_order, fetch_order) {
+                Ok(prev) => return Ok(prev),
+                Err(next_prev) => prev = next_prev,
+            }
+        }
+        Err(prev)
+    }
+
+    // Commit the buffered store to this atomic, if there is one. If `force` is false, only commits
+    // the store if its thread has drained its store buffer since making it.
+    fn commit_pending(&self, force: bool) {
+        let mut pending = self.pending.borrow_mut();
+        if let Some(store) = pending.as_ref() {
+            let visible = force
+                || ExecutionState::with(|s| {
+                    s.try_get(store.task)
+                        .map(|task| task.finished() || task.store_buffer_epoch() > store.epoch)
+                        .unwrap_or(true)
+                });
+            if visible {
+                let store = pending.take().unwrap();
+                *self.inner.borrow_mut() = store.value;
+                self.init_clock();
+                self.clock.borrow_mut().as_mut().unwrap().update(&store.clock);
+            }
+        }
+    }
+
     unsafe fn raw_load(&self) -> T {
         *self.inner.borrow()
     }
@@ -214,24 +446,48 @@ impl<T: Copy + Eq> Atomic<T> {
         self.clock.borrow_mut().get_or_insert(VectorClock::new());
     }
 
-    // Increment the clock for the current thread, and update the Atomic's clock with it
-    // The Atomic (self) "inhales" the clock from the thread
-    fn inhale_clock(&self) {
+    // Update the Atomic's clock with the clock published by a store with the given ordering (see
+    // `store_clock`). The Atomic (self) "inhales" the clock from the thread
+    fn inhale_clock(&self, order: Ordering) {
         self.init_clock();
         ExecutionState::with(|s| {
-            let clock = s.increment_clock();
+            let clock = store_clock(s, order);
             let mut self_clock = self.clock.borrow_mut();
             self_clock.as_mut().unwrap().update(clock);
         });
     }
 
-    // Increment the clock for the current thread, and update with the Atomic's current clock
-    // The Atomic (self) "exhales" its clock to the thread
-    fn exhale_clock(&self) {
+    // For an acquire load, increment the clock for the current thread, and update with the Atomic's
+    // current clock. A relaxed load instead saves the Atomic's clock for the thread's next acquire
+    // fence. The Atomic (self) "exhales" its clock to the thread
+    fn exhale_clock(&self, order: Ordering) {
         self.init_clock();
         ExecutionState::with(|s| {
             let self_clock = self.clock.borrow();
-            s.update_clock(self_clock.as_ref().unwrap());
+            if is_acquire(order) {
+                s.update_clock(self_clock.as_ref().unwrap());
+            } else {
+                s.current_mut().acquire_fence_clock.update(self_clock.as_ref().unwrap());
+            }
         });
     }
 }
+
+fn is_acquire(order: Ordering) -> bool {
+    matches!(order, Ordering::Acquire | Ordering::AcqRel | Ordering::SeqCst)
+}
+
+fn is_release(order: Ordering) -> bool {
+    matches!(order, Ordering::Release | Ordering::AcqRel | Ordering::SeqCst)
+}
+
+// The clock published by a store with the given ordering: for a release store, the current thread's
+// clock (after incrementing it), or for a relaxed store, the thread's clock as of its last release
+// fence.
+fn store_clock(state: &mut ExecutionState, order: Ordering) -> &VectorClock {
+    if is_release(order) {
+        state.increment_clock()
+    } else {
+        &state.current().release_fence_clock
+    }
+}
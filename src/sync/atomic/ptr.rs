@@ -2,6 +2,16 @@ use crate::sync::atomic::Atomic;
 use std::sync::atomic::Ordering;
 
 /// A raw pointer type which can be safely shared between threads.
+///
+/// Like the other atomic types, every operation on an `AtomicPtr` is a yield point, so
+/// compare-and-exchange on the pointer is a single atomic step that other threads can interleave
+/// around. However, Shuttle only models the pointer itself, not the memory it points to:
+/// * Reads and writes through the pointer are not yield points, and Shuttle doesn't check them for
+///   data races or use-after-free. A thread that frees a node another thread can still reach is a
+///   bug Shuttle will only catch if the program happens to crash or assert on the freed memory.
+/// * Pointer comparisons use real addresses from the system allocator, so whether the allocator
+///   reuses an address (and so whether an ABA problem manifests) is not under Shuttle's control
+///   and isn't explored.
 pub struct AtomicPtr<T> {
     inner: Atomic<*mut T>,
 }
@@ -63,11 +73,15 @@ impl<T> AtomicPtr<T> {
     /// Fetches the value, and applies a function to it that returns an optional new value.
     /// Returns a `Result` of `Ok(previous_value)` if the function returned `Some(_)`, else
     /// `Err(previous_value)`.
+    ///
+    /// Like the standard library, this is implemented as a loop around a load and
+    /// `compare_exchange_weak`, so other threads can modify the value between each attempt's
+    /// load and store, and `f` may be called more than once.
     pub fn fetch_update<F>(&self, set_order: Ordering, fetch_order: Ordering, f: F) -> Result<*mut T, *mut T>
     where
         F: FnMut(*mut T) -> Option<*mut T>,
     {
-        self.inner.fetch_update(set_order, fetch_order, f)
+        self.inner.fetch_update_loop(set_order, fetch_order, f)
     }
 
     /// Stores a value into the atomic pointer if the current value is the same as the
@@ -93,7 +107,8 @@ impl<T> AtomicPtr<T> {
         success: Ordering,
         failure: Ordering,
     ) -> Result<*mut T, *mut T> {
-        self.fetch_update(success, failure, |val| (val == current).then(|| new))
+        self.inner
+            .fetch_update(success, failure, |val| (val == current).then(|| new))
     }
 
     /// Stores a value into the atomic pointer if the current value is the same as the
@@ -103,7 +118,10 @@ impl<T> AtomicPtr<T> {
     /// even when the comparison succeeds, which can result in more efficient code on some
     /// platforms. The return value is a result indicating whether the new value was written
     /// and containing the previous value.
-    // TODO actually produce spurious failures
+    ///
+    /// If [`Config::spurious_wakeups`](crate::Config::spurious_wakeups) is enabled, Shuttle will
+    /// explore executions where this function fails spuriously because another thread ran
+    /// between its load and its store.
     pub fn compare_exchange_weak(
         &self,
         current: *mut T,
@@ -111,7 +129,7 @@ impl<T> AtomicPtr<T> {
         success: Ordering,
         failure: Ordering,
     ) -> Result<*mut T, *mut T> {
-        self.compare_exchange(current, new, success, failure)
+        self.inner.compare_exchange_weak(current, new, success, failure)
     }
 
     /// Load the atomic value directly without triggering any Shuttle context switches.
@@ -23,6 +23,9 @@ impl BarrierWaitResult {
 #[derive(Debug)]
 struct BarrierState {
     bound: usize,
+    // The number of times the barrier has released its waiters. Each generation has its own leader
+    // and clock.
+    generation: usize,
     leader: Option<TaskId>,
     waiters: HashSet<TaskId>,
     clock: VectorClock,
@@ -41,6 +44,7 @@ impl Barrier {
     pub fn new(n: usize) -> Self {
         let state = BarrierState {
             bound: n,
+            generation: 0,
             leader: None,
             waiters: HashSet::new(),
             clock: VectorClock::new(),
@@ -56,7 +60,8 @@ impl Barrier {
         let me = ExecutionState::me();
 
         let mut state = self.state.borrow_mut();
-        trace!(leader=?state.leader, waiters=?state.waiters, "waiting on barrier {:p}", self);
+        trace!(generation=state.generation, leader=?state.leader, waiters=?state.waiters, "waiting on barrier {:p}", self);
+        let my_generation = state.generation;
 
         // TODO The documentation for `Barrier` states that
         // TODO    A single (arbitrary) thread will receive a `BarrierWaitResult` that returns true
@@ -102,10 +107,21 @@ impl Barrier {
             is_leader: state.leader.unwrap() == me,
         };
 
+        if state.waiters.is_empty() {
+            // This generation is complete, so start the next one. Threads that call `wait` again
+            // will block until the whole next cohort has arrived.
+            state.generation += 1;
+            state.leader = None;
+            state.clock = VectorClock::new();
+        }
+
         drop(state);
 
         thread::switch();
 
+        // We can only have been released once our whole generation arrived
+        debug_assert!(self.state.borrow().generation > my_generation);
+
         result
     }
 }
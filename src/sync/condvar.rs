@@ -3,9 +3,9 @@ use crate::runtime::execution::ExecutionState;
 use crate::runtime::task::clock::VectorClock;
 use crate::runtime::task::TaskId;
 use crate::runtime::thread;
-use crate::sync::MutexGuard;
+use crate::sync::{Mutex, MutexGuard};
 use std::cell::RefCell;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::rc::Rc;
 use std::sync::{LockResult, PoisonError};
 use std::time::Duration;
@@ -21,6 +21,9 @@ pub struct Condvar {
 #[derive(Debug)]
 struct CondvarState {
     waiters: HashMap<TaskId, CondvarWaitStatus>,
+    // The waiters that can wake up without a notification (because they can time out, or spurious
+    // wakeups are enabled), and so remain runnable while waiting
+    early_waiters: HashSet<TaskId>,
     next_epoch: usize,
 }
 
@@ -112,11 +115,25 @@ enum CondvarWaitStatus {
 // In other words, this execution cannot deadlock -- if (4) happens-before (5), then Thread 4 is
 // guaranteed to be the thread unblocked by (5). After (5), Threads 1, 2, and 4 are all runnable,
 // and can run in any order (because they are all contending on the same mutex).
+//
+// ## `wait_timeout`
+//
+// Timed waits don't use the logical clock in `crate::time`, so any wait might time out. We model
+// this by leaving threads in `wait_timeout` runnable while they wait, so the scheduler can choose to
+// run them at any point. If a thread runs while a notification is pending for it, it consumes that
+// notification as usual; otherwise, it times out. The scheduler therefore explores both outcomes: running the
+// waiter before a `notify_*` times it out, and running it afterwards delivers the notification.
+// Whenever a waiter is made unrunnable above because all its signals were consumed, waiters in
+// `wait_timeout` stay runnable, as they can still time out.
+//
+// If `Config::spurious_wakeups` is enabled, we model spurious wakeups from `wait` the same way: the
+// waiter stays runnable, and if it runs without a pending notification, it wakes up spuriously.
 impl Condvar {
     /// Creates a new condition variable which is ready to be waited on and notified.
     pub fn new() -> Self {
         let state = CondvarState {
             waiters: HashMap::new(),
+            early_waiters: HashSet::new(),
             next_epoch: 0,
         };
 
@@ -126,74 +143,165 @@ impl Condvar {
     }
 
     /// Blocks the current thread until this condition variable receives a notification.
+    ///
+    /// If [`Config::spurious_wakeups`](crate::Config::spurious_wakeups) is enabled, this function
+    /// can also return without a notification.
     pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> LockResult<MutexGuard<'a, T>> {
+        let spurious_wakeups = ExecutionState::with(|s| s.config.spurious_wakeups);
+        self.wait_internal(guard, spurious_wakeups).0
+    }
+
+    /// Waits on this condition variable for a notification, timing out after a specified duration.
+    ///
+    /// This doesn't use Shuttle's [logical clock](crate::time), so `dur` is ignored, and the wait
+    /// times out nondeterministically: the timeout can happen at any point before the notification
+    /// arrives, and the scheduler explores both outcomes. The returned [`WaitTimeoutResult`] reports
+    /// which outcome occurred.
+    pub fn wait_timeout<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        _dur: Duration,
+    ) -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)> {
+        let (result, timed_out) = self.wait_internal(guard, true);
+        let timed_out = WaitTimeoutResult(timed_out);
+        result
+            .map(|guard| (guard, timed_out))
+            .map_err(|e| PoisonError::new((e.into_inner(), timed_out)))
+    }
+
+    /// Blocks the current thread until the provided condition becomes false.
+    ///
+    /// `condition` is checked immediately; if not satisfied, this thread waits for a notification
+    /// and then re-checks it, until it returns false.
+    pub fn wait_while<'a, T, F>(&self, mut guard: MutexGuard<'a, T>, mut condition: F) -> LockResult<MutexGuard<'a, T>>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *guard) {
+            guard = self.wait(guard)?;
+        }
+        Ok(guard)
+    }
+
+    /// Waits on this condition variable for a notification, timing out after a specified duration,
+    /// until the provided condition becomes false.
+    ///
+    /// The returned [`WaitTimeoutResult`] reports whether the wait timed out while the condition was
+    /// still true. As with [`Condvar::wait_timeout`], the timeout can happen at any point.
+    pub fn wait_timeout_while<'a, T, F>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        dur: Duration,
+        mut condition: F,
+    ) -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *guard) {
+            let (next, result) = self.wait_timeout(guard, dur)?;
+            guard = next;
+            if result.timed_out() {
+                // As in std, the condition gets one last check after the timeout
+                let timed_out = condition(&mut *guard);
+                return Ok((guard, WaitTimeoutResult(timed_out)));
+            }
+        }
+        Ok((guard, WaitTimeoutResult(false)))
+    }
+
+    /// Wait for a notification, returning the reacquired guard and whether the wait ended without
+    /// one (by timing out or waking spuriously). If `can_wake_early` is false, the wait only ends
+    /// when a notification arrives.
+    fn wait_internal<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        can_wake_early: bool,
+    ) -> (LockResult<MutexGuard<'a, T>>, bool) {
         let me = ExecutionState::me();
+        // The condvar doesn't report the objects it accesses, and its state changes on either side
+        // of the context switch in `unlock`
+        ExecutionState::with(|s| s.record_unknown_access());
 
         let mut state = self.state.borrow_mut();
 
-        trace!(waiters=?state.waiters, next_epoch=state.next_epoch, "waiting on condvar {:p}", self);
+        trace!(
+            waiters = ?state.waiters,
+            next_epoch = state.next_epoch,
+            can_wake_early,
+            "waiting on condvar {:p}",
+            self
+        );
 
         assert!(state.waiters.insert(me, CondvarWaitStatus::Waiting).is_none());
-        ExecutionState::with(|s| s.current_mut().block());
+        if can_wake_early {
+            // Stay runnable, so that the scheduler can choose to wake us early
+            state.early_waiters.insert(me);
+        } else {
+            ExecutionState::with(|s| s.current_mut().block());
+        }
         drop(state);
 
         // Release the lock, which triggers a context switch now that we are blocked
         let mutex = guard.unlock();
+        ExecutionState::with(|s| s.record_unknown_access());
 
         // After the context switch, consume whichever signal that woke this thread
         let mut state = self.state.borrow_mut();
         trace!(waiters=?state.waiters, next_epoch=state.next_epoch, "woken from condvar {:p}", self);
         let my_status = state.waiters.remove(&me).expect("should be waiting");
-        match my_status {
+        state.early_waiters.remove(&me);
+        let woke_early = match my_status {
             CondvarWaitStatus::Broadcast(clock) => {
                 // Woken by a broadcast, so nothing to do except update the clock
                 ExecutionState::with(|s| s.update_clock(&clock));
+                false
             }
             CondvarWaitStatus::Signal(mut epochs) => {
                 let (epoch, clock) = epochs.pop_front().expect("should be a pending signal");
                 // No other waiter is allowed to be unblocked by the epoch that woke us
-                for (tid, status) in state.waiters.iter_mut() {
+                let CondvarState {
+                    waiters, early_waiters, ..
+                } = &mut *state;
+                for (tid, status) in waiters.iter_mut() {
                     if let CondvarWaitStatus::Signal(epochs) = status {
                         if let Some(i) = epochs.iter().position(|e| epoch == e.0) {
                             epochs.remove(i);
                             if epochs.is_empty() {
                                 *status = CondvarWaitStatus::Waiting;
                                 // Make the task unrunnable if there are no pending signals that
-                                // could unblock it
-                                ExecutionState::with(|s| s.get_mut(*tid).block());
+                                // could unblock it, unless it can still wake up early
+                                if !early_waiters.contains(tid) {
+                                    ExecutionState::with(|s| s.get_mut(*tid).block());
+                                }
                             }
                         }
                     }
                 }
                 // Update the thread's clock with the clock from the notifier
                 ExecutionState::with(|s| s.update_clock(&clock));
+                false
             }
-            CondvarWaitStatus::Waiting => panic!("should not have been woken while in Waiting status"),
-        }
+            CondvarWaitStatus::Waiting => {
+                assert!(can_wake_early, "should not have been woken while in Waiting status");
+                true
+            }
+        };
+        trace!(woke_early, "finished waiting on condvar {:p}", self);
         drop(state);
 
         // Reacquire the lock
         // TODO The context switch involved here might be redundant? The scheduler implicitly chose
         // TODO this thread to win the lock when it ran us after the context switch above.
-        mutex.lock()
-    }
-
-    /// Waits on this condition variable for a notification, timing out after a specified duration.
-    pub fn wait_timeout<'a, T>(
-        &self,
-        guard: MutexGuard<'a, T>,
-        _dur: Duration,
-    ) -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)> {
-        // TODO support the timeout case -- this method never times out
-        self.wait(guard)
-            .map(|guard| (guard, WaitTimeoutResult(false)))
-            .map_err(|e| PoisonError::new((e.into_inner(), WaitTimeoutResult(false))))
+        (mutex.lock(), woke_early)
     }
 
     /// Wakes up one blocked thread on this condvar.
     ///
     /// If there is a blocked thread on this condition variable, then it will be woken up from its
     /// call to wait or wait_timeout. Calls to notify_one are not buffered in any way.
+    ///
+    /// As with `std`, there is no guarantee about which thread is woken up if several are waiting.
+    /// Shuttle lets the scheduler choose any of them, and explores each choice.
     pub fn notify_one(&self) {
         let me = ExecutionState::me();
 
@@ -273,3 +381,17 @@ impl WaitTimeoutResult {
         self.0
     }
 }
+
+/// Locks `mutex` and blocks the current thread until `predicate` holds for the protected data,
+/// waiting on `condvar` for notifications in between, and returns the guard.
+///
+/// The predicate is checked while holding the lock, both immediately and after every wakeup, so
+/// spurious wakeups and notifications meant for other waiters are handled correctly. This is the
+/// usual lock/wait/recheck loop built on [`Condvar::wait_while`]; threads that change the data must
+/// still notify `condvar` afterwards.
+pub fn wait_for<'a, T, F>(mutex: &'a Mutex<T>, condvar: &Condvar, mut predicate: F) -> LockResult<MutexGuard<'a, T>>
+where
+    F: FnMut(&T) -> bool,
+{
+    condvar.wait_while(mutex.lock()?, |state| !predicate(state))
+}
@@ -0,0 +1,52 @@
+use crate::sync::OnceCell;
+use std::fmt::{Debug, Formatter};
+use std::ops::Deref;
+
+/// A value which is initialized on the first access, like [`std::sync::LazyLock`].
+///
+/// If several threads access the value concurrently before it's initialized, Shuttle explores the
+/// race between them, and the initialization function runs exactly once.
+///
+/// Unlike `std`, the initialization function must be `Fn` rather than `FnOnce`. A `LazyLock` used
+/// as a static outlives each Shuttle execution, and so is initialized again in each execution that
+/// accesses it.
+pub struct LazyLock<T, F = fn() -> T> {
+    cell: OnceCell<T>,
+    init: F,
+}
+
+impl<T, F: Fn() -> T> LazyLock<T, F> {
+    /// Creates a new lazy value with the given initializing function.
+    pub const fn new(init: F) -> Self {
+        Self {
+            cell: OnceCell::new(),
+            init,
+        }
+    }
+
+    /// Forces the evaluation of this lazy value and returns a reference to the result. This is
+    /// equivalent to the `Deref` impl, but is explicit.
+    pub fn force(this: &Self) -> &T {
+        this.cell.get_or_init(|| (this.init)())
+    }
+}
+
+impl<T, F: Fn() -> T> Deref for LazyLock<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        Self::force(self)
+    }
+}
+
+impl<T: Default> Default for LazyLock<T> {
+    fn default() -> Self {
+        Self::new(T::default)
+    }
+}
+
+impl<T: Debug, F> Debug for LazyLock<T, F> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LazyLock").field("cell", &self.cell).finish()
+    }
+}
@@ -1,25 +1,52 @@
 //! Shuttle's implementation of [`std::sync`].
 
+mod arc;
 pub mod atomic;
 mod barrier;
 mod condvar;
+mod lazy_lock;
 pub mod mpsc;
 mod mutex;
 mod once;
+mod once_cell;
+mod reentrant_mutex;
 mod rwlock;
+mod semaphore;
+
+pub use arc::{Arc, Weak};
 
 pub use barrier::{Barrier, BarrierWaitResult};
-pub use condvar::{Condvar, WaitTimeoutResult};
+pub use condvar::{wait_for, Condvar, WaitTimeoutResult};
+
+pub use lazy_lock::LazyLock;
 
+pub use mutex::MappedMutexGuard;
 pub use mutex::Mutex;
 pub use mutex::MutexGuard;
 
 pub use once::Once;
 pub use once::OnceState;
 
+pub use once_cell::OnceCell;
+
+pub use reentrant_mutex::ReentrantMutex;
+pub use reentrant_mutex::ReentrantMutexGuard;
+
+pub use rwlock::MappedRwLockReadGuard;
+pub use rwlock::MappedRwLockWriteGuard;
 pub use rwlock::RwLock;
 pub use rwlock::RwLockReadGuard;
+pub use rwlock::RwLockUpgradableReadGuard;
 pub use rwlock::RwLockWriteGuard;
 
-// TODO implement true support for `Arc`
-pub use std::sync::Arc;
+pub use semaphore::{Semaphore, SemaphorePermit, TryAcquireError};
+
+// `Notify` is implemented for `tokio` compatibility, but it's also useful on its own for
+// producer-consumer tests that don't otherwise use tokio
+pub use crate::tokio::sync::{Notified, Notify};
+
+// A type-erased `std` lock guard. Mapped guards hold on to the guard of the original lock to keep
+// the underlying data borrowed, but don't know the type of that data.
+trait ErasedGuard {}
+
+impl<G> ErasedGuard for G {}
@@ -9,13 +9,13 @@ use std::cell::RefCell;
 use std::fmt::Debug;
 use std::rc::Rc;
 use std::result::Result;
-pub use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError};
+pub use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError};
 use std::sync::Arc;
 use std::time::Duration;
 use tracing::trace;
 
 // TODO
-// * Add support for try_recv() and try_send()
+// * Add support for try_send()
 // * Add support for iter() for receivers
 
 const MAX_INLINE_MESSAGES: usize = 32;
@@ -90,6 +90,7 @@ struct ChannelState<T> {
     known_receivers: usize,                                         // number or receivers referencing this channel
     waiting_senders: SmallVec<[TaskId; DEFAULT_INLINE_TASKS]>,      // list of currently blocked senders
     waiting_receivers: SmallVec<[TaskId; DEFAULT_INLINE_TASKS]>,    // list of currently blocked receivers
+    waiting_selectors: SmallVec<[TaskId; DEFAULT_INLINE_TASKS]>,    // list of currently blocked selectors
 }
 
 impl<T> Debug for ChannelState<T> {
@@ -103,10 +104,21 @@ impl<T> Debug for ChannelState<T> {
         )?;
         write!(f, "waiting_senders: [{:?}] ", self.waiting_senders)?;
         write!(f, "waiting_receivers: [{:?}] ", self.waiting_receivers)?;
+        write!(f, "waiting_selectors: [{:?}] ", self.waiting_selectors)?;
         write!(f, "}}")
     }
 }
 
+impl<T> ChannelState<T> {
+    // Unblock every selector waiting on this channel. Each one removes itself from the waiting
+    // lists of all the channels it's selecting over when it wakes up.
+    fn unblock_selectors(&self) {
+        for &tid in self.waiting_selectors.iter() {
+            ExecutionState::with(|s| s.get_mut(tid).unblock());
+        }
+    }
+}
+
 impl<T> Channel<T> {
     fn new(bound: Option<usize>) -> Self {
         let receiver_clock = if let Some(bound) = bound {
@@ -127,6 +139,7 @@ impl<T> Channel<T> {
                 known_receivers: 1,
                 waiting_senders: SmallVec::new(),
                 waiting_receivers: SmallVec::new(),
+                waiting_selectors: SmallVec::new(),
             })),
         }
     }
@@ -163,6 +176,10 @@ impl<T> Channel<T> {
             is_full || !state.waiting_senders.is_empty() || (is_rendezvous && state.waiting_receivers.is_empty());
 
         state.waiting_senders.push(me);
+        // A waiting sender makes a rendezvous channel ready to receive from, so wake any selectors
+        if is_rendezvous {
+            state.unblock_selectors();
+        }
         if sender_should_block {
             trace!(
                 state = ?state,
@@ -198,6 +215,7 @@ impl<T> Channel<T> {
             let clock = s.increment_clock();
             state.messages.push(TimestampedValue::new(message, clock.clone()));
         });
+        state.unblock_selectors();
 
         // The sender has just added a message to the channel, so unblock the first waiting receiver if any
         if let Some(&tid) = state.waiting_receivers.first() {
@@ -233,18 +251,64 @@ impl<T> Channel<T> {
     }
 
     fn recv(&self) -> Result<T, RecvError> {
+        // Receiving is a yield point, even if there's a message ready and the receiver doesn't need
+        // to block
+        thread::switch();
+
+        self.recv_internal(false).map_err(|_| RecvError)
+    }
+
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        // Receiving is a yield point, even if it fails
+        thread::switch();
+
+        let state = self.state.borrow();
+        // On a rendezvous channel, a waiting sender is ready to hand over its message, so we can
+        // receive it without blocking indefinitely
+        let sender_ready = self.bound == Some(0) && !state.waiting_senders.is_empty();
+        if state.messages.is_empty() && !sender_ready {
+            return if state.known_senders == 0 {
+                Err(TryRecvError::Disconnected)
+            } else {
+                Err(TryRecvError::Empty)
+            };
+        }
+        drop(state);
+
+        self.recv_internal(false).map_err(|_| TryRecvError::Disconnected)
+    }
+
+    fn recv_timeout(&self) -> Result<T, RecvTimeoutError> {
+        // Receiving is a yield point, even if there's a message ready and the receiver doesn't need
+        // to block
+        thread::switch();
+
+        let state = self.state.borrow();
+        // If we are about to unblock a waiting sender on a rendezvous channel, we're committed to
+        // receiving its message, so can't time out
+        let sender_ready = self.bound == Some(0) && !state.waiting_senders.is_empty();
+        drop(state);
+
+        self.recv_internal(!sender_ready)
+    }
+
+    // Receive a message, blocking until one is available. If `can_time_out` is true, the receiver
+    // stays runnable while it waits, and if it's scheduled while the channel is still empty, it
+    // times out instead (see `Receiver::recv_timeout`).
+    fn recv_internal(&self, can_time_out: bool) -> Result<T, RecvTimeoutError> {
         let me = ExecutionState::me();
         let mut state = self.state.borrow_mut();
 
         trace!(
             state = ?state,
+            can_time_out,
             "starting recv on channel {:p}",
             self,
         );
         // Check if there are any senders left; if not, and the channel is empty, fail with error
         // (If there are no senders, but the channel is nonempty, the receiver can successfully consume that message.)
         if state.messages.is_empty() && state.known_senders == 0 {
-            return Err(RecvError);
+            return Err(RecvTimeoutError::Disconnected);
         }
 
         // Pre-increment the receiver's clock before continuing
@@ -283,7 +347,9 @@ impl<T> Channel<T> {
                 me,
                 self,
             );
-            ExecutionState::with(|s| s.current_mut().block());
+            if !can_time_out {
+                ExecutionState::with(|s| s.current_mut().block());
+            }
             drop(state);
 
             thread::switch();
@@ -300,7 +366,16 @@ impl<T> Channel<T> {
             // (If there are no senders, but the channel is nonempty, the receiver can successfully consume that message.)
             // We repeat this check because the senders may have disconnected while the receiver was blocked.
             if state.messages.is_empty() && state.known_senders == 0 {
-                return Err(RecvError);
+                return Err(RecvTimeoutError::Disconnected);
+            }
+
+            // If we were scheduled before a message arrived, we timed out
+            if state.messages.is_empty() {
+                assert!(can_time_out, "receiver should only be unblocked once a message arrives");
+                let head = state.waiting_receivers.remove(0);
+                assert_eq!(head, me);
+                trace!(state = ?state, "receiver {:?} timed out on channel {:p}", me, self);
+                return Err(RecvTimeoutError::Timeout);
             }
         }
 
@@ -347,6 +422,37 @@ impl<T> Channel<T> {
     }
 }
 
+// The type-erased interface that `Select` uses to wait on channels of different message types
+trait Selectable {
+    // Whether a receive on this channel would complete without waiting for a sender to arrive
+    fn is_ready(&self) -> bool;
+    fn add_selector(&self, tid: TaskId);
+    fn remove_selector(&self, tid: TaskId);
+}
+
+impl<T> Selectable for Channel<T> {
+    fn is_ready(&self) -> bool {
+        let state = self.state.borrow();
+        // On a rendezvous channel, a waiting sender is ready to hand over its message
+        let sender_ready = self.bound == Some(0) && !state.waiting_senders.is_empty();
+        !state.messages.is_empty() || sender_ready || state.known_senders == 0
+    }
+
+    fn add_selector(&self, tid: TaskId) {
+        self.state.borrow_mut().waiting_selectors.push(tid);
+    }
+
+    fn remove_selector(&self, tid: TaskId) {
+        let mut state = self.state.borrow_mut();
+        let index = state
+            .waiting_selectors
+            .iter()
+            .position(|&t| t == tid)
+            .expect("selector should be waiting on the channel");
+        state.waiting_selectors.remove(index);
+    }
+}
+
 // Safety: A Channel is never actually passed across true threads, only across continuations. The
 // Rc<RefCell<_>> type therefore can't be preempted mid-bookkeeping-operation.
 // TODO We use this workaround in several places in Shuttle.  Maybe there's a cleaner solution.
@@ -367,11 +473,20 @@ impl<T> Receiver<T> {
         self.inner.recv()
     }
 
+    /// Attempts to return a pending value on this receiver without blocking, returning an error
+    /// if the channel is empty or if the corresponding channel has hung up.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.inner.try_recv()
+    }
+
     /// Attempts to wait for a value on this receiver, returning an error if the
     /// corresponding channel has hung up, or if it waits more than timeout.
+    ///
+    /// This doesn't use Shuttle's [logical clock](crate::time), so `timeout` is ignored, and the
+    /// receiver times out nondeterministically: it can time out at any point while it's waiting for
+    /// a message, and the scheduler explores both outcomes.
     pub fn recv_timeout(&self, _timeout: Duration) -> Result<T, RecvTimeoutError> {
-        // TODO support the timeout case -- this method never times out
-        self.inner.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        self.inner.recv_timeout()
     }
 }
 
@@ -380,6 +495,7 @@ impl<T> Drop for Receiver<T> {
         if ExecutionState::should_stop() {
             return;
         }
+        ExecutionState::with(|s| s.record_unknown_access());
         let mut state = self.inner.state.borrow_mut();
         assert!(state.known_receivers > 0);
         state.known_receivers -= 1;
@@ -392,6 +508,87 @@ impl<T> Drop for Receiver<T> {
     }
 }
 
+/// A selection over a set of [`Receiver`]s, which waits until at least one of them is ready.
+///
+/// Receivers are added with [`Select::recv`], which returns an index for that receiver. Then
+/// [`Select::ready`] blocks until some receiver is ready, meaning that it has a message available
+/// or its channel has disconnected, and returns that receiver's index. The caller can then receive
+/// from the ready receiver, which won't block. If more than one receiver is ready, Shuttle lets the
+/// scheduler choose which of them `ready` returns, and explores each choice, so tests shouldn't
+/// assume that selection is fair.
+pub struct Select<'a> {
+    receivers: Vec<&'a dyn Selectable>,
+}
+
+impl<'a> Select<'a> {
+    /// Creates an empty selection.
+    pub fn new() -> Self {
+        Self { receivers: Vec::new() }
+    }
+
+    /// Adds a receiver to the selection, and returns the index that [`Select::ready`] will return
+    /// when this receiver is ready.
+    pub fn recv<T>(&mut self, receiver: &'a Receiver<T>) -> usize {
+        self.receivers.push(receiver.inner.as_ref());
+        self.receivers.len() - 1
+    }
+
+    /// Blocks until one of the receivers in the selection is ready, and returns its index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no receivers have been added to the selection.
+    pub fn ready(&mut self) -> usize {
+        assert!(
+            !self.receivers.is_empty(),
+            "can't select over an empty set of receivers"
+        );
+        let me = ExecutionState::me();
+
+        // Selecting is a yield point, even if a receiver is ready and we don't need to block
+        thread::switch();
+
+        loop {
+            let ready = (0..self.receivers.len())
+                .filter(|&i| self.receivers[i].is_ready())
+                .collect::<SmallVec<[_; DEFAULT_INLINE_TASKS]>>();
+            if !ready.is_empty() {
+                let index = ready[ExecutionState::choose(ready.len())];
+                trace!(?ready, index, "selector {:?} chose receiver", me);
+                return index;
+            }
+
+            // Nothing is ready yet, so wait on every channel until one of them becomes ready. We
+            // might be woken up by several channels, so recheck all of them once we run again.
+            trace!("blocking selector {:?} on {} receivers", me, self.receivers.len());
+            for receiver in self.receivers.iter() {
+                receiver.add_selector(me);
+            }
+            ExecutionState::with(|s| s.current_mut().block());
+
+            thread::switch();
+
+            for receiver in self.receivers.iter() {
+                receiver.remove_selector(me);
+            }
+        }
+    }
+}
+
+impl<'a> Default for Select<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Debug for Select<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Select")
+            .field("num_receivers", &self.receivers.len())
+            .finish()
+    }
+}
+
 /// The sending-half of Rust's asynchronous [`channel`] type. This half can only be
 /// owned by one thread, but it can be cloned to send to other threads.
 #[derive(Debug)]
@@ -409,6 +606,8 @@ impl<T> Sender<T> {
 
 impl<T> Clone for Sender<T> {
     fn clone(&self) -> Self {
+        // Channels don't report the objects they access
+        ExecutionState::try_with(|s| s.record_unknown_access());
         let mut state = self.inner.state.borrow_mut();
         state.known_senders += 1;
         drop(state);
@@ -423,6 +622,7 @@ impl<T> Drop for Sender<T> {
         if ExecutionState::should_stop() {
             return;
         }
+        ExecutionState::with(|s| s.record_unknown_access());
         let mut state = self.inner.state.borrow_mut();
         assert!(state.known_senders > 0);
         state.known_senders -= 1;
@@ -431,6 +631,7 @@ impl<T> Drop for Sender<T> {
             for &tid in state.waiting_receivers.iter() {
                 ExecutionState::with(|s| s.get_mut(tid).unblock());
             }
+            state.unblock_selectors();
         }
     }
 }
@@ -457,6 +658,8 @@ impl<T> SyncSender<T> {
 
 impl<T> Clone for SyncSender<T> {
     fn clone(&self) -> Self {
+        // Channels don't report the objects they access
+        ExecutionState::try_with(|s| s.record_unknown_access());
         let mut state = self.inner.state.borrow_mut();
         state.known_senders += 1;
         drop(state);
@@ -471,6 +674,7 @@ impl<T> Drop for SyncSender<T> {
         if ExecutionState::should_stop() {
             return;
         }
+        ExecutionState::with(|s| s.record_unknown_access());
         let mut state = self.inner.state.borrow_mut();
         assert!(state.known_senders > 0);
         state.known_senders -= 1;
@@ -479,6 +683,7 @@ impl<T> Drop for SyncSender<T> {
             for &tid in state.waiting_receivers.iter() {
                 ExecutionState::with(|s| s.get_mut(tid).unblock());
             }
+            state.unblock_selectors();
         }
     }
 }
@@ -1,9 +1,15 @@
 use crate::runtime::execution::ExecutionState;
 use crate::runtime::task::clock::VectorClock;
-use crate::runtime::task::{TaskId, TaskSet};
+use crate::runtime::task::{LockId, TaskId, TaskSet};
 use crate::runtime::thread;
+use crate::scheduler::ObjectId;
+use crate::sync::ErasedGuard;
 use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
 use std::rc::Rc;
 use std::sync::{LockResult, PoisonError, TryLockError, TryLockResult};
 use tracing::trace;
@@ -24,17 +30,59 @@ pub struct MutexGuard<'a, T> {
 
 #[derive(Debug)]
 struct MutexState {
+    // Assigned lazily if the mutex was created outside of an execution
+    id: Option<LockId>,
+    name: Option<&'static str>,
     holder: Option<TaskId>,
     waiters: TaskSet,
+    // For FIFO mutexes, the order in which the current waiters started waiting for the lock. The
+    // lock is always handed to the task at the front of this queue.
+    fifo_queue: Option<VecDeque<TaskId>>,
+    poisoned: bool,
     clock: VectorClock,
 }
 
+impl MutexState {
+    fn id(&mut self) -> LockId {
+        let name = self.name;
+        *self
+            .id
+            .get_or_insert_with(|| ExecutionState::with(|s| s.new_lock_id(name)))
+    }
+}
+
 impl<T> Mutex<T> {
     /// Creates a new mutex in an unlocked state ready for use.
     pub fn new(value: T) -> Self {
+        Self::new_inner(value, false, None)
+    }
+
+    /// Creates a new mutex in an unlocked state ready for use, with a name that Shuttle uses to
+    /// refer to it in diagnostics, like deadlock reports and
+    /// [`RunStats::lock_contention`](crate::RunStats::lock_contention).
+    pub fn new_named(name: &'static str, value: T) -> Self {
+        Self::new_inner(value, false, Some(name))
+    }
+
+    /// Creates a new mutex in an unlocked state ready for use, that grants the lock to waiting
+    /// threads in the order they started waiting for it.
+    ///
+    /// By default, Shuttle lets the scheduler choose any waiting thread to acquire a mutex when it
+    /// is released. This mutex instead constrains the scheduler to hand the lock to the thread that
+    /// has waited the longest, which is useful for testing code that relies on fair lock handoff,
+    /// as a thread that continuously re-acquires this lock can't starve other waiters.
+    pub fn new_fifo(value: T) -> Self {
+        Self::new_inner(value, true, None)
+    }
+
+    fn new_inner(value: T, fifo: bool, name: Option<&'static str>) -> Self {
         let state = MutexState {
+            id: ExecutionState::try_with(|s| s.new_lock_id(name)),
+            name,
             holder: None,
             waiters: TaskSet::new(),
+            fifo_queue: if fifo { Some(VecDeque::new()) } else { None },
+            poisoned: false,
             clock: VectorClock::new(),
         };
 
@@ -49,19 +97,30 @@ impl<T> Mutex<T> {
         let me = ExecutionState::me();
 
         let mut state = self.state.borrow_mut();
-        trace!(holder=?state.holder, waiters=?state.waiters, "waiting to acquire mutex {:p}", self);
+        trace!(holder=?state.holder, waiters=?state.waiters, "waiting to acquire mutex {:p}", self.state);
 
         // We are waiting for the lock
         state.waiters.insert(me);
+        let id = state.id();
+        ExecutionState::with(|s| s.current_mut().set_waiting_lock(Some(id)));
         // If the lock is already held, then we are blocked
         if let Some(holder) = state.holder {
             assert_ne!(holder, me);
             ExecutionState::with(|s| s.current_mut().block());
         }
+        // For FIFO mutexes, we are also blocked if there are other threads ahead of us in the queue,
+        // even if the lock isn't currently held, as it has already been handed to the first of them
+        if let Some(queue) = state.fifo_queue.as_mut() {
+            if !queue.is_empty() {
+                ExecutionState::with(|s| s.current_mut().block());
+            }
+            queue.push_back(me);
+        }
+        ExecutionState::with(|s| s.record_lock_attempt(id, state.waiters.iter()));
         drop(state);
 
         // Acquiring a lock is a yield point
-        thread::switch();
+        thread::switch_on(ObjectId::lock(id));
 
         let mut state = self.state.borrow_mut();
         // Once the scheduler has resumed this thread, we are clear to become its holder. We might
@@ -70,7 +129,10 @@ impl<T> Mutex<T> {
         assert!(state.holder.is_none());
         state.holder = Some(me);
         state.waiters.remove(me);
-        trace!(waiters=?state.waiters, "acquired mutex {:p}", self);
+        if let Some(queue) = state.fifo_queue.as_mut() {
+            assert_eq!(queue.pop_front(), Some(me), "FIFO mutex granted out of order");
+        }
+        trace!(waiters=?state.waiters, "acquired mutex {:p}", self.state);
         // Block all other threads, since we won the race to take this lock
         // TODO a bit of a bummer that we have to do this (it would be cleaner if those threads
         // TODO never become unblocked), but might need to track more state to avoid this.
@@ -78,31 +140,97 @@ impl<T> Mutex<T> {
             ExecutionState::with(|s| s.get_mut(tid).block());
         }
         // Update acquiring thread's clock with the clock stored in the Mutex
-        ExecutionState::with(|s| s.update_clock(&state.clock));
+        ExecutionState::with(|s| {
+            s.update_clock(&state.clock);
+            s.current_mut().acquire_lock(id);
+        });
         drop(state);
 
-        match self.inner.try_lock() {
-            Ok(guard) => Ok(MutexGuard {
-                inner: Some(guard),
-                mutex: self,
-            }),
-            Err(TryLockError::Poisoned(guard)) => Err(PoisonError::new(MutexGuard {
-                inner: Some(guard.into_inner()),
-                mutex: self,
-            })),
-            Err(TryLockError::WouldBlock) => panic!("mutex state out of sync"),
-        }
+        self.check_poison(MutexGuard {
+            inner: Some(self.inner_guard()),
+            mutex: self,
+        })
     }
 
     /// Attempts to acquire this lock.
     ///
     /// If the lock could not be acquired at this time, then Err is returned. This function does not
     /// block.
-    pub fn try_lock(&self) -> TryLockResult<MutexGuard<T>> {
-        unimplemented!()
+    pub fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+        let me = ExecutionState::me();
+
+        let mut state = self.state.borrow_mut();
+        trace!(holder=?state.holder, waiters=?state.waiters, "trying to acquire mutex {:p}", self.state);
+
+        // We never wait for the lock, so we don't join the waiters. For FIFO mutexes, we can't jump
+        // the queue if the lock has already been handed to another thread.
+        let queue_empty = match &state.fifo_queue {
+            Some(queue) => queue.is_empty(),
+            None => true,
+        };
+        let acquired = match state.holder {
+            None if queue_empty => {
+                state.holder = Some(me);
+                true
+            }
+            None => false,
+            // Like `std`, this fails rather than panicking if the current thread holds the lock
+            Some(_) => false,
+        };
+
+        trace!(
+            waiters=?state.waiters,
+            "{} mutex {:p}",
+            if acquired { "acquired" } else { "failed to acquire" },
+            self.state
+        );
+
+        // Block all other threads waiting on this lock, since we won the race to take it
+        let id = state.id();
+        if acquired {
+            for tid in state.waiters.iter() {
+                ExecutionState::with(|s| s.get_mut(tid).block());
+            }
+            ExecutionState::with(|s| s.current_mut().acquire_lock(id));
+        }
+        // Update this thread's clock with the clock stored in the Mutex. We do this even if the
+        // attempt failed, because failing tells this thread that some other thread holds the lock,
+        // which is a causal dependency on that thread.
+        ExecutionState::with(|s| s.update_clock(&state.clock));
+        drop(state);
+
+        // Acquiring a lock is a yield point, even if we failed to acquire it
+        thread::switch_on(ObjectId::lock(id));
+
+        if !acquired {
+            return Err(TryLockError::WouldBlock);
+        }
+
+        let guard = MutexGuard {
+            inner: Some(self.inner_guard()),
+            mutex: self,
+        };
+        self.check_poison(guard).map_err(TryLockError::from)
+    }
+
+    /// Determines whether the mutex is poisoned.
+    ///
+    /// A mutex is poisoned if a thread panicked while holding it.
+    pub fn is_poisoned(&self) -> bool {
+        // The lock doesn't report the objects it accesses
+        ExecutionState::with(|s| s.record_unknown_access());
+        self.state.borrow().poisoned
+    }
+
+    /// Clear the poisoned state from this mutex, so that subsequent acquisitions succeed.
+    pub fn clear_poison(&self) {
+        ExecutionState::with(|s| s.record_unknown_access());
+        self.state.borrow_mut().poisoned = false;
     }
 
     /// Consumes this mutex, returning the underlying data.
+    ///
+    /// Returns an error if the mutex is poisoned, but the error still contains the data.
     pub fn into_inner(self) -> LockResult<T> {
         let state = self.state.borrow();
         assert!(state.holder.is_none());
@@ -111,7 +239,55 @@ impl<T> Mutex<T> {
         ExecutionState::with(|s| {
             s.update_clock(&state.clock);
         });
-        self.inner.into_inner()
+
+        // We track poisoning ourselves, so that it can be cleared, and so ignore the inner lock's
+        let value = self.inner.into_inner().unwrap_or_else(PoisonError::into_inner);
+        if state.poisoned {
+            Err(PoisonError::new(value))
+        } else {
+            Ok(value)
+        }
+    }
+
+    /// Returns a mutable reference to the underlying data. No locking is needed, as the mutable
+    /// borrow statically guarantees no other thread holds the lock.
+    ///
+    /// Returns an error if the mutex is poisoned, but the error still contains the reference.
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        let state = self.state.borrow();
+        assert!(state.holder.is_none());
+        // Update the receiver's clock with the Mutex clock
+        ExecutionState::with(|s| {
+            s.update_clock(&state.clock);
+        });
+        let poisoned = state.poisoned;
+        drop(state);
+
+        let value = self.inner.get_mut().unwrap_or_else(PoisonError::into_inner);
+        if poisoned {
+            Err(PoisonError::new(value))
+        } else {
+            Ok(value)
+        }
+    }
+
+    /// Take the inner `std` lock, which is free whenever the current thread holds this mutex
+    fn inner_guard(&self) -> std::sync::MutexGuard<'_, T> {
+        match self.inner.try_lock() {
+            Ok(guard) => guard,
+            // We track poisoning ourselves, so ignore the inner lock's
+            Err(TryLockError::Poisoned(guard)) => guard.into_inner(),
+            Err(TryLockError::WouldBlock) => panic!("mutex state out of sync"),
+        }
+    }
+
+    /// Wrap a newly acquired guard in an error if the mutex is poisoned
+    fn check_poison<G>(&self, guard: G) -> LockResult<G> {
+        if self.state.borrow().poisoned {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
+        }
     }
 }
 
@@ -133,46 +309,100 @@ impl<'a, T> MutexGuard<'a, T> {
     pub(super) fn unlock(self) -> &'a Mutex<T> {
         self.mutex
     }
+
+    /// Makes a new `MappedMutexGuard` for a component of the locked data.
+    ///
+    /// The mutex remains locked until the returned guard is dropped. This is an associated function
+    /// rather than a method to avoid conflicting with methods on the locked data.
+    pub fn map<U: ?Sized, F>(mut orig: Self, f: F) -> MappedMutexGuard<'a, U>
+    where
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        let mut inner = orig.inner.take().unwrap();
+        let data = NonNull::from(f(&mut *inner));
+        let state = Rc::clone(&orig.mutex.state);
+        // The mapped guard takes over responsibility for releasing the lock, so skip our Drop impl.
+        // This doesn't leak anything, as we took `inner` and everything else is borrowed.
+        std::mem::forget(orig);
+
+        MappedMutexGuard {
+            data,
+            inner: Some(Box::new(inner)),
+            state,
+            _p: PhantomData,
+        }
+    }
 }
 
 impl<'a, T> Drop for MutexGuard<'a, T> {
     fn drop(&mut self) {
         self.inner = None;
+        release(&self.mutex.state);
+    }
+}
 
-        let mut state = self.mutex.state.borrow_mut();
-
-        trace!(waiters=?state.waiters, "releasing mutex {:p}", self.mutex);
+/// Release the mutex with the given state, held by the current thread. This is the common drop
+/// logic for `MutexGuard` and `MappedMutexGuard`.
+fn release(mutex_state: &Rc<RefCell<MutexState>>) {
+    let mut state = mutex_state.borrow_mut();
 
-        state.holder = None;
+    trace!(waiters=?state.waiters, "releasing mutex {:p}", *mutex_state);
 
-        if ExecutionState::should_stop() {
-            return;
-        }
+    state.holder = None;
+    // As in std, releasing the lock while panicking poisons it
+    if ExecutionState::should_poison() {
+        state.poisoned = true;
+    }
 
-        // Unblock every thread waiting on this lock. The scheduler will choose one of them to win
-        // the race to this lock, and that thread will re-block all the losers.
-        let me = ExecutionState::me();
+    let id = state.id();
+    ExecutionState::release_lock(id);
 
-        // Update the Mutex clock with the owning thread's clock
-        ExecutionState::with(|s| {
-            let clock = s.increment_clock();
-            state.clock.update(clock);
+    // A thread that panics while holding the lock can't yield, but it still unblocks the waiters so
+    // they can observe that the lock is poisoned. We leave the waiters alone if the execution is
+    // being torn down, or if the panic came from this thread's own attempt to acquire the lock, as
+    // the lock's state might be inconsistent.
+    if std::thread::panicking() {
+        ExecutionState::try_with(|s| match s.try_current() {
+            Some(task) if !state.waiters.contains(task.id()) => unblock_waiters(&mut state, s),
+            _ => {}
         });
+        return;
+    }
+
+    if ExecutionState::should_stop() {
+        return;
+    }
+
+    ExecutionState::with(|s| unblock_waiters(&mut state, s));
+
+    drop(state);
+
+    // Releasing a lock is a yield point
+    thread::switch_on(ObjectId::lock(id));
+}
 
-        state.holder = None;
+/// Unblock the threads waiting for a mutex that the current thread just released. The scheduler will
+/// choose one of them to win the race to the lock, and that thread will re-block all the losers.
+fn unblock_waiters(state: &mut MutexState, s: &mut ExecutionState) {
+    let me = s.current().id();
+
+    // Update the Mutex clock with the owning thread's clock
+    let clock = s.increment_clock();
+    state.clock.update(clock);
+
+    if let Some(queue) = state.fifo_queue.as_ref() {
+        // FIFO mutexes instead hand the lock to the thread that has waited the longest
+        if let Some(&tid) = queue.front() {
+            debug_assert_ne!(tid, me);
+            s.get_mut(tid).unblock();
+        }
+    } else {
         for tid in state.waiters.iter() {
             debug_assert_ne!(tid, me);
-            ExecutionState::with(|s| {
-                let t = s.get_mut(tid);
-                debug_assert!(t.blocked());
-                t.unblock();
-            });
+            let t = s.get_mut(tid);
+            debug_assert!(t.blocked());
+            t.unblock();
         }
-
-        drop(state);
-
-        // Releasing a lock is a yield point
-        thread::switch();
     }
 }
 
@@ -189,3 +419,43 @@ impl<T> DerefMut for MutexGuard<'_, T> {
         &mut **self.inner.as_mut().unwrap()
     }
 }
+
+/// A mutex guard that only gives access to a component of the locked data. Created by
+/// [`MutexGuard::map`].
+pub struct MappedMutexGuard<'a, U: ?Sized> {
+    // Safety: points into the data protected by the `std` guard in `inner`, which lives for 'a
+    data: NonNull<U>,
+    inner: Option<Box<dyn ErasedGuard + 'a>>,
+    state: Rc<RefCell<MutexState>>,
+    _p: PhantomData<&'a mut U>,
+}
+
+impl<U: ?Sized> Drop for MappedMutexGuard<'_, U> {
+    fn drop(&mut self) {
+        self.inner = None;
+        release(&self.state);
+    }
+}
+
+impl<U: ?Sized> Deref for MappedMutexGuard<'_, U> {
+    type Target = U;
+
+    fn deref(&self) -> &Self::Target {
+        // Safety: the `std` guard in `inner` keeps the data borrowed for as long as we exist
+        unsafe { self.data.as_ref() }
+    }
+}
+
+impl<U: ?Sized> DerefMut for MappedMutexGuard<'_, U> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Safety: the `std` guard in `inner` keeps the data exclusively borrowed for as long as we
+        // exist, and we hand out at most one mutable reference at a time
+        unsafe { self.data.as_mut() }
+    }
+}
+
+impl<U: ?Sized + Debug> Debug for MappedMutexGuard<'_, U> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MappedMutexGuard").field("data", &&**self).finish()
+    }
+}
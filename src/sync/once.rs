@@ -69,6 +69,8 @@ impl Once {
     /// Returns `true` if some [`Once::call_once()`] call has completed successfully.
     pub fn is_completed(&self) -> bool {
         ExecutionState::with(|state| {
+            // A `Once` doesn't report the objects it accesses
+            state.record_unknown_access();
             let init = match self.get_state(state) {
                 Some(init) => init,
                 None => return false,
@@ -91,6 +93,7 @@ impl Once {
         F: FnOnce(&OnceState),
     {
         let lock = ExecutionState::with(|state| {
+            state.record_unknown_access();
             // Initialize the state of the `Once` cell if we're the first thread to try
             if self.get_state(state).is_none() {
                 self.init_state(state, OnceInitState::Running(Rc::new(Mutex::new(false))));
@@ -131,6 +134,7 @@ impl Once {
             // causality with future threads that try (and fail) to run `call_once`. The threads
             // that were racing with us will get causality through acquiring the `Mutex`.
             ExecutionState::with(|state| {
+                state.record_unknown_access();
                 let clock = state.increment_clock().clone();
                 *self
                     .get_state(state)
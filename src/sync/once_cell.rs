@@ -0,0 +1,74 @@
+use crate::sync::Once;
+use std::cell::UnsafeCell;
+
+/// A thread-safe cell which can be written to only once, like the `OnceCell` from the `once_cell`
+/// crate (and `std::sync::OnceLock`).
+///
+/// Initialization is mediated by a [`Once`], so if several threads race to initialize the cell,
+/// Shuttle explores which of them wins, and the others block until that initialization completes.
+#[derive(Debug)]
+pub struct OnceCell<T> {
+    once: Once,
+    // Only read once `once` has completed in the current execution. If the cell is a static, this
+    // might still hold a value from a previous execution, which we overwrite.
+    value: UnsafeCell<Option<T>>,
+}
+
+impl<T> OnceCell<T> {
+    /// Creates a new empty cell.
+    pub const fn new() -> Self {
+        Self {
+            once: Once::new(),
+            value: UnsafeCell::new(None),
+        }
+    }
+
+    /// Gets a reference to the underlying value, or `None` if the cell is empty.
+    pub fn get(&self) -> Option<&T> {
+        if self.once.is_completed() {
+            // Safety: the value is never written again once `once` is complete
+            unsafe { (*self.value.get()).as_ref() }
+        } else {
+            None
+        }
+    }
+
+    /// Sets the contents of this cell to `value`.
+    ///
+    /// Returns `Ok(())` if the cell was empty and `Err(value)` if it was full. If the cell is being
+    /// initialized by another thread, this blocks until that initialization completes.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        let mut value = Some(value);
+        // Safety: `call_once` guarantees that only one thread writes the value, and no thread reads
+        // it until the write is complete
+        self.once.call_once(|| unsafe { *self.value.get() = value.take() });
+        match value {
+            None => Ok(()),
+            Some(value) => Err(value),
+        }
+    }
+
+    /// Gets the contents of the cell, initializing it with `f` if the cell was empty.
+    ///
+    /// If several threads call this method concurrently, exactly one of them runs its closure, and
+    /// the others block until it completes and then return the same value.
+    pub fn get_or_init<F>(&self, f: F) -> &T
+    where
+        F: FnOnce() -> T,
+    {
+        // Safety: as in `set`
+        self.once.call_once(|| unsafe { *self.value.get() = Some(f()) });
+        self.get().expect("cell must be initialized by this point")
+    }
+}
+
+// Safety: as with `std::sync::OnceLock`, sharing the cell can give other threads access to the
+// value (requiring T: Sync) and can move a value set by one thread to another (requiring T: Send)
+unsafe impl<T: Send + Sync> Sync for OnceCell<T> {}
+unsafe impl<T: Send> Send for OnceCell<T> {}
+
+impl<T> Default for OnceCell<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,250 @@
+use crate::runtime::execution::ExecutionState;
+use crate::runtime::task::clock::VectorClock;
+use crate::runtime::task::{LockId, TaskId, TaskSet};
+use crate::runtime::thread;
+use crate::scheduler::ObjectId;
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::rc::Rc;
+use tracing::trace;
+
+/// A mutex that can be locked recursively by the thread that holds it, like the `ReentrantMutex`
+/// that `std` uses internally.
+///
+/// Because the same thread can hold multiple guards at once, the guards only give shared access to
+/// the protected data. Use interior mutability (e.g. a `RefCell`) to mutate it.
+///
+/// Like [`Mutex`](crate::sync::Mutex), the mutex is poisoned if a thread panics while holding it.
+/// Locking a poisoned reentrant mutex still succeeds, so use [`ReentrantMutex::is_poisoned`] to
+/// check for it.
+#[derive(Debug)]
+pub struct ReentrantMutex<T> {
+    data: T,
+    state: Rc<RefCell<ReentrantMutexState>>,
+}
+
+/// A guard for a [`ReentrantMutex`]. The mutex is released once every guard its holder acquired
+/// has been dropped.
+#[derive(Debug)]
+pub struct ReentrantMutexGuard<'a, T> {
+    mutex: &'a ReentrantMutex<T>,
+    // Guards must be dropped by the thread that holds the mutex
+    _p: PhantomData<*const ()>,
+}
+
+#[derive(Debug)]
+struct ReentrantMutexState {
+    // Assigned lazily if the mutex was created outside of an execution
+    id: Option<LockId>,
+    holder: Option<TaskId>,
+    // The number of guards the holder currently has for this mutex
+    count: usize,
+    waiters: TaskSet,
+    poisoned: bool,
+    clock: VectorClock,
+}
+
+impl ReentrantMutexState {
+    fn id(&mut self) -> LockId {
+        *self
+            .id
+            .get_or_insert_with(|| ExecutionState::with(|s| s.new_lock_id(None)))
+    }
+}
+
+impl<T> ReentrantMutex<T> {
+    /// Creates a new reentrant mutex in an unlocked state ready for use.
+    pub fn new(value: T) -> Self {
+        let state = ReentrantMutexState {
+            id: ExecutionState::try_with(|s| s.new_lock_id(None)),
+            holder: None,
+            count: 0,
+            waiters: TaskSet::new(),
+            poisoned: false,
+            clock: VectorClock::new(),
+        };
+
+        Self {
+            data: value,
+            state: Rc::new(RefCell::new(state)),
+        }
+    }
+
+    /// Acquires the mutex, blocking the current thread until it is able to do so.
+    ///
+    /// If the current thread already holds the mutex, this succeeds immediately without yielding.
+    pub fn lock(&self) -> ReentrantMutexGuard<'_, T> {
+        let me = ExecutionState::me();
+
+        let mut state = self.state.borrow_mut();
+        trace!(
+            holder = ?state.holder,
+            count = state.count,
+            waiters = ?state.waiters,
+            "waiting to acquire reentrant mutex {:p}",
+            self
+        );
+
+        // If we already hold the lock, we just take another guard for it. This isn't a yield point,
+        // as no other thread can observe the difference.
+        if state.holder == Some(me) {
+            state.count += 1;
+            trace!(count = state.count, "re-acquired reentrant mutex {:p}", self);
+            return ReentrantMutexGuard {
+                mutex: self,
+                _p: PhantomData,
+            };
+        }
+
+        // We are waiting for the lock
+        state.waiters.insert(me);
+        let id = state.id();
+        ExecutionState::with(|s| s.current_mut().set_waiting_lock(Some(id)));
+        // If the lock is already held, then we are blocked
+        if state.holder.is_some() {
+            ExecutionState::with(|s| s.current_mut().block());
+        }
+        ExecutionState::with(|s| s.record_lock_attempt(id, state.waiters.iter()));
+        drop(state);
+
+        // Acquiring a lock is a yield point
+        thread::switch_on(ObjectId::lock(id));
+
+        let mut state = self.state.borrow_mut();
+        // Once the scheduler has resumed this thread, we are clear to become its holder
+        assert!(state.holder.is_none());
+        assert_eq!(state.count, 0);
+        state.holder = Some(me);
+        state.count = 1;
+        state.waiters.remove(me);
+        trace!(waiters=?state.waiters, "acquired reentrant mutex {:p}", self);
+        // Block all other threads, since we won the race to take this lock
+        for tid in state.waiters.iter() {
+            ExecutionState::with(|s| s.get_mut(tid).block());
+        }
+        // Update acquiring thread's clock with the clock stored in the ReentrantMutex
+        ExecutionState::with(|s| {
+            s.update_clock(&state.clock);
+            s.current_mut().acquire_lock(id);
+        });
+        drop(state);
+
+        ReentrantMutexGuard {
+            mutex: self,
+            _p: PhantomData,
+        }
+    }
+
+    /// Determines whether the mutex is poisoned.
+    ///
+    /// A mutex is poisoned if a thread panicked while holding it.
+    pub fn is_poisoned(&self) -> bool {
+        // The lock doesn't report the objects it accesses
+        ExecutionState::with(|s| s.record_unknown_access());
+        self.state.borrow().poisoned
+    }
+
+    /// Clear the poisoned state from this mutex.
+    pub fn clear_poison(&self) {
+        ExecutionState::with(|s| s.record_unknown_access());
+        self.state.borrow_mut().poisoned = false;
+    }
+
+    /// Consumes this mutex, returning the underlying data.
+    pub fn into_inner(self) -> T {
+        let state = self.state.borrow();
+        assert!(state.holder.is_none());
+        assert!(state.waiters.is_empty());
+        // Update the receiver's clock with the ReentrantMutex clock
+        ExecutionState::with(|s| {
+            s.update_clock(&state.clock);
+        });
+        drop(state);
+        self.data
+    }
+}
+
+// Safety: ReentrantMutex is never actually passed across true threads, only across continuations.
+// The Rc<RefCell<_>> type therefore can't be preempted mid-bookkeeping-operation. Only one thread at
+// a time can access the data, so it only needs to be Send, as with `Mutex`.
+unsafe impl<T: Send> Send for ReentrantMutex<T> {}
+unsafe impl<T: Send> Sync for ReentrantMutex<T> {}
+
+impl<T: Default> Default for ReentrantMutex<T> {
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl<T> Drop for ReentrantMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        let mut state = self.mutex.state.borrow_mut();
+
+        trace!(count=state.count, waiters=?state.waiters, "releasing reentrant mutex {:p}", self.mutex);
+
+        assert!(state.count > 0);
+        state.count -= 1;
+        // The holder still has other guards for this lock, so it keeps holding it
+        if state.count > 0 {
+            return;
+        }
+
+        state.holder = None;
+        // As in std, releasing the lock while panicking poisons it
+        if ExecutionState::should_poison() {
+            state.poisoned = true;
+        }
+        let id = state.id();
+        ExecutionState::release_lock(id);
+
+        // A thread that panics while holding the lock can't yield, but it still unblocks the
+        // waiters so they can observe that the lock is poisoned. We leave the waiters alone if the
+        // execution is being torn down.
+        if std::thread::panicking() {
+            ExecutionState::try_with(|s| {
+                if s.try_current().is_some() {
+                    unblock_waiters(&mut state, s);
+                }
+            });
+            return;
+        }
+
+        if ExecutionState::should_stop() {
+            return;
+        }
+
+        ExecutionState::with(|s| unblock_waiters(&mut state, s));
+
+        drop(state);
+
+        // Releasing a lock is a yield point
+        thread::switch_on(ObjectId::lock(id));
+    }
+}
+
+/// Unblock every thread waiting for a reentrant mutex that the current thread just released. The
+/// scheduler will choose one of them to win the race to the lock, and that thread will re-block all
+/// the losers.
+fn unblock_waiters(state: &mut ReentrantMutexState, s: &mut ExecutionState) {
+    let me = s.current().id();
+
+    // Update the ReentrantMutex clock with the owning thread's clock
+    let clock = s.increment_clock();
+    state.clock.update(clock);
+
+    for tid in state.waiters.iter() {
+        debug_assert_ne!(tid, me);
+        let t = s.get_mut(tid);
+        debug_assert!(t.blocked());
+        t.unblock();
+    }
+}
+
+impl<T> Deref for ReentrantMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.mutex.data
+    }
+}
@@ -1,25 +1,59 @@
 use crate::runtime::execution::ExecutionState;
+use crate::runtime::storage::StorageKey;
 use crate::runtime::task::clock::VectorClock;
-use crate::runtime::task::{TaskId, TaskSet};
+use crate::runtime::task::{LockId, TaskId, TaskSet};
 use crate::runtime::thread;
+use crate::scheduler::ObjectId;
+use crate::sync::ErasedGuard;
 use std::cell::RefCell;
+use std::fmt::{Debug, Formatter};
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
 use std::ops::{Deref, DerefMut};
-use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::ptr::NonNull;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{LockResult, PoisonError, TryLockError, TryLockResult};
 use tracing::trace;
 
 /// A reader-writer lock, the same as [`std::sync::RwLock`].
-#[derive(Debug)]
+///
+/// In addition to the `std` API, this lock supports upgradable read access in the style of
+/// `parking_lot`'s `RwLock`, via [`RwLock::upgradable_read`].
+///
+/// An `RwLock` can be constructed anywhere, including in a `static`, but can only be used from
+/// within a Shuttle test. Its Shuttle state lives in the current execution, so a lock in a `static`
+/// starts each execution unlocked (although the data it protects persists across executions).
 pub struct RwLock<T> {
+    // The key of this lock's state in ExecutionState storage, or 0 if it hasn't been used yet. The
+    // key moves with the lock, so unlike its address, it identifies the lock even if it's moved.
+    key: AtomicUsize,
+    max_readers: Option<usize>,
+    prefer_writers: bool,
+    name: Option<&'static str>,
     inner: std::sync::RwLock<T>,
-    state: Rc<RefCell<RwLockState>>,
 }
 
 #[derive(Debug)]
 struct RwLockState {
+    id: LockId,
     holder: RwLockHolder,
+    // The reader (always also a member of the `RwLockHolder::Read` set) that holds upgradable read
+    // access to the lock, if any
+    upgradable_reader: Option<TaskId>,
+    // True if the upgradable reader is waiting for the other readers to drain so it can upgrade
+    upgrade_pending: bool,
+    // True if a writer panicked while holding the lock
+    poisoned: bool,
+    // The maximum number of threads that can hold read access at once, if limited
+    max_readers: Option<usize>,
+    // True if new readers must wait for any writer already waiting for the lock
+    prefer_writers: bool,
+    // One entry for each read guard a reader holds in addition to its first one, which it took
+    // with `read_recursive`
+    recursive_reads: Vec<TaskId>,
     waiting_readers: TaskSet,
+    waiting_upgradable_readers: TaskSet,
     waiting_writers: TaskSet,
     clock: VectorClock,
 }
@@ -34,144 +68,459 @@ enum RwLockHolder {
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
 enum RwLockType {
     Read,
+    UpgradableRead,
     Write,
 }
 
 impl<T> RwLock<T> {
     /// Create a new instance of an `RwLock<T>` which is unlocked.
-    pub fn new(value: T) -> Self {
-        let state = RwLockState {
-            holder: RwLockHolder::None,
-            waiting_readers: TaskSet::new(),
-            waiting_writers: TaskSet::new(),
-            clock: VectorClock::new(),
-        };
+    pub const fn new(value: T) -> Self {
+        Self {
+            key: AtomicUsize::new(0),
+            max_readers: None,
+            prefer_writers: false,
+            name: None,
+            inner: std::sync::RwLock::new(value),
+        }
+    }
+
+    /// Create a new instance of an `RwLock<T>` which is unlocked, with a name that Shuttle uses to
+    /// refer to it in diagnostics, like deadlock reports and
+    /// [`RunStats::lock_contention`](crate::RunStats::lock_contention).
+    pub const fn new_named(name: &'static str, value: T) -> Self {
+        Self {
+            key: AtomicUsize::new(0),
+            max_readers: None,
+            prefer_writers: false,
+            name: Some(name),
+            inner: std::sync::RwLock::new(value),
+        }
+    }
+
+    /// Create a new instance of an `RwLock<T>` which is unlocked, and which prefers writers over
+    /// new readers.
+    ///
+    /// A thread that calls [`RwLock::read`] while a writer is waiting for the lock waits for that
+    /// writer, even if the lock is currently held by other readers. This is the policy of many real
+    /// reader-writer locks (including `parking_lot`'s), and prevents writers from starving, but
+    /// means a thread that already holds read access deadlocks if it calls `read` again while a
+    /// writer is waiting. Use [`RwLock::read_recursive`] to take read access in that case.
+    pub const fn new_writer_preferring(value: T) -> Self {
+        Self {
+            key: AtomicUsize::new(0),
+            max_readers: None,
+            prefer_writers: true,
+            name: None,
+            inner: std::sync::RwLock::new(value),
+        }
+    }
 
+    /// Create a new instance of an `RwLock<T>` which is unlocked, and which allows at most
+    /// `max_readers` threads to hold read access to it at once.
+    ///
+    /// Once the limit is reached, further readers block until an existing reader releases the
+    /// lock, as if a writer were holding it.
+    pub const fn with_max_readers(value: T, max_readers: usize) -> Self {
+        assert!(max_readers > 0, "an RwLock must allow at least one reader");
         Self {
+            key: AtomicUsize::new(0),
+            max_readers: Some(max_readers),
+            prefer_writers: false,
+            name: None,
             inner: std::sync::RwLock::new(value),
-            state: Rc::new(RefCell::new(state)),
         }
     }
 
     /// Locks this rwlock with shared read access, blocking the current thread until it can be
     /// acquired.
+    ///
+    /// Returns an error if the lock is poisoned because a writer panicked while holding it, but the
+    /// lock is still acquired in that case.
     pub fn read(&self) -> LockResult<RwLockReadGuard<'_, T>> {
-        self.lock(RwLockType::Read);
-
-        match self.inner.try_read() {
-            Ok(guard) => Ok(RwLockReadGuard {
-                inner: Some(guard),
-                state: Rc::clone(&self.state),
-                me: ExecutionState::me(),
-            }),
-            Err(TryLockError::Poisoned(err)) => Err(PoisonError::new(RwLockReadGuard {
-                inner: Some(err.into_inner()),
-                state: Rc::clone(&self.state),
-                me: ExecutionState::me(),
-            })),
-            Err(TryLockError::WouldBlock) => panic!("rwlock state out of sync"),
+        self.lock(RwLockType::Read, false);
+        self.read_guard()
+    }
+
+    /// Locks this rwlock with shared read access, like [`RwLock::read`], but succeeds without
+    /// waiting for writers if the lock is already held by readers.
+    ///
+    /// Unlike `read`, this can be called by a thread that already holds read access to the lock,
+    /// in which case the thread holds several read guards at once, and keeps read access until it
+    /// drops all of them. It also doesn't wait for writers that are waiting for a
+    /// [writer-preferring](RwLock::new_writer_preferring) lock held by other readers. As in
+    /// `parking_lot`, this can starve writers.
+    pub fn read_recursive(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+        let me = ExecutionState::me();
+        let rwlock_state = self.state();
+        let mut state = rwlock_state.borrow_mut();
+        let already_reading = matches!(&state.holder, RwLockHolder::Read(readers) if readers.contains(me));
+        if !already_reading {
+            drop(state);
+            self.lock(RwLockType::Read, true);
+            return self.read_guard();
         }
+
+        // We already hold read access, so nothing can stop us taking another read guard
+        state.recursive_reads.push(me);
+        let id = state.id;
+        ExecutionState::with(|s| s.current_mut().acquire_lock(id));
+        trace!(
+            holder = ?state.holder,
+            recursive_reads = ?state.recursive_reads,
+            "acquired recursive Read lock on rwlock {:p}",
+            rwlock_state,
+        );
+        let object = state.object(RwLockType::Read);
+        drop(state);
+
+        // Acquiring a lock is a yield point
+        thread::switch_on(object);
+
+        self.read_guard()
+    }
+
+    /// Make the guard for read access that the current thread just acquired
+    fn read_guard(&self) -> LockResult<RwLockReadGuard<'_, T>> {
+        let inner = match self.inner.try_read() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(err)) => err.into_inner(),
+            Err(TryLockError::WouldBlock) => panic!("rwlock state out of sync"),
+        };
+        self.check_poison(RwLockReadGuard {
+            inner: Some(inner),
+            rwlock: self,
+            me: ExecutionState::me(),
+        })
+    }
+
+    /// Locks this rwlock with upgradable read access, blocking the current thread until it can be
+    /// acquired.
+    ///
+    /// Upgradable read access is shared with any number of plain readers, but excludes writers and
+    /// other upgradable readers. The returned guard can later be atomically upgraded to exclusive
+    /// write access with [`RwLockUpgradableReadGuard::upgrade`].
+    pub fn upgradable_read(&self) -> LockResult<RwLockUpgradableReadGuard<'_, T>> {
+        self.lock(RwLockType::UpgradableRead, false);
+
+        let inner = match self.inner.try_read() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(err)) => err.into_inner(),
+            Err(TryLockError::WouldBlock) => panic!("rwlock state out of sync"),
+        };
+        self.check_poison(RwLockUpgradableReadGuard {
+            inner: Some(inner),
+            rwlock: self,
+            me: ExecutionState::me(),
+        })
     }
 
     /// Locks this rwlock with exclusive write access, blocking the current thread until it can
     /// be acquired.
+    ///
+    /// Returns an error if the lock is poisoned because a writer panicked while holding it, but the
+    /// lock is still acquired in that case.
     pub fn write(&self) -> LockResult<RwLockWriteGuard<'_, T>> {
-        self.lock(RwLockType::Write);
-
-        match self.inner.try_write() {
-            Ok(guard) => Ok(RwLockWriteGuard {
-                inner: Some(guard),
-                state: Rc::clone(&self.state),
-                me: ExecutionState::me(),
-            }),
-            Err(TryLockError::Poisoned(err)) => Err(PoisonError::new(RwLockWriteGuard {
-                inner: Some(err.into_inner()),
-                state: Rc::clone(&self.state),
-                me: ExecutionState::me(),
-            })),
+        self.lock(RwLockType::Write, false);
+
+        let inner = match self.inner.try_write() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(err)) => err.into_inner(),
             Err(TryLockError::WouldBlock) => panic!("rwlock state out of sync"),
-        }
+        };
+        self.check_poison(RwLockWriteGuard {
+            inner: Some(inner),
+            rwlock: self,
+            me: ExecutionState::me(),
+        })
     }
 
     /// Attempts to acquire this rwlock with shared read access.
     ///
     /// If the access could not be granted at this time, then Err is returned. This function does
     /// not block.
-    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<T>> {
-        unimplemented!()
+    pub fn try_read(&self) -> TryLockResult<RwLockReadGuard<'_, T>> {
+        if !self.try_lock(RwLockType::Read) {
+            return Err(TryLockError::WouldBlock);
+        }
+
+        let inner = match self.inner.try_read() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(err)) => err.into_inner(),
+            Err(TryLockError::WouldBlock) => panic!("rwlock state out of sync"),
+        };
+        Ok(self.check_poison(RwLockReadGuard {
+            inner: Some(inner),
+            rwlock: self,
+            me: ExecutionState::me(),
+        })?)
     }
 
-    /// Attempts to acquire this rwlock with shared read access.
+    /// Attempts to acquire this rwlock with exclusive write access.
     ///
     /// If the access could not be granted at this time, then Err is returned. This function does
     /// not block.
-    pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<T>> {
-        unimplemented!()
+    pub fn try_write(&self) -> TryLockResult<RwLockWriteGuard<'_, T>> {
+        if !self.try_lock(RwLockType::Write) {
+            return Err(TryLockError::WouldBlock);
+        }
+
+        let inner = match self.inner.try_write() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(err)) => err.into_inner(),
+            Err(TryLockError::WouldBlock) => panic!("rwlock state out of sync"),
+        };
+        Ok(self.check_poison(RwLockWriteGuard {
+            inner: Some(inner),
+            rwlock: self,
+            me: ExecutionState::me(),
+        })?)
+    }
+
+    /// Determines whether the lock is poisoned.
+    ///
+    /// A lock is poisoned if a thread panicked while holding exclusive write access to it.
+    pub fn is_poisoned(&self) -> bool {
+        // The lock doesn't report the objects it accesses
+        ExecutionState::with(|s| s.record_unknown_access());
+        self.state().borrow().poisoned
+    }
+
+    /// Clear the poisoned state from this lock, so that subsequent acquisitions succeed.
+    pub fn clear_poison(&self) {
+        ExecutionState::with(|s| s.record_unknown_access());
+        self.state().borrow_mut().poisoned = false;
+    }
+
+    /// Returns the number of threads that currently hold read access to this lock (including
+    /// upgradable read access). A thread that holds several [recursive](RwLock::read_recursive)
+    /// read guards counts once.
+    ///
+    /// This is meant for assertions about the lock's state in tests. Unlike acquiring the lock, it
+    /// is not a yield point and doesn't synchronize with the threads holding the lock.
+    pub fn reader_count(&self) -> usize {
+        // The lock doesn't report the objects it accesses
+        ExecutionState::with(|s| s.record_unknown_access());
+        match &self.state().borrow().holder {
+            RwLockHolder::Read(readers) => readers.iter().count(),
+            _ => 0,
+        }
+    }
+
+    /// Returns whether a thread currently holds write access to this lock.
+    ///
+    /// Like [`RwLock::reader_count`], this is meant for assertions in tests, and is not a yield
+    /// point.
+    pub fn has_writer(&self) -> bool {
+        ExecutionState::with(|s| s.record_unknown_access());
+        matches!(self.state().borrow().holder, RwLockHolder::Write(_))
     }
 
     /// Consumes this `RwLock`, returning the underlying data
     pub fn into_inner(self) -> LockResult<T> {
-        let state = self.state.borrow();
+        let rwlock_state = self.state();
+        let state = rwlock_state.borrow();
         assert_eq!(state.holder, RwLockHolder::None);
         // Update the receiver's clock with the RwLock clock
         ExecutionState::with(|s| {
             s.update_clock(&state.clock);
         });
-        self.inner.into_inner()
+
+        // We need to move `inner` out of ourselves, which our Drop impl doesn't allow, so run its
+        // cleanup manually instead.
+        let this = ManuallyDrop::new(self);
+        this.forget_state();
+        // Safety: `this` is never used or dropped again, so this is the only copy of `inner`
+        let inner = unsafe { std::ptr::read(&this.inner) };
+
+        // We track poisoning ourselves, so that it can be cleared, and so ignore the inner lock's
+        let value = inner.into_inner().unwrap_or_else(PoisonError::into_inner);
+        if state.poisoned {
+            Err(PoisonError::new(value))
+        } else {
+            Ok(value)
+        }
     }
 
-    fn lock(&self, typ: RwLockType) {
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// Since this call borrows the `RwLock` mutably, no actual locking needs to take place.
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        let rwlock_state = self.state();
+        let state = rwlock_state.borrow();
+        assert_eq!(state.holder, RwLockHolder::None);
+        // Update the receiver's clock with the RwLock clock
+        ExecutionState::with(|s| {
+            s.update_clock(&state.clock);
+        });
+        let poisoned = state.poisoned;
+        drop(state);
+
+        let value = self.inner.get_mut().unwrap_or_else(PoisonError::into_inner);
+        if poisoned {
+            Err(PoisonError::new(value))
+        } else {
+            Ok(value)
+        }
+    }
+
+    /// Get the Shuttle state of this lock in the current execution, initializing it on first use
+    fn state(&self) -> Rc<RefCell<RwLockState>> {
+        ExecutionState::with(|s| {
+            if let Some(state) = s.get_storage::<_, Rc<RefCell<RwLockState>>>(self.storage_key()) {
+                return Rc::clone(state);
+            }
+            let state = Rc::new(RefCell::new(RwLockState::new(
+                s.new_lock_id(self.name),
+                self.max_readers,
+                self.prefer_writers,
+            )));
+            s.init_storage(self.storage_key(), Rc::clone(&state));
+            state
+        })
+    }
+
+    /// The key of this lock's state in ExecutionState storage, assigning a fresh one on first use.
+    /// Keys are never reused, even across executions, so a static lock's state from a previous
+    /// execution is never found in the current one.
+    fn storage_key(&self) -> StorageKey {
+        static NEXT_KEY: AtomicUsize = AtomicUsize::new(1);
+
+        let mut key = self.key.load(Ordering::Relaxed);
+        if key == 0 {
+            let new_key = NEXT_KEY.fetch_add(1, Ordering::Relaxed);
+            key = match self
+                .key
+                .compare_exchange(0, new_key, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => new_key,
+                Err(existing) => existing,
+            };
+        }
+        StorageKey(key, 0x3)
+    }
+
+    /// Whether any thread holds this lock in the current execution. Unlike `state`, this doesn't
+    /// create the lock's state if it has none, so it's safe to call outside an execution.
+    fn is_held(&self) -> bool {
+        if self.key.load(Ordering::Relaxed) == 0 {
+            return false;
+        }
+        ExecutionState::try_with(|s| {
+            s.get_storage::<_, Rc<RefCell<RwLockState>>>(self.storage_key())
+                .map(|state| state.borrow().holder != RwLockHolder::None)
+        })
+        .flatten()
+        .unwrap_or(false)
+    }
+
+    /// Discard the Shuttle state of this lock in the current execution, if it has any
+    fn forget_state(&self) {
+        if self.key.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        // The state is dropped after we release the ExecutionState, in case dropping it needs access
+        let _state = ExecutionState::try_with(|s| s.remove_storage(self.storage_key()));
+    }
+
+    /// Wrap a newly acquired guard in an error if the lock is poisoned
+    fn check_poison<G>(&self, guard: G) -> LockResult<G> {
+        if self.state().borrow().poisoned {
+            Err(PoisonError::new(guard))
+        } else {
+            Ok(guard)
+        }
+    }
+
+    /// Acquire this lock in the given mode, blocking until we can. A `recursive` reader doesn't
+    /// wait for writers that a writer-preferring lock would otherwise make it wait for.
+    fn lock(&self, typ: RwLockType, recursive: bool) {
         let me = ExecutionState::me();
 
-        let mut state = self.state.borrow_mut();
+        let rwlock_state = self.state();
+        let mut state = rwlock_state.borrow_mut();
         trace!(
             holder = ?state.holder,
             waiting_readers = ?state.waiting_readers,
             waiting_writers = ?state.waiting_writers,
             "waiting to acquire {:?} lock on rwlock {:p}",
             typ,
-            self.state,
+            rwlock_state,
         );
 
-        // We are waiting for the lock
-        if typ == RwLockType::Write {
-            state.waiting_writers.insert(me);
-        } else {
-            state.waiting_readers.insert(me);
-        }
+        // We are waiting for the lock. A thread can only wait for one kind of access at a time.
+        assert!(
+            !state.is_waiting(me),
+            "{:?} is already waiting to acquire rwlock {:p}",
+            me,
+            rwlock_state
+        );
+        state.waiting_set_mut(typ).insert(me);
+        let id = state.id;
+        ExecutionState::with(|s| s.current_mut().set_waiting_lock(Some(id)));
+        let wait_for_writers = typ == RwLockType::Read && !recursive && state.writer_preferred();
         // Block if the lock is in a state where we can't acquire it immediately
         match &state.holder {
             RwLockHolder::Write(writer) => {
-                assert_ne!(*writer, me);
+                if *writer == me {
+                    panic!(
+                        "deadlock! {:?} tried to acquire {:?} access to rwlock {:p}, but already holds Write access to it",
+                        me, typ, rwlock_state
+                    );
+                }
                 ExecutionState::with(|s| s.current_mut().block());
             }
             RwLockHolder::Read(readers) => {
-                assert!(!readers.contains(me));
-                if typ == RwLockType::Write {
+                if readers.contains(me) {
+                    panic!(
+                        "deadlock! {:?} tried to acquire {:?} access to rwlock {:p}, but already holds Read access to it",
+                        me, typ, rwlock_state
+                    );
+                }
+                let must_wait = match typ {
+                    RwLockType::Read => state.upgrade_pending || state.readers_full() || wait_for_writers,
+                    RwLockType::UpgradableRead => state.upgradable_reader.is_some() || state.readers_full(),
+                    RwLockType::Write => true,
+                };
+                if must_wait {
+                    ExecutionState::with(|s| s.current_mut().block());
+                }
+            }
+            RwLockHolder::None => {
+                if wait_for_writers {
                     ExecutionState::with(|s| s.current_mut().block());
                 }
             }
-            _ => {}
         }
+        let waiters = state
+            .waiting_readers
+            .iter()
+            .chain(state.waiting_upgradable_readers.iter())
+            .chain(state.waiting_writers.iter());
+        ExecutionState::with(|s| s.record_lock_attempt(id, waiters));
+        let object = state.object(typ);
         drop(state);
 
         // Acquiring a lock is a yield point
-        thread::switch();
+        thread::switch_on(object);
 
-        let mut state = self.state.borrow_mut();
-        // Once the scheduler has resumed this thread, we are clear to take the lock. We might
-        // not actually be in the waiters, though (if the lock was uncontended).
-        // TODO should always be in the waiters?
+        let mut state = rwlock_state.borrow_mut();
+        // Once the scheduler has resumed this thread, we are clear to take the lock
+        let upgrade_pending = state.upgrade_pending;
+        let upgradable_held = state.upgradable_reader.is_some();
+        let readers_full = state.readers_full();
         match (typ, &mut state.holder) {
             (RwLockType::Write, RwLockHolder::None) => {
                 state.holder = RwLockHolder::Write(me);
             }
-            (RwLockType::Read, RwLockHolder::None) => {
+            (RwLockType::Read, RwLockHolder::None) | (RwLockType::UpgradableRead, RwLockHolder::None) => {
                 let mut readers = TaskSet::new();
                 readers.insert(me);
                 state.holder = RwLockHolder::Read(readers);
             }
-            (RwLockType::Read, RwLockHolder::Read(readers)) => {
+            (RwLockType::Read, RwLockHolder::Read(readers)) if !upgrade_pending && !readers_full => {
+                readers.insert(me);
+            }
+            (RwLockType::UpgradableRead, RwLockHolder::Read(readers)) if !upgradable_held && !readers_full => {
                 readers.insert(me);
             }
             _ => {
@@ -181,57 +530,228 @@ impl<T> RwLock<T> {
                 );
             }
         }
-        if typ == RwLockType::Write {
-            state.waiting_writers.remove(me);
-        } else {
-            state.waiting_readers.remove(me);
+        if typ == RwLockType::UpgradableRead {
+            state.upgradable_reader = Some(me);
         }
+        // We added ourselves to exactly one waiting set above, so we should be in no others
+        let was_waiting = state.waiting_set_mut(typ).remove(me);
+        assert!(
+            was_waiting,
+            "{:?} acquired rwlock {:p} without waiting for it",
+            me, rwlock_state
+        );
+        assert!(!state.is_waiting(me));
         trace!(
             holder = ?state.holder,
             waiting_readers = ?state.waiting_readers,
             waiting_writers = ?state.waiting_writers,
             "acquired {:?} lock on rwlock {:p}",
             typ,
-            self.state
+            rwlock_state
         );
         // Update acquiring thread's clock with the clock stored in the RwLock
-        ExecutionState::with(|s| s.update_clock(&state.clock));
+        ExecutionState::with(|s| {
+            s.update_clock(&state.clock);
+            s.current_mut().acquire_lock(id);
+        });
 
         // Block all other waiters, since we won the race to take this lock
         // TODO a bit of a bummer that we have to do this (it would be cleaner if those threads
         // TODO never become unblocked), but might need to track more state to avoid this.
-        Self::block_waiters(&*state, me, typ);
+        state.block_waiters(me, typ);
+        drop(state);
+    }
+
+    /// Attempt to acquire this lock in the given mode without blocking, returning true if the lock
+    /// was acquired. Like `lock`, this is a yield point, whether or not the attempt succeeds.
+    fn try_lock(&self, typ: RwLockType) -> bool {
+        let me = ExecutionState::me();
+
+        let rwlock_state = self.state();
+        let mut state = rwlock_state.borrow_mut();
+        trace!(
+            holder = ?state.holder,
+            waiting_readers = ?state.waiting_readers,
+            waiting_writers = ?state.waiting_writers,
+            "trying to acquire {:?} lock on rwlock {:p}",
+            typ,
+            rwlock_state,
+        );
+
+        // Readers don't get to jump ahead of writers (or an upgrade) already waiting for the lock
+        let writers_waiting = !state.waiting_writers.is_empty() || state.upgrade_pending;
+        let readers_full = state.readers_full();
+        let already_reading = matches!(&state.holder, RwLockHolder::Read(readers) if readers.contains(me));
+        let acquired = match (typ, &mut state.holder) {
+            // As for `read_recursive`, nothing can stop a thread that already holds read access
+            // (including upgradable read access) from taking another read guard
+            (RwLockType::Read, _) if already_reading => {
+                state.recursive_reads.push(me);
+                true
+            }
+            (RwLockType::Write, RwLockHolder::None) => {
+                state.holder = RwLockHolder::Write(me);
+                true
+            }
+            (RwLockType::Read, RwLockHolder::None) if !writers_waiting => {
+                let mut readers = TaskSet::new();
+                readers.insert(me);
+                state.holder = RwLockHolder::Read(readers);
+                true
+            }
+            (RwLockType::Read, RwLockHolder::Read(readers)) if !writers_waiting && !readers_full => {
+                readers.insert(me);
+                true
+            }
+            _ => false,
+        };
+
+        trace!(
+            holder = ?state.holder,
+            waiting_readers = ?state.waiting_readers,
+            waiting_writers = ?state.waiting_writers,
+            "{} {:?} lock on rwlock {:p}",
+            if acquired { "acquired" } else { "failed to acquire" },
+            typ,
+            rwlock_state,
+        );
+
+        // Update this thread's clock with the clock stored in the RwLock. We do this even if the
+        // attempt failed, because failing tells this thread that some other thread is interacting
+        // with the lock, which is a causal dependency on that thread.
+        ExecutionState::with(|s| s.update_clock(&state.clock));
+
+        // Block all other waiters that can no longer take the lock, since we won the race to it
+        if acquired {
+            state.block_waiters(me, typ);
+            let id = state.id;
+            ExecutionState::with(|s| s.current_mut().acquire_lock(id));
+        }
+        let object = state.object(typ);
         drop(state);
+
+        // Acquiring a lock is a yield point, even if we failed to acquire it
+        thread::switch_on(object);
+
+        acquired
     }
+}
 
-    fn block_waiters(state: &RwLockState, me: TaskId, typ: RwLockType) {
-        // Only block waiting readers if the lock is being acquired by a writer
-        if typ == RwLockType::Write {
-            for tid in state.waiting_readers.iter() {
+impl RwLockState {
+    fn new(id: LockId, max_readers: Option<usize>, prefer_writers: bool) -> Self {
+        Self {
+            id,
+            holder: RwLockHolder::None,
+            upgradable_reader: None,
+            upgrade_pending: false,
+            poisoned: false,
+            max_readers,
+            prefer_writers,
+            recursive_reads: Vec::new(),
+            waiting_readers: TaskSet::new(),
+            waiting_upgradable_readers: TaskSet::new(),
+            waiting_writers: TaskSet::new(),
+            clock: VectorClock::new(),
+        }
+    }
+
+    fn waiting_set_mut(&mut self, typ: RwLockType) -> &mut TaskSet {
+        match typ {
+            RwLockType::Read => &mut self.waiting_readers,
+            RwLockType::UpgradableRead => &mut self.waiting_upgradable_readers,
+            RwLockType::Write => &mut self.waiting_writers,
+        }
+    }
+
+    /// Returns true if `tid` is waiting to acquire the lock in any mode
+    fn is_waiting(&self, tid: TaskId) -> bool {
+        self.waiting_readers.contains(tid)
+            || self.waiting_upgradable_readers.contains(tid)
+            || self.waiting_writers.contains(tid)
+    }
+
+    /// The shared object that an operation of type `typ` on this lock accesses. Plain read
+    /// operations commute with each other, as long as the lock has room for every reader.
+    fn object(&self, typ: RwLockType) -> ObjectId {
+        match (typ, self.max_readers) {
+            (RwLockType::Read, None) => ObjectId::shared_lock(self.id),
+            _ => ObjectId::lock(self.id),
+        }
+    }
+
+    /// Returns true if new readers must wait, because this lock prefers writers and one is waiting
+    fn writer_preferred(&self) -> bool {
+        self.prefer_writers && !self.waiting_writers.is_empty()
+    }
+
+    /// Returns true if any thread is waiting to acquire or upgrade the lock
+    fn has_waiters(&self) -> bool {
+        !self.waiting_readers.is_empty()
+            || !self.waiting_upgradable_readers.is_empty()
+            || !self.waiting_writers.is_empty()
+            || self.upgrade_pending
+    }
+
+    /// Returns true if the lock is held by as many readers as it allows
+    fn readers_full(&self) -> bool {
+        match (&self.holder, self.max_readers) {
+            (RwLockHolder::Read(readers), Some(max_readers)) => readers.iter().count() >= max_readers,
+            _ => false,
+        }
+    }
+
+    /// Block the waiters that can no longer take the lock now that `me` has acquired it as `typ`
+    fn block_waiters(&self, me: TaskId, typ: RwLockType) {
+        let readers_full = self.readers_full();
+        // Only block waiting readers if the lock is being acquired by a writer, or we were the last
+        // reader it has room for
+        if typ == RwLockType::Write || readers_full {
+            for tid in self.waiting_readers.iter() {
+                assert_ne!(tid, me);
+                ExecutionState::with(|s| s.get_mut(tid).block());
+            }
+        }
+        // Plain readers can share the lock with an upgradable reader, but nobody else can
+        if typ != RwLockType::Read || readers_full {
+            for tid in self.waiting_upgradable_readers.iter() {
                 assert_ne!(tid, me);
                 ExecutionState::with(|s| s.get_mut(tid).block());
             }
         }
         // Always block any waiting writers
-        for tid in state.waiting_writers.iter() {
+        for tid in self.waiting_writers.iter() {
             assert_ne!(tid, me);
             ExecutionState::with(|s| s.get_mut(tid).block());
         }
     }
 
-    fn unblock_waiters(state: &RwLockState, me: TaskId, drop_type: RwLockType) {
-        for tid in state.waiting_readers.iter() {
-            debug_assert_ne!(tid, me);
-            ExecutionState::with(|s| {
-                let t = s.get_mut(tid);
-                debug_assert!(drop_type == RwLockType::Read || t.blocked());
-                t.unblock();
-            });
+    /// Unblock the waiters that might be able to take the lock now that `me` has released some
+    /// access to it. The scheduler will choose one of them to win the race to this lock, and that
+    /// thread will re-block all the losers.
+    fn unblock_waiters(&self, me: TaskId) {
+        debug_assert!(!matches!(self.holder, RwLockHolder::Write(_)));
+
+        // Readers can share the lock with anyone except a writer or a pending upgrade, as long as
+        // there's room for another reader and no preferred writer is waiting
+        let readers_full = self.readers_full();
+        if !self.upgrade_pending && !readers_full && !self.writer_preferred() {
+            for tid in self.waiting_readers.iter() {
+                debug_assert_ne!(tid, me);
+                ExecutionState::with(|s| s.get_mut(tid).unblock());
+            }
+        }
+
+        // Only unblock waiting upgradable readers if nobody else holds upgradable access
+        if self.upgradable_reader.is_none() && !readers_full {
+            for tid in self.waiting_upgradable_readers.iter() {
+                debug_assert_ne!(tid, me);
+                ExecutionState::with(|s| s.get_mut(tid).unblock());
+            }
         }
 
         // Only unblock waiting writers if there are no exiting readers holding the lock
-        if state.holder == RwLockHolder::None {
-            for tid in state.waiting_writers.iter() {
+        if self.holder == RwLockHolder::None {
+            for tid in self.waiting_writers.iter() {
                 debug_assert_ne!(tid, me);
                 ExecutionState::with(|s| {
                     let t = s.get_mut(tid);
@@ -240,19 +760,109 @@ impl<T> RwLock<T> {
                 });
             }
         }
+
+        // A pending upgrade can proceed once the upgrader is the only reader left
+        if self.upgrade_pending {
+            let upgrader = self
+                .upgradable_reader
+                .expect("upgrade pending without an upgradable reader");
+            if let RwLockHolder::Read(readers) = &self.holder {
+                if readers.iter().all(|tid| tid == upgrader) {
+                    ExecutionState::with(|s| s.get_mut(upgrader).unblock());
+                }
+            }
+        }
     }
 }
 
-// Safety: RwLock is never actually passed across true threads, only across continuations. The
-// Rc<RefCell<_>> type therefore can't be preempted mid-bookkeeping-operation.
-// TODO we shouldn't need to do this, but RefCell is not Send, and anything we put within a RwLock
-// TODO needs to be Send.
-unsafe impl<T: Send> Send for RwLock<T> {}
-unsafe impl<T: Send> Sync for RwLock<T> {}
+/// Release the access of type `typ` that `me` holds on the rwlock with the given state. This is
+/// the common drop logic for all the guard types.
+fn release(rwlock_state: &RefCell<RwLockState>, me: TaskId, typ: RwLockType) {
+    let mut state = rwlock_state.borrow_mut();
+    trace!(
+        holder = ?state.holder,
+        waiting_readers = ?state.waiting_readers,
+        waiting_writers = ?state.waiting_writers,
+        "releasing {:?} lock on rwlock {:p}",
+        typ,
+        rwlock_state
+    );
+
+    match typ {
+        RwLockType::Write => {
+            assert_eq!(state.holder, RwLockHolder::Write(me));
+            state.holder = RwLockHolder::None;
+
+            // As in std, releasing write access while panicking poisons the lock
+            if ExecutionState::should_poison() {
+                state.poisoned = true;
+            }
+
+            // Update the RwLock clock with the owning thread's clock
+            ExecutionState::with(|s| {
+                let clock = s.increment_clock();
+                state.clock.update(clock);
+            });
+        }
+        RwLockType::Read | RwLockType::UpgradableRead => {
+            if let Some(index) = state.recursive_reads.iter().position(|tid| *tid == me) {
+                // We still hold another read guard, so we keep our read access
+                state.recursive_reads.swap_remove(index);
+            } else {
+                match &mut state.holder {
+                    RwLockHolder::Read(readers) => {
+                        let was_reader = readers.remove(me);
+                        assert!(was_reader);
+                        if readers.is_empty() {
+                            state.holder = RwLockHolder::None;
+                        }
+                    }
+                    _ => panic!("exiting a reader but rwlock is in the wrong state"),
+                }
+            }
+            if typ == RwLockType::UpgradableRead {
+                assert_eq!(state.upgradable_reader, Some(me));
+                state.upgradable_reader = None;
+            }
+        }
+    }
+
+    ExecutionState::release_lock(state.id);
+
+    // A thread that panics while holding the lock can't yield, but it still unblocks the waiters so
+    // they can observe that the lock is poisoned. We leave the waiters alone if the execution is
+    // being torn down, or if the panic came from this thread's own attempt to acquire the lock, as
+    // the lock's state might be inconsistent.
+    if std::thread::panicking() {
+        let running = ExecutionState::try_with(|s| s.try_current().is_some()).unwrap_or(false);
+        if running && !state.is_waiting(me) {
+            state.unblock_waiters(me);
+        }
+        return;
+    }
+
+    if ExecutionState::should_stop() {
+        return;
+    }
+
+    let object = state.object(typ);
+    let skip_yield = !state.has_waiters() && ExecutionState::with(|s| s.config.skip_uncontended_release_yields);
+    state.unblock_waiters(me);
+    drop(state);
+
+    // Releasing a lock is a yield point, unless we were asked to skip it when no thread is waiting
+    if skip_yield {
+        ExecutionState::with(|s| s.record_access(object));
+    } else {
+        thread::switch_on(object);
+    }
+}
 
-// TODO this is the RefCell biting us again
-impl<T> UnwindSafe for RwLock<T> {}
-impl<T> RefUnwindSafe for RwLock<T> {}
+impl<T> Drop for RwLock<T> {
+    fn drop(&mut self) {
+        self.forget_state();
+    }
+}
 
 impl<T: Default> Default for RwLock<T> {
     fn default() -> Self {
@@ -260,11 +870,67 @@ impl<T: Default> Default for RwLock<T> {
     }
 }
 
+impl<T> From<T> for RwLock<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+/// Formats the protected value, or `<locked>` if any thread holds the lock, whether for reading or
+/// writing. Formatting is not a yield point.
+impl<T: Debug> Debug for RwLock<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut d = f.debug_struct("RwLock");
+        if self.is_held() {
+            d.field("data", &format_args!("<locked>"));
+        } else {
+            match self.inner.try_read() {
+                Ok(guard) => d.field("data", &&*guard),
+                Err(TryLockError::Poisoned(err)) => d.field("data", &&**err.get_ref()),
+                Err(TryLockError::WouldBlock) => d.field("data", &format_args!("<locked>")),
+            };
+        }
+        d.finish_non_exhaustive()
+    }
+}
+
+/// Cloning an `RwLock` creates a new, unlocked lock holding a clone of the current value, with the
+/// same configuration as the original. The new lock is independent of the original, so holding one
+/// doesn't affect the other.
+///
+/// Panics if any thread holds the original lock, as the value might be in the middle of an update.
+impl<T: Clone> Clone for RwLock<T> {
+    fn clone(&self) -> Self {
+        // The lock doesn't report the objects it accesses
+        ExecutionState::with(|s| s.record_unknown_access());
+        let rwlock_state = self.state();
+        let state = rwlock_state.borrow();
+        assert_eq!(
+            state.holder,
+            RwLockHolder::None,
+            "cannot clone an RwLock while it is held"
+        );
+        // Update the cloning thread's clock with the RwLock clock, as for `into_inner`
+        ExecutionState::with(|s| s.update_clock(&state.clock));
+        drop(state);
+
+        // The new lock's Shuttle state is created when it's first used, under its own key
+        let value = self.inner.read().unwrap_or_else(PoisonError::into_inner).clone();
+        Self {
+            key: AtomicUsize::new(0),
+            max_readers: self.max_readers,
+            prefer_writers: self.prefer_writers,
+            name: self.name,
+            inner: std::sync::RwLock::new(value),
+        }
+    }
+}
+
 /// RAII structure used to release the shared read access of a `RwLock` when dropped.
 #[derive(Debug)]
 pub struct RwLockReadGuard<'a, T> {
     inner: Option<std::sync::RwLockReadGuard<'a, T>>,
-    state: Rc<RefCell<RwLockState>>,
+    rwlock: &'a RwLock<T>,
     me: TaskId,
 }
 
@@ -279,40 +945,246 @@ impl<T> Deref for RwLockReadGuard<'_, T> {
 impl<T> Drop for RwLockReadGuard<'_, T> {
     fn drop(&mut self) {
         self.inner = None;
+        release(&self.rwlock.state(), self.me, RwLockType::Read);
+    }
+}
+
+impl<'a, T> RwLockReadGuard<'a, T> {
+    /// Makes a new `MappedRwLockReadGuard` for a component of the locked data.
+    ///
+    /// The lock remains held until the returned guard is dropped. This is an associated function
+    /// rather than a method to avoid conflicting with methods on the locked data.
+    pub fn map<U: ?Sized, F>(mut orig: Self, f: F) -> MappedRwLockReadGuard<'a, U>
+    where
+        F: FnOnce(&T) -> &U,
+    {
+        let inner = orig.inner.take().unwrap();
+        let data = NonNull::from(f(&*inner));
+        let release = MappedRelease {
+            inner: Some(Box::new(inner)),
+            state: orig.rwlock.state(),
+            me: orig.me,
+            typ: RwLockType::Read,
+        };
+        // The mapped guard takes over responsibility for releasing the lock, so skip our Drop impl.
+        // This doesn't leak anything, as we took `inner` and everything else is borrowed.
+        std::mem::forget(orig);
+
+        MappedRwLockReadGuard {
+            data,
+            release,
+            _p: PhantomData,
+        }
+    }
+}
 
-        let mut state = self.state.borrow_mut();
+// The part of a mapped guard that releases the lock when dropped. Mapped guards themselves don't
+// implement Drop, so that mapping an already mapped guard can move this into the new guard.
+struct MappedRelease<'a> {
+    inner: Option<Box<dyn ErasedGuard + 'a>>,
+    state: Rc<RefCell<RwLockState>>,
+    me: TaskId,
+    typ: RwLockType,
+}
 
+impl Drop for MappedRelease<'_> {
+    fn drop(&mut self) {
+        self.inner = None;
+        release(&self.state, self.me, self.typ);
+    }
+}
+
+/// RAII structure used to release the shared read access of a `RwLock` when dropped, which only
+/// gives access to a component of the locked data. Created by [`RwLockReadGuard::map`].
+pub struct MappedRwLockReadGuard<'a, U: ?Sized> {
+    // Safety: points into the data protected by the `std` guard in `release`, which lives for 'a
+    data: NonNull<U>,
+    release: MappedRelease<'a>,
+    _p: PhantomData<&'a U>,
+}
+
+impl<'a, U: ?Sized> MappedRwLockReadGuard<'a, U> {
+    /// Makes a new `MappedRwLockReadGuard` for a component of the already mapped data.
+    pub fn map<V: ?Sized, F>(orig: Self, f: F) -> MappedRwLockReadGuard<'a, V>
+    where
+        F: FnOnce(&U) -> &V,
+    {
+        MappedRwLockReadGuard {
+            data: NonNull::from(f(&*orig)),
+            release: orig.release,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<U: ?Sized> Deref for MappedRwLockReadGuard<'_, U> {
+    type Target = U;
+
+    fn deref(&self) -> &Self::Target {
+        // Safety: the `std` guard in `release` keeps the data borrowed for as long as we exist
+        unsafe { self.data.as_ref() }
+    }
+}
+
+impl<U: ?Sized + Debug> Debug for MappedRwLockReadGuard<'_, U> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MappedRwLockReadGuard").field("data", &&**self).finish()
+    }
+}
+
+/// RAII structure used to release the upgradable read access of a `RwLock` when dropped.
+#[derive(Debug)]
+pub struct RwLockUpgradableReadGuard<'a, T> {
+    inner: Option<std::sync::RwLockReadGuard<'a, T>>,
+    rwlock: &'a RwLock<T>,
+    me: TaskId,
+}
+
+impl<'a, T> RwLockUpgradableReadGuard<'a, T> {
+    /// Upgrades this upgradable read lock into an exclusive write lock, blocking the current thread
+    /// until all other readers have released the lock.
+    ///
+    /// No writer or other upgradable reader can take the lock in the meantime, and new readers are
+    /// not admitted while the upgrade is waiting.
+    pub fn upgrade(mut self) -> RwLockWriteGuard<'a, T> {
+        // Release the underlying read lock so that we can take the write lock once the other
+        // readers are gone. Shuttle still considers this thread a reader until then.
+        self.inner = None;
+
+        let rwlock = self.rwlock;
+        let me = self.me;
+        // Our write guard takes over responsibility for releasing the lock, so skip our Drop impl.
+        // This doesn't leak anything, as `inner` is now None and everything else is borrowed.
+        std::mem::forget(self);
+
+        let rwlock_state = rwlock.state();
+        let mut state = rwlock_state.borrow_mut();
         trace!(
             holder = ?state.holder,
             waiting_readers = ?state.waiting_readers,
             waiting_writers = ?state.waiting_writers,
-            "releasing Read lock on rwlock {:p}",
-            self.state
+            "upgrading UpgradableRead lock on rwlock {:p}",
+            rwlock_state
         );
 
-        match &mut state.holder {
-            RwLockHolder::Read(readers) => {
-                let was_reader = readers.remove(self.me);
-                assert!(was_reader);
-                if readers.is_empty() {
-                    state.holder = RwLockHolder::None;
-                }
-            }
-            _ => panic!("exiting a reader but rwlock is in the wrong state"),
+        assert_eq!(state.upgradable_reader, Some(me));
+        if state.recursive_reads.contains(&me) {
+            panic!(
+                "deadlock! {:?} tried to upgrade its access to rwlock {:p}, but still holds another Read guard for it",
+                me, rwlock_state
+            );
+        }
+        state.upgrade_pending = true;
+        // Readers that were already waiting to share the lock with us must now wait for the upgrade
+        for tid in state.waiting_readers.iter() {
+            assert_ne!(tid, me);
+            ExecutionState::with(|s| s.get_mut(tid).block());
         }
+        // Block until the last other reader releases the lock and unblocks us
+        let other_readers = match &state.holder {
+            RwLockHolder::Read(readers) => readers.iter().any(|tid| tid != me),
+            _ => panic!("upgrading a reader but rwlock is in the wrong state"),
+        };
+        if other_readers {
+            ExecutionState::with(|s| s.current_mut().block());
+        }
+        // We keep holding the lock while we wait for the upgrade
+        let id = state.id;
+        ExecutionState::with(|s| s.current_mut().set_waiting_lock(Some(id)));
+        drop(state);
 
-        if ExecutionState::should_stop() {
-            return;
+        // Acquiring a lock is a yield point
+        thread::switch_on(ObjectId::lock(id));
+
+        let mut state = rwlock_state.borrow_mut();
+        // No new readers can have joined while the upgrade was pending, so we are the only one left
+        match &state.holder {
+            RwLockHolder::Read(readers) => assert!(readers.iter().all(|tid| tid == me)),
+            _ => panic!(
+                "resumed an upgrading thread while the lock was in state {:?}",
+                state.holder
+            ),
         }
+        state.holder = RwLockHolder::Write(me);
+        state.upgradable_reader = None;
+        state.upgrade_pending = false;
+        trace!(
+            holder = ?state.holder,
+            waiting_readers = ?state.waiting_readers,
+            waiting_writers = ?state.waiting_writers,
+            "upgraded to Write lock on rwlock {:p}",
+            rwlock_state
+        );
+        // Update acquiring thread's clock with the clock stored in the RwLock
+        ExecutionState::with(|s| {
+            s.update_clock(&state.clock);
+            s.current_mut().set_waiting_lock(None);
+        });
+
+        // Block all other waiters, as we now hold the lock exclusively
+        state.block_waiters(me, RwLockType::Write);
+        drop(state);
+
+        let inner = match rwlock.inner.try_write() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(err)) => err.into_inner(),
+            Err(TryLockError::WouldBlock) => panic!("rwlock state out of sync"),
+        };
 
-        // Unblock every thread waiting on this lock. The scheduler will choose one of them to win
-        // the race to this lock, and that thread will re-block all the losers.
-        RwLock::<T>::unblock_waiters(&*state, self.me, RwLockType::Read);
+        RwLockWriteGuard {
+            inner: Some(inner),
+            rwlock,
+            me,
+        }
+    }
+
+    /// Downgrades this upgradable read lock into a plain read lock, allowing another thread to take
+    /// upgradable read access.
+    pub fn downgrade(mut self) -> RwLockReadGuard<'a, T> {
+        let inner = self.inner.take();
+        let rwlock = self.rwlock;
+        let me = self.me;
+        // Our read guard takes over responsibility for releasing the lock, so skip our Drop impl.
+        // This doesn't leak anything, as we took `inner` and everything else is borrowed.
+        std::mem::forget(self);
 
+        let rwlock_state = rwlock.state();
+        let mut state = rwlock_state.borrow_mut();
+        trace!(
+            holder = ?state.holder,
+            waiting_readers = ?state.waiting_readers,
+            waiting_writers = ?state.waiting_writers,
+            "downgrading UpgradableRead lock on rwlock {:p}",
+            rwlock_state
+        );
+
+        assert_eq!(state.upgradable_reader, Some(me));
+        state.upgradable_reader = None;
+
+        // Unblock any upgradable readers waiting on this lock, as they can now share it with us
+        state.unblock_waiters(me);
+        let id = state.id;
         drop(state);
 
-        // Releasing a lock is a yield point
-        thread::switch();
+        // Downgrading a lock is a yield point, as it allows waiting upgradable readers to proceed
+        thread::switch_on(ObjectId::lock(id));
+
+        RwLockReadGuard { inner, rwlock, me }
+    }
+}
+
+impl<T> Deref for RwLockUpgradableReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.inner.as_ref().unwrap().deref()
+    }
+}
+
+impl<T> Drop for RwLockUpgradableReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.inner = None;
+        release(&self.rwlock.state(), self.me, RwLockType::UpgradableRead);
     }
 }
 
@@ -320,43 +1192,103 @@ impl<T> Drop for RwLockReadGuard<'_, T> {
 #[derive(Debug)]
 pub struct RwLockWriteGuard<'a, T> {
     inner: Option<std::sync::RwLockWriteGuard<'a, T>>,
-    state: Rc<RefCell<RwLockState>>,
+    rwlock: &'a RwLock<T>,
     me: TaskId,
 }
 
-impl<T> Drop for RwLockWriteGuard<'_, T> {
-    fn drop(&mut self) {
+impl<'a, T> RwLockWriteGuard<'a, T> {
+    /// Atomically downgrades this write lock into a read lock, without allowing any writers to take
+    /// exclusive access to the lock in the meantime.
+    ///
+    /// Other readers waiting on the lock will be allowed to acquire it concurrently with the
+    /// returned guard, but waiting writers remain blocked until all read guards are released.
+    pub fn downgrade(mut self) -> RwLockReadGuard<'a, T> {
+        // Release the underlying write lock so that we can re-take it for reading. No other thread
+        // can take it in between, because we're not yielding and Shuttle still considers this
+        // thread the lock's writer.
         self.inner = None;
 
-        let mut state = self.state.borrow_mut();
+        let rwlock = self.rwlock;
+        let me = self.me;
+        // Our read guard takes over responsibility for releasing the lock, so skip our Drop impl.
+        // This doesn't leak anything, as `inner` is now None and everything else is borrowed.
+        std::mem::forget(self);
+
+        let rwlock_state = rwlock.state();
+        let mut state = rwlock_state.borrow_mut();
         trace!(
             holder = ?state.holder,
             waiting_readers = ?state.waiting_readers,
             waiting_writers = ?state.waiting_writers,
-            "releasing Write lock on rwlock {:p}",
-            self.state
+            "downgrading Write lock on rwlock {:p}",
+            rwlock_state
         );
 
-        assert_eq!(state.holder, RwLockHolder::Write(self.me));
-        state.holder = RwLockHolder::None;
+        assert_eq!(state.holder, RwLockHolder::Write(me));
+        let mut readers = TaskSet::new();
+        readers.insert(me);
+        state.holder = RwLockHolder::Read(readers);
 
-        // Update the RwLock clock with the owning thread's clock
+        // Update the RwLock clock with the owning thread's clock, as this releases our write access
         ExecutionState::with(|s| {
             let clock = s.increment_clock();
             state.clock.update(clock);
         });
 
-        if ExecutionState::should_stop() {
-            return;
+        // Unblock every reader waiting on this lock, as they can now share it with us, but writers
+        // must continue to wait until the read lock is released.
+        state.unblock_waiters(me);
+        let id = state.id;
+        drop(state);
+
+        let inner = match rwlock.inner.try_read() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(err)) => err.into_inner(),
+            Err(TryLockError::WouldBlock) => panic!("rwlock state out of sync"),
+        };
+
+        // Downgrading a lock is a yield point, as it allows waiting readers to proceed
+        thread::switch_on(ObjectId::lock(id));
+
+        RwLockReadGuard {
+            inner: Some(inner),
+            rwlock,
+            me,
         }
+    }
 
-        // Unblock every thread waiting on this lock. The scheduler will choose one of them to win
-        // the race to this lock, and that thread will re-block all the losers.
-        RwLock::<T>::unblock_waiters(&*state, self.me, RwLockType::Write);
-        drop(state);
+    /// Makes a new `MappedRwLockWriteGuard` for a component of the locked data.
+    ///
+    /// The lock remains held until the returned guard is dropped. This is an associated function
+    /// rather than a method to avoid conflicting with methods on the locked data.
+    pub fn map<U: ?Sized, F>(mut orig: Self, f: F) -> MappedRwLockWriteGuard<'a, U>
+    where
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        let mut inner = orig.inner.take().unwrap();
+        let data = NonNull::from(f(&mut *inner));
+        let release = MappedRelease {
+            inner: Some(Box::new(inner)),
+            state: orig.rwlock.state(),
+            me: orig.me,
+            typ: RwLockType::Write,
+        };
+        // The mapped guard takes over responsibility for releasing the lock, so skip our Drop impl.
+        // This doesn't leak anything, as we took `inner` and everything else is borrowed.
+        std::mem::forget(orig);
 
-        // Releasing a lock is a yield point
-        thread::switch();
+        MappedRwLockWriteGuard {
+            data,
+            release,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<T> Drop for RwLockWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.inner = None;
+        release(&self.rwlock.state(), self.me, RwLockType::Write);
     }
 }
 
@@ -373,3 +1305,51 @@ impl<T> DerefMut for RwLockWriteGuard<'_, T> {
         self.inner.as_mut().unwrap().deref_mut()
     }
 }
+
+/// RAII structure used to release the exclusive write access of a `RwLock` when dropped, which only
+/// gives access to a component of the locked data. Created by [`RwLockWriteGuard::map`].
+pub struct MappedRwLockWriteGuard<'a, U: ?Sized> {
+    // Safety: points into the data protected by the `std` guard in `release`, which lives for 'a
+    data: NonNull<U>,
+    release: MappedRelease<'a>,
+    _p: PhantomData<&'a mut U>,
+}
+
+impl<'a, U: ?Sized> MappedRwLockWriteGuard<'a, U> {
+    /// Makes a new `MappedRwLockWriteGuard` for a component of the already mapped data.
+    pub fn map<V: ?Sized, F>(mut orig: Self, f: F) -> MappedRwLockWriteGuard<'a, V>
+    where
+        F: FnOnce(&mut U) -> &mut V,
+    {
+        MappedRwLockWriteGuard {
+            data: NonNull::from(f(&mut *orig)),
+            release: orig.release,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<U: ?Sized> Deref for MappedRwLockWriteGuard<'_, U> {
+    type Target = U;
+
+    fn deref(&self) -> &Self::Target {
+        // Safety: the `std` guard in `release` keeps the data borrowed for as long as we exist
+        unsafe { self.data.as_ref() }
+    }
+}
+
+impl<U: ?Sized> DerefMut for MappedRwLockWriteGuard<'_, U> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // Safety: the `std` guard in `release` keeps the data exclusively borrowed for as long as we
+        // exist, and we hand out at most one mutable reference at a time
+        unsafe { self.data.as_mut() }
+    }
+}
+
+impl<U: ?Sized + Debug> Debug for MappedRwLockWriteGuard<'_, U> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MappedRwLockWriteGuard")
+            .field("data", &&**self)
+            .finish()
+    }
+}
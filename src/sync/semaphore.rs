@@ -0,0 +1,249 @@
+use crate::runtime::execution::ExecutionState;
+use crate::runtime::task::clock::VectorClock;
+use crate::runtime::task::TaskId;
+use crate::runtime::thread;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::Display;
+use std::rc::Rc;
+use tracing::trace;
+
+/// A counting semaphore, which hands out a bounded number of permits to threads that acquire it.
+///
+/// Permits are returned to the semaphore when the [`SemaphorePermit`] that holds them is dropped.
+#[derive(Debug)]
+pub struct Semaphore {
+    state: Rc<RefCell<SemaphoreState>>,
+}
+
+/// An RAII guard holding permits acquired from a [`Semaphore`]. The permits are returned to the
+/// semaphore when the guard is dropped.
+#[derive(Debug)]
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    permits: usize,
+}
+
+/// The error returned by [`Semaphore::try_acquire`] when there are not enough permits available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TryAcquireError(());
+
+#[derive(Debug)]
+struct SemaphoreState {
+    permits: usize,
+    // The number of permits each waiting thread is trying to acquire
+    waiters: HashMap<TaskId, usize>,
+    clock: VectorClock,
+}
+
+impl Semaphore {
+    /// Creates a new semaphore with the given number of permits.
+    pub fn new(permits: usize) -> Self {
+        let state = SemaphoreState {
+            permits,
+            waiters: HashMap::new(),
+            clock: VectorClock::new(),
+        };
+
+        Self {
+            state: Rc::new(RefCell::new(state)),
+        }
+    }
+
+    /// Returns the number of permits currently available.
+    pub fn available_permits(&self) -> usize {
+        // The semaphore doesn't report the objects it accesses
+        ExecutionState::with(|s| s.record_unknown_access());
+        self.state.borrow().permits
+    }
+
+    /// Acquires a permit, blocking the current thread until one is available.
+    pub fn acquire(&self) -> SemaphorePermit<'_> {
+        self.acquire_inner(1)
+    }
+
+    /// Attempts to acquire a permit without blocking.
+    ///
+    /// Like [`Semaphore::acquire`], this is a yield point, whether or not it succeeds.
+    pub fn try_acquire(&self) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        self.try_acquire_inner(1)
+    }
+
+    /// Acquires `n` permits at once, blocking the current thread until they are all available.
+    ///
+    /// The permits are acquired atomically: this thread never holds only some of them while waiting
+    /// for the rest.
+    pub fn acquire_many(&self, n: usize) -> SemaphorePermit<'_> {
+        self.acquire_inner(n)
+    }
+
+    /// Attempts to acquire `n` permits at once without blocking. Either all the permits are
+    /// acquired, or none are.
+    ///
+    /// Like [`Semaphore::acquire_many`], this is a yield point, whether or not it succeeds.
+    pub fn try_acquire_many(&self, n: usize) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        self.try_acquire_inner(n)
+    }
+
+    fn acquire_inner(&self, permits: usize) -> SemaphorePermit<'_> {
+        let me = ExecutionState::me();
+
+        let mut state = self.state.borrow_mut();
+        trace!(
+            permits = state.permits,
+            waiters = ?state.waiters,
+            "waiting to acquire {} permits from semaphore {:p}",
+            permits,
+            self
+        );
+
+        // We are waiting for the permits, and are blocked if there aren't enough of them yet
+        assert!(state.waiters.insert(me, permits).is_none());
+        if state.permits < permits {
+            ExecutionState::with(|s| s.current_mut().block());
+        }
+        drop(state);
+
+        // Acquiring permits is a yield point
+        thread::switch();
+
+        let mut state = self.state.borrow_mut();
+        // Once the scheduler has resumed this thread, there must be enough permits for us
+        assert!(state.permits >= permits);
+        state.permits -= permits;
+        state.waiters.remove(&me);
+        trace!(
+            permits = state.permits,
+            waiters = ?state.waiters,
+            "acquired {} permits from semaphore {:p}",
+            permits,
+            self
+        );
+        // Block all other waiters that can no longer be satisfied, since we won the race to the
+        // permits
+        state.block_waiters();
+        // Update acquiring thread's clock with the clock stored in the Semaphore
+        ExecutionState::with(|s| s.update_clock(&state.clock));
+        drop(state);
+
+        SemaphorePermit {
+            semaphore: self,
+            permits,
+        }
+    }
+
+    fn try_acquire_inner(&self, permits: usize) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        let mut state = self.state.borrow_mut();
+        trace!(
+            permits = state.permits,
+            waiters = ?state.waiters,
+            "trying to acquire {} permits from semaphore {:p}",
+            permits,
+            self
+        );
+
+        // We never wait for the permits, so we don't join the waiters
+        let acquired = state.permits >= permits;
+        if acquired {
+            state.permits -= permits;
+            state.block_waiters();
+        }
+
+        trace!(
+            permits = state.permits,
+            waiters = ?state.waiters,
+            "{} {} permits from semaphore {:p}",
+            if acquired { "acquired" } else { "failed to acquire" },
+            permits,
+            self
+        );
+
+        // Update this thread's clock with the clock stored in the Semaphore. We do this even if the
+        // attempt failed, because failing tells this thread that other threads hold the permits,
+        // which is a causal dependency on those threads.
+        ExecutionState::with(|s| s.update_clock(&state.clock));
+        drop(state);
+
+        // Acquiring permits is a yield point, even if we failed to acquire them
+        thread::switch();
+
+        if acquired {
+            Ok(SemaphorePermit {
+                semaphore: self,
+                permits,
+            })
+        } else {
+            Err(TryAcquireError(()))
+        }
+    }
+}
+
+impl SemaphorePermit<'_> {
+    /// Returns the number of permits held by this guard.
+    pub fn num_permits(&self) -> usize {
+        self.permits
+    }
+}
+
+impl SemaphoreState {
+    /// Block every waiter that is trying to acquire more permits than are currently available
+    fn block_waiters(&self) {
+        for (tid, needed) in self.waiters.iter() {
+            if *needed > self.permits {
+                ExecutionState::with(|s| s.get_mut(*tid).block());
+            }
+        }
+    }
+}
+
+// Safety: Semaphore is never actually passed across true threads, only across continuations. The
+// Rc<RefCell<_>> type therefore can't be preempted mid-bookkeeping-operation.
+// TODO we shouldn't need to do this, but RefCell is not Send
+unsafe impl Send for Semaphore {}
+unsafe impl Sync for Semaphore {}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        let mut state = self.semaphore.state.borrow_mut();
+        state.permits += self.permits;
+        trace!(
+            permits = state.permits,
+            waiters = ?state.waiters,
+            "released {} permits to semaphore {:p}",
+            self.permits,
+            self.semaphore
+        );
+
+        if ExecutionState::should_stop() {
+            return;
+        }
+
+        // Update the Semaphore clock with the releasing thread's clock
+        ExecutionState::with(|s| {
+            let clock = s.increment_clock();
+            state.clock.update(clock);
+        });
+
+        // Unblock every waiter that could now be satisfied. The scheduler will choose which of them
+        // wins the race to the permits, and that thread will re-block any that can no longer be
+        // satisfied.
+        for (tid, needed) in state.waiters.iter() {
+            if *needed <= state.permits {
+                ExecutionState::with(|s| s.get_mut(*tid).unblock());
+            }
+        }
+        drop(state);
+
+        // Releasing permits is a yield point
+        thread::switch();
+    }
+}
+
+impl Display for TryAcquireError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no permits available")
+    }
+}
+
+impl Error for TryAcquireError {}
@@ -1,13 +1,21 @@
 //! Shuttle's implementation of [`std::thread`].
 
 use crate::runtime::execution::ExecutionState;
+use crate::runtime::task::clock::VectorClock;
 use crate::runtime::task::TaskId;
 use crate::runtime::thread;
+use crate::scheduler::ObjectId;
 use std::marker::PhantomData;
+use std::panic;
 use std::time::Duration;
 
 /// A unique identifier for a running thread
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+///
+/// Thread IDs are unique within an execution and are assigned in the order threads are spawned,
+/// starting from 0 for the test's main thread, so the same thread gets the same ID when a schedule
+/// is replayed. The ID converts (via `usize::from`) to the task number Shuttle uses in its
+/// scheduling traces and failure reports, and IDs are ordered the same way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ThreadId {
     // TODO Should we add an execution id here, like Loom does?
     task_id: TaskId,
@@ -36,6 +44,21 @@ impl Thread {
     pub fn id(&self) -> ThreadId {
         self.id
     }
+
+    /// Atomically makes the thread's unpark token available if it is not already, like
+    /// [`std::thread::Thread::unpark`].
+    ///
+    /// If the thread is blocked in [`park`], this unblocks it and it consumes the token; otherwise,
+    /// its next call to `park` returns immediately. The token is a single slot, so unparking a
+    /// thread several times before it parks only lets one call to `park` return.
+    pub fn unpark(&self) {
+        ExecutionState::with(|state| {
+            let clock = state.increment_clock().clone();
+            state.get_mut(self.id.task_id).unpark(&clock);
+        });
+
+        thread::switch_after(ObjectId::task(self.id.task_id));
+    }
 }
 
 /// Spawn a new thread, returning a JoinHandle for it.
@@ -48,14 +71,51 @@ where
     F: Send + 'static,
     T: Send + 'static,
 {
-    spawn_named(f, None, None)
+    spawn_named(f, None, None, false)
+}
+
+/// Spawn a new setup thread, returning a JoinHandle for it.
+///
+/// A setup thread runs before any other thread is scheduled, including the one that spawned it,
+/// and isn't preempted until it calls [`yield_now`] or finishes. This makes it easy to initialize
+/// shared state deterministically without threading a barrier through every other thread. If the
+/// setup thread blocks (e.g., on a lock), other threads run until it can continue, at which point
+/// it takes priority again.
+pub fn spawn_setup<F, T>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce() -> T,
+    F: Send + 'static,
+    T: Send + 'static,
+{
+    spawn_named(f, None, None, true)
 }
 
-fn spawn_named<F, T>(f: F, name: Option<String>, stack_size: Option<usize>) -> JoinHandle<T>
+fn spawn_named<F, T>(f: F, name: Option<String>, stack_size: Option<usize>, setup: bool) -> JoinHandle<T>
 where
     F: FnOnce() -> T,
     F: Send + 'static,
     T: Send + 'static,
+{
+    // Safety: `f` and `T` are 'static, so they outlive the thread
+    unsafe { spawn_internal(f, name, stack_size, None, setup) }
+}
+
+/// Spawn a new thread that runs `f`, and if `scope` is given, tell the scope when the thread has
+/// finished. If `setup` is true, the thread runs before all others until it yields.
+///
+/// Safety: the caller must ensure that `f` and its result outlive the spawned thread. For a scoped
+/// thread, that's guaranteed by `scope` waiting for all its threads to finish.
+unsafe fn spawn_internal<'a, F, T>(
+    f: F,
+    name: Option<String>,
+    stack_size: Option<usize>,
+    scope: Option<std::sync::Arc<std::sync::Mutex<ScopeState>>>,
+    setup: bool,
+) -> JoinHandle<T>
+where
+    F: FnOnce() -> T,
+    F: Send + 'a,
+    T: Send + 'a,
 {
     // TODO Check if it's worth avoiding the call to `ExecutionState::config()` if we're going
     // TODO to use an existing continuation from the pool.
@@ -64,7 +124,21 @@ where
     let task_id = {
         let result = std::sync::Arc::clone(&result);
         let f = move || {
-            let ret = f();
+            // Catch a panic so that we can return it from `JoinHandle::join`, unless the handle is
+            // gone and so nobody can observe it, in which case the panic fails the test. An aborted
+            // thread's unwind never fails the test. We never catch the panic the continuation uses
+            // to unwind a thread when an execution stops.
+            let ret = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+                // Starting to run is a cancellation point
+                check_abort();
+                f()
+            }));
+            let ret = match ret {
+                Err(payload) if payload.is::<generator::Error>() || !(can_catch_panic() || is_aborted()) => {
+                    panic::resume_unwind(payload)
+                }
+                ret => ret,
+            };
 
             // Run thread-local destructors before publishing the result, because
             // [`JoinHandle::join`] says join "waits for the associated thread to finish", but
@@ -78,17 +152,35 @@ where
             // Publish the result and unblock the waiter. We need to do this now, because once this
             // closure completes, the Execution will consider this task Finished and invoke the
             // scheduler.
-            *result.lock().unwrap() = Some(Ok(ret));
+            *result.lock().unwrap() = Some(ret);
             ExecutionState::with(|state| {
+                let me = state.current().id();
+                state.record_access(ObjectId::task(me));
                 if let Some(waiter) = state.current_mut().take_waiter() {
                     state.get_mut(waiter).unblock();
                 }
             });
+
+            // If the handle is already gone, this drops the result, which a scoped thread must do
+            // before its scope can end
+            drop(result);
+            if let Some(scope) = scope {
+                ScopeState::finish_thread(&scope);
+            }
         };
-        ExecutionState::spawn_thread(f, stack_size, name.clone(), None)
+        let f: Box<dyn FnOnce() + Send + 'a> = Box::new(f);
+        // Safety: the caller guarantees that `f` outlives the thread
+        let f = std::mem::transmute::<Box<dyn FnOnce() + Send + 'a>, Box<dyn FnOnce() + Send + 'static>>(f);
+        let task_id = ExecutionState::spawn_thread(f, stack_size, name.clone(), None);
+        ExecutionState::with(|state| {
+            let task = state.get_mut(task_id);
+            task.catch_panic = true;
+            task.setup = setup;
+        });
+        task_id
     };
 
-    thread::switch();
+    thread::switch_after(ObjectId::task(task_id));
 
     let thread = Thread {
         id: ThreadId { task_id },
@@ -102,6 +194,28 @@ where
     }
 }
 
+// Whether a panic in the current task can be returned from its `JoinHandle::join`
+pub(crate) fn can_catch_panic() -> bool {
+    ExecutionState::try_with(|state| state.try_current().map(|task| task.catch_panic))
+        .flatten()
+        .unwrap_or(false)
+}
+
+// Whether `JoinHandle::abort` was called for the current thread
+fn is_aborted() -> bool {
+    ExecutionState::try_with(|state| state.try_current().map(|task| task.aborted))
+        .flatten()
+        .unwrap_or(false)
+}
+
+/// Unwind the current thread if it has been aborted. Threads only stop at these cancellation points,
+/// where they aren't in the middle of updating the state of a Shuttle primitive.
+fn check_abort() {
+    if is_aborted() {
+        panic::resume_unwind(Box::new("thread was aborted"));
+    }
+}
+
 /// An owned permission to join on a thread (block on its termination).
 #[derive(Debug)]
 pub struct JoinHandle<T> {
@@ -112,6 +226,12 @@ pub struct JoinHandle<T> {
 
 impl<T> JoinHandle<T> {
     /// Waits for the associated thread to finish.
+    ///
+    /// If the thread panicked, this returns an `Err` containing the panic payload, and the test
+    /// continues. If the handle is dropped without being joined, a panic in the thread fails the
+    /// test instead. Note that Shuttle primitives the thread drops while unwinding from its panic
+    /// don't wake up any threads waiting on them, except for [`Mutex`](crate::sync::Mutex) and
+    /// [`RwLock`](crate::sync::RwLock) guards, which release (and poison) their locks.
     pub fn join(self) -> std::thread::Result<T> {
         ExecutionState::with(|state| {
             let me = state.current().id();
@@ -122,7 +242,7 @@ impl<T> JoinHandle<T> {
         });
 
         // TODO can we soundly skip the yield if the target thread has already finished?
-        thread::switch();
+        thread::switch_on(ObjectId::task(self.task_id));
 
         // Waiting thread inherits the clock of the finished thread
         ExecutionState::with(|state| {
@@ -134,27 +254,225 @@ impl<T> JoinHandle<T> {
         self.result.lock().unwrap().take().expect("target should have finished")
     }
 
+    /// Checks if the associated thread has finished running its main function, without blocking.
+    ///
+    /// This is a yield point, so a loop polling it interleaves with the thread finishing. If it
+    /// returns `true`, [`join`](JoinHandle::join) returns without blocking.
+    pub fn is_finished(&self) -> bool {
+        thread::switch_on(ObjectId::task(self.task_id));
+
+        self.result.lock().unwrap().is_some()
+    }
+
     /// Extracts a handle to the underlying thread.
     pub fn thread(&self) -> &Thread {
         &self.thread
     }
+
+    /// Forcibly stops the associated thread, to model systems that terminate worker threads.
+    ///
+    /// Like deferred cancellation of POSIX threads, the thread doesn't stop immediately, but at its
+    /// next cancellation point: a call to [`yield_now`], [`sleep`], [`park`], or [`park_timeout`]
+    /// (which the abort wakes it from), or before it starts running if it hasn't yet. It then
+    /// unwinds, dropping any lock guards it holds, which releases the locks (and poisons them if
+    /// [`Config::poison_on_abort`](crate::Config::poison_on_abort) is enabled) and wakes the threads
+    /// waiting for them. [`join`](JoinHandle::join) returns an `Err` for an aborted thread, but
+    /// aborting never fails the test. Aborting a thread that has already finished has no effect.
+    ///
+    /// This is a yield point.
+    pub fn abort(&self) {
+        ExecutionState::with(|state| {
+            let clock = state.increment_clock().clone();
+            let target = state.get_mut(self.task_id);
+            if !target.finished() {
+                target.aborted = true;
+                target.unpark(&clock);
+            }
+        });
+
+        thread::switch_after(ObjectId::task(self.task_id));
+    }
+}
+
+impl<T> Drop for JoinHandle<T> {
+    fn drop(&mut self) {
+        if ExecutionState::should_stop() {
+            return;
+        }
+        // Once the handle is gone, nobody can observe a panic in the thread, so it fails the test,
+        // including if the thread already panicked but was never joined
+        let aborted = ExecutionState::with(|state| {
+            state.record_access(ObjectId::task(self.task_id));
+            let target = state.get_mut(self.task_id);
+            target.catch_panic = false;
+            target.aborted
+        });
+        let result = self.result.lock().unwrap().take();
+        match result {
+            Some(Err(payload)) if !aborted => panic::resume_unwind(payload),
+            _ => {}
+        }
+    }
+}
+
+/// Create a scope for spawning scoped threads, like [`std::thread::scope`].
+///
+/// The function `f` is passed a [`Scope`] that can be used to spawn threads that borrow non-'static
+/// data from outside the scope. All threads spawned in the scope that haven't been joined by the
+/// time `f` returns are joined before `scope` returns. That join is a yield point, even if every
+/// thread has already finished.
+pub fn scope<'env, F, T>(f: F) -> T
+where
+    F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> T,
+{
+    let scope = Scope {
+        state: std::sync::Arc::new(std::sync::Mutex::new(ScopeState {
+            running: 0,
+            waiter: None,
+            clock: VectorClock::new(),
+        })),
+        scope: PhantomData,
+        env: PhantomData,
+    };
+
+    // Like `std`, we wait for the scoped threads even if `f` panics, as they might be borrowing
+    // data that the panic is about to drop. But if the execution is stopping, the continuation is
+    // unwinding this thread, and Shuttle takes care of unwinding the scoped threads first.
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| f(&scope)));
+    match result {
+        Err(payload) if payload.is::<generator::Error>() => panic::resume_unwind(payload),
+        _ => {}
+    }
+
+    ExecutionState::with(|state| {
+        let mut scope_state = scope.state.lock().unwrap();
+        if scope_state.running > 0 {
+            scope_state.waiter = Some(state.current().id());
+            state.current_mut().block();
+        }
+    });
+
+    thread::switch();
+
+    // The scope inherits the clocks of all its finished threads
+    ExecutionState::with(|state| {
+        let scope_state = scope.state.lock().unwrap();
+        assert_eq!(
+            scope_state.running, 0,
+            "scope should only be unblocked once all its threads finish"
+        );
+        state.update_clock(&scope_state.clock);
+    });
+
+    match result {
+        Ok(ret) => ret,
+        Err(payload) => panic::resume_unwind(payload),
+    }
+}
+
+/// A scope to spawn scoped threads in. See [`scope`] for details.
+#[derive(Debug)]
+pub struct Scope<'scope, 'env: 'scope> {
+    state: std::sync::Arc<std::sync::Mutex<ScopeState>>,
+    // Invariance over both lifetimes, as in `std`
+    scope: PhantomData<&'scope mut &'scope ()>,
+    env: PhantomData<&'env mut &'env ()>,
+}
+
+#[derive(Debug)]
+struct ScopeState {
+    // The number of threads spawned in the scope that haven't finished yet
+    running: usize,
+    // The thread waiting for the scope to end, if it's blocked
+    waiter: Option<TaskId>,
+    // The joined clocks of all threads in the scope that have finished
+    clock: VectorClock,
+}
+
+impl ScopeState {
+    fn finish_thread(state: &std::sync::Mutex<ScopeState>) {
+        let mut state = state.lock().unwrap();
+        ExecutionState::with(|s| {
+            state.clock.update(&s.current().clock);
+            state.running -= 1;
+            if state.running == 0 {
+                if let Some(waiter) = state.waiter.take() {
+                    s.get_mut(waiter).unblock();
+                }
+            }
+        });
+    }
+}
+
+impl<'scope, 'env> Scope<'scope, 'env> {
+    /// Spawns a new thread within the scope, returning a [`ScopedJoinHandle`] for it.
+    ///
+    /// Unlike [`spawn`], the thread can borrow non-'static data from outside the scope. If the
+    /// thread isn't joined explicitly, it is joined at the end of the scope.
+    pub fn spawn<F, T>(&'scope self, f: F) -> ScopedJoinHandle<'scope, T>
+    where
+        F: FnOnce() -> T + Send + 'scope,
+        T: Send + 'scope,
+    {
+        self.state.lock().unwrap().running += 1;
+        // Safety: the scope doesn't end until this thread has finished and dropped its result
+        let handle = unsafe { spawn_internal(f, None, None, Some(std::sync::Arc::clone(&self.state)), false) };
+        ScopedJoinHandle {
+            handle,
+            scope: PhantomData,
+        }
+    }
+}
+
+/// An owned permission to join on a scoped thread (block on its termination).
+#[derive(Debug)]
+pub struct ScopedJoinHandle<'scope, T> {
+    handle: JoinHandle<T>,
+    scope: PhantomData<&'scope ()>,
+}
+
+impl<'scope, T> ScopedJoinHandle<'scope, T> {
+    /// Waits for the associated thread to finish. See [`JoinHandle::join`] for details.
+    pub fn join(self) -> std::thread::Result<T> {
+        self.handle.join()
+    }
+
+    /// Checks if the associated thread has finished running its main function, without blocking.
+    /// See [`JoinHandle::is_finished`] for details.
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Extracts a handle to the underlying thread.
+    pub fn thread(&self) -> &Thread {
+        self.handle.thread()
+    }
 }
 
 /// Cooperatively gives up a timeslice to the Shuttle scheduler.
 ///
-/// Some Shuttle schedulers use this as a hint to deprioritize the current thread in order for other
-/// threads to make progress (e.g., in a spin loop).
+/// This is always a yield point, so it can be used to make Shuttle consider a context switch at a
+/// specific point in a test. Some Shuttle schedulers also use it as a hint to deprioritize the
+/// current thread in order for other threads to make progress (e.g., in a spin loop). It also ends
+/// the setup phase of a thread spawned with [`spawn_setup`].
 pub fn yield_now() {
-    let waker = ExecutionState::with(|state| state.current().waker());
+    let waker = ExecutionState::with(|state| {
+        state.current_mut().setup = false;
+        state.current().waker()
+    });
     waker.wake_by_ref();
     ExecutionState::request_yield();
     thread::switch();
+    check_abort();
 }
 
 /// Puts the current thread to sleep for at least the specified amount of time.
-// Note that Shuttle does not model time, so this behaves just like a context switch.
+///
+/// This doesn't use Shuttle's logical clock, and behaves just like a context switch. Use
+/// [`time::sleep`](crate::time::sleep) to sleep on the logical clock.
 pub fn sleep(_dur: Duration) {
     thread::switch();
+    check_abort();
 }
 
 /// Get a handle to the thread that invokes it
@@ -170,7 +488,45 @@ pub fn current() -> Thread {
     }
 }
 
-// TODO: Implement park(), unpark()
+/// Blocks the current thread until its unpark token is made available by a call to
+/// [`Thread::unpark`], then consumes the token, like [`std::thread::park`].
+///
+/// If the token is already available, this returns immediately, but is still a yield point. If
+/// [`Config::spurious_wakeups`](crate::Config::spurious_wakeups) is enabled, this function can
+/// also return without the token.
+pub fn park() {
+    let spurious_wakeups = ExecutionState::with(|s| s.config.spurious_wakeups);
+    park_internal(spurious_wakeups);
+}
+
+/// Blocks the current thread until its unpark token is made available or the timeout elapses, like
+/// [`std::thread::park_timeout`].
+///
+/// This doesn't use Shuttle's [logical clock](crate::time), so `dur` is ignored, and the timeout
+/// happens nondeterministically, at any point before the thread is unparked. Use
+/// [`time::sleep`](crate::time::sleep) to sleep on the logical clock.
+pub fn park_timeout(_dur: Duration) {
+    park_internal(true);
+}
+
+// Wait for the unpark token and consume it. If `can_wake_early` is true, the thread stays runnable
+// while it waits, so the scheduler can choose to resume it before it's unparked (timing out or
+// waking spuriously), just like `Condvar::wait_timeout`.
+fn park_internal(can_wake_early: bool) {
+    if !can_wake_early {
+        ExecutionState::with(|state| state.current_mut().park());
+    }
+
+    let me = ExecutionState::me();
+    thread::switch_on(ObjectId::task(me));
+    check_abort();
+
+    // The parked thread inherits the clocks of the threads that unparked it
+    ExecutionState::with(|state| match state.current_mut().take_park_token() {
+        Some(clock) => state.update_clock(&clock),
+        None => assert!(can_wake_early, "parked thread should only resume once unparked"),
+    });
+}
 
 /// Thread factory, which can be used in order to configure the properties of a new thread.
 #[derive(Debug, Default)]
@@ -207,7 +563,7 @@ impl Builder {
         F: Send + 'static,
         T: Send + 'static,
     {
-        Ok(spawn_named(f, self.name, self.stack_size))
+        Ok(spawn_named(f, self.name, self.stack_size, false))
     }
 }
 
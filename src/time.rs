@@ -0,0 +1,127 @@
+//! Shuttle's implementation of a logical clock, modeling [`std::time::Instant`] and sleeping.
+//!
+//! Each execution has a logical clock that starts at zero. The clock is only advanced by Shuttle:
+//! when every task is blocked and at least one is [sleeping](sleep), the clock jumps forward to the
+//! earliest time a sleeping task should wake up, and that task is woken. Time therefore never
+//! passes while any task can still run, so the order in which timers fire is deterministic, and
+//! tasks whose timers expire at the same time are woken together, in an order Shuttle explores.
+//!
+//! Note that [`thread::sleep`](crate::thread::sleep) does not use this clock; it's just a yield
+//! point. Timed waits like [`Condvar::wait_timeout`](crate::sync::Condvar::wait_timeout),
+//! [`thread::park_timeout`](crate::thread::park_timeout), and
+//! [`Receiver::recv_timeout`](crate::sync::mpsc::Receiver::recv_timeout) don't use it either; they
+//! time out nondeterministically, at any point while they wait.
+
+use crate::runtime::execution::ExecutionState;
+use crate::runtime::thread;
+use std::ops::{Add, AddAssign, Sub, SubAssign};
+use std::time::Duration;
+
+/// A measurement of the logical clock of the current execution, like [`std::time::Instant`].
+///
+/// Instants are only meaningful within the execution that created them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant {
+    // Logical time since the execution started
+    since_start: Duration,
+}
+
+impl Instant {
+    /// Returns the current time on the logical clock.
+    pub fn now() -> Self {
+        Self {
+            since_start: ExecutionState::with(|s| s.now()),
+        }
+    }
+
+    /// Returns the amount of logical time elapsed from `earlier` to this instant. Returns zero if
+    /// `earlier` is later than this instant.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        self.saturating_duration_since(earlier)
+    }
+
+    /// Returns the amount of logical time elapsed from `earlier` to this instant, or `None` if
+    /// `earlier` is later than this instant.
+    pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+        self.since_start.checked_sub(earlier.since_start)
+    }
+
+    /// Returns the amount of logical time elapsed from `earlier` to this instant, or zero if
+    /// `earlier` is later than this instant.
+    pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+        self.checked_duration_since(earlier).unwrap_or_default()
+    }
+
+    /// Returns the amount of logical time elapsed since this instant was created.
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().duration_since(*self)
+    }
+
+    /// Returns the instant `duration` after this one, or `None` if it can't be represented.
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        self.since_start
+            .checked_add(duration)
+            .map(|since_start| Instant { since_start })
+    }
+
+    /// Returns the instant `duration` before this one, or `None` if that's before the execution
+    /// started.
+    pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+        self.since_start
+            .checked_sub(duration)
+            .map(|since_start| Instant { since_start })
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, other: Duration) -> Instant {
+        self.checked_add(other)
+            .expect("overflow when adding duration to instant")
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, other: Duration) {
+        *self = *self + other;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, other: Duration) -> Instant {
+        self.checked_sub(other)
+            .expect("overflow when subtracting duration from instant")
+    }
+}
+
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, other: Duration) {
+        *self = *self - other;
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, other: Instant) -> Duration {
+        self.duration_since(other)
+    }
+}
+
+/// Puts the current task to sleep until the logical clock has advanced by `duration`.
+///
+/// Sleeping is a yield point. The task wakes up once the clock reaches its deadline, which only
+/// happens after every other task has blocked or gone to sleep until a later time.
+pub fn sleep(duration: Duration) {
+    sleep_until(Instant::now() + duration);
+}
+
+/// Puts the current task to sleep until the logical clock reaches `deadline`. If `deadline` has
+/// already passed, this is just a yield point.
+pub fn sleep_until(deadline: Instant) {
+    ExecutionState::with(|s| s.sleep_until(deadline.since_start));
+    thread::switch();
+}
@@ -0,0 +1,19 @@
+//! Shuttle's implementations of tokio's synchronization primitives.
+//!
+//! The types here have the same API and semantics as their [tokio] counterparts, but they're
+//! scheduled by Shuttle, so code written against tokio's types can be tested by swapping its
+//! imports:
+//!
+//! ```ignore
+//! #[cfg(feature = "shuttle")]
+//! use shuttle::tokio::sync::Notify;
+//! #[cfg(not(feature = "shuttle"))]
+//! use tokio::sync::Notify;
+//! ```
+//!
+//! Only [`Notify`](sync::Notify) and [`Semaphore`](sync::Semaphore) are implemented so far. Async
+//! tasks using them should be run with Shuttle's executor in [`future`](crate::future).
+//!
+//! [tokio]: https://docs.rs/tokio/1/tokio/
+
+pub mod sync;
@@ -0,0 +1,10 @@
+//! Shuttle's implementation of [`tokio::sync`].
+//!
+//! [`tokio::sync`]: https://docs.rs/tokio/1/tokio/sync/index.html
+
+mod notify;
+mod semaphore;
+
+pub use notify::{Notified, Notify};
+
+pub use semaphore::{AcquireError, Semaphore, SemaphorePermit, TryAcquireError};
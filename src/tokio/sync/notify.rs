@@ -0,0 +1,247 @@
+use crate::runtime::execution::ExecutionState;
+use crate::runtime::task::clock::VectorClock;
+use crate::runtime::thread;
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+use tracing::trace;
+
+/// Notifies a single task to wake up, with the same semantics as [`tokio::sync::Notify`].
+///
+/// A `Notify` holds at most one permit. [`Notify::notify_one`] wakes the longest-waiting task if
+/// there is one, and otherwise stores the permit so that the next call to [`Notify::notified`]
+/// completes immediately. [`Notify::notify_waiters`] wakes every task currently waiting, including
+/// every [`Notified`] future that has been created but not yet polled, but never stores a permit,
+/// so a task that starts waiting afterwards misses the notification.
+///
+/// Calling either notify method, and first polling a [`Notified`] future, are yield points.
+///
+/// [`tokio::sync::Notify`]: https://docs.rs/tokio/1/tokio/sync/struct.Notify.html
+#[derive(Debug)]
+pub struct Notify {
+    state: Rc<RefCell<NotifyState>>,
+}
+
+/// The future returned by [`Notify::notified`], which completes once the task is notified.
+#[derive(Debug)]
+pub struct Notified<'a> {
+    notify: &'a Notify,
+    // The number of `notify_waiters` calls when this future was created. Any later call notifies
+    // this future, even if it hasn't been polled yet.
+    notify_waiters_calls: usize,
+    // Our id in the `waiters` list, if we've started waiting
+    waiter: Option<usize>,
+    polled: bool,
+    done: bool,
+}
+
+#[derive(Debug)]
+struct NotifyState {
+    permit: bool,
+    notify_waiters_calls: usize,
+    // The futures waiting for a notification, in the order they started waiting
+    waiters: Vec<Waiter>,
+    next_waiter: usize,
+    clock: VectorClock,
+}
+
+#[derive(Debug)]
+struct Waiter {
+    id: usize,
+    waker: Waker,
+    // Set once `notify_one` has chosen this waiter
+    notified: bool,
+}
+
+impl Notify {
+    /// Creates a new `Notify` with no stored permit.
+    pub fn new() -> Self {
+        let state = NotifyState {
+            permit: false,
+            notify_waiters_calls: 0,
+            waiters: Vec::new(),
+            next_waiter: 0,
+            clock: VectorClock::new(),
+        };
+
+        Self {
+            state: Rc::new(RefCell::new(state)),
+        }
+    }
+
+    /// Returns a future that completes once this `Notify` is notified.
+    pub fn notified(&self) -> Notified<'_> {
+        Notified {
+            notify: self,
+            notify_waiters_calls: self.state.borrow().notify_waiters_calls,
+            waiter: None,
+            polled: false,
+            done: false,
+        }
+    }
+
+    /// Notifies the longest-waiting task, or stores a permit for the next task to wait if no task is
+    /// waiting right now.
+    pub fn notify_one(&self) {
+        let mut state = self.state.borrow_mut();
+        trace!(
+            waiters = state.waiters.len(),
+            permit = state.permit,
+            "notify_one on {:p}",
+            self
+        );
+
+        if ExecutionState::should_stop() {
+            return;
+        }
+
+        state.update_clock();
+        let waker = state.notify_one();
+        drop(state);
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+
+        // Notifying is a yield point
+        thread::switch();
+    }
+
+    /// Notifies every task that is currently waiting, including every [`Notified`] future created
+    /// before this call. No permit is stored, so a future created after this call is not notified.
+    pub fn notify_waiters(&self) {
+        let mut state = self.state.borrow_mut();
+        trace!(waiters = state.waiters.len(), "notify_waiters on {:p}", self);
+
+        if ExecutionState::should_stop() {
+            return;
+        }
+
+        state.update_clock();
+        state.notify_waiters_calls += 1;
+        let waiters = std::mem::take(&mut state.waiters);
+        drop(state);
+
+        for waiter in waiters {
+            waiter.waker.wake();
+        }
+
+        // Notifying is a yield point
+        thread::switch();
+    }
+}
+
+impl NotifyState {
+    /// Update the Notify clock with the notifying task's clock
+    fn update_clock(&mut self) {
+        ExecutionState::with(|s| {
+            let clock = s.increment_clock();
+            self.clock.update(clock);
+        });
+    }
+
+    /// Hand a notification to the longest-waiting task that hasn't already been notified, or store
+    /// a permit if there's no such task. Returns the waker to call, if any.
+    fn notify_one(&mut self) -> Option<Waker> {
+        match self.waiters.iter_mut().find(|w| !w.notified) {
+            Some(waiter) => {
+                waiter.notified = true;
+                Some(waiter.waker.clone())
+            }
+            None => {
+                self.permit = true;
+                None
+            }
+        }
+    }
+}
+
+impl Future for Notified<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        assert!(!self.done, "Notified polled after completion");
+
+        if !self.polled {
+            self.polled = true;
+            // Starting to wait is a yield point
+            thread::switch();
+        }
+
+        let mut state = self.notify.state.borrow_mut();
+        let position = self.waiter.and_then(|id| state.waiters.iter().position(|w| w.id == id));
+
+        let notified = if state.notify_waiters_calls != self.notify_waiters_calls {
+            // `notify_waiters` already removed us from the waiters list
+            true
+        } else if let Some(position) = position {
+            if state.waiters[position].notified {
+                state.waiters.remove(position);
+                true
+            } else {
+                state.waiters[position].waker = cx.waker().clone();
+                false
+            }
+        } else if std::mem::replace(&mut state.permit, false) {
+            true
+        } else {
+            let id = state.next_waiter;
+            state.next_waiter += 1;
+            state.waiters.push(Waiter {
+                id,
+                waker: cx.waker().clone(),
+                notified: false,
+            });
+            self.waiter = Some(id);
+            false
+        };
+
+        trace!(notified, "polled notified future on {:p}", self.notify);
+
+        if notified {
+            // Update the notified task's clock with the clock stored in the Notify
+            ExecutionState::with(|s| s.update_clock(&state.clock));
+            drop(state);
+            self.done = true;
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Notified<'_> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+
+        let mut state = self.notify.state.borrow_mut();
+        let position = self.waiter.and_then(|id| state.waiters.iter().position(|w| w.id == id));
+        if let Some(position) = position {
+            let waiter = state.waiters.remove(position);
+            // If `notify_one` chose us but we never saw the notification, pass it on to the next
+            // waiter so it isn't lost
+            if waiter.notified && !ExecutionState::should_stop() {
+                let waker = state.notify_one();
+                drop(state);
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+            }
+        }
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Safety: Notify is never actually passed across true threads, only across continuations. The
+// Rc<RefCell<_>> type therefore can't be preempted mid-bookkeeping-operation.
+unsafe impl Send for Notify {}
+unsafe impl Sync for Notify {}
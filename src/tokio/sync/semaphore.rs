@@ -0,0 +1,393 @@
+use crate::runtime::execution::ExecutionState;
+use crate::runtime::task::clock::VectorClock;
+use crate::runtime::thread;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt::Display;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+use tracing::trace;
+
+/// An async counting semaphore, with the same semantics as [`tokio::sync::Semaphore`].
+///
+/// Like tokio's semaphore, this semaphore is fair: permits are handed out in the order they were
+/// requested. A task waiting for more permits than are available holds up every task that asked
+/// after it, even tasks that want fewer permits, and [`Semaphore::try_acquire`] fails while any
+/// task is waiting. Permits released while a task is waiting are assigned to it as they become
+/// available.
+///
+/// Acquiring and releasing permits are yield points.
+///
+/// [`tokio::sync::Semaphore`]: https://docs.rs/tokio/1/tokio/sync/struct.Semaphore.html
+#[derive(Debug)]
+pub struct Semaphore {
+    state: Rc<RefCell<SemaphoreState>>,
+}
+
+/// A permit from a [`Semaphore`]. The permits are returned to the semaphore when the permit is
+/// dropped, unless it was [forgotten](SemaphorePermit::forget).
+#[derive(Debug)]
+pub struct SemaphorePermit<'a> {
+    semaphore: &'a Semaphore,
+    permits: u32,
+}
+
+/// The error returned by [`Semaphore::acquire`] when the semaphore has been closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AcquireError(());
+
+/// The error returned by [`Semaphore::try_acquire`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryAcquireError {
+    /// The semaphore has been closed.
+    Closed,
+    /// There are not enough permits available, or other tasks are waiting for them.
+    NoPermits,
+}
+
+#[derive(Debug)]
+struct SemaphoreState {
+    permits: usize,
+    closed: bool,
+    // The tasks waiting for permits, in the order they asked for them
+    waiters: VecDeque<Waiter>,
+    next_waiter: usize,
+    clock: VectorClock,
+}
+
+#[derive(Debug)]
+struct Waiter {
+    id: usize,
+    needed: usize,
+    // The permits already handed to this waiter
+    assigned: usize,
+    waker: Waker,
+}
+
+impl Semaphore {
+    /// Creates a new semaphore with the given number of permits.
+    pub fn new(permits: usize) -> Self {
+        let state = SemaphoreState {
+            permits,
+            closed: false,
+            waiters: VecDeque::new(),
+            next_waiter: 0,
+            clock: VectorClock::new(),
+        };
+
+        Self {
+            state: Rc::new(RefCell::new(state)),
+        }
+    }
+
+    /// Returns the number of permits currently available. Permits already assigned to a waiting
+    /// task are not available.
+    pub fn available_permits(&self) -> usize {
+        // The semaphore doesn't report the objects it accesses
+        ExecutionState::with(|s| s.record_unknown_access());
+        self.state.borrow().permits
+    }
+
+    /// Adds `n` new permits to the semaphore, handing them to waiting tasks first.
+    pub fn add_permits(&self, n: usize) {
+        self.release(n);
+    }
+
+    /// Acquires a permit, waiting until one is available. Returns an error if the semaphore is
+    /// closed before the permit is acquired.
+    pub async fn acquire(&self) -> Result<SemaphorePermit<'_>, AcquireError> {
+        self.acquire_many(1).await
+    }
+
+    /// Acquires `n` permits at once, waiting until they are all available. Returns an error if the
+    /// semaphore is closed before the permits are acquired.
+    pub async fn acquire_many(&self, n: u32) -> Result<SemaphorePermit<'_>, AcquireError> {
+        // Acquiring permits is a yield point
+        thread::switch();
+        Acquire {
+            semaphore: self,
+            needed: n as usize,
+            waiter: None,
+            done: false,
+        }
+        .await
+    }
+
+    /// Attempts to acquire a permit without waiting.
+    pub fn try_acquire(&self) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        self.try_acquire_many(1)
+    }
+
+    /// Attempts to acquire `n` permits at once without waiting. Either all the permits are
+    /// acquired, or none are.
+    ///
+    /// Like [`Semaphore::acquire_many`], this is a yield point, whether or not it succeeds.
+    pub fn try_acquire_many(&self, n: u32) -> Result<SemaphorePermit<'_>, TryAcquireError> {
+        let mut state = self.state.borrow_mut();
+        let result = if state.closed {
+            Err(TryAcquireError::Closed)
+        } else if state.waiters.is_empty() && state.permits >= n as usize {
+            state.permits -= n as usize;
+            Ok(SemaphorePermit {
+                semaphore: self,
+                permits: n,
+            })
+        } else {
+            Err(TryAcquireError::NoPermits)
+        };
+        trace!(
+            permits = state.permits,
+            waiters = state.waiters.len(),
+            "{} {} permits from semaphore {:p}",
+            if result.is_ok() {
+                "acquired"
+            } else {
+                "failed to acquire"
+            },
+            n,
+            self
+        );
+
+        // Update this task's clock with the clock stored in the Semaphore, even if the attempt
+        // failed, because failing tells this task that other tasks hold the permits
+        ExecutionState::with(|s| s.update_clock(&state.clock));
+        drop(state);
+
+        // Acquiring permits is a yield point, even if we failed to acquire them
+        thread::switch();
+
+        result
+    }
+
+    /// Closes the semaphore. Every task waiting for permits, and every later attempt to acquire
+    /// permits, fails with an error. Permits already acquired are unaffected.
+    pub fn close(&self) {
+        let mut state = self.state.borrow_mut();
+        trace!(waiters = state.waiters.len(), "closing semaphore {:p}", self);
+        state.closed = true;
+
+        if ExecutionState::should_stop() {
+            return;
+        }
+
+        // The waiters will see that the semaphore is closed when they're next polled
+        let wakers = state.waiters.iter().map(|w| w.waker.clone()).collect::<Vec<_>>();
+        drop(state);
+        for waker in wakers {
+            waker.wake();
+        }
+
+        thread::switch();
+    }
+
+    /// Returns whether the semaphore has been closed.
+    pub fn is_closed(&self) -> bool {
+        self.state.borrow().closed
+    }
+
+    fn release(&self, n: usize) {
+        let mut state = self.state.borrow_mut();
+        state.permits += n;
+        trace!(
+            permits = state.permits,
+            waiters = state.waiters.len(),
+            "released {} permits to semaphore {:p}",
+            n,
+            self
+        );
+
+        if ExecutionState::should_stop() {
+            return;
+        }
+
+        // Update the Semaphore clock with the releasing task's clock
+        ExecutionState::with(|s| {
+            let clock = s.increment_clock();
+            state.clock.update(clock);
+        });
+
+        let wakers = state.assign_permits();
+        drop(state);
+        for waker in wakers {
+            waker.wake();
+        }
+
+        // Releasing permits is a yield point
+        thread::switch();
+    }
+}
+
+impl SemaphoreState {
+    /// Hand available permits to the waiters in the order they started waiting, and return the
+    /// wakers of the waiters that now have all the permits they need
+    fn assign_permits(&mut self) -> Vec<Waker> {
+        let mut wakers = Vec::new();
+        for waiter in self.waiters.iter_mut() {
+            let assign = (waiter.needed - waiter.assigned).min(self.permits);
+            waiter.assigned += assign;
+            self.permits -= assign;
+            if waiter.assigned < waiter.needed {
+                break;
+            }
+            if assign > 0 {
+                wakers.push(waiter.waker.clone());
+            }
+        }
+        wakers
+    }
+}
+
+impl SemaphorePermit<'_> {
+    /// Returns the number of permits held by this permit.
+    pub fn num_permits(&self) -> usize {
+        self.permits as usize
+    }
+
+    /// Forgets the permits without releasing them back to the semaphore, permanently reducing the
+    /// number of permits the semaphore has.
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        if self.permits > 0 {
+            self.semaphore.release(self.permits as usize);
+        }
+    }
+}
+
+/// The future returned by [`Semaphore::acquire_many`], which resolves once the permits have all
+/// been assigned to this task
+struct Acquire<'a> {
+    semaphore: &'a Semaphore,
+    needed: usize,
+    // Our id in the `waiters` queue, if we've started waiting
+    waiter: Option<usize>,
+    done: bool,
+}
+
+impl<'a> Future for Acquire<'a> {
+    type Output = Result<SemaphorePermit<'a>, AcquireError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.semaphore.state.borrow_mut();
+        let position = self
+            .waiter
+            .map(|id| state.waiters.iter().position(|w| w.id == id).unwrap());
+
+        let result = if state.closed {
+            if let Some(position) = position {
+                // Give back any permits we were already assigned
+                let waiter = state.waiters.remove(position).unwrap();
+                state.permits += waiter.assigned;
+            }
+            Some(Err(AcquireError(())))
+        } else if let Some(position) = position {
+            if state.waiters[position].assigned == self.needed {
+                state.waiters.remove(position);
+                Some(Ok(()))
+            } else {
+                state.waiters[position].waker = cx.waker().clone();
+                None
+            }
+        } else if state.waiters.is_empty() && state.permits >= self.needed {
+            state.permits -= self.needed;
+            Some(Ok(()))
+        } else {
+            let id = state.next_waiter;
+            state.next_waiter += 1;
+            state.waiters.push_back(Waiter {
+                id,
+                needed: self.needed,
+                assigned: 0,
+                waker: cx.waker().clone(),
+            });
+            // Every waiter ahead of us might already have its permits, in which case we get the
+            // next claim on whatever permits are available
+            state.assign_permits();
+            let waiter = state.waiters.back().unwrap();
+            if waiter.assigned == waiter.needed {
+                state.waiters.pop_back();
+                Some(Ok(()))
+            } else {
+                self.waiter = Some(id);
+                None
+            }
+        };
+
+        trace!(
+            permits = state.permits,
+            waiters = state.waiters.len(),
+            ready = result.is_some(),
+            "polled acquire of {} permits from semaphore {:p}",
+            self.needed,
+            self.semaphore
+        );
+
+        match result {
+            Some(result) => {
+                // Update acquiring task's clock with the clock stored in the Semaphore
+                ExecutionState::with(|s| s.update_clock(&state.clock));
+                drop(state);
+                self.done = true;
+                Poll::Ready(result.map(|()| SemaphorePermit {
+                    semaphore: self.semaphore,
+                    permits: self.needed as u32,
+                }))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for Acquire<'_> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+
+        let mut state = self.semaphore.state.borrow_mut();
+        let position = self.waiter.and_then(|id| state.waiters.iter().position(|w| w.id == id));
+        if let Some(position) = position {
+            // Give back any permits we were already assigned, and let the waiters behind us have them
+            let waiter = state.waiters.remove(position).unwrap();
+            state.permits += waiter.assigned;
+            if !ExecutionState::should_stop() {
+                let wakers = state.assign_permits();
+                drop(state);
+                for waker in wakers {
+                    waker.wake();
+                }
+            }
+        }
+    }
+}
+
+// Safety: Semaphore is never actually passed across true threads, only across continuations. The
+// Rc<RefCell<_>> type therefore can't be preempted mid-bookkeeping-operation.
+unsafe impl Send for Semaphore {}
+unsafe impl Sync for Semaphore {}
+
+impl Display for AcquireError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "semaphore closed")
+    }
+}
+
+impl Error for AcquireError {}
+
+impl Display for TryAcquireError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TryAcquireError::Closed => write!(f, "semaphore closed"),
+            TryAcquireError::NoPermits => write!(f, "no permits available"),
+        }
+    }
+}
+
+impl Error for TryAcquireError {}
@@ -0,0 +1,141 @@
+use shuttle::sync::{Arc, Weak};
+use shuttle::{check_dfs, thread};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use test_env_log::test;
+
+// Counts how many times a value has been dropped
+struct DropCounter(std::sync::Arc<AtomicUsize>);
+
+impl Drop for DropCounter {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn arc_counts() {
+    check_dfs(
+        || {
+            let mut arc = Arc::new(0usize);
+            assert_eq!(Arc::strong_count(&arc), 1);
+            assert_eq!(Arc::weak_count(&arc), 0);
+            *Arc::get_mut(&mut arc).unwrap() = 1;
+
+            let weak = Arc::downgrade(&arc);
+            assert_eq!(Arc::weak_count(&arc), 1);
+            assert!(Arc::get_mut(&mut arc).is_none());
+
+            let arc2 = weak.upgrade().unwrap();
+            assert_eq!(Arc::strong_count(&arc), 2);
+            assert_eq!(weak.strong_count(), 2);
+            assert!(Arc::ptr_eq(&arc, &arc2));
+            assert_eq!(*arc2, 1);
+
+            let arc = Arc::try_unwrap(arc).unwrap_err();
+            drop(arc2);
+            assert_eq!(Arc::try_unwrap(arc).ok(), Some(1));
+            assert_eq!(weak.strong_count(), 0);
+            assert_eq!(weak.weak_count(), 0);
+            assert!(weak.upgrade().is_none());
+
+            assert!(Weak::<usize>::new().upgrade().is_none());
+        },
+        None,
+    );
+}
+
+// However the clones are interleaved, the value is dropped exactly once, by the last owner
+#[test]
+fn arc_drop_once() {
+    check_dfs(
+        || {
+            let drops = std::sync::Arc::new(AtomicUsize::new(0));
+            let arc = Arc::new(DropCounter(std::sync::Arc::clone(&drops)));
+
+            let thds = (0..2)
+                .map(|_| {
+                    let arc = Arc::clone(&arc);
+                    thread::spawn(move || drop(arc))
+                })
+                .collect::<Vec<_>>();
+            drop(arc);
+            assert!(drops.load(Ordering::SeqCst) <= 1);
+
+            for thd in thds {
+                thd.join().unwrap();
+            }
+            assert_eq!(drops.load(Ordering::SeqCst), 1);
+        },
+        None,
+    );
+}
+
+// A `Weak::upgrade` racing with the drop of the last strong reference can either succeed, keeping
+// the value alive, or fail because the value was already dropped
+#[test]
+fn arc_upgrade_races_with_last_drop() {
+    let observed = std::sync::Arc::new(Mutex::new(HashSet::new()));
+    let observed_clone = std::sync::Arc::clone(&observed);
+
+    check_dfs(
+        move || {
+            let drops = std::sync::Arc::new(AtomicUsize::new(0));
+            let arc = Arc::new(DropCounter(std::sync::Arc::clone(&drops)));
+            let weak = Arc::downgrade(&arc);
+
+            let thd = thread::spawn(move || drop(arc));
+
+            let upgraded = match weak.upgrade() {
+                Some(arc) => {
+                    // We hold a strong reference, so the value can't have been dropped
+                    assert_eq!(drops.load(Ordering::SeqCst), 0);
+                    drop(arc);
+                    true
+                }
+                // The value might not have been dropped yet, as the other thread might still be
+                // between releasing its strong reference and running the destructor
+                None => false,
+            };
+            thd.join().unwrap();
+            assert_eq!(drops.load(Ordering::SeqCst), 1);
+            observed_clone.lock().unwrap().insert(upgraded);
+        },
+        None,
+    );
+
+    let observed = observed.lock().unwrap();
+    assert!(observed.contains(&true));
+    assert!(observed.contains(&false));
+}
+
+// If two threads race to `try_unwrap` their clones, at most one of them succeeds, and it's possible
+// for both to fail
+#[test]
+fn arc_try_unwrap_race() {
+    let observed = std::sync::Arc::new(Mutex::new(HashSet::new()));
+    let observed_clone = std::sync::Arc::clone(&observed);
+
+    check_dfs(
+        move || {
+            let arc = Arc::new(42usize);
+            let thds = (0..2)
+                .map(|_| {
+                    let arc = Arc::clone(&arc);
+                    thread::spawn(move || Arc::try_unwrap(arc).is_ok())
+                })
+                .collect::<Vec<_>>();
+            drop(arc);
+
+            let successes = thds.into_iter().map(|thd| thd.join().unwrap()).filter(|ok| *ok).count();
+            assert!(successes <= 1);
+            observed_clone.lock().unwrap().insert(successes);
+        },
+        None,
+    );
+
+    let observed = observed.lock().unwrap();
+    assert!(observed.contains(&0));
+    assert!(observed.contains(&1));
+}
@@ -1,16 +1,31 @@
 use crate::basic::clocks::{check_clock, me};
+use shuttle::scheduler::{DfsScheduler, RandomScheduler};
 use shuttle::sync::atomic::*;
-use shuttle::{asynch, check_dfs, thread};
+use shuttle::{check_dfs, check_random, future, thread, Config, Runner};
 use std::collections::HashSet;
 use std::sync::Arc;
 use test_env_log::test;
 
 macro_rules! int_tests {
-    ($name:ident, $ty:ident) => {
+    ($name:ident, $ty:ident, $int_type:ident) => {
         mod $name {
             use super::*;
             use test_env_log::test;
 
+            #[test]
+            fn fetch_add_wraps() {
+                check_dfs(
+                    || {
+                        let x = $ty::new($int_type::MAX);
+                        assert_eq!(x.fetch_add(1, Ordering::SeqCst), $int_type::MAX);
+                        assert_eq!(x.load(Ordering::SeqCst), $int_type::MIN);
+                        assert_eq!(x.fetch_sub(1, Ordering::SeqCst), $int_type::MIN);
+                        assert_eq!(x.load(Ordering::SeqCst), $int_type::MAX);
+                    },
+                    None,
+                );
+            }
+
             #[test]
             fn store_store_reordering() {
                 let observed_states = Arc::new(std::sync::Mutex::new(HashSet::new()));
@@ -321,25 +336,110 @@ macro_rules! int_tests {
                 );
 
                 let observed_values = Arc::try_unwrap(observed_values).unwrap().into_inner().unwrap();
-                // TODO our compare_exchange_weak cannot spuriously fail
+                // compare_exchange_weak can't fail spuriously unless `Config::spurious_wakeups` is enabled
                 assert_eq!(observed_values.len(), 2);
                 assert!(observed_values.contains(&Ok(1)));
                 assert!(observed_values.contains(&Err(0)));
             }
+
+            // Each read-modify-write operation, paired with the function std defines it to apply to
+            // the atomic's previous value
+            #[allow(clippy::type_complexity)]
+            fn fetch_ops() -> [(
+                fn(&$ty, $int_type) -> $int_type,
+                fn($int_type, $int_type) -> $int_type,
+            ); 6] {
+                [
+                    (
+                        |x, val| x.fetch_max(val, Ordering::SeqCst),
+                        |old, val| old.max(val),
+                    ),
+                    (
+                        |x, val| x.fetch_min(val, Ordering::SeqCst),
+                        |old, val| old.min(val),
+                    ),
+                    (|x, val| x.fetch_and(val, Ordering::SeqCst), |old, val| old & val),
+                    (|x, val| x.fetch_or(val, Ordering::SeqCst), |old, val| old | val),
+                    (|x, val| x.fetch_xor(val, Ordering::SeqCst), |old, val| old ^ val),
+                    (
+                        |x, val| x.fetch_nand(val, Ordering::SeqCst),
+                        |old, val| !(old & val),
+                    ),
+                ]
+            }
+
+            #[test]
+            fn fetch_ops_match_std() {
+                check_dfs(
+                    || {
+                        let values = [$int_type::MIN, 0, 1, 5, 6, $int_type::MAX];
+                        for (fetch_op, apply) in fetch_ops() {
+                            for old in values {
+                                for val in values {
+                                    let x = $ty::new(old);
+                                    assert_eq!(fetch_op(&x, val), old);
+                                    assert_eq!(x.load(Ordering::SeqCst), apply(old, val));
+                                }
+                            }
+                        }
+                    },
+                    None,
+                );
+            }
+
+            // Two threads apply each operation to the same atomic concurrently. Whichever order they
+            // run in, each sees the value the other left behind, and the final value reflects both.
+            #[test]
+            fn fetch_ops_concurrent() {
+                const INITIAL: $int_type = 0b0101;
+                const OPERANDS: [$int_type; 2] = [0b0110, 0b0011];
+
+                for (fetch_op, apply) in fetch_ops() {
+                    check_dfs(
+                        move || {
+                            let x = Arc::new($ty::new(INITIAL));
+                            let thds = OPERANDS
+                                .iter()
+                                .map(|&val| {
+                                    let x = Arc::clone(&x);
+                                    thread::spawn(move || fetch_op(&x, val))
+                                })
+                                .collect::<Vec<_>>();
+                            let results = thds
+                                .into_iter()
+                                .map(|thd| thd.join().unwrap())
+                                .collect::<Vec<_>>();
+
+                            let [a, b] = OPERANDS;
+                            let final_value = x.load(Ordering::SeqCst);
+                            let first_ran_first = results[0] == INITIAL
+                                && results[1] == apply(INITIAL, a)
+                                && final_value == apply(apply(INITIAL, a), b);
+                            let second_ran_first = results[1] == INITIAL
+                                && results[0] == apply(INITIAL, b)
+                                && final_value == apply(apply(INITIAL, b), a);
+                            assert!(first_ran_first || second_ran_first);
+                        },
+                        None,
+                    );
+                }
+            }
         }
     };
 }
 
-int_tests!(int_i8, AtomicI8);
-int_tests!(int_i16, AtomicI16);
-int_tests!(int_i32, AtomicI32);
-int_tests!(int_i64, AtomicI64);
-int_tests!(int_isize, AtomicIsize);
-int_tests!(int_u8, AtomicU8);
-int_tests!(int_u16, AtomicU16);
-int_tests!(int_u32, AtomicU32);
-int_tests!(int_u64, AtomicU64);
-int_tests!(int_usize, AtomicUsize);
+int_tests!(int_i8, AtomicI8, i8);
+int_tests!(int_i16, AtomicI16, i16);
+int_tests!(int_i32, AtomicI32, i32);
+int_tests!(int_i64, AtomicI64, i64);
+int_tests!(int_isize, AtomicIsize, isize);
+int_tests!(int_u8, AtomicU8, u8);
+int_tests!(int_u16, AtomicU16, u16);
+int_tests!(int_u32, AtomicU32, u32);
+int_tests!(int_u64, AtomicU64, u64);
+int_tests!(int_usize, AtomicUsize, usize);
+int_tests!(int_i128, AtomicI128, i128);
+int_tests!(int_u128, AtomicU128, u128);
 
 mod bool {
     use super::*;
@@ -508,6 +608,158 @@ mod ptr {
         let observed_values = Arc::try_unwrap(observed_values).unwrap().into_inner().unwrap();
         assert_eq!(observed_values.len(), 2);
     }
+
+    struct Node {
+        value: usize,
+        next: *mut Node,
+    }
+
+    /// A minimal Treiber stack. Popped nodes aren't freed until the stack is dropped, so that a
+    /// concurrent `pop` never reads the `next` pointer of a freed node.
+    struct TreiberStack {
+        head: AtomicPtr<Node>,
+        popped: std::sync::Mutex<Vec<*mut Node>>,
+    }
+
+    // Safety: the raw pointers in `popped` are only freed when the stack is dropped
+    unsafe impl Send for TreiberStack {}
+    unsafe impl Sync for TreiberStack {}
+
+    impl TreiberStack {
+        fn new() -> Self {
+            Self {
+                head: AtomicPtr::new(std::ptr::null_mut()),
+                popped: std::sync::Mutex::new(Vec::new()),
+            }
+        }
+
+        fn push(&self, value: usize) {
+            let node = Box::into_raw(Box::new(Node {
+                value,
+                next: std::ptr::null_mut(),
+            }));
+            loop {
+                let head = self.head.load(Ordering::Acquire);
+                unsafe { (*node).next = head };
+                if self
+                    .head
+                    .compare_exchange(head, node, Ordering::Release, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    return;
+                }
+            }
+        }
+
+        fn pop(&self) -> Option<usize> {
+            loop {
+                let head = self.head.load(Ordering::Acquire);
+                if head.is_null() {
+                    return None;
+                }
+                let next = unsafe { (*head).next };
+                if self
+                    .head
+                    .compare_exchange(head, next, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    self.popped.lock().unwrap().push(head);
+                    return Some(unsafe { (*head).value });
+                }
+            }
+        }
+    }
+
+    impl Drop for TreiberStack {
+        fn drop(&mut self) {
+            let mut node = *self.head.get_mut();
+            while !node.is_null() {
+                let next = unsafe { (*node).next };
+                drop(unsafe { Box::from_raw(node) });
+                node = next;
+            }
+            for node in self.popped.get_mut().unwrap().drain(..) {
+                drop(unsafe { Box::from_raw(node) });
+            }
+        }
+    }
+
+    // Each thread pushes some values and then pops the same number. No value may be lost or
+    // popped twice, and the stack must end up empty.
+    fn treiber_stack(num_threads: usize, values_per_thread: usize) {
+        let stack = Arc::new(TreiberStack::new());
+
+        let thds = (0..num_threads)
+            .map(|i| {
+                let stack = Arc::clone(&stack);
+                thread::spawn(move || {
+                    for j in 0..values_per_thread {
+                        stack.push(i * values_per_thread + j);
+                    }
+                    (0..values_per_thread)
+                        .map(|_| stack.pop().expect("stack can't be empty after our own push"))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let mut popped = thds.into_iter().flat_map(|thd| thd.join().unwrap()).collect::<Vec<_>>();
+        popped.sort_unstable();
+        assert_eq!(popped, (0..num_threads * values_per_thread).collect::<Vec<_>>());
+        assert!(stack.pop().is_none());
+    }
+
+    #[test]
+    fn treiber_stack_concurrent_push() {
+        check_dfs(
+            || {
+                let stack = Arc::new(TreiberStack::new());
+                let thds = (0..2)
+                    .map(|i| {
+                        let stack = Arc::clone(&stack);
+                        thread::spawn(move || stack.push(i))
+                    })
+                    .collect::<Vec<_>>();
+                for thd in thds {
+                    thd.join().unwrap();
+                }
+
+                let mut popped = vec![stack.pop().unwrap(), stack.pop().unwrap()];
+                popped.sort_unstable();
+                assert_eq!(popped, vec![0, 1]);
+                assert!(stack.pop().is_none());
+            },
+            None,
+        );
+    }
+
+    #[test]
+    fn treiber_stack_concurrent_pop() {
+        check_dfs(
+            || {
+                let stack = Arc::new(TreiberStack::new());
+                stack.push(0);
+                stack.push(1);
+                let thds = (0..2)
+                    .map(|_| {
+                        let stack = Arc::clone(&stack);
+                        thread::spawn(move || stack.pop().unwrap())
+                    })
+                    .collect::<Vec<_>>();
+
+                let mut popped = thds.into_iter().map(|thd| thd.join().unwrap()).collect::<Vec<_>>();
+                popped.sort_unstable();
+                assert_eq!(popped, vec![0, 1]);
+                assert!(stack.pop().is_none());
+            },
+            None,
+        );
+    }
+
+    #[test]
+    fn treiber_stack_random() {
+        check_random(|| treiber_stack(3, 2), 1000);
+    }
 }
 
 // We don't support relaxed orderings, but they at least shouldn't crash. This test should fail if
@@ -536,6 +788,265 @@ fn relaxed_orderings_work() {
     );
 }
 
+// Message passing through relaxed operations, synchronized by a release fence in the writer and
+// (optionally) an acquire fence in the reader. The reader only inherits the writer's clock if both
+// fences are present.
+fn fence_message_passing(acquire_fence: bool) {
+    let flag = Arc::new(AtomicBool::new(false));
+    let data = Arc::new(AtomicU64::new(0));
+
+    let thd = {
+        let flag = Arc::clone(&flag);
+        let data = Arc::clone(&data);
+        thread::spawn(move || {
+            data.store(42, Ordering::Relaxed);
+            fence(Ordering::Release);
+            flag.store(true, Ordering::Relaxed);
+        })
+    };
+
+    if flag.load(Ordering::Relaxed) {
+        if acquire_fence {
+            fence(Ordering::Acquire);
+        }
+        assert_eq!(data.load(Ordering::Relaxed), 42);
+        check_clock(|i, c| (c > 0) == (i == 0 || acquire_fence));
+    } else {
+        check_clock(|i, c| (c > 0) == (i == 0));
+    }
+
+    thd.join().unwrap();
+}
+
+#[test]
+fn fence_message_passing_acquire_release() {
+    check_dfs(|| fence_message_passing(true), None);
+}
+
+#[test]
+fn fence_message_passing_release_only() {
+    check_dfs(|| fence_message_passing(false), None);
+}
+
+fn store_buffering_config() -> Config {
+    let mut config = Config::new();
+    config.silence_atomic_ordering_warning = true;
+    config.store_buffering = true;
+    config
+}
+
+// Dekker-style mutual exclusion: each thread raises its own flag and then enters the critical
+// section only if the other thread's flag is down. This is only correct if the store can't be
+// reordered after the load.
+fn dekker(order: Ordering) {
+    let flags = Arc::new([AtomicBool::new(false), AtomicBool::new(false)]);
+    let in_critical = Arc::new(AtomicUsize::new(0));
+
+    let thds = (0..2)
+        .map(|i| {
+            let flags = Arc::clone(&flags);
+            let in_critical = Arc::clone(&in_critical);
+            thread::spawn(move || {
+                flags[i].store(true, order);
+                if !flags[1 - i].load(order) {
+                    let others = in_critical.fetch_add(1, Ordering::SeqCst);
+                    assert_eq!(others, 0, "both threads entered the critical section");
+                    in_critical.fetch_sub(1, Ordering::SeqCst);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // Propagate a panic from either thread as is, so that tests can check its message
+    for thd in thds {
+        if let Err(payload) = thd.join() {
+            std::panic::resume_unwind(payload);
+        }
+    }
+}
+
+#[test]
+fn dekker_seq_cst() {
+    let scheduler = DfsScheduler::new(None, false);
+    let runner = Runner::new(scheduler).with_config(store_buffering_config());
+    runner.run(|| dekker(Ordering::SeqCst));
+}
+
+#[test]
+#[should_panic(expected = "both threads entered the critical section")]
+fn dekker_relaxed() {
+    let scheduler = DfsScheduler::new(None, false);
+    let runner = Runner::new(scheduler).with_config(store_buffering_config());
+    runner.run(|| dekker(Ordering::Relaxed));
+}
+
+// Without store buffering, Shuttle treats every ordering as SeqCst, so it can't find the bug
+#[test]
+fn dekker_relaxed_without_store_buffering() {
+    let mut config = Config::new();
+    config.silence_atomic_ordering_warning = true;
+    let scheduler = DfsScheduler::new(None, false);
+    let runner = Runner::new(scheduler).with_config(config);
+    runner.run(|| dekker(Ordering::Relaxed));
+}
+
+// A thread always sees its own buffered stores, and other threads see them once they're drained
+#[test]
+fn store_buffering_visibility() {
+    let scheduler = DfsScheduler::new(None, false);
+    let runner = Runner::new(scheduler).with_config(store_buffering_config());
+    runner.run(|| {
+        let x = Arc::new(AtomicUsize::new(0));
+        let y = Arc::new(AtomicUsize::new(0));
+
+        let thd = {
+            let x = Arc::clone(&x);
+            let y = Arc::clone(&y);
+            thread::spawn(move || {
+                x.store(1, Ordering::Relaxed);
+                assert_eq!(x.load(Ordering::Relaxed), 1);
+                // The load drained the buffered store to `x`
+                y.store(1, Ordering::SeqCst);
+            })
+        };
+
+        if y.load(Ordering::SeqCst) == 1 {
+            assert_eq!(x.load(Ordering::Relaxed), 1);
+        }
+        thd.join().unwrap();
+        assert_eq!(x.load(Ordering::Relaxed), 1);
+    });
+}
+
+// A thread spinning on a buffered store must eventually see it
+#[test]
+fn store_buffering_spin_loop() {
+    let scheduler = RandomScheduler::new(1000);
+    let runner = Runner::new(scheduler).with_config(store_buffering_config());
+    runner.run(|| {
+        let flag = Arc::new(AtomicBool::new(false));
+
+        let thd = {
+            let flag = Arc::clone(&flag);
+            thread::spawn(move || while !flag.load(Ordering::Relaxed) {})
+        };
+
+        flag.store(true, Ordering::Relaxed);
+        thd.join().unwrap();
+    });
+}
+
+// Two threads apply non-commutative updates with `fetch_update`. The final value must match one of
+// the two serial orders, and because each attempt's load and store are separate steps, some
+// schedules must retry an update.
+fn fetch_update_serializable(config: Config) {
+    let observed = Arc::new(std::sync::Mutex::new(HashSet::new()));
+    let retried = Arc::new(std::sync::atomic::AtomicBool::new(false));
+
+    let scheduler = DfsScheduler::new(None, false);
+    let runner = Runner::new(scheduler).with_config(config);
+    {
+        let observed = Arc::clone(&observed);
+        let retried = Arc::clone(&retried);
+        runner.run(move || {
+            let x = Arc::new(AtomicUsize::new(1));
+            let updates: [fn(usize) -> usize; 2] = [|v| v * 2, |v| v + 3];
+
+            let thds = updates
+                .iter()
+                .map(|&update| {
+                    let x = Arc::clone(&x);
+                    let retried = Arc::clone(&retried);
+                    thread::spawn(move || {
+                        let mut attempts = 0;
+                        x.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |v| {
+                            attempts += 1;
+                            Some(update(v))
+                        })
+                        .unwrap();
+                        if attempts > 1 {
+                            retried.store(true, std::sync::atomic::Ordering::SeqCst);
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            for thd in thds {
+                thd.join().unwrap();
+            }
+
+            let x = x.load(Ordering::SeqCst);
+            assert!(x == 5 || x == 8, "{} isn't a serialization of the updates", x);
+            observed.lock().unwrap().insert(x);
+        });
+    }
+
+    assert_eq!(*observed.lock().unwrap(), HashSet::from([5, 8]));
+    assert!(retried.load(std::sync::atomic::Ordering::SeqCst));
+}
+
+#[test]
+fn fetch_update_non_commutative() {
+    fetch_update_serializable(Config::new());
+}
+
+#[test]
+fn fetch_update_non_commutative_spurious_failures() {
+    fetch_update_serializable(spurious_failures_config());
+}
+
+// Increment a counter with `compare_exchange_weak` while another thread does unrelated work. With no
+// contention on the counter, a single attempt only fails if the operation fails spuriously.
+fn cas_weak_increment(retry: bool) {
+    let counter = Arc::new(AtomicUsize::new(0));
+    let other = Arc::new(AtomicUsize::new(0));
+
+    let thd = {
+        let other = Arc::clone(&other);
+        thread::spawn(move || {
+            other.fetch_add(1, Ordering::SeqCst);
+        })
+    };
+
+    let mut current = counter.load(Ordering::SeqCst);
+    loop {
+        match counter.compare_exchange_weak(current, current + 1, Ordering::SeqCst, Ordering::SeqCst) {
+            Ok(_) => break,
+            Err(actual) if retry => current = actual,
+            Err(_) => break,
+        }
+    }
+
+    thd.join().unwrap();
+    assert_eq!(counter.load(Ordering::SeqCst), 1, "lost an increment");
+}
+
+fn spurious_failures_config() -> Config {
+    let mut config = Config::new();
+    config.spurious_wakeups = true;
+    config
+}
+
+#[test]
+fn compare_exchange_weak_loop() {
+    let scheduler = DfsScheduler::new(None, false);
+    let runner = Runner::new(scheduler).with_config(spurious_failures_config());
+    runner.run(|| cas_weak_increment(true));
+}
+
+#[test]
+#[should_panic(expected = "lost an increment")]
+fn compare_exchange_weak_single_shot() {
+    let scheduler = DfsScheduler::new(None, false);
+    let runner = Runner::new(scheduler).with_config(spurious_failures_config());
+    runner.run(|| cas_weak_increment(false));
+}
+
+// Without spurious failures, `compare_exchange_weak` behaves like `compare_exchange`
+#[test]
+fn compare_exchange_weak_single_shot_without_spurious_failures() {
+    check_dfs(|| cas_weak_increment(false), None);
+}
+
 // Check that atomics work from within futures
 #[test]
 fn atomics_futures() {
@@ -545,10 +1056,10 @@ fn atomics_futures() {
 
             let future = {
                 let flag = Arc::clone(&flag);
-                asynch::spawn(async move { flag.fetch_add(1, Ordering::SeqCst) })
+                future::spawn(async move { flag.fetch_add(1, Ordering::SeqCst) })
             };
 
-            let old = asynch::block_on(future).unwrap();
+            let old = future::block_on(future).unwrap();
 
             assert_eq!(old, 0);
             assert_eq!(flag.load(Ordering::SeqCst), 1);
@@ -556,3 +1067,98 @@ fn atomics_futures() {
         None,
     )
 }
+
+// Two threads each update two counters of their own, which can't race. Returns the number of
+// schedules DFS explores, with and without wrapping each thread's updates in `critical`.
+fn independent_updates(use_critical: bool) -> usize {
+    let runner = Runner::new(DfsScheduler::new(None, false));
+    runner
+        .run(move || {
+            let counters = Arc::new([
+                AtomicUsize::new(0),
+                AtomicUsize::new(0),
+                AtomicUsize::new(0),
+                AtomicUsize::new(0),
+            ]);
+            let threads = (0..2)
+                .map(|i| {
+                    let counters = Arc::clone(&counters);
+                    thread::spawn(move || {
+                        let update = || {
+                            counters[2 * i].fetch_add(1, Ordering::SeqCst);
+                            counters[2 * i + 1].fetch_add(1, Ordering::SeqCst);
+                        };
+                        if use_critical {
+                            critical(update);
+                        } else {
+                            update();
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            for thd in threads {
+                thd.join().unwrap();
+            }
+            assert!(counters.iter().all(|c| c.load(Ordering::SeqCst) == 1));
+        })
+        .iterations
+}
+
+#[test]
+fn critical_shrinks_search_space() {
+    let without = independent_updates(false);
+    let with = independent_updates(true);
+    assert!(
+        with < without,
+        "critical explored {} schedules, not fewer than {}",
+        with,
+        without
+    );
+}
+
+// `critical` is unsound if the operations inside it can race: DFS never explores the other thread
+// running between the load and the store, so it misses the lost update
+#[test]
+fn critical_hides_races() {
+    check_dfs(
+        || {
+            let counter = Arc::new(AtomicUsize::new(0));
+            let threads = (0..2)
+                .map(|_| {
+                    let counter = Arc::clone(&counter);
+                    thread::spawn(move || {
+                        critical(|| {
+                            let value = counter.load(Ordering::SeqCst);
+                            counter.store(value + 1, Ordering::SeqCst);
+                        })
+                    })
+                })
+                .collect::<Vec<_>>();
+            for thd in threads {
+                thd.join().unwrap();
+            }
+            assert_eq!(counter.load(Ordering::SeqCst), 2);
+        },
+        None,
+    );
+}
+
+// A thread that blocks inside a critical region still lets other threads run
+#[test]
+fn critical_blocking() {
+    check_dfs(
+        || {
+            let lock = Arc::new(shuttle::sync::Mutex::new(0));
+            let guard = lock.lock().unwrap();
+            let thd = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || critical(|| *lock.lock().unwrap() += 1))
+            };
+            thread::yield_now();
+            drop(guard);
+            thd.join().unwrap();
+            assert_eq!(*lock.lock().unwrap(), 1);
+        },
+        None,
+    );
+}
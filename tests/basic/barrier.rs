@@ -1,4 +1,5 @@
-use shuttle::sync::{mpsc::channel, Barrier};
+use shuttle::sync::mpsc::{channel, TryRecvError};
+use shuttle::sync::Barrier;
 use shuttle::{check_dfs, thread};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
@@ -56,8 +57,7 @@ fn barrier_test(n: usize, c: usize) {
 
     // At this point, all spawned threads should be blocked,
     // so we shouldn't get anything from the port
-    // TODO uncomment this line after we add support for try_recv()
-    // assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
 
     let mut leader_found = barrier.wait().is_leader();
 
@@ -117,3 +117,53 @@ fn barrier_test_reuse_zero() {
         None,
     );
 }
+
+#[test]
+fn barrier_generations() {
+    const THREADS: usize = 3;
+    const ROUNDS: usize = 2;
+
+    check_dfs(
+        || {
+            let barrier = Arc::new(Barrier::new(THREADS));
+            // For each round, how many threads have arrived at the barrier and how many leaders
+            // were chosen
+            let arrived = Arc::new((0..ROUNDS).map(|_| AtomicUsize::new(0)).collect::<Vec<_>>());
+            let leaders = Arc::new((0..ROUNDS).map(|_| AtomicUsize::new(0)).collect::<Vec<_>>());
+
+            let participant = {
+                let barrier = Arc::clone(&barrier);
+                let arrived = Arc::clone(&arrived);
+                let leaders = Arc::clone(&leaders);
+                move || {
+                    for round in 0..ROUNDS {
+                        arrived[round].fetch_add(1, Ordering::SeqCst);
+                        let result = barrier.wait();
+                        // Nobody gets through the barrier until the whole generation has arrived,
+                        // and nobody can have moved on to the next generation yet
+                        assert_eq!(arrived[round].load(Ordering::SeqCst), THREADS);
+                        if round + 1 < ROUNDS {
+                            assert!(arrived[round + 1].load(Ordering::SeqCst) < THREADS);
+                        }
+                        if result.is_leader() {
+                            leaders[round].fetch_add(1, Ordering::SeqCst);
+                        }
+                    }
+                }
+            };
+
+            let handles = (0..THREADS - 1)
+                .map(|_| thread::spawn(participant.clone()))
+                .collect::<Vec<_>>();
+            participant();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+
+            for round in 0..ROUNDS {
+                assert_eq!(leaders[round].load(Ordering::SeqCst), 1, "round {} leaders", round);
+            }
+        },
+        None,
+    );
+}
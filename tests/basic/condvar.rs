@@ -1,7 +1,8 @@
 use rand::Rng;
-use shuttle::sync::{Condvar, Mutex};
-use shuttle::{check_dfs, check_random, replay, thread};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use shuttle::scheduler::DfsScheduler;
+use shuttle::sync::{wait_for, Condvar, Mutex};
+use shuttle::{check_dfs, check_random, replay, thread, Config, Runner};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use test_env_log::test;
@@ -204,6 +205,49 @@ fn notify_one_order() {
     )
 }
 
+#[test]
+#[should_panic(expected = "deadlock")]
+fn notify_one_wakes_wrong_waiter() {
+    // Two waiters wait on the same condvar for different conditions, but we only notify one of them.
+    // If the scheduler wakes the waiter whose condition wasn't satisfied, it goes back to waiting,
+    // the notification is lost, and the other waiter never wakes up.
+    check_dfs(
+        || {
+            // (number of waiters, id of the waiter allowed to proceed)
+            let lock = Arc::new(Mutex::new((0usize, 0usize)));
+            let cond = Arc::new(Condvar::new());
+            // Auxiliary cond used to wait until both waiters are waiting on `cond`
+            let sequencer_cond = Arc::new(Condvar::new());
+
+            for id in 1..=2 {
+                let lock = Arc::clone(&lock);
+                let cond = Arc::clone(&cond);
+                let sequencer_cond = Arc::clone(&sequencer_cond);
+                thread::spawn(move || {
+                    let mut guard = lock.lock().unwrap();
+                    guard.0 += 1;
+                    sequencer_cond.notify_one();
+                    while guard.1 != id {
+                        guard = cond.wait(guard).unwrap();
+                    }
+                    // Hand off to the next waiter
+                    guard.1 += 1;
+                    cond.notify_one();
+                });
+            }
+
+            let mut guard = lock.lock().unwrap();
+            while guard.0 < 2 {
+                guard = sequencer_cond.wait(guard).unwrap();
+            }
+            // Only waiter 1 can make progress, so this is only correct if waiter 1 is woken first
+            guard.1 = 1;
+            cond.notify_one();
+        },
+        None,
+    )
+}
+
 /// From "Operating Systems: Three Easy Pieces", Figure 30.8.
 /// Demonstrates why a waiter needs to check the condition in a `while` loop, not an if.
 /// http://pages.cs.wisc.edu/~remzi/OSTEP/threads-cv.pdf
@@ -429,8 +473,8 @@ fn producer_consumer_random() {
 
 #[test]
 fn notify_one_timeout() {
-    // TODO we don't currently implement timeouts in `wait_timeout`, so this test is identical
-    // TODO to `notify_one`.
+    // The waiter can time out any number of times before the notification arrives, so bound the
+    // search
     check_dfs(
         || {
             let lock = Arc::new(Mutex::new(false));
@@ -451,6 +495,242 @@ fn notify_one_timeout() {
             // Note: it's valid to signal a condvar while not holding the corresponding lock
             cond.notify_one();
         },
+        Some(1000),
+    )
+}
+
+#[test]
+fn wait_timeout_explores_both_outcomes() {
+    let saw_timeout = Arc::new(AtomicBool::new(false));
+    let saw_notify = Arc::new(AtomicBool::new(false));
+
+    {
+        let saw_timeout = Arc::clone(&saw_timeout);
+        let saw_notify = Arc::clone(&saw_notify);
+
+        check_dfs(
+            move || {
+                let lock = Arc::new(Mutex::new(()));
+                let cond = Arc::new(Condvar::new());
+
+                {
+                    let cond = Arc::clone(&cond);
+                    thread::spawn(move || {
+                        cond.notify_one();
+                    });
+                }
+
+                let guard = lock.lock().unwrap();
+                let (_guard, result) = cond.wait_timeout(guard, Duration::from_secs(10)).unwrap();
+                if result.timed_out() {
+                    saw_timeout.store(true, Ordering::SeqCst);
+                } else {
+                    saw_notify.store(true, Ordering::SeqCst);
+                }
+            },
+            None,
+        );
+    }
+
+    assert!(saw_timeout.load(Ordering::SeqCst));
+    assert!(saw_notify.load(Ordering::SeqCst));
+}
+
+#[test]
+fn wait_timeout_without_notify() {
+    // Nobody ever notifies the condvar, so the waiter can only make progress by timing out
+    check_random(
+        || {
+            let lock = Arc::new(Mutex::new(false));
+            let cond = Arc::new(Condvar::new());
+
+            let waiter = {
+                let lock = Arc::clone(&lock);
+                let cond = Arc::clone(&cond);
+                thread::spawn(move || {
+                    let mut guard = lock.lock().unwrap();
+                    while !*guard {
+                        let (next, result) = cond.wait_timeout(guard, Duration::from_secs(10)).unwrap();
+                        assert!(result.timed_out());
+                        guard = next;
+                    }
+                })
+            };
+
+            *lock.lock().unwrap() = true;
+            waiter.join().unwrap();
+        },
+        1000,
+    )
+}
+
+#[test]
+fn wait_while() {
+    check_dfs(
+        || {
+            let lock = Arc::new(Mutex::new(false));
+            let cond = Arc::new(Condvar::new());
+
+            {
+                let lock = Arc::clone(&lock);
+                let cond = Arc::clone(&cond);
+                thread::spawn(move || {
+                    *lock.lock().unwrap() = true;
+                    cond.notify_one();
+                });
+            }
+
+            let guard = cond.wait_while(lock.lock().unwrap(), |ready| !*ready).unwrap();
+            assert!(*guard);
+        },
         None,
     )
 }
+
+#[test]
+fn wait_timeout_while() {
+    let saw_timeout = Arc::new(AtomicBool::new(false));
+
+    {
+        let saw_timeout = Arc::clone(&saw_timeout);
+
+        check_dfs(
+            move || {
+                let lock = Arc::new(Mutex::new(false));
+                let cond = Arc::new(Condvar::new());
+
+                {
+                    let lock = Arc::clone(&lock);
+                    let cond = Arc::clone(&cond);
+                    thread::spawn(move || {
+                        *lock.lock().unwrap() = true;
+                        cond.notify_one();
+                    });
+                }
+
+                let (guard, result) = cond
+                    .wait_timeout_while(lock.lock().unwrap(), Duration::from_secs(10), |ready| !*ready)
+                    .unwrap();
+                // We only report a timeout if the condition still didn't hold
+                assert_eq!(result.timed_out(), !*guard);
+                if result.timed_out() {
+                    saw_timeout.store(true, Ordering::SeqCst);
+                }
+            },
+            None,
+        );
+    }
+
+    assert!(saw_timeout.load(Ordering::SeqCst));
+}
+
+// A consumer that doesn't re-check its condition after waking up, which is only correct if there
+// are no spurious wakeups
+fn wait_without_recheck() {
+    let lock = Arc::new(Mutex::new(false));
+    let cond = Arc::new(Condvar::new());
+
+    {
+        let lock = Arc::clone(&lock);
+        let cond = Arc::clone(&cond);
+        thread::spawn(move || {
+            *lock.lock().unwrap() = true;
+            cond.notify_one();
+        });
+    }
+
+    let mut guard = lock.lock().unwrap();
+    if !*guard {
+        guard = cond.wait(guard).unwrap();
+    }
+    assert!(*guard, "woke up before the condition was true");
+}
+
+#[test]
+fn wait_without_recheck_no_spurious_wakeups() {
+    check_dfs(wait_without_recheck, None)
+}
+
+#[test]
+#[should_panic(expected = "woke up before the condition was true")]
+fn wait_without_recheck_spurious_wakeups() {
+    let mut config = Config::new();
+    config.spurious_wakeups = true;
+    let scheduler = DfsScheduler::new(None, false);
+    let runner = Runner::new(scheduler).with_config(config);
+    runner.run(wait_without_recheck);
+}
+
+#[test]
+fn wait_while_spurious_wakeups() {
+    let mut config = Config::new();
+    config.spurious_wakeups = true;
+    // Spurious wakeups can happen any number of times, so bound the search
+    let scheduler = DfsScheduler::new(Some(1000), false);
+    let runner = Runner::new(scheduler).with_config(config);
+    runner.run(|| {
+        let lock = Arc::new(Mutex::new(false));
+        let cond = Arc::new(Condvar::new());
+
+        let waiter = {
+            let lock = Arc::clone(&lock);
+            let cond = Arc::clone(&cond);
+            thread::spawn(move || {
+                // The predicate is re-checked after every wakeup, spurious or not
+                let guard = cond.wait_while(lock.lock().unwrap(), |ready| !*ready).unwrap();
+                assert!(*guard);
+            })
+        };
+
+        *lock.lock().unwrap() = true;
+        cond.notify_one();
+        waiter.join().unwrap();
+    });
+}
+
+// Two waiters wait for a counter to reach different thresholds. When the producer raises the
+// counter to 1 and notifies everyone, only the first waiter can proceed.
+fn wait_for_thresholds() {
+    let lock = Arc::new(Mutex::new(0));
+    let cond = Arc::new(Condvar::new());
+    let proceeded = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+    let waiters = (1..=2)
+        .map(|threshold| {
+            let lock = Arc::clone(&lock);
+            let cond = Arc::clone(&cond);
+            let proceeded = Arc::clone(&proceeded);
+            thread::spawn(move || {
+                let guard = wait_for(&lock, &cond, |count| *count >= threshold).unwrap();
+                assert!(*guard >= threshold);
+                proceeded.lock().unwrap().push(threshold);
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut waiters = waiters.into_iter();
+
+    *lock.lock().unwrap() = 1;
+    cond.notify_all();
+    waiters.next().unwrap().join().unwrap();
+    assert_eq!(*proceeded.lock().unwrap(), vec![1]);
+
+    *lock.lock().unwrap() = 2;
+    cond.notify_all();
+    waiters.next().unwrap().join().unwrap();
+    assert_eq!(*proceeded.lock().unwrap(), vec![1, 2]);
+}
+
+#[test]
+fn wait_for_wakes_waiters_that_can_proceed() {
+    check_dfs(wait_for_thresholds, None)
+}
+
+#[test]
+fn wait_for_spurious_wakeups() {
+    let mut config = Config::new();
+    config.spurious_wakeups = true;
+    // Spurious wakeups can happen any number of times, so bound the search
+    let scheduler = DfsScheduler::new(Some(1000), false);
+    let runner = Runner::new(scheduler).with_config(config);
+    runner.run(wait_for_thresholds);
+}
@@ -1,4 +1,4 @@
-use shuttle::scheduler::RandomScheduler;
+use shuttle::scheduler::{DfsScheduler, RandomScheduler};
 use shuttle::sync::Mutex;
 use shuttle::{thread, Config, Runner};
 use std::sync::Arc;
@@ -8,7 +8,7 @@ fn many_tasks_with_mutex() {
     let num_spawn = 1000;
     let config = Config::new();
     let scheduler = RandomScheduler::new(10);
-    let runner = Runner::new(scheduler, config);
+    let runner = Runner::new(scheduler).with_config(config);
     runner.run(move || {
         let count = Arc::new(Mutex::new(0));
         let handles = (0..num_spawn)
@@ -28,6 +28,42 @@ fn many_tasks_with_mutex() {
     });
 }
 
+fn max_tasks(n: usize) -> Config {
+    let mut config = Config::new();
+    config.max_tasks = Some(n);
+    config
+}
+
+// Each thread spawns the next one, so the tasks are all alive at once
+fn spawn_chain(depth: usize) {
+    if depth > 0 {
+        thread::spawn(move || spawn_chain(depth - 1)).join().unwrap();
+    }
+}
+
+#[test]
+#[should_panic(expected = "exceeded max_tasks bound 3: task 2 tried to spawn a new task while 3 tasks were alive")]
+fn max_tasks_exceeded() {
+    let runner = Runner::new(DfsScheduler::new(None, false)).with_config(max_tasks(3));
+    runner.run(|| spawn_chain(3));
+}
+
+#[test]
+fn max_tasks_not_exceeded() {
+    let runner = Runner::new(DfsScheduler::new(None, false)).with_config(max_tasks(3));
+    runner.run(|| spawn_chain(2));
+}
+
+#[test]
+fn max_tasks_ignores_finished_tasks() {
+    let runner = Runner::new(DfsScheduler::new(None, false)).with_config(max_tasks(2));
+    runner.run(|| {
+        for _ in 0..5 {
+            thread::spawn(|| ()).join().unwrap();
+        }
+    });
+}
+
 #[test]
 #[ignore] // Crashes with SIGBUS if you run it
 fn max_stack_depth() {
@@ -35,7 +71,7 @@ fn max_stack_depth() {
     config.stack_size = 1024;
 
     let scheduler = RandomScheduler::new(100);
-    let runner = Runner::new(scheduler, config);
+    let runner = Runner::new(scheduler).with_config(config);
     runner.run(|| {
         thread::spawn(|| {
             let v = vec![99; 1024];
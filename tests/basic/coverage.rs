@@ -0,0 +1,60 @@
+use shuttle::scheduler::DfsScheduler;
+use shuttle::sync::Mutex;
+use shuttle::{current, thread, Config, Runner};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use test_env_log::test;
+
+// Run a program where the main thread and a worker thread both increment a counter, and return
+// the coverage labels it hit. If `join_first` is set, the main thread waits for the worker to
+// finish before its critical section, so the critical section never races with the worker.
+fn coverage(join_first: bool) -> BTreeSet<&'static str> {
+    let mut config = Config::new();
+    config.record_coverage = true;
+
+    let runner = Runner::new(DfsScheduler::new(None, false)).with_config(config);
+    let stats = runner.run(move || {
+        let lock = Arc::new(Mutex::new(0));
+
+        current::set_coverage_label(Some("spawn"));
+        let mut thd = {
+            let lock = Arc::clone(&lock);
+            Some(thread::spawn(move || *lock.lock().unwrap() += 1))
+        };
+        current::set_coverage_label(None);
+
+        if join_first {
+            thd.take().unwrap().join().unwrap();
+        }
+        current::set_coverage_label(Some("critical section"));
+        *lock.lock().unwrap() += 1;
+        current::set_coverage_label(None);
+        if let Some(thd) = thd {
+            thd.join().unwrap();
+        }
+
+        assert_eq!(*lock.lock().unwrap(), 2);
+    });
+    stats.coverage().clone()
+}
+
+#[test]
+fn coverage_detects_lost_interleaving() {
+    let racy = coverage(false);
+    let sequential = coverage(true);
+    assert_eq!(racy, BTreeSet::from(["critical section", "spawn"]));
+    assert_eq!(sequential, BTreeSet::from(["spawn"]));
+
+    let lost = racy.difference(&sequential).copied().collect::<Vec<_>>();
+    assert_eq!(lost, vec!["critical section"]);
+}
+
+#[test]
+fn coverage_disabled_by_default() {
+    let runner = Runner::new(DfsScheduler::new(None, false));
+    let stats = runner.run(|| {
+        current::set_coverage_label(Some("main"));
+        thread::spawn(|| ()).join().unwrap();
+    });
+    assert!(stats.coverage().is_empty());
+}
@@ -0,0 +1,100 @@
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64Mcg;
+use shuttle::scheduler::{Schedule, Scheduler, TaskId};
+use shuttle::sync::Mutex;
+use shuttle::{thread, Config, MaxSteps, Runner};
+use std::sync::Arc;
+use test_env_log::test;
+
+/// A custom scheduler that usually runs the lowest-numbered runnable task, but sometimes picks a
+/// random one instead
+#[derive(Debug)]
+struct LowBiasScheduler {
+    iterations: usize,
+    max_iterations: usize,
+    rng: Pcg64Mcg,
+}
+
+impl LowBiasScheduler {
+    fn new(max_iterations: usize) -> Self {
+        Self {
+            iterations: 0,
+            max_iterations,
+            rng: Pcg64Mcg::seed_from_u64(0),
+        }
+    }
+}
+
+impl Scheduler for LowBiasScheduler {
+    fn new_execution(&mut self) -> Option<Schedule> {
+        if self.iterations >= self.max_iterations {
+            None
+        } else {
+            self.iterations += 1;
+            Some(Schedule::new(0))
+        }
+    }
+
+    fn next_task(&mut self, runnable: &[TaskId], _current: Option<TaskId>, _is_yielding: bool) -> Option<TaskId> {
+        if self.rng.gen_bool(0.75) {
+            runnable.iter().min().copied()
+        } else {
+            Some(runnable[self.rng.gen_range(0, runnable.len())])
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.gen()
+    }
+}
+
+#[test]
+fn custom_scheduler_biases_low_tasks() {
+    let first = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+    let runner = Runner::new(LowBiasScheduler::new(100));
+    let stats = {
+        let first = Arc::clone(&first);
+        runner.run(move || {
+            let order = Arc::new(Mutex::new(Vec::new()));
+            let threads = (1..=2)
+                .map(|i| {
+                    let order = Arc::clone(&order);
+                    thread::spawn(move || order.lock().unwrap().push(i))
+                })
+                .collect::<Vec<_>>();
+            for thd in threads {
+                thd.join().unwrap();
+            }
+            first.lock().unwrap().push(order.lock().unwrap()[0]);
+        })
+    };
+    assert_eq!(stats.iterations, 100);
+
+    // Both orders are possible, but the lower-numbered thread usually goes first
+    let first = first.lock().unwrap();
+    let low_first = first.iter().filter(|i| **i == 1).count();
+    assert!(low_first < first.len());
+    assert!(low_first > first.len() / 2);
+}
+
+#[test]
+fn custom_scheduler_with_config() {
+    let mut config = Config::new();
+    config.max_steps = MaxSteps::ContinueAfter(50);
+
+    let runner = Runner::new(LowBiasScheduler::new(10)).with_config(config);
+    let stats = runner.run(|| {
+        let thd = thread::spawn(|| {
+            for _ in 0..1000 {
+                thread::yield_now();
+            }
+        });
+        thd.join().unwrap();
+    });
+
+    // Every iteration is cut short by the step bound, rather than failing
+    assert_eq!(stats.iterations, 10);
+    assert_eq!(stats.max_steps, 50);
+    assert!(!stats.exhausted);
+}
@@ -1,6 +1,6 @@
 use shuttle::scheduler::DfsScheduler;
 use shuttle::sync::Mutex;
-use shuttle::{thread, Config, MaxSteps, Runner};
+use shuttle::{check_dfs_bounded, thread, Config, MaxSteps, Runner};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use test_env_log::test;
@@ -21,7 +21,7 @@ fn trivial_one_thread() {
     {
         let counter = Arc::clone(&iterations);
         let scheduler = DfsScheduler::new(None, false);
-        let runner = Runner::new(scheduler, Default::default());
+        let runner = Runner::new(scheduler);
         runner.run(move || {
             counter.fetch_add(1, Ordering::SeqCst);
         });
@@ -37,7 +37,7 @@ fn trivial_two_threads() {
     {
         let counter = Arc::clone(&iterations);
         let scheduler = DfsScheduler::new(None, false);
-        let runner = Runner::new(scheduler, Default::default());
+        let runner = Runner::new(scheduler);
         runner.run(move || {
             counter.fetch_add(1, Ordering::SeqCst);
 
@@ -72,7 +72,7 @@ fn two_threads() {
     {
         let counter = Arc::clone(&iterations);
         let scheduler = DfsScheduler::new(None, false);
-        let runner = Runner::new(scheduler, Default::default());
+        let runner = Runner::new(scheduler);
         runner.run(move || two_threads_work(&counter));
     }
 
@@ -88,7 +88,7 @@ fn two_threads_depth_4() {
     {
         let counter = Arc::clone(&iterations);
         let scheduler = DfsScheduler::new(None, false);
-        let runner = Runner::new(scheduler, max_steps(4));
+        let runner = Runner::new(scheduler).with_config(max_steps(4));
         runner.run(move || two_threads_work(&counter));
     }
 
@@ -113,7 +113,7 @@ fn two_threads_depth_5() {
     {
         let counter = Arc::clone(&iterations);
         let scheduler = DfsScheduler::new(None, false);
-        let runner = Runner::new(scheduler, max_steps(5));
+        let runner = Runner::new(scheduler).with_config(max_steps(5));
         runner.run(move || two_threads_work(&counter));
     }
 
@@ -140,7 +140,7 @@ fn yield_loop_one_thread() {
     {
         let counter = Arc::clone(&iterations);
         let scheduler = DfsScheduler::new(None, false);
-        let runner = Runner::new(scheduler, Default::default());
+        let runner = Runner::new(scheduler);
         runner.run(move || {
             counter.fetch_add(1, Ordering::SeqCst);
 
@@ -165,7 +165,7 @@ fn yield_loop_two_threads() {
     {
         let counter = Arc::clone(&iterations);
         let scheduler = DfsScheduler::new(None, false);
-        let runner = Runner::new(scheduler, Default::default());
+        let runner = Runner::new(scheduler);
         runner.run(move || {
             counter.fetch_add(1, Ordering::SeqCst);
 
@@ -193,7 +193,7 @@ fn yield_loop_two_threads_bounded() {
     {
         let counter = Arc::clone(&iterations);
         let scheduler = DfsScheduler::new(Some(100), false);
-        let runner = Runner::new(scheduler, Default::default());
+        let runner = Runner::new(scheduler);
         runner.run(move || {
             counter.fetch_add(1, Ordering::SeqCst);
 
@@ -219,7 +219,7 @@ fn yield_loop_three_threads() {
     {
         let counter = Arc::clone(&iterations);
         let scheduler = DfsScheduler::new(None, false);
-        let runner = Runner::new(scheduler, Default::default());
+        let runner = Runner::new(scheduler);
         runner.run(move || {
             counter.fetch_add(1, Ordering::SeqCst);
 
@@ -251,7 +251,7 @@ fn yield_loop_max_depth() {
     {
         let counter = Arc::clone(&iterations);
         let scheduler = DfsScheduler::new(None, false);
-        let runner = Runner::new(scheduler, max_steps(20));
+        let runner = Runner::new(scheduler).with_config(max_steps(20));
         runner.run(move || {
             for _ in 0..100 {
                 counter.fetch_add(1, Ordering::SeqCst);
@@ -262,3 +262,128 @@ fn yield_loop_max_depth() {
 
     assert_eq!(iterations.load(Ordering::SeqCst), 20);
 }
+
+// A bug that needs two preemptions: the main thread must be preempted while `x` is 1, and then the
+// other thread must be preempted while `y` is 1
+fn two_preemption_bug() {
+    let x = Arc::new(Mutex::new(0));
+    let y = Arc::new(Mutex::new(0));
+
+    let thd = {
+        let x = Arc::clone(&x);
+        let y = Arc::clone(&y);
+        thread::spawn(move || {
+            if *x.lock().unwrap() == 1 {
+                *y.lock().unwrap() = 1;
+                *y.lock().unwrap() = 0;
+            }
+        })
+    };
+
+    *x.lock().unwrap() = 1;
+    *x.lock().unwrap() = 0;
+    assert_eq!(*y.lock().unwrap(), 0, "found the two-preemption bug");
+    thd.join().unwrap();
+}
+
+#[test]
+#[should_panic(expected = "found the two-preemption bug")]
+fn preemption_bound_finds_bug() {
+    check_dfs_bounded(two_preemption_bug, 2);
+}
+
+#[test]
+fn preemption_bound_too_small() {
+    check_dfs_bounded(two_preemption_bug, 0);
+    check_dfs_bounded(two_preemption_bug, 1);
+}
+
+// Blocking isn't a preemption, so with a bound of zero the main thread runs until it blocks on the
+// join, and only then does the spawned thread run
+#[test]
+fn preemption_bound_zero_allows_blocking() {
+    let iterations = Arc::new(AtomicUsize::new(0));
+
+    {
+        let counter = Arc::clone(&iterations);
+        let scheduler = DfsScheduler::new_with_preemption_bound(None, 0, false);
+        let runner = Runner::new(scheduler);
+        runner.run(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+
+            let lock = Arc::new(Mutex::new(0));
+            let thd = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    *lock.lock().unwrap() += 1;
+                })
+            };
+            *lock.lock().unwrap() += 1;
+            thd.join().unwrap();
+            assert_eq!(*lock.lock().unwrap(), 2);
+        });
+    }
+
+    assert_eq!(iterations.load(Ordering::SeqCst), 1);
+}
+
+// Each round of the search re-explores the schedules of the rounds before it, and then some
+#[test]
+fn preemption_bound_iterates() {
+    fn count(max_preemptions: usize) -> usize {
+        let iterations = Arc::new(AtomicUsize::new(0));
+        {
+            let counter = Arc::clone(&iterations);
+            let scheduler = DfsScheduler::new_with_preemption_bound(None, max_preemptions, false);
+            let runner = Runner::new(scheduler);
+            runner.run(move || two_threads_work(&counter));
+        }
+        iterations.load(Ordering::SeqCst)
+    }
+
+    // There is 1 schedule with no preemptions, 4 with at most one, and 10 with at most two
+    assert_eq!(count(0), 1);
+    assert_eq!(count(1), 1 + 4);
+    assert_eq!(count(2), 1 + 4 + 10);
+}
+
+fn yielding_threads() {
+    thread::spawn(|| {
+        thread::yield_now();
+    });
+    thread::yield_now();
+}
+
+#[test]
+fn dfs_exhausted() {
+    let runner = Runner::new(DfsScheduler::new(None, false));
+    let stats = runner.run(yielding_threads);
+    assert_eq!(stats.iterations, 6);
+    assert!(stats.exhausted);
+}
+
+#[test]
+fn dfs_not_exhausted_at_max_iterations() {
+    let runner = Runner::new(DfsScheduler::new(Some(2), false));
+    let stats = runner.run(yielding_threads);
+    assert_eq!(stats.iterations, 2);
+    assert!(!stats.exhausted);
+}
+
+// Executions cut short by the step bound may have had more schedules below them
+#[test]
+fn dfs_not_exhausted_at_max_steps() {
+    let mut config = Config::new();
+    config.max_steps = MaxSteps::ContinueAfter(2);
+    let runner = Runner::new(DfsScheduler::new(None, false)).with_config(config);
+    let stats = runner.run(yielding_threads);
+    assert!(!stats.exhausted);
+}
+
+#[test]
+fn dfs_not_exhausted_with_preemption_bound() {
+    let scheduler = DfsScheduler::new_with_preemption_bound(None, 10, false);
+    let runner = Runner::new(scheduler);
+    let stats = runner.run(yielding_threads);
+    assert!(!stats.exhausted);
+}
@@ -0,0 +1,281 @@
+use shuttle::scheduler::{DfsScheduler, DporScheduler, Scheduler};
+use shuttle::sync::atomic::{AtomicUsize, Ordering};
+use shuttle::sync::{mpsc, Mutex, ReentrantMutex, RwLock};
+use shuttle::{check_dpor, thread, Runner};
+use std::collections::HashSet;
+use std::sync::Arc;
+use test_env_log::test;
+
+// Count the executions a scheduler explores for the given test
+fn count_executions<S, F>(scheduler: S, f: F) -> usize
+where
+    S: Scheduler + 'static,
+    F: Fn() + Send + Sync + 'static,
+{
+    let executions = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    {
+        let executions = Arc::clone(&executions);
+        let runner = Runner::new(scheduler);
+        runner.run(move || {
+            executions.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            f();
+        });
+    }
+    executions.load(std::sync::atomic::Ordering::SeqCst)
+}
+
+// Each thread repeatedly takes its own lock, so no two threads' steps depend on each other
+fn independent_locks() {
+    let threads = (0..2)
+        .map(|_| {
+            thread::spawn(|| {
+                let lock = Mutex::new(0);
+                for _ in 0..2 {
+                    *lock.lock().unwrap() += 1;
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
+
+#[test]
+fn dpor_independent_locks() {
+    let dfs = count_executions(DfsScheduler::new(None, false), independent_locks);
+    let dpor = count_executions(DporScheduler::new(None, false), independent_locks);
+    assert!(
+        dpor * 100 < dfs,
+        "DPOR explored {} executions, not far fewer than DFS's {}",
+        dpor,
+        dfs
+    );
+}
+
+// Like `independent_locks`, but with reentrant mutexes that each thread takes recursively
+fn independent_reentrant_locks() {
+    let threads = (0..2)
+        .map(|_| {
+            thread::spawn(|| {
+                let lock = ReentrantMutex::new(std::cell::Cell::new(0));
+                for _ in 0..2 {
+                    let outer = lock.lock();
+                    let inner = lock.lock();
+                    inner.set(outer.get() + 1);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
+
+#[test]
+fn dpor_independent_reentrant_locks() {
+    let dfs = count_executions(DfsScheduler::new(None, false), independent_reentrant_locks);
+    let dpor = count_executions(DporScheduler::new(None, false), independent_reentrant_locks);
+    assert!(
+        dpor * 100 < dfs,
+        "DPOR explored {} executions, not far fewer than DFS's {}",
+        dpor,
+        dfs
+    );
+}
+
+// Two threads race on a lock-protected counter, but also take their own independent locks, so DPOR
+// prunes most interleavings but must still explore both orders of the shared lock
+#[test]
+fn dpor_explores_both_lock_orders() {
+    let orders = Arc::new(std::sync::Mutex::new(HashSet::new()));
+    {
+        let orders = Arc::clone(&orders);
+        check_dpor(
+            move || {
+                let shared = Arc::new(Mutex::new(Vec::new()));
+                let threads = (0..2)
+                    .map(|i| {
+                        let shared = Arc::clone(&shared);
+                        thread::spawn(move || {
+                            let own = Mutex::new(0);
+                            *own.lock().unwrap() += 1;
+                            shared.lock().unwrap().push(i);
+                            *own.lock().unwrap() += 1;
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                for thread in threads {
+                    thread.join().unwrap();
+                }
+                orders.lock().unwrap().insert(shared.lock().unwrap().clone());
+            },
+            None,
+        );
+    }
+    let orders = Arc::try_unwrap(orders).unwrap().into_inner().unwrap();
+    assert_eq!(orders, HashSet::from([vec![0, 1], vec![1, 0]]));
+}
+
+// The final values DFS and DPOR observe for a program with racing atomics should be the same
+#[test]
+fn dpor_same_outcomes_as_dfs() {
+    fn outcomes<S: Scheduler + 'static>(scheduler: S) -> HashSet<(usize, usize)> {
+        let outcomes = Arc::new(std::sync::Mutex::new(HashSet::new()));
+        {
+            let outcomes = Arc::clone(&outcomes);
+            let runner = Runner::new(scheduler);
+            runner.run(move || {
+                let x = Arc::new(AtomicUsize::new(0));
+                let y = Arc::new(AtomicUsize::new(0));
+                let thd = {
+                    let x = Arc::clone(&x);
+                    let y = Arc::clone(&y);
+                    thread::spawn(move || {
+                        x.store(1, Ordering::SeqCst);
+                        y.load(Ordering::SeqCst)
+                    })
+                };
+                y.store(1, Ordering::SeqCst);
+                let a = x.load(Ordering::SeqCst);
+                let b = thd.join().unwrap();
+                outcomes.lock().unwrap().insert((a, b));
+            });
+        }
+        Arc::try_unwrap(outcomes).unwrap().into_inner().unwrap()
+    }
+
+    let dfs = outcomes(DfsScheduler::new(None, false));
+    assert_eq!(dfs, HashSet::from([(0, 1), (1, 0), (1, 1)]));
+    assert_eq!(outcomes(DporScheduler::new(None, false)), dfs);
+}
+
+// A lost update hidden between independent lock operations
+#[test]
+#[should_panic(expected = "lost update")]
+fn dpor_finds_lost_update() {
+    check_dpor(
+        || {
+            let counter = Arc::new(AtomicUsize::new(0));
+            let threads = (0..2)
+                .map(|_| {
+                    let counter = Arc::clone(&counter);
+                    thread::spawn(move || {
+                        let own = Mutex::new(0);
+                        *own.lock().unwrap() += 1;
+                        let value = counter.load(Ordering::SeqCst);
+                        *own.lock().unwrap() += 1;
+                        counter.store(value + 1, Ordering::SeqCst);
+                    })
+                })
+                .collect::<Vec<_>>();
+            for thread in threads {
+                thread.join().unwrap();
+            }
+            assert_eq!(counter.load(Ordering::SeqCst), 2, "lost update");
+        },
+        None,
+    );
+}
+
+// Channels don't report the objects they access, so DPOR must conservatively explore both orders
+// of two senders
+#[test]
+#[should_panic(expected = "wrong order")]
+fn dpor_unknown_accesses() {
+    check_dpor(
+        || {
+            let (tx, rx) = mpsc::channel();
+            let tx2 = tx.clone();
+            thread::spawn(move || {
+                let _ = tx.send(0);
+            });
+            thread::spawn(move || {
+                let _ = tx2.send(1);
+            });
+            assert_eq!(rx.recv().unwrap(), 0, "wrong order");
+        },
+        None,
+    );
+}
+
+#[derive(Clone, Copy)]
+enum LockAccess {
+    None,
+    Read,
+    Write,
+}
+
+// Some threads that each access the same lock in the given way, and are then joined
+fn concurrent_lock_accesses(threads: usize, access: LockAccess) -> impl Fn() + Send + Sync + 'static {
+    move || {
+        let lock = Arc::new(RwLock::new(0));
+        let threads = (0..threads)
+            .map(|_| {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || match access {
+                    LockAccess::None => {}
+                    LockAccess::Read => assert_eq!(*lock.read().unwrap(), 0),
+                    LockAccess::Write => *lock.write().unwrap() += 1,
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+}
+
+// Read accesses to the same RwLock commute, so concurrent readers add no executions to the ones DPOR
+// already explores for the joins, while the number of orders of the same number of writers grows
+// combinatorially
+#[test]
+fn dpor_concurrent_readers() {
+    let executions = |threads, access| {
+        count_executions(
+            DporScheduler::new(None, false),
+            concurrent_lock_accesses(threads, access),
+        )
+    };
+    for threads in 1..=5 {
+        assert_eq!(
+            executions(threads, LockAccess::Read),
+            executions(threads, LockAccess::None)
+        );
+    }
+    assert_eq!(executions(2, LockAccess::None), 5);
+    assert_eq!(executions(2, LockAccess::Write), 60);
+}
+
+// A writer still races with readers of the same lock, so DPOR must explore the reader seeing the
+// lock both before and after the write
+#[test]
+fn dpor_explores_reader_writer_race() {
+    let observed = Arc::new(std::sync::Mutex::new(HashSet::new()));
+    {
+        let observed = Arc::clone(&observed);
+        check_dpor(
+            move || {
+                let lock = Arc::new(RwLock::new(0));
+                let readers = (0..2)
+                    .map(|_| {
+                        let lock = Arc::clone(&lock);
+                        thread::spawn(move || *lock.read().unwrap())
+                    })
+                    .collect::<Vec<_>>();
+                *lock.write().unwrap() = 1;
+                let values = readers
+                    .into_iter()
+                    .map(|thread| thread.join().unwrap())
+                    .collect::<Vec<_>>();
+                observed.lock().unwrap().insert(values);
+            },
+            None,
+        );
+    }
+    let observed = Arc::try_unwrap(observed).unwrap().into_inner().unwrap();
+    assert_eq!(
+        observed,
+        HashSet::from([vec![0, 0], vec![0, 1], vec![1, 0], vec![1, 1]])
+    );
+}
@@ -33,7 +33,7 @@ fn max_steps_none() {
     config.max_steps = MaxSteps::None;
 
     let scheduler = RandomScheduler::new(10);
-    let runner = Runner::new(scheduler, config);
+    let runner = Runner::new(scheduler).with_config(config);
     runner.run(move || {
         for _ in 0..100 {
             counter.fetch_add(1, Ordering::SeqCst);
@@ -53,7 +53,7 @@ fn max_steps_continue() {
     config.max_steps = MaxSteps::ContinueAfter(50);
 
     let scheduler = RandomScheduler::new(10);
-    let runner = Runner::new(scheduler, config);
+    let runner = Runner::new(scheduler).with_config(config);
     runner.run(move || {
         for _ in 0..100 {
             counter.fetch_add(1, Ordering::SeqCst);
@@ -73,7 +73,7 @@ fn max_steps_fail() {
     config.max_steps = MaxSteps::FailAfter(50);
 
     let scheduler = RandomScheduler::new(10);
-    let runner = Runner::new(scheduler, config);
+    let runner = Runner::new(scheduler).with_config(config);
     let result = catch_unwind(AssertUnwindSafe(move || {
         runner.run(move || {
             for _ in 0..100 {
@@ -148,7 +148,7 @@ fn max_steps_early_exit_scheduler() {
     config.max_steps = MaxSteps::FailAfter(51);
 
     let scheduler = EarlyExitScheduler::new(10, 50);
-    let runner = Runner::new(scheduler, config);
+    let runner = Runner::new(scheduler).with_config(config);
     runner.run(move || {
         for _ in 0..100 {
             counter.fetch_add(1, Ordering::SeqCst);
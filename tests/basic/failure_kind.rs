@@ -0,0 +1,117 @@
+use shuttle::scheduler::{DfsScheduler, RandomScheduler, Scheduler};
+use shuttle::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use shuttle::sync::Mutex;
+use shuttle::{thread, Config, MaxSteps, Runner};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+
+fn failure_message<F, S>(scheduler: S, config: Config, f: F) -> String
+where
+    F: Fn() + Send + Sync + 'static,
+    S: Scheduler + 'static,
+{
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let runner = Runner::new(scheduler).with_config(config);
+        runner.run(f);
+    }))
+    .expect_err("test should fail");
+    *result.downcast::<String>().unwrap()
+}
+
+#[test]
+fn deadlock_is_classified() {
+    let message = failure_message(DfsScheduler::new(None, false), Config::new(), || {
+        let lock = Arc::new(Mutex::new(()));
+        let _guard = lock.lock().unwrap();
+        let lock2 = Arc::clone(&lock);
+        // Joining the thread while holding the lock it needs can never finish
+        thread::spawn(move || drop(lock2.lock().unwrap())).join().unwrap();
+    });
+
+    assert!(message.starts_with("deadlock!"), "{}", message);
+    assert!(message.contains("\nfailure kind: deadlock\nhint: "), "{}", message);
+    assert!(!message.contains("livelock"), "{}", message);
+    assert!(!message.contains("logic bug"), "{}", message);
+}
+
+#[test]
+fn livelock_is_classified() {
+    let mut config = Config::new();
+    config.max_steps = MaxSteps::FailAfter(100);
+
+    let message = failure_message(RandomScheduler::new(10), config, || {
+        let flag = Arc::new(AtomicBool::new(false));
+        let flag2 = Arc::clone(&flag);
+        // Nobody ever sets the flag, so both tasks keep running without making progress
+        thread::spawn(move || {
+            while !flag2.load(Ordering::SeqCst) {
+                thread::yield_now();
+            }
+        });
+        while !flag.load(Ordering::SeqCst) {
+            thread::yield_now();
+        }
+    });
+
+    assert!(message.starts_with("exceeded max_steps bound 100"), "{}", message);
+    assert!(
+        message.contains("runnable: [main-thread (task 0), <unknown> (task 1)]"),
+        "{}",
+        message
+    );
+    assert!(
+        message.contains("\nfailure kind: probable livelock\nhint: "),
+        "{}",
+        message
+    );
+    assert!(!message.contains("logic bug"), "{}", message);
+}
+
+#[test]
+fn logic_bug_is_classified() {
+    let message = failure_message(DfsScheduler::new(None, false), Config::new(), || {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let thds = (0..2)
+            .map(|_| {
+                let counter = Arc::clone(&counter);
+                // Racy increment: both threads can read 0 before either writes
+                thread::spawn(move || {
+                    let value = counter.load(Ordering::SeqCst);
+                    counter.store(value + 1, Ordering::SeqCst);
+                })
+            })
+            .collect::<Vec<_>>();
+        for thd in thds {
+            thd.join().unwrap();
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+    });
+
+    assert!(
+        message.starts_with("test panicked in task 'main-thread'"),
+        "{}",
+        message
+    );
+    assert!(message.contains("\nfailure kind: logic bug\nhint: "), "{}", message);
+    assert!(!message.contains("deadlock"), "{}", message);
+    assert!(!message.contains("livelock"), "{}", message);
+}
+
+#[test]
+fn deadlock_at_step_bound_is_not_livelock() {
+    // This program deadlocks after exactly 4 steps, so the bound has been hit by the time every
+    // task is blocked, but nothing is left running that could be livelocked
+    let mut config = Config::new();
+    config.max_steps = MaxSteps::FailAfter(4);
+
+    let message = failure_message(DfsScheduler::new(Some(1), false), config, || {
+        let lock = Arc::new(Mutex::new(()));
+        let _guard = lock.lock().unwrap();
+        let lock2 = Arc::clone(&lock);
+        thread::spawn(move || drop(lock2.lock().unwrap())).join().unwrap();
+    });
+
+    assert!(message.starts_with("deadlock!"), "{}", message);
+    assert!(message.contains("\nfailure kind: deadlock\nhint: "), "{}", message);
+    assert!(!message.contains("livelock"), "{}", message);
+}
@@ -0,0 +1,79 @@
+use shuttle::scheduler::{FairScheduler, RandomScheduler, Schedule, Scheduler, TaskId};
+use shuttle::sync::atomic::{AtomicBool, Ordering};
+use shuttle::{thread, Config, MaxSteps, Runner};
+use std::panic;
+use std::sync::Arc;
+use test_env_log::test;
+
+/// An adversarial scheduler that always runs the highest-numbered runnable task
+#[derive(Debug, Default)]
+struct HighestTaskScheduler {
+    iterations: usize,
+}
+
+impl Scheduler for HighestTaskScheduler {
+    fn new_execution(&mut self) -> Option<Schedule> {
+        if self.iterations >= 10 {
+            None
+        } else {
+            self.iterations += 1;
+            Some(Schedule::new(0))
+        }
+    }
+
+    fn next_task(&mut self, runnable: &[TaskId], _current: Option<TaskId>, _is_yielding: bool) -> Option<TaskId> {
+        runnable.iter().max().copied()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        unimplemented!()
+    }
+}
+
+// The main thread holds a naive spinlock while it spawns a thread that spins trying to acquire it
+fn spinlock() {
+    let locked = Arc::new(AtomicBool::new(true));
+
+    let thd = {
+        let locked = Arc::clone(&locked);
+        thread::spawn(move || {
+            while locked.swap(true, Ordering::SeqCst) {}
+            locked.store(false, Ordering::SeqCst);
+        })
+    };
+
+    locked.store(false, Ordering::SeqCst);
+    thd.join().unwrap();
+}
+
+fn max_steps(n: usize) -> Config {
+    let mut config = Config::new();
+    config.max_steps = MaxSteps::FailAfter(n);
+    config
+}
+
+#[test]
+fn spinlock_livelocks_under_adversarial_scheduler() {
+    // The adversarial scheduler only ever runs the spinning thread, so the lock is never released
+    let result = panic::catch_unwind(|| {
+        let runner = Runner::new(HighestTaskScheduler::default()).with_config(max_steps(1000));
+        runner.run(spinlock);
+    })
+    .expect_err("spinlock should livelock");
+    assert!(result
+        .downcast::<String>()
+        .unwrap()
+        .contains("exceeded max_steps bound"));
+}
+
+#[test]
+fn spinlock_progresses_under_fair_scheduler() {
+    let runner = Runner::new(FairScheduler::new(HighestTaskScheduler::default(), 10)).with_config(max_steps(1000));
+    assert_eq!(runner.run(spinlock).iterations, 10);
+}
+
+#[test]
+fn fair_scheduler_random() {
+    let runner = Runner::new(FairScheduler::new(RandomScheduler::new(100), 3)).with_config(max_steps(1000));
+    assert_eq!(runner.run(spinlock).iterations, 100);
+}
@@ -0,0 +1,42 @@
+use shuttle::{assert_invariant, check_dfs, thread};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use test_env_log::test;
+
+// Move a unit from one counter to the other, yielding in the middle if `yield_midway` is set. The
+// total is only momentarily wrong, so checking it at the end of the test never notices.
+fn transfer(yield_midway: bool) {
+    let from = Arc::new(AtomicUsize::new(10));
+    let to = Arc::new(AtomicUsize::new(0));
+    {
+        let from = Arc::clone(&from);
+        let to = Arc::clone(&to);
+        assert_invariant(move || from.load(Ordering::SeqCst) + to.load(Ordering::SeqCst) == 10);
+    }
+
+    let thd = {
+        let from = Arc::clone(&from);
+        let to = Arc::clone(&to);
+        thread::spawn(move || {
+            from.fetch_sub(1, Ordering::SeqCst);
+            if yield_midway {
+                thread::yield_now();
+            }
+            to.fetch_add(1, Ordering::SeqCst);
+        })
+    };
+    thd.join().unwrap();
+
+    assert_eq!(from.load(Ordering::SeqCst) + to.load(Ordering::SeqCst), 10);
+}
+
+#[test]
+#[should_panic(expected = "invariant registered at tests/basic/invariant.rs")]
+fn invariant_broken_midway() {
+    check_dfs(|| transfer(true), None);
+}
+
+#[test]
+fn invariant_holds_at_every_yield() {
+    check_dfs(|| transfer(false), None);
+}
@@ -0,0 +1,52 @@
+use shuttle::sync::LazyLock;
+use shuttle::{check_dfs, check_random, thread};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use test_env_log::test;
+
+#[test]
+fn lazy_lock_concurrent_access() {
+    check_dfs(
+        || {
+            let inits = Arc::new(AtomicUsize::new(0));
+            let lazy = {
+                let inits = Arc::clone(&inits);
+                Arc::new(LazyLock::new(move || inits.fetch_add(1, Ordering::SeqCst) + 42))
+            };
+
+            let thds = (0..2)
+                .map(|_| {
+                    let lazy = Arc::clone(&lazy);
+                    thread::spawn(move || **lazy)
+                })
+                .collect::<Vec<_>>();
+
+            for thd in thds {
+                assert_eq!(thd.join().unwrap(), 42);
+            }
+            assert_eq!(*LazyLock::force(&lazy), 42);
+            assert_eq!(inits.load(Ordering::SeqCst), 1);
+        },
+        None,
+    );
+}
+
+#[test]
+fn lazy_lock_static() {
+    static INITS: AtomicUsize = AtomicUsize::new(0);
+    static LAZY: LazyLock<usize> = LazyLock::new(|| INITS.fetch_add(1, Ordering::SeqCst));
+
+    check_random(
+        || {
+            let before = INITS.load(Ordering::SeqCst);
+
+            let thds = (0..3).map(|_| thread::spawn(|| *LAZY)).collect::<Vec<_>>();
+            let values = thds.into_iter().map(|thd| thd.join().unwrap()).collect::<Vec<_>>();
+
+            // The static is initialized exactly once per execution, and every thread sees that value
+            assert_eq!(INITS.load(Ordering::SeqCst), before + 1);
+            assert!(values.iter().all(|value| *value == before));
+        },
+        100,
+    );
+}
@@ -1,8 +1,12 @@
-use shuttle::scheduler::RandomScheduler;
-use shuttle::{check_random, thread, Runner};
+use shuttle::scheduler::{DfsScheduler, RandomScheduler, RoundRobinScheduler};
+use shuttle::sync::{Mutex, RwLock};
+use shuttle::{check_random, thread, Config, ProgressInterval, Runner};
+use std::cell::RefCell;
 use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::rc::Rc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::field::{Field, Visit};
 use tracing::span::{Attributes, Record};
 use tracing::{Event, Id, Metadata, Subscriber};
@@ -111,8 +115,192 @@ fn iterations_without_running() {
     {
         let _guard = tracing::subscriber::set_default(metrics.clone());
         let scheduler = RandomScheduler::new(10);
-        let _runner = Runner::new(scheduler, Default::default());
+        let _runner = Runner::new(scheduler);
     }
 
     assert_eq!(metrics.iterations.load(Ordering::SeqCst), 0);
 }
+
+#[test]
+fn run_stats_dfs() {
+    let scheduler = DfsScheduler::new(None, false);
+    let runner = Runner::new(scheduler);
+    let stats = runner.run(|| {
+        thread::spawn(|| {
+            thread::yield_now();
+        });
+        thread::yield_now();
+    });
+
+    // DFS explores every interleaving of the two threads' steps, and each interleaving takes the
+    // same number of steps
+    assert_eq!(stats.iterations, 6);
+    assert_eq!(stats.max_steps, 5);
+    assert_eq!(stats.steps, 6 * 5);
+    assert!(!stats.hit_time_limit);
+    assert!(stats.exhausted);
+}
+
+#[test]
+fn run_stats_time_limit() {
+    let mut config = Config::new();
+    config.max_time = Some(Duration::from_millis(10));
+    let runner = Runner::new(RandomScheduler::new(usize::MAX)).with_config(config);
+    let stats = runner.run(|| {
+        thread::spawn(|| {
+            thread::yield_now();
+        });
+    });
+
+    assert!(stats.hit_time_limit);
+    assert!(!stats.exhausted);
+    assert!(stats.elapsed >= Duration::from_millis(10));
+    assert!(stats.iterations > 0);
+    assert!(stats.steps >= stats.iterations);
+}
+
+fn spawn_and_yield() {
+    thread::spawn(|| {
+        thread::yield_now();
+    });
+    thread::yield_now();
+}
+
+#[test]
+fn progress_callback_every_n_iterations() {
+    let reports = Rc::new(RefCell::new(Vec::new()));
+    let mut runner = Runner::new(RandomScheduler::new(10));
+    {
+        let reports = Rc::clone(&reports);
+        runner.set_progress_callback(ProgressInterval::Iterations(3), move |stats| {
+            reports.borrow_mut().push(stats.iterations)
+        });
+    }
+    let stats = runner.run(spawn_and_yield);
+
+    assert_eq!(stats.iterations, 10);
+    assert_eq!(*reports.borrow(), vec![3, 6, 9]);
+}
+
+#[test]
+fn progress_callback_time() {
+    let reports = Rc::new(RefCell::new(Vec::new()));
+    let mut runner = Runner::new(RandomScheduler::new(10));
+    {
+        let reports = Rc::clone(&reports);
+        runner.set_progress_callback(ProgressInterval::Time(Duration::from_secs(0)), move |stats| {
+            reports.borrow_mut().push(stats.steps)
+        });
+    }
+    let stats = runner.run(spawn_and_yield);
+
+    // With no delay between reports, the callback runs after every iteration
+    let reports = reports.borrow();
+    assert_eq!(reports.len(), 10);
+    assert_eq!(*reports.last().unwrap(), stats.steps);
+}
+
+fn contention_config() -> Config {
+    let mut config = Config::new();
+    config.record_lock_contention = true;
+    config
+}
+
+// Threads that all try to take a lock while the main thread holds it
+fn contended_mutex() {
+    let lock = Arc::new(Mutex::new(0usize));
+    let guard = lock.lock().unwrap();
+    let threads = (0..3)
+        .map(|_| {
+            let lock = Arc::clone(&lock);
+            thread::spawn(move || *lock.lock().unwrap() += 1)
+        })
+        .collect::<Vec<_>>();
+    for _ in 0..3 {
+        thread::yield_now();
+    }
+    drop(guard);
+    for thd in threads {
+        thd.join().unwrap();
+    }
+}
+
+#[test]
+fn lock_contention_mutex() {
+    let runner = Runner::new(RoundRobinScheduler::new()).with_config(contention_config());
+    let stats = runner.run(contended_mutex);
+
+    assert_eq!(stats.lock_contention.len(), 1);
+    let contention = &stats.lock_contention[0];
+    assert_eq!(contention.acquisitions, 4);
+    assert_eq!(contention.blocked_acquisitions, 3);
+    assert_eq!(contention.max_waiters, 3);
+}
+
+#[test]
+fn lock_contention_rwlock() {
+    let runner = Runner::new(RoundRobinScheduler::new()).with_config(contention_config());
+    let stats = runner.run(|| {
+        let lock = Arc::new(RwLock::new(0usize));
+        let writer = lock.write().unwrap();
+        let readers = (0..2)
+            .map(|_| {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    let _ = *lock.read().unwrap();
+                })
+            })
+            .collect::<Vec<_>>();
+        // Let both readers start waiting while we hold the write lock
+        thread::yield_now();
+        thread::yield_now();
+        drop(writer);
+        for thd in readers {
+            thd.join().unwrap();
+        }
+    });
+
+    assert_eq!(stats.lock_contention.len(), 1);
+    let contention = &stats.lock_contention[0];
+    assert_eq!(contention.acquisitions, 3);
+    assert_eq!(contention.blocked_acquisitions, 2);
+    assert_eq!(contention.max_waiters, 2);
+}
+
+#[test]
+fn lock_contention_uncontended() {
+    let runner = Runner::new(DfsScheduler::new(None, false)).with_config(contention_config());
+    let stats = runner.run(|| {
+        let lock = Mutex::new(0usize);
+        for _ in 0..5 {
+            *lock.lock().unwrap() += 1;
+        }
+    });
+
+    assert_eq!(stats.lock_contention.len(), 1);
+    assert_eq!(stats.lock_contention[0].acquisitions, 5);
+    assert_eq!(stats.lock_contention[0].blocked_acquisitions, 0);
+    assert_eq!(stats.lock_contention[0].max_waiters, 0);
+}
+
+#[test]
+fn lock_contention_named() {
+    let runner = Runner::new(DfsScheduler::new(None, false)).with_config(contention_config());
+    let stats = runner.run(|| {
+        let named = Mutex::new_named("counter", 0usize);
+        let unnamed = Mutex::new(0usize);
+        *named.lock().unwrap() += 1;
+        *unnamed.lock().unwrap() += 1;
+    });
+
+    assert_eq!(stats.lock_contention.len(), 2);
+    assert_eq!(stats.lock_contention[0].name, Some("counter"));
+    assert_eq!(stats.lock_contention[1].name, None);
+}
+
+#[test]
+fn lock_contention_disabled() {
+    let runner = Runner::new(RoundRobinScheduler::new());
+    let stats = runner.run(contended_mutex);
+    assert!(stats.lock_contention.is_empty());
+}
@@ -1,19 +1,38 @@
+mod arc;
 mod atomic;
 mod barrier;
 mod clocks;
 mod condvar;
 mod config;
+mod coverage;
+mod custom_scheduler;
 mod dfs;
+mod dpor;
 mod execution;
+mod failure_kind;
+mod fair;
+mod invariant;
+mod lazy_lock;
 mod metrics;
 mod mpsc;
 mod mutex;
+mod nondet;
+mod notify;
 mod once;
+mod once_cell;
 mod panic;
 mod pct;
 mod portfolio;
+mod random;
+mod reentrant_mutex;
 mod replay;
+mod round_robin;
 mod rwlock;
+mod semaphore;
 mod shrink;
 mod thread;
+mod time;
 mod timeout;
+mod trace_events;
+mod unsafe_cell;
+mod weighted_random;
@@ -1,6 +1,12 @@
 use crate::basic::clocks::{check_clock, me};
-use shuttle::sync::mpsc::{channel, sync_channel, RecvError};
+use shuttle::sync::mpsc::{
+    channel, sync_channel, Receiver, RecvError, RecvTimeoutError, Select, Sender, SyncSender, TryRecvError,
+};
 use shuttle::{check_dfs, check_random, thread};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use test_env_log::test;
 
 // The following tests (prefixed with mpsc_loom) are from the
@@ -128,6 +134,63 @@ fn mpsc_drop_sender_unbounded() {
     );
 }
 
+// Every message from several senders on an unbounded channel is received exactly once, and the
+// receiver sees the channel disconnect once all the senders are gone
+#[test]
+fn mpsc_multiple_senders_exactly_once() {
+    check_dfs(
+        || {
+            let (tx, rx) = channel();
+            for i in 0..2 {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    for j in 0..2 {
+                        tx.send(i * 2 + j).unwrap();
+                    }
+                });
+            }
+            drop(tx);
+
+            let mut received = Vec::new();
+            while let Ok(val) = rx.recv() {
+                received.push(val);
+            }
+            received.sort_unstable();
+            assert_eq!(received, vec![0, 1, 2, 3]);
+            assert_eq!(rx.recv(), Err(RecvError));
+        },
+        None,
+    );
+}
+
+// Receiving is a yield point even if a message is already available
+#[test]
+fn mpsc_recv_is_yield_point() {
+    let observed = Arc::new(Mutex::new(HashSet::new()));
+    let observed_clone = Arc::clone(&observed);
+
+    check_dfs(
+        move || {
+            let (tx, rx) = channel();
+            tx.send(()).unwrap();
+
+            let flag = Arc::new(AtomicBool::new(false));
+            {
+                let flag = Arc::clone(&flag);
+                thread::spawn(move || flag.store(true, Ordering::SeqCst));
+            }
+
+            let before = flag.load(Ordering::SeqCst);
+            rx.recv().unwrap();
+            let after = flag.load(Ordering::SeqCst);
+            observed_clone.lock().unwrap().insert((before, after));
+        },
+        None,
+    );
+
+    assert!(observed.lock().unwrap().contains(&(false, true)));
+}
+
 #[test]
 fn mpsc_drop_receiver_unbounded() {
     check_dfs(
@@ -286,6 +349,40 @@ fn mpsc_bounded_sender_blocked() {
     );
 }
 
+// With a capacity-1 channel, the producer can never get more than one message ahead of the
+// consumer, but it can get exactly one ahead
+#[test]
+fn mpsc_bounded_lockstep() {
+    let observed = Arc::new(Mutex::new(HashSet::new()));
+    let observed_clone = Arc::clone(&observed);
+
+    check_dfs(
+        move || {
+            let (tx, rx) = sync_channel::<usize>(1);
+            let sent = Arc::new(AtomicUsize::new(0));
+            {
+                let sent = Arc::clone(&sent);
+                thread::spawn(move || {
+                    for i in 0..3 {
+                        tx.send(i).unwrap();
+                        sent.fetch_add(1, Ordering::SeqCst);
+                    }
+                });
+            }
+
+            for received in 0..3 {
+                let ahead = sent.load(Ordering::SeqCst) - received;
+                assert!(ahead <= 1, "producer got {} messages ahead", ahead);
+                observed_clone.lock().unwrap().insert(ahead);
+                assert_eq!(rx.recv().unwrap(), received);
+            }
+        },
+        None,
+    );
+
+    assert_eq!(*observed.lock().unwrap(), HashSet::from([0, 1]));
+}
+
 // The following set of tests (prefixed `mpsc_rendezvous_`) check rendezvous channels.
 
 #[test]
@@ -338,6 +435,33 @@ fn mpsc_rendezvous_two_threads() {
     );
 }
 
+// On a rendezvous channel, each send completes only once the matching recv happens, so the producer
+// can never get ahead of the consumer
+#[test]
+fn mpsc_rendezvous_pairing() {
+    check_dfs(
+        || {
+            let (tx, rx) = sync_channel::<usize>(0);
+            let sent = Arc::new(AtomicUsize::new(0));
+            {
+                let sent = Arc::clone(&sent);
+                thread::spawn(move || {
+                    for i in 0..3 {
+                        tx.send(i).unwrap();
+                        sent.fetch_add(1, Ordering::SeqCst);
+                    }
+                });
+            }
+
+            for received in 1..=3 {
+                assert_eq!(rx.recv().unwrap(), received - 1);
+                assert_eq!(sent.load(Ordering::SeqCst), received);
+            }
+        },
+        None,
+    );
+}
+
 // An mpsc Receiver is not clone-able and is !Sync, so it can't be shared, but
 // it is Send, so it can be transferred between threads. In this example, we
 // rendezvous two separate threads with the main thread by passing the receiver
@@ -422,12 +546,7 @@ fn mpsc_recv_from_outside_runtime() {
 }
 
 // From libstd test suite
-// TODO This test checks that joining on a child thread that panicked returns Err, but Shuttle
-// TODO aborts a test as soon as any thread panics (and propagates the panic), so we never get a
-// TODO chance to check the join result. If this abort behavior ever changes, this test should start
-// TODO failing because it no longer propagates the thread panic.
 #[test]
-#[should_panic(expected = "RecvError")]
 fn mpsc_oneshot_single_thread_recv_chan_close() {
     check_dfs(
         || {
@@ -531,15 +650,251 @@ fn test_nested_recv_iter() {
 }
 */
 
-/*
-** TODO: try_recv() not yet supported
 #[test]
 fn mpsc_oneshot_single_thread_peek_close() {
-    check_dfs(|| {
-        let (tx, rx) = channel::<i32>();
-        drop(tx);
-        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
-        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
-    }, None, None);
+    check_dfs(
+        || {
+            let (tx, rx) = channel::<i32>();
+            drop(tx);
+            assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+            assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+        },
+        None,
+    );
+}
+
+#[test]
+fn mpsc_try_recv_empty() {
+    check_dfs(
+        || {
+            let (tx, rx) = channel::<i32>();
+            assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+            tx.send(1).unwrap();
+            assert_eq!(rx.try_recv(), Ok(1));
+            assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        },
+        None,
+    );
+}
+
+// A consumer that polls with `try_recv` drains every message, and then sees the channel disconnect
+// once all the senders are gone. The consumer spins, so we use a random scheduler to avoid
+// exploring schedules where it starves the senders forever.
+fn mpsc_try_recv_polling<S: Clone + Send + 'static>(make_channel: fn() -> (S, Receiver<usize>), send: fn(&S, usize)) {
+    check_random(
+        move || {
+            let (tx, rx) = make_channel();
+            for i in 0..2 {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    for j in 0..2 {
+                        send(&tx, i * 2 + j);
+                    }
+                });
+            }
+            drop(tx);
+
+            let mut received = Vec::new();
+            loop {
+                match rx.try_recv() {
+                    Ok(val) => received.push(val),
+                    Err(TryRecvError::Empty) => thread::yield_now(),
+                    Err(TryRecvError::Disconnected) => break,
+                }
+            }
+            received.sort_unstable();
+            assert_eq!(received, vec![0, 1, 2, 3]);
+        },
+        1000,
+    );
+}
+
+#[test]
+fn mpsc_try_recv_polling_unbounded() {
+    mpsc_try_recv_polling(channel, |tx: &Sender<usize>, i| tx.send(i).unwrap());
+}
+
+#[test]
+fn mpsc_try_recv_polling_bounded() {
+    mpsc_try_recv_polling(|| sync_channel(1), |tx: &SyncSender<usize>, i| tx.send(i).unwrap());
+}
+
+#[test]
+fn mpsc_try_recv_polling_rendezvous() {
+    mpsc_try_recv_polling(|| sync_channel(0), |tx: &SyncSender<usize>, i| tx.send(i).unwrap());
+}
+
+// `recv_timeout` can either receive the message or time out, depending on the schedule
+#[test]
+fn mpsc_recv_timeout() {
+    let observed = Arc::new(Mutex::new(HashSet::new()));
+    let observed_clone = Arc::clone(&observed);
+
+    check_dfs(
+        move || {
+            let (tx, rx) = channel();
+            let thd = thread::spawn(move || match rx.recv_timeout(Duration::from_secs(1)) {
+                Ok(val) => Some(val),
+                Err(e) => {
+                    assert_eq!(e, RecvTimeoutError::Timeout);
+                    // The message is still delivered to a later receive
+                    assert_eq!(rx.recv(), Ok(42));
+                    None
+                }
+            });
+            tx.send(42).unwrap();
+            observed_clone.lock().unwrap().insert(thd.join().unwrap());
+        },
+        None,
+    );
+
+    let observed = observed.lock().unwrap();
+    assert!(observed.contains(&Some(42)));
+    assert!(observed.contains(&None));
+}
+
+#[test]
+fn mpsc_recv_timeout_disconnected() {
+    check_dfs(
+        || {
+            let (tx, rx) = sync_channel::<i32>(0);
+            let thd = thread::spawn(move || rx.recv_timeout(Duration::from_secs(1)));
+            drop(tx);
+            let result = thd.join().unwrap();
+            assert!(matches!(
+                result,
+                Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected)
+            ));
+        },
+        None,
+    );
+}
+
+// On a rendezvous channel, a sender only hands over its message to a receiver that's waiting for it,
+// so a receiver that times out never loses a message. The receiver spins, so it runs in a spawned
+// thread, otherwise DFS would first explore schedules where it starves the sender.
+#[test]
+fn mpsc_recv_timeout_rendezvous() {
+    check_dfs(
+        || {
+            let (tx, rx) = sync_channel::<i32>(0);
+            let thd = thread::spawn(move || loop {
+                match rx.recv_timeout(Duration::from_secs(1)) {
+                    Ok(val) => {
+                        assert_eq!(val, 42);
+                        break;
+                    }
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => panic!("sender can't disconnect before sending"),
+                }
+            });
+            tx.send(42).unwrap();
+            thd.join().unwrap();
+        },
+        Some(1000),
+    );
+}
+
+// Two producers each feed their own channel, and a consumer selects over both channels until they
+// disconnect. The consumer can't assume selection is fair, but it should still drain every message.
+fn mpsc_select_two_producers<S: Send + 'static>(make_channel: fn() -> (S, Receiver<usize>), send: fn(&S, usize)) {
+    check_dfs(
+        move || {
+            let mut receivers = Vec::new();
+            for i in 0..2 {
+                let (tx, rx) = make_channel();
+                receivers.push(rx);
+                thread::spawn(move || {
+                    for j in 0..2 {
+                        send(&tx, i * 2 + j);
+                    }
+                });
+            }
+
+            let mut connected = vec![true; receivers.len()];
+            let mut received = Vec::new();
+            while connected.iter().any(|c| *c) {
+                let live = (0..receivers.len()).filter(|&i| connected[i]).collect::<Vec<_>>();
+                let mut select = Select::new();
+                for &i in live.iter() {
+                    select.recv(&receivers[i]);
+                }
+                let i = live[select.ready()];
+                match receivers[i].try_recv() {
+                    Ok(val) => received.push(val),
+                    Err(TryRecvError::Disconnected) => connected[i] = false,
+                    Err(TryRecvError::Empty) => panic!("a ready receiver should not be empty"),
+                }
+            }
+            received.sort_unstable();
+            assert_eq!(received, vec![0, 1, 2, 3]);
+        },
+        None,
+    );
+}
+
+#[test]
+fn mpsc_select_two_producers_unbounded() {
+    mpsc_select_two_producers(channel, |tx: &Sender<usize>, i| tx.send(i).unwrap());
+}
+
+#[test]
+fn mpsc_select_two_producers_rendezvous() {
+    mpsc_select_two_producers(|| sync_channel(0), |tx: &SyncSender<usize>, i| tx.send(i).unwrap());
+}
+
+// When several receivers are ready, the scheduler can choose any of them
+#[test]
+fn mpsc_select_chooses_any_ready() {
+    let observed = Arc::new(Mutex::new(HashSet::new()));
+    let observed_clone = Arc::clone(&observed);
+
+    check_dfs(
+        move || {
+            let (tx1, rx1) = channel();
+            let (tx2, rx2) = channel();
+            tx1.send(1).unwrap();
+            tx2.send(2).unwrap();
+
+            let mut select = Select::new();
+            let index1 = select.recv(&rx1);
+            let index2 = select.recv(&rx2);
+            let index = select.ready();
+            let val = if index == index1 {
+                rx1.recv().unwrap()
+            } else {
+                assert_eq!(index, index2);
+                rx2.recv().unwrap()
+            };
+            observed_clone.lock().unwrap().insert(val);
+        },
+        None,
+    );
+
+    let observed = observed.lock().unwrap();
+    assert!(observed.contains(&1));
+    assert!(observed.contains(&2));
+}
+
+// A selector that's waiting on a channel is woken when the channel's last sender disconnects
+#[test]
+fn mpsc_select_disconnected() {
+    check_dfs(
+        || {
+            let (tx1, rx1) = channel::<i32>();
+            let (tx2, rx2) = channel::<i32>();
+            let thd = thread::spawn(move || {
+                drop(tx2);
+                tx1
+            });
+
+            let mut select = Select::new();
+            select.recv(&rx1);
+            let index = select.recv(&rx2);
+            assert_eq!(select.ready(), index);
+            assert_eq!(rx2.recv(), Err(RecvError));
+            drop(thd.join().unwrap());
+        },
+        None,
+    );
 }
-*/
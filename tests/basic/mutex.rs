@@ -1,7 +1,8 @@
-use shuttle::scheduler::PctScheduler;
-use shuttle::sync::Mutex;
-use shuttle::{check, check_random, thread, Runner};
-use std::sync::Arc;
+use shuttle::scheduler::{DfsScheduler, PctScheduler};
+use shuttle::sync::{Mutex, MutexGuard};
+use shuttle::{check, check_dfs, check_random, thread, Config, Runner};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, TryLockError};
 use test_env_log::test;
 
 #[test]
@@ -54,12 +55,82 @@ fn deadlock_random() {
     check_random(deadlock, 200);
 }
 
+#[test]
+#[should_panic(
+    expected = "lock cycle: main-thread (task 0) holds L1 waiting L0; <unknown> (task 1) holds L0 waiting L1"
+)]
+fn deadlock_reports_lock_cycle() {
+    check_dfs(deadlock, None);
+}
+
+#[test]
+#[should_panic(expected = "leaked lock guard! finished tasks still hold locks: <unknown> (task 1) holds L0")]
+fn mutex_leaked_guard() {
+    check_dfs(
+        || {
+            let lock = Arc::new(Mutex::new(0));
+            thread::spawn(move || {
+                // Forgetting the guard means the lock is never released
+                std::mem::forget(lock.lock().unwrap());
+            })
+            .join()
+            .unwrap();
+        },
+        None,
+    );
+}
+
+#[inline(never)]
+fn lock_first_then_second(first: &Mutex<usize>, second: &Mutex<usize>) {
+    let _first = first.lock().unwrap();
+    let _second = second.lock().unwrap();
+}
+
+#[inline(never)]
+fn lock_second_then_first(first: &Mutex<usize>, second: &Mutex<usize>) {
+    let _second = second.lock().unwrap();
+    let _first = first.lock().unwrap();
+}
+
+#[test]
+fn deadlock_reports_backtraces() {
+    let mut config = Config::new();
+    config.capture_backtraces = true;
+
+    let result = std::panic::catch_unwind(|| {
+        let runner = Runner::new(DfsScheduler::new(None, false)).with_config(config);
+        runner.run(|| {
+            let first = Arc::new(Mutex::new(0usize));
+            let second = Arc::new(Mutex::new(0usize));
+            {
+                let first = Arc::clone(&first);
+                let second = Arc::clone(&second);
+                thread::spawn(move || lock_second_then_first(&first, &second));
+            }
+            lock_first_then_second(&first, &second);
+        });
+    })
+    .expect_err("should deadlock");
+    let message = result.downcast::<String>().unwrap();
+
+    // Each blocked task's report shows the function it's blocked in
+    assert!(message.contains("deadlock"), "{}", message);
+    let main = message
+        .find("main-thread (task 0) is blocked at:")
+        .expect("main task report");
+    let other = message
+        .find("<unknown> (task 1) is blocked at:")
+        .expect("other task report");
+    assert!(message[main..other].contains("lock_first_then_second"), "{}", message);
+    assert!(message[other..].contains("lock_second_then_first"), "{}", message);
+}
+
 #[test]
 #[should_panic(expected = "deadlock")]
 fn deadlock_pct() {
     // 100 tries should be enough to find a deadlocking execution
     let scheduler = PctScheduler::new(2, 100);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(deadlock);
 }
 
@@ -67,7 +138,7 @@ fn deadlock_pct() {
 #[should_panic(expected = "racing increments")]
 fn concurrent_increment_buggy() {
     let scheduler = PctScheduler::new(2, 100);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(|| {
         let lock = Arc::new(Mutex::new(0usize));
 
@@ -93,7 +164,7 @@ fn concurrent_increment_buggy() {
 #[test]
 fn concurrent_increment() {
     let scheduler = PctScheduler::new(2, 100);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(|| {
         let lock = Arc::new(Mutex::new(0usize));
 
@@ -150,3 +221,240 @@ fn mutex_into_inner() {
         None,
     )
 }
+
+#[test]
+fn mutex_get_mut() {
+    check_dfs(
+        || {
+            let mut lock = Mutex::new(0u64);
+            *lock.get_mut().unwrap() += 5;
+            assert_eq!(*lock.lock().unwrap(), 5);
+        },
+        None,
+    )
+}
+
+#[test]
+fn mutex_try_lock_mutual_exclusion() {
+    check_dfs(
+        || {
+            let lock = Arc::new(Mutex::new(0usize));
+            let in_critical = Arc::new(AtomicBool::new(false));
+
+            let threads = (0..2)
+                .map(|_| {
+                    let lock = Arc::clone(&lock);
+                    let in_critical = Arc::clone(&in_critical);
+                    thread::spawn(move || {
+                        for _ in 0..2 {
+                            if let Ok(mut guard) = lock.try_lock() {
+                                assert!(!in_critical.swap(true, Ordering::SeqCst));
+                                *guard += 1;
+                                thread::yield_now();
+                                in_critical.store(false, Ordering::SeqCst);
+                            }
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for thd in threads {
+                thd.join().unwrap();
+            }
+
+            assert!(*lock.lock().unwrap() >= 1);
+        },
+        None,
+    )
+}
+
+#[test]
+fn mutex_try_lock_contended() {
+    check_dfs(
+        || {
+            let lock = Arc::new(Mutex::new(0usize));
+
+            let thd = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    *lock.lock().unwrap() += 1;
+                })
+            };
+
+            match lock.try_lock() {
+                Ok(mut guard) => *guard += 1,
+                // A failed attempt leaves the lock usable by us and the other thread
+                Err(TryLockError::WouldBlock) => *lock.lock().unwrap() += 1,
+                Err(TryLockError::Poisoned(_)) => panic!("lock should not be poisoned"),
+            }
+
+            thd.join().unwrap();
+            assert_eq!(*lock.lock().unwrap(), 2);
+        },
+        None,
+    )
+}
+
+// Like `std`, trying to lock a mutex the current thread already holds fails instead of deadlocking
+#[test]
+fn mutex_try_lock_held_by_self() {
+    check_dfs(
+        || {
+            let lock = Mutex::new(0usize);
+            let mut guard = lock.lock().unwrap();
+            assert!(matches!(lock.try_lock(), Err(TryLockError::WouldBlock)));
+            *guard += 1;
+            drop(guard);
+            assert_eq!(*lock.try_lock().unwrap(), 1);
+        },
+        None,
+    )
+}
+
+#[test]
+fn mutex_map() {
+    check_dfs(
+        || {
+            let lock = Arc::new(Mutex::new((0usize, 0usize)));
+
+            let thd = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    let mut second = MutexGuard::map(lock.lock().unwrap(), |pair| &mut pair.1);
+                    *second += 1;
+                })
+            };
+
+            {
+                let mut first = MutexGuard::map(lock.lock().unwrap(), |pair| &mut pair.0);
+                *first += 1;
+            }
+
+            thd.join().unwrap();
+            assert_eq!(*lock.lock().unwrap(), (1, 1));
+        },
+        None,
+    )
+}
+
+#[test]
+fn mutex_mapped_guard_releases_on_drop() {
+    check_dfs(
+        || {
+            let lock = Arc::new(Mutex::new((0usize, 0usize)));
+
+            let thd = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    // We can only take the lock before the main thread does, or after its mapped
+                    // guard has been dropped, so we never see a partial update
+                    let value = lock.lock().unwrap().0;
+                    assert!(value == 0 || value == 2, "saw partial update {}", value);
+                })
+            };
+
+            let mut first = MutexGuard::map(lock.lock().unwrap(), |pair| &mut pair.0);
+            *first += 1;
+            thread::yield_now();
+            *first += 1;
+            drop(first);
+
+            thd.join().unwrap();
+            // The lock was released when the mapped guard was dropped
+            assert_eq!(lock.try_lock().unwrap().0, 2);
+        },
+        None,
+    )
+}
+
+// Count how many times the main thread reacquires the lock while the other thread is waiting for it
+fn relock_while_waiting(lock: Mutex<()>) -> usize {
+    let lock = Arc::new(lock);
+    let waiting = Arc::new(AtomicBool::new(false));
+    let acquired = Arc::new(AtomicBool::new(false));
+
+    let thd = {
+        let lock = Arc::clone(&lock);
+        let waiting = Arc::clone(&waiting);
+        let acquired = Arc::clone(&acquired);
+        thread::spawn(move || {
+            // No yield point between these, so once `waiting` is set we are in the lock's waiters
+            waiting.store(true, Ordering::SeqCst);
+            let _guard = lock.lock().unwrap();
+            acquired.store(true, Ordering::SeqCst);
+        })
+    };
+
+    let mut relocks = 0;
+    for _ in 0..3 {
+        let _guard = lock.lock().unwrap();
+        if waiting.load(Ordering::SeqCst) && !acquired.load(Ordering::SeqCst) {
+            relocks += 1;
+        }
+    }
+
+    thd.join().unwrap();
+    relocks
+}
+
+#[test]
+fn mutex_fifo_no_starvation() {
+    check_dfs(
+        || {
+            // The waiting thread is handed the lock as soon as the first guard we hold while it's
+            // waiting is released, so we can never reacquire the lock ahead of it
+            let relocks = relock_while_waiting(Mutex::new_fifo(()));
+            assert!(
+                relocks <= 1,
+                "relocked {} times while the other thread was waiting",
+                relocks
+            );
+        },
+        None,
+    )
+}
+
+#[test]
+fn mutex_unfair_starvation() {
+    let saw_starvation = Arc::new(AtomicBool::new(false));
+
+    {
+        let saw_starvation = Arc::clone(&saw_starvation);
+        check_dfs(
+            move || {
+                if relock_while_waiting(Mutex::new(())) > 1 {
+                    saw_starvation.store(true, Ordering::SeqCst);
+                }
+            },
+            None,
+        );
+    }
+
+    assert!(saw_starvation.load(Ordering::SeqCst));
+}
+
+#[test]
+fn mutex_fifo_try_lock() {
+    check_dfs(
+        || {
+            let lock = Arc::new(Mutex::new_fifo(0usize));
+
+            let thd = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    *lock.lock().unwrap() += 1;
+                })
+            };
+
+            match lock.try_lock() {
+                Ok(mut guard) => *guard += 1,
+                Err(TryLockError::WouldBlock) => *lock.lock().unwrap() += 1,
+                Err(TryLockError::Poisoned(_)) => panic!("lock should not be poisoned"),
+            }
+
+            thd.join().unwrap();
+            assert_eq!(*lock.lock().unwrap(), 2);
+        },
+        None,
+    )
+}
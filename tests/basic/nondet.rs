@@ -0,0 +1,163 @@
+use crate::check_replay_roundtrip;
+use shuttle::scheduler::{FairScheduler, PctScheduler, RandomScheduler, Scheduler, WeightedRandomScheduler};
+use shuttle::sync::mpsc::channel;
+use shuttle::{check_dfs, check_random, nondet, thread, Runner};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use test_env_log::test;
+
+// Run `f` under DFS and collect the value it returns from each execution
+fn dfs_outcomes<T, F>(f: F) -> (usize, HashSet<T>)
+where
+    T: Eq + std::hash::Hash + std::fmt::Debug + Send + 'static,
+    F: Fn() -> T + Send + Sync + 'static,
+{
+    let outcomes = Arc::new(Mutex::new((0, HashSet::new())));
+    {
+        let outcomes = Arc::clone(&outcomes);
+        check_dfs(
+            move || {
+                let value = f();
+                let mut outcomes = outcomes.lock().unwrap();
+                outcomes.0 += 1;
+                outcomes.1.insert(value);
+            },
+            None,
+        );
+    }
+    Arc::try_unwrap(outcomes).unwrap().into_inner().unwrap()
+}
+
+#[test]
+fn nondet_choose_dfs_covers_all_values() {
+    let (executions, values) = dfs_outcomes(|| *nondet::choose(&["a", "b", "c"]));
+    assert_eq!(executions, 3);
+    assert_eq!(values, ["a", "b", "c"].iter().copied().collect());
+}
+
+#[test]
+fn nondet_bool_dfs_covers_both() {
+    let (executions, values) = dfs_outcomes(|| (nondet::bool(), nondet::bool()));
+    assert_eq!(executions, 4);
+    assert_eq!(values.len(), 4);
+}
+
+#[test]
+fn nondet_single_value() {
+    let (executions, values) = dfs_outcomes(|| *nondet::choose(&[7]));
+    assert_eq!(executions, 1);
+    assert_eq!(values, [7].iter().copied().collect());
+}
+
+#[test]
+#[should_panic(expected = "at least one value")]
+fn nondet_choose_empty() {
+    check_dfs(
+        || {
+            nondet::choose::<u32>(&[]);
+        },
+        None,
+    );
+}
+
+// The harness chooses which order to send two messages in, and DFS explores both orders alongside
+// the interleavings of the two threads
+#[test]
+fn nondet_choose_send_order() {
+    let (_, orders) = dfs_outcomes(|| {
+        let (tx, rx) = channel();
+        let send_a_first = nondet::bool();
+        let thd = thread::spawn(move || {
+            if send_a_first {
+                tx.send("a").unwrap();
+                tx.send("b").unwrap();
+            } else {
+                tx.send("b").unwrap();
+                tx.send("a").unwrap();
+            }
+        });
+        let order = (rx.recv().unwrap(), rx.recv().unwrap());
+        thd.join().unwrap();
+        order
+    });
+    assert_eq!(orders, [("a", "b"), ("b", "a")].iter().copied().collect());
+}
+
+#[test]
+fn nondet_choose_random() {
+    let values = Arc::new(Mutex::new(HashSet::new()));
+    {
+        let values = Arc::clone(&values);
+        check_random(
+            move || {
+                values.lock().unwrap().insert(*nondet::choose(&[1, 2, 3]));
+            },
+            100,
+        );
+    }
+    assert_eq!(*values.lock().unwrap(), [1, 2, 3].iter().copied().collect());
+}
+
+// Run a `choose` over three values under `scheduler` and collect the values it chose
+fn choose_outcomes<S: Scheduler + 'static>(scheduler: S) -> HashSet<u32> {
+    let values = Arc::new(Mutex::new(HashSet::new()));
+    {
+        let values = Arc::clone(&values);
+        let runner = Runner::new(scheduler);
+        runner.run(move || {
+            values.lock().unwrap().insert(*nondet::choose(&[1, 2, 3]));
+        });
+    }
+    Arc::try_unwrap(values).unwrap().into_inner().unwrap()
+}
+
+#[test]
+fn nondet_choose_pct() {
+    assert_eq!(
+        choose_outcomes(PctScheduler::new(3, 100)),
+        [1, 2, 3].iter().copied().collect()
+    );
+}
+
+// Task weights don't apply to choices, so an option whose index is the id of a task with weight
+// zero is still chosen
+#[test]
+fn nondet_choose_weighted_random() {
+    let scheduler = WeightedRandomScheduler::new(100, |tid| if usize::from(tid) == 0 { 0 } else { 1 });
+    assert_eq!(choose_outcomes(scheduler), [1, 2, 3].iter().copied().collect());
+
+    let values = Arc::new(Mutex::new(HashSet::new()));
+    {
+        let values = Arc::clone(&values);
+        let scheduler = WeightedRandomScheduler::new(100, |tid| if usize::from(tid) == 0 { 0 } else { 1 });
+        let runner = Runner::new(scheduler);
+        runner.run(move || {
+            values.lock().unwrap().insert(nondet::bool());
+        });
+    }
+    assert_eq!(*values.lock().unwrap(), [false, true].iter().copied().collect());
+}
+
+#[test]
+fn nondet_choose_fair() {
+    assert_eq!(
+        choose_outcomes(FairScheduler::new(RandomScheduler::new(100), 2)),
+        [1, 2, 3].iter().copied().collect()
+    );
+}
+
+fn fails_on_choice() {
+    let x = *nondet::choose(&[0, 1, 2, 3]);
+    let y = *nondet::choose(&[0, 1, 2, 3]);
+    assert!(x + y < 5, "x + y = {} is too large", x + y);
+}
+
+#[test]
+fn nondet_replay_random() {
+    check_replay_roundtrip(fails_on_choice, RandomScheduler::new(1000));
+}
+
+#[test]
+fn nondet_replay_pct() {
+    check_replay_roundtrip(fails_on_choice, PctScheduler::new(2, 1000));
+}
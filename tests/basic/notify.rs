@@ -0,0 +1,76 @@
+use shuttle::sync::Notify;
+use shuttle::{check_dfs, future};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use test_env_log::test;
+
+// The waiter checks for work and only then waits for a notification. If the producer's notify_one
+// lands between the check and the wait, the stored permit means the notification isn't lost.
+#[test]
+fn notify_between_check_and_wait() {
+    check_dfs(
+        || {
+            let notify = Arc::new(Notify::new());
+            let ready = Arc::new(AtomicBool::new(false));
+
+            let consumer = {
+                let notify = Arc::clone(&notify);
+                let ready = Arc::clone(&ready);
+                future::spawn(async move {
+                    while !ready.load(Ordering::SeqCst) {
+                        notify.notified().await;
+                    }
+                })
+            };
+
+            ready.store(true, Ordering::SeqCst);
+            notify.notify_one();
+            future::block_on(consumer).unwrap();
+        },
+        None,
+    );
+}
+
+// Two consumers take items from a shared queue, waiting on the same Notify whenever it's empty. The
+// producer notifies once per item, but notifications sent while no consumer is waiting coalesce
+// into a single permit, so a consumer that takes an item passes the notification on if there are
+// more items left. Every consumer gets an item, however the waits and notifications interleave.
+#[test]
+fn notify_one_concurrent_waiters() {
+    check_dfs(
+        || {
+            let notify = Arc::new(Notify::new());
+            let items = Arc::new(AtomicUsize::new(0));
+
+            let consumers = (0..2)
+                .map(|_| {
+                    let notify = Arc::clone(&notify);
+                    let items = Arc::clone(&items);
+                    future::spawn(async move {
+                        while items
+                            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                            .is_err()
+                        {
+                            notify.notified().await;
+                        }
+                        if items.load(Ordering::SeqCst) > 0 {
+                            notify.notify_one();
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for _ in 0..2 {
+                items.fetch_add(1, Ordering::SeqCst);
+                notify.notify_one();
+            }
+            future::block_on(async move {
+                for consumer in consumers {
+                    consumer.await.unwrap();
+                }
+            });
+            assert_eq!(items.load(Ordering::SeqCst), 0);
+        },
+        None,
+    );
+}
@@ -3,7 +3,7 @@ use shuttle::sync::Once;
 use shuttle::{check_dfs, check_pct, thread, Runner};
 use std::collections::HashSet;
 use std::panic;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use test_env_log::test;
 
@@ -185,7 +185,7 @@ fn shared_static() {
                 let counter = Arc::clone(&counter);
                 std::thread::spawn(move || {
                     let scheduler = DfsScheduler::new(None, false);
-                    let runner = Runner::new(scheduler, Default::default());
+                    let runner = Runner::new(scheduler);
                     runner.run(move || {
                         let thds = (0..2)
                             .map(|_| {
@@ -206,7 +206,10 @@ fn shared_static() {
             })
             .collect::<Vec<_>>();
 
-        total_executions += threads.into_iter().map(|handle| handle.join().unwrap()).sum::<usize>();
+        total_executions += threads
+            .into_iter()
+            .map(|handle| handle.join().unwrap().iterations)
+            .sum::<usize>();
     }
 
     // The Once cell should be initialized exactly once per test execution, otherwise the tests are
@@ -249,3 +252,58 @@ fn poison() {
         None,
     );
 }
+
+#[test]
+fn basic_dfs_three_threads() {
+    basic(3, |f| check_dfs(f, None));
+}
+
+// One thread's initializer panics while another thread races to initialize the cell with
+// `call_once_force`. The second thread only sees a poisoned cell if the first thread's initializer
+// ran first, and either way the cell ends up initialized.
+#[test]
+fn poison_recovered_by_other_thread() {
+    static O: Once = Once::new();
+
+    let saw_poisoned = Arc::new(AtomicUsize::new(0));
+    let saw_clean = Arc::new(AtomicUsize::new(0));
+
+    {
+        let saw_poisoned = Arc::clone(&saw_poisoned);
+        let saw_clean = Arc::clone(&saw_clean);
+
+        check_dfs(
+            move || {
+                let panicked = Arc::new(AtomicBool::new(false));
+
+                let thd = {
+                    let panicked = Arc::clone(&panicked);
+                    thread::spawn(move || {
+                        O.call_once(|| {
+                            panicked.store(true, Ordering::SeqCst);
+                            panic!("expected panic");
+                        })
+                    })
+                };
+
+                O.call_once_force(|state| {
+                    assert_eq!(state.is_poisoned(), panicked.load(Ordering::SeqCst));
+                    if state.is_poisoned() {
+                        saw_poisoned.fetch_add(1, Ordering::SeqCst);
+                    } else {
+                        saw_clean.fetch_add(1, Ordering::SeqCst);
+                    }
+                });
+                assert!(O.is_completed());
+
+                // The spawned thread's initializer either panicked, or never ran because the cell
+                // was already initialized
+                assert_eq!(thd.join().is_err(), panicked.load(Ordering::SeqCst));
+            },
+            None,
+        );
+    }
+
+    assert!(saw_poisoned.load(Ordering::SeqCst) > 0);
+    assert!(saw_clean.load(Ordering::SeqCst) > 0);
+}
@@ -0,0 +1,91 @@
+use shuttle::sync::OnceCell;
+use shuttle::{check_dfs, thread};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use test_env_log::test;
+
+#[test]
+fn once_cell_get_or_init_race() {
+    let winners = Arc::new([AtomicBool::new(false), AtomicBool::new(false)]);
+
+    {
+        let winners = Arc::clone(&winners);
+
+        check_dfs(
+            move || {
+                let cell = Arc::new(OnceCell::new());
+                let inits = Arc::new(AtomicUsize::new(0));
+
+                let thd = {
+                    let cell = Arc::clone(&cell);
+                    let inits = Arc::clone(&inits);
+                    thread::spawn(move || {
+                        *cell.get_or_init(|| {
+                            inits.fetch_add(1, Ordering::SeqCst);
+                            1usize
+                        })
+                    })
+                };
+
+                let value = *cell.get_or_init(|| {
+                    inits.fetch_add(1, Ordering::SeqCst);
+                    0usize
+                });
+                let other_value = thd.join().unwrap();
+
+                // Exactly one initializer ran, and both threads saw its value
+                assert_eq!(inits.load(Ordering::SeqCst), 1);
+                assert_eq!(value, other_value);
+                assert!(std::ptr::eq(cell.get().unwrap(), cell.get_or_init(|| unreachable!())));
+                winners[value].store(true, Ordering::SeqCst);
+            },
+            None,
+        );
+    }
+
+    // Either thread can win the race
+    assert!(winners.iter().all(|winner| winner.load(Ordering::SeqCst)));
+}
+
+#[test]
+fn once_cell_set() {
+    check_dfs(
+        || {
+            let cell = Arc::new(OnceCell::new());
+            assert_eq!(cell.get(), None);
+
+            let thd = {
+                let cell = Arc::clone(&cell);
+                thread::spawn(move || cell.set(1usize).is_ok())
+            };
+
+            let won = cell.set(0usize).is_ok();
+            let other_won = thd.join().unwrap();
+            assert!(won != other_won);
+
+            // Once the cell is full, further sets fail and don't change the value
+            let value = *cell.get().unwrap();
+            assert_eq!(value, if won { 0 } else { 1 });
+            assert_eq!(cell.set(2), Err(2));
+            assert_eq!(*cell.get_or_init(|| 3), value);
+        },
+        None,
+    );
+}
+
+#[test]
+fn once_cell_static() {
+    static CELL: OnceCell<usize> = OnceCell::new();
+
+    check_dfs(
+        || {
+            // Each execution starts with an empty cell, even though the static outlives it
+            assert_eq!(CELL.get(), None);
+
+            let thd = thread::spawn(|| *CELL.get_or_init(|| 1));
+            let value = *CELL.get_or_init(|| 0);
+            assert_eq!(value, thd.join().unwrap());
+        },
+        None,
+    );
+}
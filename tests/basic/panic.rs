@@ -1,9 +1,9 @@
 use shuttle::scheduler::DfsScheduler;
-use shuttle::sync::{Mutex, RwLock};
+use shuttle::sync::{Mutex, ReentrantMutex, RwLock, RwLockWriteGuard};
 use shuttle::{check_dfs, thread, Config, Runner};
 use std::collections::VecDeque;
 use std::panic::{catch_unwind, AssertUnwindSafe};
-use std::sync::{Arc, PoisonError};
+use std::sync::{Arc, PoisonError, TryLockError};
 use test_env_log::test;
 
 #[test]
@@ -32,6 +32,138 @@ fn mutex_poison() {
     )
 }
 
+// Spawn a thread that panics while holding the lock, poisoning it
+fn poison_mutex<T: Send + 'static>(lock: &Arc<Mutex<T>>, value: T) {
+    let thd = {
+        let lock = Arc::clone(lock);
+        thread::spawn(move || {
+            let _err = catch_unwind(AssertUnwindSafe(move || {
+                let mut guard = lock.lock().unwrap();
+                *guard = value;
+                panic!("expected panic");
+            }))
+            .unwrap_err();
+        })
+    };
+    thd.join().unwrap();
+}
+
+#[test]
+fn mutex_poison_clear() {
+    check_dfs(
+        || {
+            let lock = Arc::new(Mutex::new(0usize));
+            assert!(!lock.is_poisoned());
+            poison_mutex(&lock, 1);
+
+            assert!(lock.is_poisoned());
+            // The lock is still acquired even though it's poisoned
+            let result = lock.lock();
+            assert_eq!(*result.unwrap_err().into_inner(), 1);
+            assert!(matches!(lock.try_lock(), Err(TryLockError::Poisoned(_))));
+
+            lock.clear_poison();
+            assert!(!lock.is_poisoned());
+            *lock.lock().unwrap() = 2;
+            assert_eq!(*lock.try_lock().unwrap(), 2);
+        },
+        None,
+    )
+}
+
+#[test]
+fn mutex_poison_into_inner() {
+    check_dfs(
+        || {
+            let lock = Arc::new(Mutex::new(0usize));
+            poison_mutex(&lock, 1);
+
+            let lock = Arc::try_unwrap(lock).unwrap();
+            let result = lock.into_inner();
+            assert!(matches!(result, Err(PoisonError { .. })));
+            // The error still contains the data
+            assert_eq!(result.unwrap_err().into_inner(), 1);
+        },
+        None,
+    )
+}
+
+#[test]
+fn mutex_poison_get_mut() {
+    check_dfs(
+        || {
+            let lock = Arc::new(Mutex::new(0usize));
+            poison_mutex(&lock, 1);
+
+            let mut lock = Arc::try_unwrap(lock).unwrap();
+            assert_eq!(*lock.get_mut().unwrap_err().into_inner(), 1);
+            lock.clear_poison();
+            *lock.get_mut().unwrap() = 2;
+            assert_eq!(lock.into_inner().unwrap(), 2);
+        },
+        None,
+    )
+}
+
+// A thread that is blocked waiting for the lock when its holder panics wakes up to find the lock
+// poisoned
+#[test]
+fn mutex_poison_wakes_waiter() {
+    check_dfs(
+        || {
+            let lock = Arc::new(Mutex::new(0usize));
+
+            let thd = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    let mut guard = lock.lock().unwrap();
+                    *guard = 1;
+                    panic!("expected panic");
+                })
+            };
+
+            match lock.lock() {
+                Ok(guard) => assert_eq!(*guard, 0),
+                Err(err) => assert_eq!(*err.into_inner(), 1),
+            }
+            assert!(thd.join().is_err());
+        },
+        None,
+    )
+}
+
+// A thread that panics while holding a reentrant mutex poisons it, and wakes up any thread waiting
+// for it without yielding while unwinding
+#[test]
+fn reentrant_mutex_poison_wakes_waiter() {
+    check_dfs(
+        || {
+            let lock = Arc::new(ReentrantMutex::new(std::cell::Cell::new(0usize)));
+
+            let thd = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    let outer = lock.lock();
+                    let inner = lock.lock();
+                    inner.set(1);
+                    drop(outer);
+                    panic!("expected panic");
+                })
+            };
+
+            let guard = lock.lock();
+            assert_eq!(lock.is_poisoned(), guard.get() == 1);
+            drop(guard);
+            assert!(thd.join().is_err());
+            assert!(lock.is_poisoned());
+
+            lock.clear_poison();
+            assert!(!lock.is_poisoned());
+        },
+        None,
+    )
+}
+
 #[test]
 fn rwlock_poison() {
     check_dfs(
@@ -58,6 +190,90 @@ fn rwlock_poison() {
     )
 }
 
+// A thread that is blocked waiting for the lock when a writer panics wakes up to find the lock
+// poisoned
+#[test]
+fn rwlock_poison_wakes_waiter() {
+    check_dfs(
+        || {
+            let lock = Arc::new(RwLock::new(0usize));
+
+            let thd = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    let mut guard = lock.write().unwrap();
+                    *guard = 1;
+                    panic!("expected panic");
+                })
+            };
+
+            match lock.read() {
+                Ok(guard) => assert_eq!(*guard, 0),
+                Err(err) => assert_eq!(*err.into_inner(), 1),
+            }
+            assert!(thd.join().is_err());
+        },
+        None,
+    )
+}
+
+// The lock's Shuttle state, including whether it's poisoned, moves with it
+#[test]
+fn rwlock_poison_survives_move() {
+    check_dfs(
+        || {
+            let lock = RwLock::new(0usize);
+            let _err = catch_unwind(AssertUnwindSafe(|| {
+                let _guard = lock.write().unwrap();
+                panic!("expected panic");
+            }))
+            .unwrap_err();
+            assert!(lock.is_poisoned());
+
+            let lock = Box::new(lock);
+            assert!(lock.is_poisoned());
+            assert!(matches!(lock.read(), Err(PoisonError { .. })));
+        },
+        None,
+    )
+}
+
+#[test]
+fn rwlock_poison_clear() {
+    check_dfs(
+        || {
+            let lock = Arc::new(RwLock::new(0usize));
+
+            let thd = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    let _err = catch_unwind(AssertUnwindSafe(move || {
+                        let mut value = RwLockWriteGuard::map(lock.write().unwrap(), |value| value);
+                        *value = 1;
+                        panic!("expected panic");
+                    }))
+                    .unwrap_err();
+                })
+            };
+
+            thd.join().unwrap();
+
+            assert!(lock.is_poisoned());
+            let result = lock.write();
+            assert!(matches!(result, Err(PoisonError { .. })));
+            // The lock is still acquired even though it's poisoned
+            assert_eq!(*result.unwrap_err().into_inner(), 1);
+            assert!(matches!(lock.try_read(), Err(TryLockError::Poisoned(_))));
+
+            lock.clear_poison();
+            assert!(!lock.is_poisoned());
+            *lock.write().unwrap() = 2;
+            assert_eq!(*lock.try_read().unwrap(), 2);
+        },
+        None,
+    )
+}
+
 // This test generates a panic that poisons a lock, and then while unwinding due to that panic, runs
 // a Drop handler that tries to acquire that same lock. That leads to a double panic, which aborts
 // the process. Since this is a common pattern in Rust, we want to check that we at least get a
@@ -67,7 +283,7 @@ fn rwlock_poison() {
 fn max_steps_panic_during_drop() {
     let config = Config::new();
     let scheduler = DfsScheduler::new(None, false);
-    let runner = Runner::new(scheduler, config);
+    let runner = Runner::new(scheduler).with_config(config);
     runner.run(|| {
         #[derive(Clone)]
         struct Pool {
@@ -39,14 +39,14 @@ fn figure5_random() {
 fn figure5_pct() {
     // Change of hitting the bug should be 1 - (1 - 1/2)^20 > 99.9999%, so this should trip the assert
     let scheduler = PctScheduler::new(1, 20);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(figure5);
 }
 
 #[test]
 fn one_step() {
     let scheduler = PctScheduler::new(2, 100);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(|| {
         thread::spawn(|| {});
     });
@@ -61,7 +61,7 @@ fn yield_spin_loop(use_yield: bool) {
     let scheduler = PctScheduler::new(1, 100);
     let mut config = Config::new();
     config.max_steps = MaxSteps::FailAfter(50);
-    let runner = Runner::new(scheduler, config);
+    let runner = Runner::new(scheduler).with_config(config);
     runner.run(move || {
         let count = Arc::new(AtomicUsize::new(0usize));
 
@@ -103,7 +103,7 @@ fn figure1a_pct() {
     // n=2, d=1, so probability of finding the bug is at least 1/2
     // So probability of hitting the bug in 20 iterations = 1 - (1 - 1/2)^20 > 99.9%
     let scheduler = PctScheduler::new(1, 20);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(|| {
         let t1 = Arc::new(Mutex::new(None));
         let t2 = Arc::clone(&t1);
@@ -132,6 +132,14 @@ fn figure1a_pct() {
 
 // Based on Fig 1(b) from the PCT paper.  We model NULL pointer dereference with an Option unwrap.
 fn figure1b(num_threads: usize) {
+    figure1b_with(num_threads, || panic!("null dereference"));
+}
+
+// Like `figure1b`, but calls `null_dereference` instead of panicking if the bug happens
+fn figure1b_with<F>(num_threads: usize, null_dereference: F)
+where
+    F: Fn() + Send + 'static,
+{
     assert!(num_threads >= 2);
 
     let x1 = Arc::new(Mutex::new(Some(1)));
@@ -165,8 +173,8 @@ fn figure1b(num_threads: usize) {
             let b = x2.lock().unwrap().is_some();
             b
         };
-        if b {
-            let _ = x2.lock().unwrap().expect("null dereference");
+        if b && x2.lock().unwrap().is_none() {
+            null_dereference();
         }
         for _ in 0..4 {
             thread::sleep(Duration::from_millis(1));
@@ -180,12 +188,43 @@ fn figure1b_pct() {
     // n=2, k=20, d=2, so probability of finding the bug in one iteration is at least 1/(2*20)
     // So probability of hitting the bug in 300 iterations = 1 - (1 - 1/40)^300 > 99.9%
     let scheduler = PctScheduler::new(2, 300);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(|| {
         figure1b(2);
     });
 }
 
+// PCT's guarantee is per iteration, so it should hit a depth 2 bug in at least the guaranteed
+// fraction of iterations, and without making any iteration longer than the program itself
+#[test]
+fn figure1b_pct_hit_rate() {
+    const ITERATIONS: usize = 1000;
+    let hits = Arc::new(AtomicUsize::new(0));
+
+    let stats = {
+        let hits = Arc::clone(&hits);
+        let scheduler = PctScheduler::new_from_seed(0x1234_5678, 2, ITERATIONS);
+        let runner = Runner::new(scheduler);
+        runner.run(move || {
+            let hits = Arc::clone(&hits);
+            figure1b_with(2, move || {
+                hits.fetch_add(1, Ordering::SeqCst);
+            });
+        })
+    };
+
+    // n=2, k=20, d=2, so each iteration hits the bug with probability at least 1/(2*20)
+    let hits = hits.load(Ordering::SeqCst);
+    assert!(
+        hits >= ITERATIONS / 40,
+        "hit the bug in only {} of {} iterations",
+        hits,
+        ITERATIONS
+    );
+    // Each worker takes at most 10 steps, and spawning and finishing take a few more
+    assert!(stats.max_steps <= 30, "an iteration took {} steps", stats.max_steps);
+}
+
 #[test]
 #[should_panic(expected = "null dereference")]
 fn figure1b_pct_with_many_tasks() {
@@ -193,7 +232,7 @@ fn figure1b_pct_with_many_tasks() {
     // n=50, k=110, d=2, so probability of finding the bug in one iteration is at least 1/(20*110)
     // So probability of hitting the bug in 16_000 iterations = 1 - (1 - 1/2200)^16_000 > 99.9%
     let scheduler = PctScheduler::new(2, 16_000);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(|| {
         figure1b(20);
     });
@@ -207,7 +246,7 @@ fn figure_1c() {
     // n=2, k=2*14, d=2, so probability of finding the bug is at least 1/(2*28)
     // So probability of hitting the bug in 400 iterations = 1 - (1 - 1/56)^400 > 99.9%
     let scheduler = PctScheduler::new(2, 400);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(|| {
         let a1 = Arc::new(Mutex::new(0));
         let a2 = Arc::clone(&a1);
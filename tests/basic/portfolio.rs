@@ -1,6 +1,6 @@
 use shuttle::scheduler::{PctScheduler, RandomScheduler};
 use shuttle::sync::Mutex;
-use shuttle::{thread, PortfolioRunner, Runner};
+use shuttle::{check_random_parallel, check_random_parallel_with_seed, thread, PortfolioRunner, Runner};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use test_env_log::test;
@@ -64,7 +64,7 @@ fn two_thread_deadlock() {
 fn two_thread_deadlock_pct_depth_one() {
     // depth 1 shouldn't fail
     let scheduler = PctScheduler::new(1, 1000);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(two_thread_deadlock);
 }
 
@@ -100,3 +100,79 @@ fn two_thread_deadlock_portfolio_no_early_stop() {
         "PCT depth 1 should have run to completion"
     );
 }
+
+#[test]
+#[should_panic(expected = "deadlock")]
+fn two_thread_deadlock_random_parallel() {
+    check_random_parallel(two_thread_deadlock, 1000, 4);
+}
+
+#[test]
+#[should_panic(expected = "deadlock")]
+fn two_thread_deadlock_random_parallel_one_thread() {
+    check_random_parallel(two_thread_deadlock, 1000, 1);
+}
+
+// Run the schedulers in parallel and return the order in which three racing threads ran in each
+// iteration, sorted so that it doesn't depend on how the OS interleaved the schedulers
+fn racing_orders(schedulers: Vec<RandomScheduler>) -> Vec<Vec<usize>> {
+    racing_orders_with(|f| {
+        let mut runner = PortfolioRunner::new(true, Default::default());
+        for scheduler in schedulers {
+            runner.add(scheduler);
+        }
+        runner.run(f);
+    })
+}
+
+fn racing_orders_with<F: FnOnce(Box<dyn Fn() + Send + Sync>)>(check: F) -> Vec<Vec<usize>> {
+    let orders = Arc::new(std::sync::Mutex::new(Vec::new()));
+    {
+        let orders = Arc::clone(&orders);
+        check(Box::new(move || {
+            let order = Arc::new(Mutex::new(Vec::new()));
+            let threads = (0..3)
+                .map(|i| {
+                    let order = Arc::clone(&order);
+                    thread::spawn(move || order.lock().unwrap().push(i))
+                })
+                .collect::<Vec<_>>();
+            for thd in threads {
+                thd.join().unwrap();
+            }
+            orders.lock().unwrap().push(order.lock().unwrap().clone());
+        }));
+    }
+    let mut orders = Arc::try_unwrap(orders).unwrap().into_inner().unwrap();
+    orders.sort();
+    orders
+}
+
+#[test]
+fn random_split_reproducible() {
+    let orders = racing_orders(RandomScheduler::split(42, 100, 4));
+    assert_eq!(orders.len(), 100);
+    assert_eq!(orders, racing_orders(RandomScheduler::split(42, 100, 4)));
+    assert_ne!(orders, racing_orders(RandomScheduler::split(43, 100, 4)));
+
+    // Splitting into one scheduler is the same as not splitting at all
+    assert_eq!(
+        racing_orders(RandomScheduler::split(42, 100, 1)),
+        racing_orders(vec![RandomScheduler::new_from_seed(42, 100)])
+    );
+}
+
+#[test]
+fn check_random_parallel_same_seed_same_schedules() {
+    let orders = racing_orders_with(|f| check_random_parallel_with_seed(f, 100, 4, 42));
+    assert_eq!(orders.len(), 100);
+    assert_eq!(
+        orders,
+        racing_orders_with(|f| check_random_parallel_with_seed(f, 100, 4, 42))
+    );
+    assert_ne!(
+        orders,
+        racing_orders_with(|f| check_random_parallel_with_seed(f, 100, 4, 43))
+    );
+    assert_eq!(orders, racing_orders(RandomScheduler::split(42, 100, 4)));
+}
@@ -0,0 +1,86 @@
+use shuttle::scheduler::RandomScheduler;
+use shuttle::sync::Mutex;
+use shuttle::{check_random_with_seed, thread, Runner};
+use std::panic;
+use std::sync::Arc;
+use test_env_log::test;
+
+// Run three threads that race to record their number, and return the order they ran in for each
+// iteration
+fn racing_orders<F: FnOnce(Box<dyn Fn() + Send + Sync>)>(check: F) -> Vec<Vec<usize>> {
+    let orders = Arc::new(std::sync::Mutex::new(Vec::new()));
+    {
+        let orders = Arc::clone(&orders);
+        check(Box::new(move || {
+            let order = Arc::new(Mutex::new(Vec::new()));
+            let threads = (0..3)
+                .map(|i| {
+                    let order = Arc::clone(&order);
+                    thread::spawn(move || order.lock().unwrap().push(i))
+                })
+                .collect::<Vec<_>>();
+            for thd in threads {
+                thd.join().unwrap();
+            }
+            orders.lock().unwrap().push(order.lock().unwrap().clone());
+        }));
+    }
+    Arc::try_unwrap(orders).unwrap().into_inner().unwrap()
+}
+
+#[test]
+fn random_same_seed_same_schedules() {
+    let orders = racing_orders(|f| check_random_with_seed(f, 100, 42));
+    assert_eq!(orders.len(), 100);
+    assert_eq!(orders, racing_orders(|f| check_random_with_seed(f, 100, 42)));
+    assert_ne!(orders, racing_orders(|f| check_random_with_seed(f, 100, 43)));
+}
+
+#[test]
+fn random_scheduler_seed() {
+    let scheduler = RandomScheduler::new(100);
+    let seed = scheduler.seed();
+    let orders = racing_orders(|f| {
+        Runner::new(scheduler).run(f);
+    });
+    assert_eq!(orders, racing_orders(|f| check_random_with_seed(f, 100, seed)));
+}
+
+// Fails only if the threads happen to run in reverse order
+fn reverse_order_fails(iterations: Arc<std::sync::Mutex<usize>>) -> impl Fn() + Send + Sync + 'static {
+    move || {
+        *iterations.lock().unwrap() += 1;
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let threads = (0..3)
+            .map(|i| {
+                let order = Arc::clone(&order);
+                thread::spawn(move || order.lock().unwrap().push(i))
+            })
+            .collect::<Vec<_>>();
+        for thd in threads {
+            thd.join().unwrap();
+        }
+        assert_ne!(*order.lock().unwrap(), vec![2, 1, 0], "threads ran in reverse order");
+    }
+}
+
+#[test]
+fn random_seed_reproduces_failure() {
+    // Find a failure with a fresh seed, and remember how many iterations it took
+    let scheduler = RandomScheduler::new(1000);
+    let seed = scheduler.seed();
+    let iterations = Arc::new(std::sync::Mutex::new(0));
+    let f = reverse_order_fails(Arc::clone(&iterations));
+    panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        Runner::new(scheduler).run(f);
+    }))
+    .expect_err("random scheduler should find the reverse order");
+    let failing_iteration = *iterations.lock().unwrap();
+
+    // Rerunning with the same seed fails in the same iteration
+    let rerun_iterations = Arc::new(std::sync::Mutex::new(0));
+    let f = reverse_order_fails(Arc::clone(&rerun_iterations));
+    panic::catch_unwind(panic::AssertUnwindSafe(|| check_random_with_seed(f, 1000, seed)))
+        .expect_err("the same seed should reproduce the failure");
+    assert_eq!(*rerun_iterations.lock().unwrap(), failing_iteration);
+}
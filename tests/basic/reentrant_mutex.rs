@@ -0,0 +1,113 @@
+use shuttle::sync::{Mutex, ReentrantMutex};
+use shuttle::{check_dfs, current, thread};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use test_env_log::test;
+
+#[test]
+fn reentrant_mutex_nested_lock() {
+    check_dfs(
+        || {
+            let lock = ReentrantMutex::new(Cell::new(0usize));
+
+            let outer = lock.lock();
+            outer.set(1);
+            // Nested locking by the holder succeeds immediately, without a context switch
+            let switches = current::context_switches();
+            let inner = lock.lock();
+            assert_eq!(current::context_switches(), switches);
+            assert_eq!(inner.get(), 1);
+            inner.set(2);
+            drop(inner);
+            assert_eq!(outer.get(), 2);
+        },
+        None,
+    );
+}
+
+#[test]
+fn reentrant_mutex_excludes_other_threads() {
+    check_dfs(
+        || {
+            let lock = Arc::new(ReentrantMutex::new(Cell::new(0usize)));
+            let held = Arc::new(AtomicBool::new(false));
+
+            let thd = {
+                let lock = Arc::clone(&lock);
+                let held = Arc::clone(&held);
+                thread::spawn(move || {
+                    let guard = lock.lock();
+                    // The other thread must have dropped all of its guards before we get the lock
+                    assert!(!held.load(Ordering::SeqCst));
+                    guard.set(guard.get() + 1);
+                })
+            };
+
+            {
+                let outer = lock.lock();
+                held.store(true, Ordering::SeqCst);
+                {
+                    let inner = lock.lock();
+                    inner.set(inner.get() + 1);
+                    thread::yield_now();
+                }
+                // Dropping the nested guard doesn't release the lock
+                thread::yield_now();
+                outer.set(outer.get() + 1);
+                held.store(false, Ordering::SeqCst);
+            }
+
+            thd.join().unwrap();
+            assert_eq!(lock.lock().get(), 3);
+        },
+        None,
+    );
+}
+
+#[test]
+#[should_panic(
+    expected = "lock cycle: main-thread (task 0) holds L0 waiting L1; <unknown> (task 1) holds L1 waiting L0"
+)]
+fn reentrant_mutex_mutex_deadlock_reports_lock_cycle() {
+    check_dfs(
+        || {
+            let reentrant = Arc::new(ReentrantMutex::new(()));
+            let mutex = Arc::new(Mutex::new(()));
+
+            let thd = {
+                let reentrant = Arc::clone(&reentrant);
+                let mutex = Arc::clone(&mutex);
+                thread::spawn(move || {
+                    let _mutex = mutex.lock().unwrap();
+                    let _reentrant = reentrant.lock();
+                })
+            };
+
+            {
+                let _reentrant = reentrant.lock();
+                let _mutex = mutex.lock().unwrap();
+            }
+            thd.join().unwrap();
+        },
+        None,
+    );
+}
+
+#[test]
+#[should_panic(expected = "leaked lock guard! finished tasks still hold locks: <unknown> (task 1) holds L0")]
+fn reentrant_mutex_leaked_guard() {
+    check_dfs(
+        || {
+            let lock = Arc::new(ReentrantMutex::new(()));
+            thread::spawn(move || {
+                let _outer = lock.lock();
+                // Forgetting one of the guards means the lock is never released
+                std::mem::forget(lock.lock());
+            })
+            .join()
+            .unwrap();
+        },
+        None,
+    );
+}
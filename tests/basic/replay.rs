@@ -1,5 +1,6 @@
 use crate::{check_replay_roundtrip, check_replay_roundtrip_file, Config, FailurePersistence};
-use shuttle::scheduler::{PctScheduler, ReplayScheduler, Schedule};
+use shuttle::rand::Rng;
+use shuttle::scheduler::{PctScheduler, ReplayScheduler, Schedule, TaskId};
 use shuttle::sync::Mutex;
 use shuttle::{replay, thread, Runner};
 use std::panic;
@@ -102,7 +103,7 @@ fn replay_deadlock3_block() {
     // Reproduce deadlock
     let schedule = Schedule::new_from_task_ids(0, vec![0, 0, 1, 2, 1, 2, 0, 0]);
     let scheduler = ReplayScheduler::new_from_schedule(schedule);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(deadlock_3);
 }
 
@@ -112,7 +113,7 @@ fn replay_deadlock3_end_early() {
     let schedule = Schedule::new_from_task_ids(0, vec![0, 0, 1, 2]);
     let mut scheduler = ReplayScheduler::new_from_schedule(schedule);
     scheduler.set_allow_incomplete();
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(deadlock_3);
 }
 
@@ -122,7 +123,7 @@ fn replay_deadlock3_task_disabled() {
     let schedule = Schedule::new_from_task_ids(0, vec![0, 1, 2, 0]);
     let mut scheduler = ReplayScheduler::new_from_schedule(schedule);
     scheduler.set_allow_incomplete();
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(deadlock_3);
 }
 
@@ -132,10 +133,60 @@ fn replay_deadlock3_drop_mutex() {
     let schedule = Schedule::new_from_task_ids(0, vec![0, 0, 1, 1, 2]);
     let mut scheduler = ReplayScheduler::new_from_schedule(schedule);
     scheduler.set_allow_incomplete();
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(deadlock_3);
 }
 
+fn spawn_and_join() {
+    let lock = Arc::new(Mutex::new(0usize));
+    let thd = {
+        let lock = Arc::clone(&lock);
+        thread::spawn(move || *lock.lock().unwrap() += 1)
+    };
+    *lock.lock().unwrap() += 1;
+    thd.join().unwrap();
+    assert_eq!(*lock.lock().unwrap(), 2);
+}
+
+#[test]
+#[should_panic(
+    expected = "schedule diverged at step 1: the schedule runs TaskId(2), but the runnable tasks were [TaskId(0), TaskId(1)]"
+)]
+fn replay_stale_schedule() {
+    // A schedule recorded for a version of the test that spawned more threads
+    let schedule = Schedule::new_from_task_ids(0, vec![0, 2, 1, 0]);
+    let runner = Runner::new(ReplayScheduler::new_from_schedule(schedule));
+    runner.run(spawn_and_join);
+}
+
+#[test]
+#[should_panic(expected = "schedule diverged at step 2: the schedule ended")]
+fn replay_schedule_too_short() {
+    let schedule = Schedule::new_from_task_ids(0, vec![0, 0]);
+    let runner = Runner::new(ReplayScheduler::new_from_schedule(schedule));
+    runner.run(spawn_and_join);
+}
+
+#[test]
+#[should_panic(expected = "schedule diverged at step 1: expected a random choice")]
+fn replay_schedule_wrong_kind_of_step() {
+    let schedule = Schedule::new_from_task_ids(0, vec![0, 0]);
+    let runner = Runner::new(ReplayScheduler::new_from_schedule(schedule));
+    runner.run(|| {
+        let _ = shuttle::rand::thread_rng().gen::<u64>();
+    });
+}
+
+#[test]
+fn replay_stale_schedule_random_fallback() {
+    for schedule in [vec![0, 2, 1, 0], vec![0, 0], vec![]] {
+        let mut scheduler = ReplayScheduler::new_from_schedule(Schedule::new_from_task_ids(0, schedule));
+        scheduler.set_random_fallback();
+        let runner = Runner::new(scheduler);
+        runner.run(spawn_and_join);
+    }
+}
+
 // Check that FailurePersistence::None does not print a schedule
 #[test]
 fn replay_persist_none() {
@@ -143,12 +194,57 @@ fn replay_persist_none() {
         let scheduler = PctScheduler::new(2, 100);
         let mut config = Config::new();
         config.failure_persistence = FailurePersistence::None;
-        let runner = Runner::new(scheduler, config);
+        let runner = Runner::new(scheduler).with_config(config);
         runner.run(concurrent_increment_buggy);
     })
     .expect_err("test should panic");
     let output = result.downcast::<String>().unwrap();
     assert!(output.contains("counter is wrong"));
+    // The failure kind doesn't depend on persistence
+    assert!(output.contains("\nfailure kind: logic bug\nhint: "), "{}", output);
     // All our current failure persistence modes print the word "schedule", so check that's missing
     assert!(!output.contains("schedule"));
 }
+
+#[test]
+fn schedule_encode_roundtrip() {
+    let mut schedule = Schedule::new_from_task_ids(42, vec![0, 0, 1, 2, 1000]);
+    schedule.push_random();
+    schedule.push_task(TaskId::from(1));
+
+    let encoded = schedule.encode();
+    assert_eq!(Schedule::decode(&encoded), Some(schedule.clone()));
+    // Whitespace around the encoding, like a trailing newline, is ignored
+    assert_eq!(Schedule::decode(&format!(" {}\n", encoded)), Some(schedule));
+
+    assert_eq!(Schedule::decode(&Schedule::new(0).encode()), Some(Schedule::new(0)));
+}
+
+#[test]
+fn schedule_decode_invalid() {
+    assert_eq!(Schedule::decode(""), None);
+    assert_eq!(Schedule::decode("hello"), None);
+    let encoded = Schedule::new_from_task_ids(0, vec![0, 1, 0, 1]).encode();
+    assert_eq!(Schedule::decode(&encoded[..encoded.len() - 2]), None);
+}
+
+// The schedule a failing test prints decodes to a schedule that replays the failure
+#[test]
+fn schedule_decode_printed_failure() {
+    let result = panic::catch_unwind(|| {
+        let runner = Runner::new(PctScheduler::new(2, 100));
+        runner.run(concurrent_increment_buggy);
+    })
+    .expect_err("test should panic");
+    let output = result.downcast::<String>().unwrap();
+    let encoded = crate::parse_schedule::from_stdout(&output).expect("output should contain a schedule");
+
+    let schedule = Schedule::decode(&encoded).expect("printed schedule should decode");
+    assert_eq!(schedule.encode(), encoded);
+    let result = panic::catch_unwind(|| {
+        let runner = Runner::new(ReplayScheduler::new_from_schedule(schedule));
+        runner.run(concurrent_increment_buggy);
+    })
+    .expect_err("replay should panic");
+    assert_eq!(*result.downcast::<String>().unwrap(), *output);
+}
@@ -0,0 +1,62 @@
+use shuttle::scheduler::RoundRobinScheduler;
+use shuttle::sync::Mutex;
+use shuttle::{thread, Runner};
+use std::sync::Arc;
+use test_env_log::test;
+
+// Run a ping-pong between two threads under the round-robin scheduler, and return the order in
+// which the threads took their turns
+fn ping_pong() -> Vec<usize> {
+    let turns = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let runner = Runner::new(RoundRobinScheduler::new());
+    {
+        let turns = Arc::clone(&turns);
+        runner.run(move || {
+            // A std mutex, so recording a turn isn't a yield point
+            let turns = Arc::clone(&turns);
+            let play = move |player: usize| {
+                for _ in 0..3 {
+                    turns.lock().unwrap().push(player);
+                    thread::yield_now();
+                }
+            };
+
+            let pong = {
+                let play = play.clone();
+                thread::spawn(move || play(1))
+            };
+            play(0);
+            pong.join().unwrap();
+        });
+    }
+    let turns = turns.lock().unwrap().clone();
+    turns
+}
+
+#[test]
+fn round_robin_ping_pong() {
+    let turns = ping_pong();
+    assert_eq!(turns, vec![1, 0, 1, 0, 1, 0]);
+    // The schedule is deterministic
+    assert_eq!(ping_pong(), turns);
+}
+
+#[test]
+#[should_panic(expected = "deadlock")]
+fn round_robin_deadlock() {
+    let runner = Runner::new(RoundRobinScheduler::new());
+    runner.run(|| {
+        let lock1 = Arc::new(Mutex::new(0));
+        let lock2 = Arc::new(Mutex::new(0));
+        let lock1_clone = Arc::clone(&lock1);
+        let lock2_clone = Arc::clone(&lock2);
+
+        thread::spawn(move || {
+            let _l2 = lock2_clone.lock().unwrap();
+            let _l1 = lock1_clone.lock().unwrap();
+        });
+
+        let _l1 = lock1.lock().unwrap();
+        let _l2 = lock2.lock().unwrap();
+    });
+}
@@ -1,8 +1,8 @@
-use shuttle::scheduler::PctScheduler;
-use shuttle::sync::{mpsc::channel, RwLock};
-use shuttle::{check, check_random, thread, Runner};
+use shuttle::scheduler::{DfsScheduler, PctScheduler};
+use shuttle::sync::{mpsc::channel, Barrier, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use shuttle::{check, check_dfs, check_random, thread, Config, Runner};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, TryLockError};
 use test_env_log::test;
 
 #[test]
@@ -92,10 +92,303 @@ fn deadlock_random() {
 fn deadlock_pct() {
     // 200 tries should be enough to find a deadlocking execution
     let scheduler = PctScheduler::new(2, 100);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(deadlock);
 }
 
+#[test]
+#[should_panic(expected = "deadlock! TaskId(0) tried to acquire Write access")]
+fn rwlock_read_then_write_self_deadlock() {
+    check_dfs(
+        || {
+            let lock = RwLock::new(0usize);
+            let _read = lock.read().unwrap();
+            let _write = lock.write().unwrap();
+        },
+        None,
+    );
+}
+
+#[test]
+#[should_panic(expected = "deadlock! TaskId(0) tried to acquire Read access")]
+fn rwlock_write_then_read_self_deadlock() {
+    check_dfs(
+        || {
+            let lock = RwLock::new(0usize);
+            let _write = lock.write().unwrap();
+            let _read = lock.read().unwrap();
+        },
+        None,
+    );
+}
+
+#[test]
+fn rwlock_read_recursive() {
+    check_dfs(
+        || {
+            let lock = Arc::new(RwLock::new(0usize));
+
+            let writer = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || *lock.write().unwrap() += 1)
+            };
+
+            // Hold two read guards at once, released in either order
+            let first = lock.read().unwrap();
+            let second = lock.read_recursive().unwrap();
+            assert_eq!(*first, *second);
+            drop(first);
+            let third = lock.read_recursive().unwrap();
+            assert_eq!(*second, *third);
+            drop(third);
+            drop(second);
+
+            writer.join().unwrap();
+            assert_eq!(*lock.read().unwrap(), 1);
+        },
+        None,
+    );
+}
+
+// A thread that already holds read access can always take another read guard with `try_read`,
+// even if a writer is waiting for the lock
+#[test]
+fn rwlock_try_read_while_reading() {
+    check_dfs(
+        || {
+            let lock = Arc::new(RwLock::new(0usize));
+
+            let writer = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || *lock.write().unwrap() += 1)
+            };
+
+            let first = lock.read().unwrap();
+            let second = lock.try_read().unwrap();
+            assert_eq!(*first, *second);
+            drop(first);
+            drop(second);
+
+            let upgradable = lock.upgradable_read().unwrap();
+            let read = lock.try_read().unwrap();
+            assert_eq!(*upgradable, *read);
+            drop(read);
+            drop(upgradable);
+
+            writer.join().unwrap();
+            assert_eq!(*lock.read().unwrap(), 1);
+        },
+        None,
+    );
+}
+
+#[test]
+#[should_panic(expected = "still holds another Read guard")]
+fn rwlock_read_recursive_then_upgrade_deadlock() {
+    check_dfs(
+        || {
+            let lock = RwLock::new(0usize);
+            let upgradable = lock.upgradable_read().unwrap();
+            let _read = lock.read_recursive().unwrap();
+            let _write = upgradable.upgrade();
+        },
+        None,
+    );
+}
+
+// Hold read access to `lock` until a writer is waiting for it, and then spawn a reader that takes
+// read access with `read`, or `read_recursive` if `recursive` is set, and returns what it saw
+fn read_while_writer_waits(lock: Arc<RwLock<usize>>, recursive: bool) -> usize {
+    let guard = lock.read().unwrap();
+
+    let writer = {
+        let lock = Arc::clone(&lock);
+        thread::spawn(move || *lock.write().unwrap() = 1)
+    };
+    let reader = {
+        let lock = Arc::clone(&lock);
+        thread::spawn(move || {
+            // `try_read` fails once the writer is waiting for the lock, unless the writer has
+            // already finished
+            while matches!(lock.try_read().as_deref(), Ok(0)) {
+                thread::yield_now();
+            }
+            if recursive {
+                *lock.read_recursive().unwrap()
+            } else {
+                *lock.read().unwrap()
+            }
+        })
+    };
+
+    // A reader that doesn't wait for the writer can finish while we still hold read access
+    let seen = if recursive {
+        let seen = reader.join().unwrap();
+        drop(guard);
+        seen
+    } else {
+        drop(guard);
+        reader.join().unwrap()
+    };
+    writer.join().unwrap();
+    seen
+}
+
+#[test]
+fn rwlock_writer_preferring_blocks_new_readers() {
+    check_random(
+        || {
+            let lock = Arc::new(RwLock::new_writer_preferring(0));
+            assert_eq!(read_while_writer_waits(lock, false), 1);
+        },
+        100,
+    );
+}
+
+#[test]
+fn rwlock_writer_preferring_read_recursive() {
+    check_random(
+        || {
+            let lock = Arc::new(RwLock::new_writer_preferring(0));
+            assert_eq!(read_while_writer_waits(lock, true), 0);
+        },
+        100,
+    );
+}
+
+#[test]
+fn rwlock_without_writer_preference_admits_new_readers() {
+    let saw_old_value = Arc::new(AtomicBool::new(false));
+    {
+        let saw_old_value = Arc::clone(&saw_old_value);
+        check_random(
+            move || {
+                let lock = Arc::new(RwLock::new(0));
+                if read_while_writer_waits(lock, false) == 0 {
+                    saw_old_value.store(true, Ordering::SeqCst);
+                }
+            },
+            100,
+        );
+    }
+    assert!(saw_old_value.load(Ordering::SeqCst));
+}
+
+#[test]
+#[should_panic(expected = "leaked lock guard! finished tasks still hold locks: main-thread (task 0) holds L0, L1")]
+fn rwlock_leaked_guards() {
+    check_dfs(
+        || {
+            let first = RwLock::new(0);
+            let second = RwLock::new(0);
+            let guards = vec![first.read().unwrap(), second.read().unwrap()];
+            std::mem::forget(guards);
+        },
+        None,
+    );
+}
+
+#[test]
+fn rwlock_reader_count() {
+    check_random(
+        || {
+            let lock = Arc::new(RwLock::new(0usize));
+            // Both readers hold their guards between the two barriers
+            let acquired = Arc::new(Barrier::new(3));
+            let release = Arc::new(Barrier::new(3));
+
+            let readers = (0..2)
+                .map(|_| {
+                    let lock = Arc::clone(&lock);
+                    let acquired = Arc::clone(&acquired);
+                    let release = Arc::clone(&release);
+                    thread::spawn(move || {
+                        let _first = lock.read().unwrap();
+                        let _second = lock.read_recursive().unwrap();
+                        acquired.wait();
+                        release.wait();
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            acquired.wait();
+            assert_eq!(lock.reader_count(), 2);
+            assert!(!lock.has_writer());
+            release.wait();
+            for reader in readers {
+                reader.join().unwrap();
+            }
+
+            assert_eq!(lock.reader_count(), 0);
+            let _write = lock.write().unwrap();
+            assert_eq!(lock.reader_count(), 0);
+            assert!(lock.has_writer());
+        },
+        100,
+    );
+}
+
+#[test]
+fn rwlock_clone_is_independent() {
+    check_dfs(
+        || {
+            let lock = RwLock::new(1usize);
+            let clone = lock.clone();
+            // Holding the clone doesn't hold the original
+            let mut guard = clone.write().unwrap();
+            *guard = 2;
+            assert_eq!(*lock.read().unwrap(), 1);
+            assert!(!lock.has_writer());
+            drop(guard);
+            assert_eq!(clone.into_inner().unwrap(), 2);
+            assert_eq!(lock.into_inner().unwrap(), 1);
+        },
+        None,
+    );
+}
+
+#[test]
+#[should_panic(expected = "cannot clone an RwLock while it is held")]
+fn rwlock_clone_while_held() {
+    check_dfs(
+        || {
+            let lock = RwLock::new(1usize);
+            let _guard = lock.read().unwrap();
+            let _clone = lock.clone();
+        },
+        None,
+    );
+}
+
+#[test]
+fn rwlock_from() {
+    check_dfs(
+        || {
+            let lock: RwLock<usize> = 5.into();
+            assert_eq!(*lock.read().unwrap(), 5);
+        },
+        None,
+    );
+}
+
+#[test]
+fn rwlock_debug() {
+    check_dfs(
+        || {
+            let lock = RwLock::new(1usize);
+            assert_eq!(format!("{:?}", lock), "RwLock { data: 1, .. }");
+            {
+                let _read = lock.read().unwrap();
+                assert_eq!(format!("{:?}", lock), "RwLock { data: <locked>, .. }");
+            }
+            assert_eq!(format!("{:?}", lock), "RwLock { data: 1, .. }");
+            let _write = lock.write().unwrap();
+            assert_eq!(format!("{:?}", lock), "RwLock { data: <locked>, .. }");
+        },
+        None,
+    );
+}
+
 // Test case for a bug we found in Loom: https://github.com/tokio-rs/loom/pull/135
 #[test]
 fn rwlock_two_writers() {
@@ -220,3 +513,671 @@ fn rwlock_into_inner() {
         None,
     )
 }
+
+#[test]
+fn rwlock_get_mut() {
+    check_dfs(
+        || {
+            let mut lock = RwLock::new(0u64);
+            *lock.get_mut().unwrap() += 5;
+            let lock = Arc::new(lock);
+
+            let reader = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    assert_eq!(*lock.read().unwrap(), 5);
+                })
+            };
+
+            assert_eq!(*lock.read().unwrap(), 5);
+            reader.join().unwrap();
+        },
+        None,
+    )
+}
+
+#[test]
+fn rwlock_try_read_racing_write() {
+    let saw_success = Arc::new(AtomicBool::new(false));
+    let saw_would_block = Arc::new(AtomicBool::new(false));
+
+    {
+        let saw_success = Arc::clone(&saw_success);
+        let saw_would_block = Arc::clone(&saw_would_block);
+
+        check_dfs(
+            move || {
+                let lock = Arc::new(RwLock::new(0usize));
+
+                let writer = {
+                    let lock = Arc::clone(&lock);
+                    thread::spawn(move || {
+                        // The value is only 1 while the writer is in the middle of its critical
+                        // section
+                        let mut w = lock.write().unwrap();
+                        *w = 1;
+                        thread::yield_now();
+                        *w = 2;
+                    })
+                };
+
+                match lock.try_read() {
+                    Ok(r) => {
+                        // The writer can't be halfway through its critical section
+                        assert_ne!(*r, 1, "try_read saw a partial write");
+                        saw_success.store(true, Ordering::SeqCst);
+                    }
+                    Err(TryLockError::WouldBlock) => saw_would_block.store(true, Ordering::SeqCst),
+                    Err(TryLockError::Poisoned(_)) => panic!("rwlock should not be poisoned"),
+                }
+
+                writer.join().unwrap();
+            },
+            None,
+        );
+    }
+
+    assert!(saw_success.load(Ordering::SeqCst));
+    assert!(saw_would_block.load(Ordering::SeqCst));
+}
+
+#[test]
+fn rwlock_try_read_concurrent_readers() {
+    check_dfs(
+        || {
+            let lock = Arc::new(RwLock::new(0usize));
+
+            let reader = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    let _r = lock.read().unwrap();
+                    thread::yield_now();
+                })
+            };
+
+            // Readers never exclude each other, so this should always succeed
+            assert_eq!(*lock.try_read().unwrap(), 0);
+
+            reader.join().unwrap();
+        },
+        None,
+    );
+}
+
+#[test]
+fn rwlock_try_write_mutual_exclusion() {
+    let saw_both_fail = Arc::new(AtomicBool::new(false));
+
+    {
+        let saw_both_fail = Arc::clone(&saw_both_fail);
+
+        check_dfs(
+            move || {
+                let lock = Arc::new(RwLock::new(0usize));
+                let holders = Arc::new(AtomicUsize::new(0));
+                let successes = Arc::new(AtomicUsize::new(0));
+
+                let threads = (0..2)
+                    .map(|_| {
+                        let lock = Arc::clone(&lock);
+                        let holders = Arc::clone(&holders);
+                        let successes = Arc::clone(&successes);
+                        thread::spawn(move || {
+                            if let Ok(mut w) = lock.try_write() {
+                                assert_eq!(holders.fetch_add(1, Ordering::SeqCst), 0);
+                                successes.fetch_add(1, Ordering::SeqCst);
+                                thread::yield_now();
+                                *w += 1;
+                                holders.fetch_sub(1, Ordering::SeqCst);
+                            }
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                for thread in threads {
+                    thread.join().unwrap();
+                }
+
+                // A failed `try_write` must not leave the lock in a state where it can't be taken
+                let value = *lock.write().unwrap();
+                assert_eq!(value, successes.load(Ordering::SeqCst));
+                if value == 0 {
+                    saw_both_fail.store(true, Ordering::SeqCst);
+                }
+            },
+            None,
+        );
+    }
+
+    // Neither `try_write` can fail unless the other one holds the lock
+    assert!(!saw_both_fail.load(Ordering::SeqCst));
+}
+
+#[test]
+fn rwlock_try_write_excludes_readers() {
+    check_dfs(
+        || {
+            let lock = Arc::new(RwLock::new(0usize));
+
+            let reader = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    let r = lock.read().unwrap();
+                    thread::yield_now();
+                    *r
+                })
+            };
+
+            if let Ok(mut w) = lock.try_write() {
+                *w = 1;
+            }
+
+            let value = reader.join().unwrap();
+            assert!(value == 0 || value == 1);
+        },
+        None,
+    );
+}
+
+#[test]
+fn rwlock_downgrade() {
+    check_dfs(
+        || {
+            let lock = Arc::new(RwLock::new(0usize));
+
+            let writer = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    *lock.write().unwrap() = 2;
+                })
+            };
+
+            let mut w = lock.write().unwrap();
+            let before = *w;
+            *w = 1;
+            let r = w.downgrade();
+            // No writer can sneak in between the write and the downgraded read
+            assert_eq!(*r, 1);
+            thread::yield_now();
+            assert_eq!(*r, 1);
+            drop(r);
+
+            writer.join().unwrap();
+            // The other writer ran either entirely before or entirely after our critical section
+            assert!(before == 0 || before == 2);
+            assert_eq!(*lock.read().unwrap(), if before == 0 { 2 } else { 1 });
+        },
+        None,
+    );
+}
+
+#[test]
+fn rwlock_downgrade_admits_readers() {
+    let saw_concurrent_read = Arc::new(AtomicBool::new(false));
+
+    {
+        let saw_concurrent_read = Arc::clone(&saw_concurrent_read);
+
+        check_dfs(
+            move || {
+                let lock = Arc::new(RwLock::new(0usize));
+                let downgraded = Arc::new(AtomicBool::new(false));
+
+                let reader = {
+                    let lock = Arc::clone(&lock);
+                    let downgraded = Arc::clone(&downgraded);
+                    let saw_concurrent_read = Arc::clone(&saw_concurrent_read);
+                    thread::spawn(move || {
+                        let r = lock.read().unwrap();
+                        if downgraded.load(Ordering::SeqCst) {
+                            assert_eq!(*r, 1);
+                            saw_concurrent_read.store(true, Ordering::SeqCst);
+                        }
+                    })
+                };
+
+                let mut w = lock.write().unwrap();
+                *w = 1;
+                let r = w.downgrade();
+                downgraded.store(true, Ordering::SeqCst);
+                thread::yield_now();
+                downgraded.store(false, Ordering::SeqCst);
+                drop(r);
+
+                reader.join().unwrap();
+            },
+            None,
+        );
+    }
+
+    assert!(saw_concurrent_read.load(Ordering::SeqCst));
+}
+
+#[test]
+fn rwlock_upgradable_read_exclusive() {
+    check_dfs(
+        || {
+            let lock = Arc::new(RwLock::new(0usize));
+            let holders = Arc::new(AtomicUsize::new(0));
+
+            let threads = (0..2)
+                .map(|_| {
+                    let lock = Arc::clone(&lock);
+                    let holders = Arc::clone(&holders);
+                    thread::spawn(move || {
+                        let guard = lock.upgradable_read().unwrap();
+                        assert_eq!(holders.fetch_add(1, Ordering::SeqCst), 0);
+                        thread::yield_now();
+                        holders.fetch_sub(1, Ordering::SeqCst);
+                        drop(guard);
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for thd in threads {
+                thd.join().unwrap();
+            }
+        },
+        None,
+    );
+}
+
+#[test]
+fn rwlock_upgrade_waits_for_readers() {
+    let saw_shared_read = Arc::new(AtomicBool::new(false));
+
+    {
+        let saw_shared_read = Arc::clone(&saw_shared_read);
+
+        check_dfs(
+            move || {
+                let lock = Arc::new(RwLock::new(0usize));
+                let reading = Arc::new(AtomicBool::new(false));
+                let upgradable_held = Arc::new(AtomicBool::new(false));
+
+                let reader = {
+                    let lock = Arc::clone(&lock);
+                    let reading = Arc::clone(&reading);
+                    let upgradable_held = Arc::clone(&upgradable_held);
+                    let saw_shared_read = Arc::clone(&saw_shared_read);
+                    thread::spawn(move || {
+                        let r = lock.read().unwrap();
+                        reading.store(true, Ordering::SeqCst);
+                        if upgradable_held.load(Ordering::SeqCst) {
+                            saw_shared_read.store(true, Ordering::SeqCst);
+                        }
+                        thread::yield_now();
+                        reading.store(false, Ordering::SeqCst);
+                        drop(r);
+                    })
+                };
+
+                let u = lock.upgradable_read().unwrap();
+                upgradable_held.store(true, Ordering::SeqCst);
+                thread::yield_now();
+                upgradable_held.store(false, Ordering::SeqCst);
+                let mut w = u.upgrade();
+                // The plain reader must have drained before the upgrade completed
+                assert!(!reading.load(Ordering::SeqCst));
+                *w += 1;
+                drop(w);
+
+                reader.join().unwrap();
+                assert_eq!(*lock.read().unwrap(), 1);
+            },
+            None,
+        );
+    }
+
+    assert!(saw_shared_read.load(Ordering::SeqCst));
+}
+
+#[test]
+fn rwlock_upgradable_downgrade() {
+    check_random(
+        || {
+            let lock = Arc::new(RwLock::new(0usize));
+
+            let other = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    let mut w = lock.upgradable_read().unwrap().upgrade();
+                    *w += 1;
+                })
+            };
+
+            let u = lock.upgradable_read().unwrap();
+            let before = *u;
+            let r = u.downgrade();
+            // The other thread can now take upgradable access, but can't upgrade while we're reading
+            thread::yield_now();
+            assert_eq!(*r, before);
+            drop(r);
+
+            other.join().unwrap();
+            assert_eq!(*lock.read().unwrap(), 1);
+        },
+        1000,
+    );
+}
+
+#[test]
+fn rwlock_mapped_guards() {
+    check_dfs(
+        || {
+            let lock = Arc::new(RwLock::new((0usize, 0usize)));
+
+            let writer = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    let mut second = RwLockWriteGuard::map(lock.write().unwrap(), |pair| &mut pair.1);
+                    *second += 1;
+                })
+            };
+
+            {
+                let mut first = RwLockWriteGuard::map(lock.write().unwrap(), |pair| &mut pair.0);
+                *first += 1;
+            }
+
+            // Dropping the mapped guards must release the lock, or this would deadlock
+            writer.join().unwrap();
+            let first = RwLockReadGuard::map(lock.read().unwrap(), |pair| &pair.0);
+            assert_eq!(*first, 1);
+            drop(first);
+            assert_eq!(*lock.write().unwrap(), (1, 1));
+        },
+        None,
+    );
+}
+
+#[test]
+fn rwlock_mapped_guard_drop_is_yield_point() {
+    let saw_switch_on_drop = Arc::new(AtomicBool::new(false));
+
+    {
+        let saw_switch_on_drop = Arc::clone(&saw_switch_on_drop);
+
+        check_dfs(
+            move || {
+                let lock = Arc::new(RwLock::new(0usize));
+                let flag = Arc::new(AtomicBool::new(false));
+
+                {
+                    let flag = Arc::clone(&flag);
+                    thread::spawn(move || {
+                        flag.store(true, Ordering::SeqCst);
+                    });
+                }
+
+                let guard = RwLockReadGuard::map(lock.read().unwrap(), |value| value);
+                let before = flag.load(Ordering::SeqCst);
+                drop(guard);
+                let after = flag.load(Ordering::SeqCst);
+                // The other thread can only run in between these loads if dropping was a yield point
+                if !before && after {
+                    saw_switch_on_drop.store(true, Ordering::SeqCst);
+                }
+            },
+            None,
+        );
+    }
+
+    assert!(saw_switch_on_drop.load(Ordering::SeqCst));
+}
+
+#[test]
+fn rwlock_max_readers() {
+    let saw_max_readers = Arc::new(AtomicBool::new(false));
+
+    {
+        let saw_max_readers = Arc::clone(&saw_max_readers);
+
+        check_random(
+            move || {
+                let lock = Arc::new(RwLock::with_max_readers(0usize, 2));
+                let readers = Arc::new(AtomicUsize::new(0));
+
+                let threads = (0..3)
+                    .map(|_| {
+                        let lock = Arc::clone(&lock);
+                        let readers = Arc::clone(&readers);
+                        let saw_max_readers = Arc::clone(&saw_max_readers);
+                        thread::spawn(move || {
+                            let guard = lock.read().unwrap();
+                            let count = readers.fetch_add(1, Ordering::SeqCst) + 1;
+                            assert!(count <= 2, "too many concurrent readers");
+                            if count == 2 {
+                                saw_max_readers.store(true, Ordering::SeqCst);
+                            }
+                            thread::yield_now();
+                            readers.fetch_sub(1, Ordering::SeqCst);
+                            drop(guard);
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                for thd in threads {
+                    thd.join().unwrap();
+                }
+            },
+            1000,
+        );
+    }
+
+    assert!(saw_max_readers.load(Ordering::SeqCst));
+}
+
+#[test]
+fn rwlock_max_readers_try_read() {
+    check_dfs(
+        || {
+            let lock = Arc::new(RwLock::with_max_readers(0usize, 1));
+            let (held_tx, held_rx) = channel();
+            let (release_tx, release_rx) = channel();
+            // Another thread takes the only reader slot, as a thread that already holds read access
+            // can always take another read guard
+            let reader = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    let _guard = lock.read().unwrap();
+                    held_tx.send(()).unwrap();
+                    release_rx.recv().unwrap();
+                })
+            };
+            held_rx.recv().unwrap();
+            assert!(matches!(lock.try_read(), Err(TryLockError::WouldBlock)));
+            release_tx.send(()).unwrap();
+            reader.join().unwrap();
+            assert!(lock.try_read().is_ok());
+        },
+        None,
+    );
+}
+
+// Threads that repeatedly wait for the lock in different modes exercise the waiting-set bookkeeping,
+// which asserts internally that no thread is ever waiting in more than one mode at once
+#[test]
+fn rwlock_mixed_waiters() {
+    check_random(
+        || {
+            let lock = Arc::new(RwLock::new(0usize));
+
+            let threads = (0..3)
+                .map(|i| {
+                    let lock = Arc::clone(&lock);
+                    thread::spawn(move || {
+                        for j in 0..3 {
+                            match (i + j) % 3 {
+                                0 => {
+                                    let _ = *lock.read().unwrap();
+                                }
+                                1 => *lock.write().unwrap() += 1,
+                                _ => *lock.upgradable_read().unwrap().upgrade() += 1,
+                            }
+                            if let Ok(mut guard) = lock.try_write() {
+                                *guard += 1;
+                            }
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for thd in threads {
+                thd.join().unwrap();
+            }
+
+            assert!(*lock.read().unwrap() >= 6);
+        },
+        1000,
+    );
+}
+
+#[test]
+fn rwlock_static() {
+    static LOCK: RwLock<usize> = RwLock::new(0);
+
+    check_dfs(
+        || {
+            // The data in a static lock persists across executions, but its Shuttle state does not
+            *LOCK.write().unwrap() = 0;
+
+            let threads = (0..2)
+                .map(|_| {
+                    thread::spawn(|| {
+                        *LOCK.write().unwrap() += 1;
+                        assert!(*LOCK.read().unwrap() > 0);
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for thd in threads {
+                thd.join().unwrap();
+            }
+
+            assert_eq!(*LOCK.read().unwrap(), 2);
+        },
+        None,
+    );
+}
+
+#[test]
+#[should_panic(
+    expected = "lock cycle: main-thread (task 0) holds L0 waiting L1; <unknown> (task 1) holds L1 waiting L0"
+)]
+fn rwlock_mutex_deadlock_reports_lock_cycle() {
+    check_dfs(
+        || {
+            let rwlock = Arc::new(RwLock::new(0usize));
+            // Mutexes are assigned their lock ID on creation, but RwLocks only when first used
+            let mutex = Arc::new(Mutex::new(0usize));
+
+            {
+                let rwlock = Arc::clone(&rwlock);
+                let mutex = Arc::clone(&mutex);
+                thread::spawn(move || {
+                    let _read = rwlock.read().unwrap();
+                    let _lock = mutex.lock().unwrap();
+                });
+            }
+
+            let _lock = mutex.lock().unwrap();
+            let _write = rwlock.write().unwrap();
+        },
+        None,
+    )
+}
+
+#[test]
+#[should_panic(
+    expected = "lock cycle: main-thread (task 0) holds index (L0) waiting cache_lock (L1); <unknown> (task 1) holds cache_lock (L1) waiting index (L0)"
+)]
+fn named_locks_deadlock_reports_names() {
+    check_dfs(
+        || {
+            let rwlock = Arc::new(RwLock::new_named("cache_lock", 0usize));
+            let mutex = Arc::new(Mutex::new_named("index", 0usize));
+
+            {
+                let rwlock = Arc::clone(&rwlock);
+                let mutex = Arc::clone(&mutex);
+                thread::spawn(move || {
+                    let _read = rwlock.read().unwrap();
+                    let _lock = mutex.lock().unwrap();
+                });
+            }
+
+            let _lock = mutex.lock().unwrap();
+            let _write = rwlock.write().unwrap();
+        },
+        None,
+    )
+}
+
+fn skip_uncontended_release_yields(skip: bool) -> Config {
+    let mut config = Config::new();
+    config.skip_uncontended_release_yields = skip;
+    config
+}
+
+// Two threads that each take write and then read access to a lock no other thread uses
+fn uncontended_rwlocks() {
+    let thd = thread::spawn(|| {
+        let rwlock = RwLock::new(0usize);
+        *rwlock.write().unwrap() += 1;
+        assert_eq!(*rwlock.read().unwrap(), 1);
+    });
+
+    let rwlock = RwLock::new(0usize);
+    *rwlock.write().unwrap() += 1;
+    assert_eq!(*rwlock.read().unwrap(), 1);
+
+    thd.join().unwrap();
+}
+
+#[test]
+fn rwlock_skip_uncontended_release_yields() {
+    let with_yields = Runner::new(DfsScheduler::new(None, false))
+        .with_config(skip_uncontended_release_yields(false))
+        .run(uncontended_rwlocks);
+    let without_yields = Runner::new(DfsScheduler::new(None, false))
+        .with_config(skip_uncontended_release_yields(true))
+        .run(uncontended_rwlocks);
+    assert_eq!(with_yields.iterations, 252);
+    assert_eq!(without_yields.iterations, 20);
+}
+
+#[test]
+fn rwlock_skip_uncontended_release_yields_contended() {
+    let saw_waiter_run_first = Arc::new(AtomicBool::new(false));
+
+    {
+        let saw_waiter_run_first = Arc::clone(&saw_waiter_run_first);
+        let runner = Runner::new(DfsScheduler::new(None, false)).with_config(skip_uncontended_release_yields(true));
+        runner.run(move || {
+            let rwlock = Arc::new(RwLock::new(()));
+            // Shuttle can't see this flag, so only a yield after the release can let the waiter run
+            // before the main thread sets it
+            let released = Arc::new(AtomicBool::new(false));
+            let guard = rwlock.write().unwrap();
+
+            let thd = {
+                let rwlock = Arc::clone(&rwlock);
+                let released = Arc::clone(&released);
+                let saw_waiter_run_first = Arc::clone(&saw_waiter_run_first);
+                thread::spawn(move || {
+                    let _guard = rwlock.write().unwrap();
+                    if !released.load(Ordering::SeqCst) {
+                        saw_waiter_run_first.store(true, Ordering::SeqCst);
+                    }
+                })
+            };
+
+            drop(guard);
+            released.store(true, Ordering::SeqCst);
+            thd.join().unwrap();
+        });
+    }
+
+    assert!(saw_waiter_run_first.load(Ordering::SeqCst));
+}
@@ -0,0 +1,147 @@
+use shuttle::sync::Semaphore;
+use shuttle::{check_dfs, check_random, thread};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use test_env_log::test;
+
+#[test]
+fn semaphore_bounds_holders() {
+    check_random(
+        || {
+            let semaphore = Arc::new(Semaphore::new(2));
+            let holders = Arc::new(AtomicUsize::new(0));
+
+            let thds = (0..4)
+                .map(|_| {
+                    let semaphore = Arc::clone(&semaphore);
+                    let holders = Arc::clone(&holders);
+                    thread::spawn(move || {
+                        let _permit = semaphore.acquire();
+                        let now_holding = holders.fetch_add(1, Ordering::SeqCst) + 1;
+                        assert!(now_holding <= 2, "{} threads hold a permit", now_holding);
+                        thread::yield_now();
+                        holders.fetch_sub(1, Ordering::SeqCst);
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for thd in thds {
+                thd.join().unwrap();
+            }
+            assert_eq!(semaphore.available_permits(), 2);
+        },
+        1000,
+    );
+}
+
+#[test]
+fn semaphore_try_acquire() {
+    check_dfs(
+        || {
+            let semaphore = Semaphore::new(1);
+            let permit = semaphore.try_acquire().unwrap();
+            assert_eq!(semaphore.try_acquire().unwrap_err().to_string(), "no permits available");
+            drop(permit);
+            assert!(semaphore.try_acquire().is_ok());
+        },
+        None,
+    );
+}
+
+#[test]
+fn semaphore_release_is_yield_point() {
+    let saw_waiter_first = Arc::new(AtomicBool::new(false));
+
+    {
+        let saw_waiter_first = Arc::clone(&saw_waiter_first);
+
+        check_dfs(
+            move || {
+                let semaphore = Arc::new(Semaphore::new(1));
+                let acquired = Arc::new(AtomicBool::new(false));
+
+                let permit = semaphore.acquire();
+                let thd = {
+                    let semaphore = Arc::clone(&semaphore);
+                    let acquired = Arc::clone(&acquired);
+                    thread::spawn(move || {
+                        let _permit = semaphore.acquire();
+                        acquired.store(true, Ordering::SeqCst);
+                    })
+                };
+
+                drop(permit);
+                // The waiter can only have taken the permit already if releasing it was a yield point
+                if acquired.load(Ordering::SeqCst) {
+                    saw_waiter_first.store(true, Ordering::SeqCst);
+                }
+                thd.join().unwrap();
+            },
+            None,
+        );
+    }
+
+    assert!(saw_waiter_first.load(Ordering::SeqCst));
+}
+
+#[test]
+#[should_panic(expected = "deadlock")]
+fn semaphore_exhausted() {
+    check_dfs(
+        || {
+            let semaphore = Semaphore::new(1);
+            let _permit = semaphore.acquire();
+            let _another = semaphore.acquire();
+        },
+        None,
+    );
+}
+
+#[test]
+fn semaphore_acquire_many() {
+    check_dfs(
+        || {
+            let semaphore = Arc::new(Semaphore::new(4));
+            let held = Arc::new(AtomicUsize::new(0));
+
+            let thds = [2, 3]
+                .iter()
+                .map(|&n| {
+                    let semaphore = Arc::clone(&semaphore);
+                    let held = Arc::clone(&held);
+                    thread::spawn(move || {
+                        let permit = semaphore.acquire_many(n);
+                        assert_eq!(permit.num_permits(), n);
+                        let now_held = held.fetch_add(n, Ordering::SeqCst) + n;
+                        assert!(now_held <= 4, "{} permits held", now_held);
+                        thread::yield_now();
+                        held.fetch_sub(n, Ordering::SeqCst);
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            // Both threads complete: neither ends up holding some permits while waiting for more
+            for thd in thds {
+                thd.join().unwrap();
+            }
+            assert_eq!(semaphore.available_permits(), 4);
+        },
+        None,
+    );
+}
+
+#[test]
+fn semaphore_try_acquire_many() {
+    check_dfs(
+        || {
+            let semaphore = Semaphore::new(4);
+            let permit = semaphore.try_acquire_many(3).unwrap();
+            // A failed attempt doesn't consume any of the remaining permits
+            assert!(semaphore.try_acquire_many(2).is_err());
+            assert_eq!(semaphore.available_permits(), 1);
+            drop(permit);
+            assert_eq!(semaphore.try_acquire_many(4).unwrap().num_permits(), 4);
+        },
+        None,
+    );
+}
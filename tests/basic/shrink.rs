@@ -1,5 +1,6 @@
-use shuttle::scheduler::{ReplayScheduler, Schedule};
-use shuttle::{thread, Runner};
+use shuttle::scheduler::{RandomScheduler, ReplayScheduler, Schedule};
+use shuttle::{minimize_schedule, replay, thread, Runner};
+use std::panic;
 use std::sync::atomic::{AtomicI32, Ordering};
 use std::sync::Arc;
 use test_env_log::test;
@@ -30,7 +31,7 @@ fn shrink_before_min() {
     // increment counter to 3 and then decrement to 0 to cause panic
     let schedule = Schedule::new_from_task_ids(0, vec![0, 0, 1, 1, 1, 2, 2, 2, 2]);
     let scheduler = ReplayScheduler::new_from_schedule(schedule);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(counter_test);
 }
 
@@ -40,6 +41,48 @@ fn shrink_after_min() {
     // minimal schedule requires no increments
     let min_schedule = Schedule::new_from_task_ids(0, vec![0, 0, 2]);
     let scheduler = ReplayScheduler::new_from_schedule(min_schedule);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(counter_test);
 }
+
+// Two threads race on a store, while some other threads make noise
+fn noisy_race() {
+    let x = Arc::new(AtomicI32::new(0));
+
+    {
+        let x = Arc::clone(&x);
+        thread::spawn(move || x.store(1, Ordering::SeqCst));
+    }
+    {
+        let x = Arc::clone(&x);
+        thread::spawn(move || assert_eq!(x.load(Ordering::SeqCst), 0, "saw the store"));
+    }
+
+    for _ in 0..3 {
+        thread::spawn(|| {
+            for _ in 0..10 {
+                thread::yield_now();
+            }
+        });
+    }
+}
+
+#[test]
+fn minimize_random_schedule() {
+    // The random scheduler runs the noisy threads for a while before it finds the race
+    let result = panic::catch_unwind(|| {
+        let runner = Runner::new(RandomScheduler::new_from_seed(4, 1000));
+        runner.run(noisy_race);
+    })
+    .expect_err("test should panic");
+    let output = result.downcast::<String>().unwrap();
+    let encoded = crate::parse_schedule::from_stdout(&output).expect("output should contain a schedule");
+    let schedule = Schedule::decode(&encoded).unwrap();
+    assert!(schedule.len() > 20);
+
+    // The minimal schedule spawns the racing threads and runs them in the wrong order
+    let minimized = minimize_schedule(noisy_race, schedule);
+    assert!(minimized.len() <= 5, "{:?}", minimized);
+    let result = panic::catch_unwind(|| replay(noisy_race, &minimized.encode())).expect_err("replay should panic");
+    assert!(result.downcast::<String>().unwrap().contains("saw the store"));
+}
@@ -1,7 +1,10 @@
-use shuttle::sync::Mutex;
-use shuttle::{check_dfs, check_random, thread};
+use shuttle::scheduler::DfsScheduler;
+use shuttle::sync::{Mutex, ReentrantMutex};
+use shuttle::{check_dfs, check_random, thread, Config, Runner};
+use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use test_env_log::test;
 
 #[test]
@@ -32,6 +35,44 @@ fn thread_yield_point() {
     assert_eq!(success_clone.load(Ordering::SeqCst), 0x3);
 }
 
+// Returns whether a thread can observe the intermediate value of two writes that aren't yield
+// points, with or without a `yield_now` between them
+fn observes_intermediate_write(yield_between: bool) -> bool {
+    let observed = Arc::new(AtomicBool::new(false));
+    let observed_clone = Arc::clone(&observed);
+
+    check_dfs(
+        move || {
+            // Not a Shuttle atomic, so accesses to it aren't yield points
+            let value = Arc::new(AtomicU8::new(0));
+            let writer = {
+                let value = Arc::clone(&value);
+                thread::spawn(move || {
+                    value.store(1, Ordering::SeqCst);
+                    if yield_between {
+                        thread::yield_now();
+                    }
+                    value.store(2, Ordering::SeqCst);
+                })
+            };
+
+            if value.load(Ordering::SeqCst) == 1 {
+                observed.store(true, Ordering::SeqCst);
+            }
+            writer.join().unwrap();
+        },
+        None,
+    );
+
+    observed_clone.load(Ordering::SeqCst)
+}
+
+#[test]
+fn thread_yield_now_adds_interleaving() {
+    assert!(!observes_intermediate_write(false));
+    assert!(observes_intermediate_write(true));
+}
+
 #[test]
 fn thread_join() {
     check_random(
@@ -50,6 +91,75 @@ fn thread_join() {
     );
 }
 
+// A thread that panics returns the panic payload from `join`, and the test carries on
+#[test]
+fn thread_join_panic() {
+    check_dfs(
+        || {
+            let lock = Arc::new(Mutex::new(0));
+            let lock_clone = Arc::clone(&lock);
+            let handle = thread::spawn(move || {
+                *lock_clone.lock().unwrap() += 1;
+                panic!("expected panic");
+            });
+            *lock.lock().unwrap() += 1;
+
+            let err = handle.join().unwrap_err();
+            assert_eq!(err.downcast_ref::<&str>(), Some(&"expected panic"));
+            assert_eq!(*lock.lock().unwrap(), 2);
+        },
+        None,
+    );
+}
+
+#[test]
+fn thread_join_panic_typed_payload() {
+    #[derive(Debug, PartialEq)]
+    struct WorkerError {
+        code: usize,
+    }
+
+    check_dfs(
+        || {
+            let handle = thread::spawn(|| {
+                thread::yield_now();
+                std::panic::panic_any(WorkerError { code: 42 });
+            });
+            thread::yield_now();
+
+            let err = handle.join().unwrap_err();
+            assert_eq!(err.downcast_ref::<WorkerError>(), Some(&WorkerError { code: 42 }));
+        },
+        None,
+    );
+}
+
+// If nobody joins a thread that panics, the panic still fails the test
+#[test]
+#[should_panic(expected = "expected panic")]
+fn thread_join_drop_panic() {
+    check_dfs(
+        || {
+            let handle = thread::spawn(|| panic!("expected panic"));
+            drop(handle);
+        },
+        None,
+    );
+}
+
+#[test]
+#[should_panic(expected = "expected panic")]
+fn thread_join_drop_after_panic() {
+    check_dfs(
+        || {
+            let handle = thread::spawn(|| panic!("expected panic"));
+            thread::yield_now();
+            drop(handle);
+        },
+        None,
+    );
+}
+
 #[test]
 fn thread_join_drop() {
     check_random(
@@ -67,6 +177,45 @@ fn thread_join_drop() {
     );
 }
 
+fn poll_until_finished() {
+    let value = Arc::new(Mutex::new(0));
+    let producer = {
+        let value = Arc::clone(&value);
+        thread::spawn(move || {
+            *value.lock().unwrap() = 42;
+            7
+        })
+    };
+
+    while !producer.is_finished() {
+        assert_eq!(*value.lock().unwrap() % 42, 0);
+    }
+    // Once the producer has finished, all of its effects are visible
+    assert_eq!(*value.lock().unwrap(), 42);
+    assert_eq!(producer.join().unwrap(), 7);
+}
+
+// The poller can spin any number of times before the producer finishes, so these tests use the
+// random scheduler, which eventually runs the producer, rather than exhaustive search
+#[test]
+fn thread_join_is_finished() {
+    check_random(poll_until_finished, 1000)
+}
+
+#[test]
+fn thread_scope_is_finished() {
+    check_random(
+        || {
+            thread::scope(|s| {
+                let handle = s.spawn(|| 1);
+                while !handle.is_finished() {}
+                assert_eq!(handle.join().unwrap(), 1);
+            });
+        },
+        1000,
+    )
+}
+
 #[test]
 fn thread_builder_name() {
     check_random(
@@ -83,6 +232,45 @@ fn thread_builder_name() {
     );
 }
 
+#[test]
+#[should_panic(expected = "worker (task 1)")]
+fn thread_builder_name_in_deadlock() {
+    check_dfs(
+        || {
+            let lock = Arc::new(Mutex::new(()));
+            let _guard = lock.lock().unwrap();
+
+            let lock_clone = Arc::clone(&lock);
+            let handle = thread::Builder::new()
+                .name("worker".into())
+                .spawn(move || {
+                    let _guard = lock_clone.lock().unwrap();
+                })
+                .unwrap();
+            handle.join().unwrap();
+        },
+        None,
+    );
+}
+
+#[test]
+#[should_panic(expected = "test panicked in task 'worker'")]
+fn thread_builder_name_in_panic() {
+    check_dfs(
+        || {
+            thread::Builder::new()
+                .name("worker".into())
+                .spawn(|| {
+                    assert_eq!(thread::current().name(), Some("worker"));
+                    // Formatted so the payload is a `String` that Shuttle adds the failure report to
+                    panic!("expected panic in {}", thread::current().name().unwrap());
+                })
+                .unwrap();
+        },
+        None,
+    );
+}
+
 #[test]
 fn thread_identity() {
     check_dfs(
@@ -116,6 +304,264 @@ fn thread_identity() {
     );
 }
 
+// Thread IDs are stable within a thread, distinct across threads, and numbered in spawn order, so
+// they can key per-thread data
+#[test]
+fn thread_id_keyed_map() {
+    check_random(
+        || {
+            let seen = Arc::new(Mutex::new(HashMap::new()));
+            let record = {
+                let seen = Arc::clone(&seen);
+                move |value: usize| {
+                    let id = thread::current().id();
+                    thread::yield_now();
+                    assert_eq!(thread::current().id(), id);
+                    assert!(seen.lock().unwrap().insert(id, value).is_none());
+                }
+            };
+
+            let handles = (1..3)
+                .map(|i| {
+                    let record = record.clone();
+                    thread::spawn(move || record(i))
+                })
+                .collect::<Vec<_>>();
+            record(0);
+
+            let mut ids = vec![thread::current().id()];
+            for handle in handles {
+                ids.push(handle.thread().id());
+                handle.join().unwrap();
+            }
+
+            let seen = seen.lock().unwrap();
+            for (i, id) in ids.iter().enumerate() {
+                assert_eq!(usize::from(*id), i);
+                assert_eq!(seen[id], i);
+            }
+            assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+        },
+        100,
+    );
+}
+
+// Scoped threads can each mutably borrow a disjoint part of a local array
+#[test]
+fn thread_scope_disjoint_mut() {
+    check_dfs(
+        || {
+            let mut data = [0usize; 4];
+            thread::scope(|s| {
+                for (i, chunk) in data.chunks_mut(2).enumerate() {
+                    s.spawn(move || {
+                        for value in chunk.iter_mut() {
+                            *value = i + 1;
+                        }
+                    });
+                }
+            });
+            // The scope joined all the threads, so their writes are visible
+            assert_eq!(data, [1, 1, 2, 2]);
+        },
+        None,
+    );
+}
+
+// Scoped threads can share borrowed data, and return values through their join handles
+#[test]
+fn thread_scope_shared_ref() {
+    check_dfs(
+        || {
+            let data = vec![1, 2, 3];
+            let counter = Mutex::new(0);
+            let total = thread::scope(|s| {
+                let handles = (0..2)
+                    .map(|_| {
+                        s.spawn(|| {
+                            *counter.lock().unwrap() += 1;
+                            data.iter().sum::<usize>()
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                handles.into_iter().map(|h| h.join().unwrap()).sum::<usize>()
+            });
+            assert_eq!(total, 12);
+            assert_eq!(*counter.lock().unwrap(), 2);
+            assert_eq!(data, vec![1, 2, 3]);
+        },
+        None,
+    );
+}
+
+// The end of a scope waits for threads spawned by other scoped threads too
+#[test]
+fn thread_scope_nested_spawn() {
+    check_dfs(
+        || {
+            let counter = Mutex::new(0);
+            let counter_ref = &counter;
+            thread::scope(|s| {
+                s.spawn(move || {
+                    s.spawn(move || *counter_ref.lock().unwrap() += 1);
+                    *counter_ref.lock().unwrap() += 1;
+                });
+            });
+            assert_eq!(*counter.lock().unwrap(), 2);
+        },
+        None,
+    );
+}
+
+// A consumer parks until a producer hands it a value. If an unpark could be lost (e.g., because it
+// happened before the consumer parked), some schedule would deadlock.
+#[test]
+fn thread_park_handoff() {
+    check_dfs(
+        || {
+            let slot = Arc::new(Mutex::new(None));
+            let consumer = {
+                let slot = Arc::clone(&slot);
+                thread::spawn(move || loop {
+                    if let Some(value) = slot.lock().unwrap().take() {
+                        return value;
+                    }
+                    thread::park();
+                })
+            };
+
+            *slot.lock().unwrap() = Some(42);
+            consumer.thread().unpark();
+
+            assert_eq!(consumer.join().unwrap(), 42);
+        },
+        None,
+    );
+}
+
+#[test]
+fn thread_unpark_before_park() {
+    check_dfs(
+        || {
+            let main = thread::current();
+            let handle = thread::spawn(move || main.unpark());
+            handle.join().unwrap();
+            // The token is already available, so this doesn't block
+            thread::park();
+        },
+        None,
+    );
+}
+
+// The unpark token is a single slot, so two unparks only let one park return
+#[test]
+#[should_panic(expected = "deadlock")]
+fn thread_unpark_token_single_slot() {
+    check_dfs(
+        || {
+            let main = thread::current();
+            let handle = thread::spawn(move || {
+                main.unpark();
+                main.unpark();
+            });
+            handle.join().unwrap();
+            thread::park();
+            thread::park();
+        },
+        None,
+    );
+}
+
+#[test]
+fn thread_park_timeout_without_unpark() {
+    // Nobody ever unparks the poller, so it can only make progress by timing out
+    check_random(
+        || {
+            let done = Arc::new(Mutex::new(false));
+            let poller = {
+                let done = Arc::clone(&done);
+                thread::spawn(move || {
+                    while !*done.lock().unwrap() {
+                        thread::park_timeout(Duration::from_secs(10));
+                    }
+                })
+            };
+
+            *done.lock().unwrap() = true;
+            poller.join().unwrap();
+        },
+        1000,
+    )
+}
+
+#[test]
+fn thread_park_timeout_explores_both_outcomes() {
+    let saw_timeout = Arc::new(AtomicBool::new(false));
+    let saw_unpark = Arc::new(AtomicBool::new(false));
+
+    {
+        let saw_timeout = Arc::clone(&saw_timeout);
+        let saw_unpark = Arc::clone(&saw_unpark);
+
+        check_dfs(
+            move || {
+                let unparked = Arc::new(AtomicBool::new(false));
+                let main = thread::current();
+                {
+                    let unparked = Arc::clone(&unparked);
+                    thread::spawn(move || {
+                        unparked.store(true, Ordering::SeqCst);
+                        main.unpark();
+                    });
+                }
+
+                thread::park_timeout(Duration::from_secs(10));
+                if unparked.load(Ordering::SeqCst) {
+                    saw_unpark.store(true, Ordering::SeqCst);
+                } else {
+                    saw_timeout.store(true, Ordering::SeqCst);
+                }
+            },
+            None,
+        );
+    }
+
+    assert!(saw_timeout.load(Ordering::SeqCst));
+    assert!(saw_unpark.load(Ordering::SeqCst));
+}
+
+fn park_without_recheck() {
+    let ready = Arc::new(Mutex::new(false));
+    let main = thread::current();
+    {
+        let ready = Arc::clone(&ready);
+        thread::spawn(move || {
+            *ready.lock().unwrap() = true;
+            main.unpark();
+        });
+    }
+
+    if !*ready.lock().unwrap() {
+        thread::park();
+    }
+    assert!(*ready.lock().unwrap(), "woke up before being unparked");
+}
+
+#[test]
+fn thread_park_without_recheck_no_spurious_wakeups() {
+    check_dfs(park_without_recheck, None)
+}
+
+#[test]
+#[should_panic(expected = "woke up before being unparked")]
+fn thread_park_without_recheck_spurious_wakeups() {
+    let mut config = Config::new();
+    config.spurious_wakeups = true;
+    let scheduler = DfsScheduler::new(None, false);
+    let runner = Runner::new(scheduler).with_config(config);
+    runner.run(park_without_recheck);
+}
+
 /// Thread local tests
 ///
 /// The first three are based on Loom's thread local tests:
@@ -349,6 +795,35 @@ mod thread_local {
         );
     }
 
+    // Every thread in every execution starts from a fresh value, so a value set in one execution
+    // never leaks into the next
+    #[test]
+    fn fresh_across_executions() {
+        shuttle::thread_local! {
+            static LOCAL: RefCell<usize> = RefCell::new(0);
+        }
+
+        fn increment_once() {
+            LOCAL.with(|local| {
+                assert_eq!(*local.borrow(), 0);
+                *local.borrow_mut() += 1;
+            });
+            thread::yield_now();
+            LOCAL.with(|local| assert_eq!(*local.borrow(), 1));
+        }
+
+        check_random(
+            || {
+                let threads = (0..2).map(|_| thread::spawn(increment_once)).collect::<Vec<_>>();
+                increment_once();
+                for thd in threads {
+                    thd.join().unwrap();
+                }
+            },
+            100,
+        )
+    }
+
     #[test]
     fn multiple_accesses() {
         shuttle::thread_local! {
@@ -376,3 +851,172 @@ mod thread_local {
         )
     }
 }
+
+// Abort a thread that might be in the middle of a critical section, and check that the lock is
+// released (and poisoned, if `poison_on_abort` is set) so the main thread can take it. Returns the
+// values the main thread saw in the lock.
+fn abort_in_critical_section(poison_on_abort: bool) -> HashSet<usize> {
+    let seen = Arc::new(std::sync::Mutex::new(HashSet::new()));
+
+    let mut config = Config::new();
+    config.poison_on_abort = poison_on_abort;
+    let runner = Runner::new(DfsScheduler::new(None, false)).with_config(config);
+    {
+        let seen = Arc::clone(&seen);
+        runner.run(move || {
+            let lock = Arc::new(Mutex::new(0));
+            let worker = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || {
+                    let mut guard = lock.lock().unwrap();
+                    *guard = 1;
+                    // A cancellation point in the middle of the critical section
+                    thread::yield_now();
+                    *guard = 2;
+                })
+            };
+
+            worker.abort();
+            let (value, poisoned) = match lock.lock() {
+                Ok(guard) => (*guard, false),
+                Err(err) => (*err.into_inner(), true),
+            };
+            // The worker only stops early if it was aborted before it could finish, and only an
+            // abort in the middle of the critical section can poison the lock
+            assert_eq!(worker.join().is_err(), value != 2);
+            assert_eq!(poisoned, poison_on_abort && value == 1);
+            seen.lock().unwrap().insert(value);
+        });
+    }
+
+    Arc::try_unwrap(seen).unwrap().into_inner().unwrap()
+}
+
+#[test]
+fn thread_abort_releases_lock() {
+    // The abort can land before the worker starts, in its critical section, or after it finishes
+    assert_eq!(abort_in_critical_section(false), HashSet::from([0, 1, 2]));
+}
+
+#[test]
+fn thread_abort_poisons_lock() {
+    assert_eq!(abort_in_critical_section(true), HashSet::from([0, 1, 2]));
+}
+
+// Like `thread_abort_releases_lock`, for a reentrant mutex the worker holds several guards for
+#[test]
+fn thread_abort_releases_reentrant_mutex() {
+    let seen = Arc::new(std::sync::Mutex::new(HashSet::new()));
+    {
+        let seen = Arc::clone(&seen);
+        check_dfs(
+            move || {
+                let lock = Arc::new(ReentrantMutex::new(std::cell::Cell::new(0)));
+                let worker = {
+                    let lock = Arc::clone(&lock);
+                    thread::spawn(move || {
+                        let outer = lock.lock();
+                        let inner = lock.lock();
+                        inner.set(1);
+                        thread::yield_now();
+                        outer.set(2);
+                    })
+                };
+
+                worker.abort();
+                let value = lock.lock().get();
+                assert_eq!(worker.join().is_err(), value != 2);
+                assert!(!lock.is_poisoned());
+                seen.lock().unwrap().insert(value);
+            },
+            None,
+        );
+    }
+    assert_eq!(*seen.lock().unwrap(), HashSet::from([0, 1, 2]));
+}
+
+#[test]
+fn thread_abort_parked() {
+    check_dfs(
+        || {
+            let worker = thread::spawn(|| loop {
+                thread::park();
+            });
+            worker.abort();
+            let err = worker.join().unwrap_err();
+            assert_eq!(err.downcast_ref::<&str>(), Some(&"thread was aborted"));
+        },
+        None,
+    );
+}
+
+// Dropping the handle of an aborted thread doesn't fail the test
+#[test]
+fn thread_abort_drop_handle() {
+    check_dfs(
+        || {
+            let worker = thread::spawn(thread::yield_now);
+            worker.abort();
+        },
+        None,
+    );
+}
+
+fn setup_state(spawn_init: fn(Box<dyn FnOnce() + Send>) -> thread::JoinHandle<()>) {
+    use shuttle::sync::atomic::{AtomicUsize, Ordering};
+
+    let fields = Arc::new([AtomicUsize::new(0), AtomicUsize::new(0), AtomicUsize::new(0)]);
+    let init = {
+        let fields = Arc::clone(&fields);
+        spawn_init(Box::new(move || {
+            for field in fields.iter() {
+                field.store(1, Ordering::SeqCst);
+            }
+        }))
+    };
+    let worker = {
+        let fields = Arc::clone(&fields);
+        thread::spawn(move || fields.iter().filter(|f| f.load(Ordering::SeqCst) == 1).count())
+    };
+
+    assert_eq!(worker.join().unwrap(), 3, "worker saw uninitialized fields");
+    init.join().unwrap();
+}
+
+#[test]
+fn thread_spawn_setup() {
+    check_dfs(|| setup_state(thread::spawn_setup), None);
+}
+
+#[test]
+#[should_panic(expected = "worker saw uninitialized fields")]
+fn thread_spawn_without_setup() {
+    check_dfs(|| setup_state(thread::spawn), None);
+}
+
+#[test]
+fn thread_spawn_setup_ends_at_yield() {
+    check_random(
+        || {
+            use shuttle::sync::atomic::{AtomicBool, Ordering};
+
+            let ready = Arc::new(AtomicBool::new(false));
+            let go = Arc::new(AtomicBool::new(false));
+            let setup = {
+                let ready = Arc::clone(&ready);
+                let go = Arc::clone(&go);
+                thread::spawn_setup(move || {
+                    ready.store(true, Ordering::SeqCst);
+                    // Once the setup thread yields, other threads can run, so this loop can finish
+                    while !go.load(Ordering::SeqCst) {
+                        thread::yield_now();
+                    }
+                })
+            };
+            assert!(ready.load(Ordering::SeqCst));
+            go.store(true, Ordering::SeqCst);
+            setup.join().unwrap();
+        },
+        100,
+    );
+}
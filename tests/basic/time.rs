@@ -0,0 +1,146 @@
+use shuttle::sync::Mutex;
+use shuttle::time::{self, Instant};
+use shuttle::{check_dfs, check_random, future, thread};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use test_env_log::test;
+
+// Spawn a thread for each duration that sleeps for that long and then logs its index and the time
+// it woke up
+fn sleepers(durations: &[u64]) -> Vec<(usize, Duration)> {
+    let start = Instant::now();
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let thds = durations
+        .iter()
+        .enumerate()
+        .map(|(i, millis)| {
+            let log = Arc::clone(&log);
+            let millis = *millis;
+            thread::spawn(move || {
+                time::sleep(Duration::from_millis(millis));
+                log.lock().unwrap().push((i, start.elapsed()));
+            })
+        })
+        .collect::<Vec<_>>();
+    for thd in thds {
+        thd.join().unwrap();
+    }
+    let log = log.lock().unwrap();
+    log.clone()
+}
+
+#[test]
+fn sleep_wakes_in_duration_order() {
+    check_dfs(
+        || {
+            let log = sleepers(&[20, 10]);
+            assert_eq!(
+                log,
+                vec![(1, Duration::from_millis(10)), (0, Duration::from_millis(20))]
+            );
+        },
+        None,
+    );
+}
+
+// Timers that expire at the same time are woken together, so both orders are explored
+#[test]
+fn sleep_same_duration_explores_both_orders() {
+    let orders = Arc::new(std::sync::Mutex::new(HashSet::new()));
+    {
+        let orders = Arc::clone(&orders);
+        check_dfs(
+            move || {
+                let log = sleepers(&[10, 10]);
+                assert!(log.iter().all(|(_, elapsed)| *elapsed == Duration::from_millis(10)));
+                orders
+                    .lock()
+                    .unwrap()
+                    .insert(log.iter().map(|(i, _)| *i).collect::<Vec<_>>());
+            },
+            None,
+        );
+    }
+    let orders = orders.lock().unwrap();
+    assert_eq!(*orders, vec![vec![0, 1], vec![1, 0]].into_iter().collect());
+}
+
+// The clock doesn't advance while any task can still run
+#[test]
+fn clock_only_advances_when_all_tasks_wait() {
+    check_random(
+        || {
+            let start = Instant::now();
+            let thd = thread::spawn(move || {
+                time::sleep(Duration::from_secs(1));
+                start.elapsed()
+            });
+            for _ in 0..5 {
+                thread::yield_now();
+                assert_eq!(start.elapsed(), Duration::ZERO);
+            }
+            assert_eq!(thd.join().unwrap(), Duration::from_secs(1));
+            assert_eq!(start.elapsed(), Duration::from_secs(1));
+        },
+        100,
+    );
+}
+
+#[test]
+fn sleep_until_past_deadline_is_yield() {
+    check_dfs(
+        || {
+            let start = Instant::now();
+            time::sleep(Duration::from_millis(5));
+            time::sleep_until(start);
+            time::sleep(Duration::ZERO);
+            assert_eq!(start.elapsed(), Duration::from_millis(5));
+        },
+        None,
+    );
+}
+
+#[test]
+fn sleep_in_async_tasks() {
+    check_dfs(
+        || {
+            let log = Arc::new(Mutex::new(Vec::new()));
+            let tasks = [30, 10, 20]
+                .iter()
+                .map(|millis| {
+                    let log = Arc::clone(&log);
+                    let millis = *millis;
+                    future::spawn(async move {
+                        time::sleep(Duration::from_millis(millis));
+                        log.lock().unwrap().push(millis);
+                    })
+                })
+                .collect::<Vec<_>>();
+            future::block_on(async move {
+                for task in tasks {
+                    task.await.unwrap();
+                }
+            });
+            assert_eq!(*log.lock().unwrap(), vec![10, 20, 30]);
+        },
+        None,
+    );
+}
+
+#[test]
+fn instant_arithmetic() {
+    check_dfs(
+        || {
+            let start = Instant::now();
+            let later = start + Duration::from_secs(2);
+            assert_eq!(later - start, Duration::from_secs(2));
+            assert_eq!(start - later, Duration::ZERO);
+            assert_eq!(start.checked_duration_since(later), None);
+            assert_eq!(later - Duration::from_secs(2), start);
+            assert_eq!(start.checked_sub(Duration::from_secs(1)), None);
+            assert!(later > start);
+        },
+        None,
+    );
+}
@@ -1,9 +1,11 @@
 use shuttle::scheduler::{RandomScheduler, Schedule, Scheduler, TaskId};
+use shuttle::sync::atomic::{AtomicBool, Ordering};
 use shuttle::sync::Mutex;
-use shuttle::Config;
 use shuttle::{thread, Runner};
+use shuttle::{Config, MaxSteps};
+use std::panic;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use test_env_log::test;
 
 /// A scheduler that sleeps between executions to test timeout behavior
@@ -47,9 +49,9 @@ fn runner_timeout() {
     let mut config = Config::new();
     config.max_time = Some(Duration::from_secs(1));
 
-    let runner = Runner::new(scheduler, config);
+    let runner = Runner::new(scheduler).with_config(config);
 
-    let iterations = runner.run(|| {
+    let stats = runner.run(|| {
         let lock = Arc::new(Mutex::new(0usize));
         let lock_clone = Arc::clone(&lock);
 
@@ -62,9 +64,48 @@ fn runner_timeout() {
         *counter += 1;
     });
 
+    let iterations = stats.iterations;
     assert!(iterations < 10000, "test must stop well before max_iterations");
     assert!(
         iterations >= 1,
         "test must run at least once (maybe running on a _very_ slow host?"
     );
 }
+
+// A thread that spins forever waiting for a flag that is never set
+fn spin_forever() {
+    let flag = Arc::new(AtomicBool::new(false));
+    thread::spawn(move || while !flag.load(Ordering::SeqCst) {});
+}
+
+#[test]
+fn runner_timeout_stops_spinning_iteration() {
+    let mut config = Config::new();
+    config.max_steps = MaxSteps::None;
+    config.max_time = Some(Duration::from_millis(100));
+
+    let start = Instant::now();
+    let runner = Runner::new(RandomScheduler::new(100)).with_config(config);
+    let stats = runner.run(spin_forever);
+
+    // The first iteration never finishes on its own, so the time limit must have stopped it
+    assert_eq!(stats.iterations, 1);
+    assert!(stats.hit_time_limit);
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn max_steps_reports_livelock() {
+    let mut config = Config::new();
+    config.max_steps = MaxSteps::FailAfter(100);
+
+    let result = panic::catch_unwind(|| {
+        let runner = Runner::new(RandomScheduler::new(100)).with_config(config);
+        runner.run(spin_forever);
+    })
+    .expect_err("spin loop should exceed the step bound");
+    let message = result.downcast::<String>().unwrap();
+    assert!(message.contains("exceeded max_steps bound 100"), "{}", message);
+    assert!(message.contains("livelock"), "{}", message);
+    assert!(message.contains("failing schedule"), "{}", message);
+}
@@ -0,0 +1,189 @@
+use shuttle::scheduler::{RoundRobinScheduler, Schedule};
+use shuttle::sync::Mutex;
+use shuttle::{thread, Runner};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use test_env_log::test;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Record};
+use tracing::{Event, Id, Metadata, Subscriber};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RecordedEvent {
+    message: String,
+    // The `task` field of the event itself, if any
+    task: Option<u64>,
+    // The `task` field of the innermost span the event happened in, if any
+    step_task: Option<u64>,
+}
+
+// `Subscriber` that records every event along with the task of the `step` span it happened in
+#[derive(Clone, Default)]
+struct EventSubscriber {
+    next_id: Arc<AtomicU64>,
+    span_tasks: Arc<std::sync::Mutex<HashMap<u64, Option<u64>>>>,
+    stack: Arc<std::sync::Mutex<Vec<u64>>>,
+    events: Arc<std::sync::Mutex<Vec<RecordedEvent>>>,
+}
+
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    task: Option<u64>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "task" {
+            self.task = Some(value);
+        }
+    }
+}
+
+impl Subscriber for EventSubscriber {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let mut visitor = FieldVisitor::default();
+        span.record(&mut visitor);
+        self.span_tasks.lock().unwrap().insert(id, visitor.task);
+        Id::from_u64(id)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let step_task = self
+            .stack
+            .lock()
+            .unwrap()
+            .last()
+            .and_then(|id| self.span_tasks.lock().unwrap()[id]);
+        self.events.lock().unwrap().push(RecordedEvent {
+            message: visitor.message,
+            task: visitor.task,
+            step_task,
+        });
+    }
+
+    fn enter(&self, span: &Id) {
+        self.stack.lock().unwrap().push(span.into_u64());
+    }
+
+    fn exit(&self, _span: &Id) {
+        self.stack.lock().unwrap().pop();
+    }
+}
+
+#[test]
+fn trace_events_for_lock_program() {
+    let subscriber = EventSubscriber::default();
+    {
+        let _guard = tracing::subscriber::set_default(subscriber.clone());
+        let runner = Runner::new(RoundRobinScheduler::new());
+        runner.run(|| {
+            let lock = Arc::new(Mutex::new(0usize));
+            let thd = {
+                let lock = Arc::clone(&lock);
+                thread::spawn(move || *lock.lock().unwrap() += 1)
+            };
+            *lock.lock().unwrap() += 1;
+            thd.join().unwrap();
+        });
+    }
+
+    let events = subscriber.events.lock().unwrap();
+    let find = |prefix: &'static str| events.iter().filter(move |e| e.message.starts_with(prefix));
+
+    // The main thread spawns task 1
+    assert!(find("spawned").any(|e| e.task == Some(1) && e.step_task == Some(0)));
+    assert!(find("scheduling decision").count() > 0);
+
+    // Each task acquires and releases the lock inside its own step span
+    for task in 0..2 {
+        assert!(find("acquired mutex").any(|e| e.step_task == Some(task)));
+        assert!(find("releasing mutex").any(|e| e.step_task == Some(task)));
+    }
+
+    // The main thread blocks joining the other thread, and is unblocked when it finishes
+    assert!(find("blocked").any(|e| e.task == Some(0)));
+    assert!(find("unblocked").any(|e| e.task == Some(0) && e.step_task == Some(1)));
+}
+
+fn lock_handoff() {
+    let lock = Arc::new(Mutex::new(0usize));
+    let guard = lock.lock().unwrap();
+    let thd = {
+        let lock = Arc::clone(&lock);
+        thread::spawn(move || *lock.lock().unwrap() += 1)
+    };
+    // Let the other thread try to take the lock while we hold it
+    thread::yield_now();
+    drop(guard);
+    thd.join().unwrap();
+}
+
+#[test]
+fn display_trace_lock_program() {
+    let schedule = Schedule::new_from_task_ids(0, vec![0, 0, 1, 0, 0, 1, 1, 0, 0]);
+    let trace = schedule.display_trace(lock_handoff);
+
+    // Strip the mutex addresses, which differ from run to run
+    let lines = trace
+        .lines()
+        .map(|line| line.split(" 0x").next().unwrap())
+        .collect::<Vec<_>>();
+    let expected = [
+        "step 1: task 0 acquired mutex",
+        "step 1: task 0 spawned task 1",
+        "step 2: task 1 waiting to acquire mutex",
+        "step 2: task 1 blocked",
+        "step 4: task 0 releasing mutex",
+        "step 4: task 0 unblocked task 1",
+        "step 5: task 1 acquired mutex",
+        "step 5: task 1 releasing mutex",
+    ];
+    let positions = expected
+        .iter()
+        .map(|line| {
+            lines
+                .iter()
+                .position(|l| l == line)
+                .unwrap_or_else(|| panic!("missing {:?} in\n{}", line, trace))
+        })
+        .collect::<Vec<_>>();
+    assert!(positions.windows(2).all(|w| w[0] < w[1]), "{}", trace);
+    assert!(!trace.contains("execution failed"), "{}", trace);
+}
+
+#[test]
+fn display_trace_failure() {
+    // `lock_handoff` can't finish if the main thread never releases the lock
+    let schedule = Schedule::new_from_task_ids(0, vec![0, 0, 1, 0]);
+    let trace = schedule.display_trace(|| {
+        let lock = Arc::new(Mutex::new(0usize));
+        let _guard = lock.lock().unwrap();
+        let thd = {
+            let lock = Arc::clone(&lock);
+            thread::spawn(move || *lock.lock().unwrap() += 1)
+        };
+        thd.join().unwrap();
+    });
+    assert!(trace.contains("step 2: task 1 blocked"), "{}", trace);
+    assert!(trace.contains("\nexecution failed: deadlock!"), "{}", trace);
+    assert!(trace.contains("\nfailure kind: deadlock\n"), "{}", trace);
+}
@@ -0,0 +1,120 @@
+use shuttle::cell::UnsafeCell;
+use shuttle::sync::Mutex;
+use shuttle::{check_dfs, thread};
+use std::panic;
+use std::sync::Arc;
+use test_env_log::test;
+
+struct Shared(UnsafeCell<usize>);
+
+// Safety: the tests only access the cell through `UnsafeCell::with` and `UnsafeCell::with_mut`,
+// which check for races
+unsafe impl Sync for Shared {}
+
+fn increment(shared: &Shared) {
+    shared.0.with_mut(|ptr| unsafe { *ptr += 1 });
+}
+
+fn read(shared: &Shared) -> usize {
+    shared.0.with(|ptr| unsafe { *ptr })
+}
+
+#[test]
+fn unsafe_cell_unsynchronized_writes_race() {
+    let result = panic::catch_unwind(|| {
+        check_dfs(
+            || {
+                let shared = Arc::new(Shared(UnsafeCell::new(0)));
+                let thds = (0..2)
+                    .map(|_| {
+                        let shared = Arc::clone(&shared);
+                        thread::spawn(move || increment(&shared))
+                    })
+                    .collect::<Vec<_>>();
+                for thd in thds {
+                    if let Err(e) = thd.join() {
+                        panic::resume_unwind(e);
+                    }
+                }
+            },
+            None,
+        )
+    })
+    .expect_err("unsynchronized writes should race");
+    let message = result.downcast::<String>().unwrap();
+    assert!(
+        message.contains("data race on UnsafeCell: write by task"),
+        "{}",
+        message
+    );
+    // The report names both of the spawned tasks
+    assert!(message.contains("TaskId(1)"), "{}", message);
+    assert!(message.contains("TaskId(2)"), "{}", message);
+}
+
+#[test]
+fn unsafe_cell_locked_writes_dont_race() {
+    check_dfs(
+        || {
+            let lock = Arc::new(Mutex::new(()));
+            let shared = Arc::new(Shared(UnsafeCell::new(0)));
+            let thds = (0..2)
+                .map(|_| {
+                    let lock = Arc::clone(&lock);
+                    let shared = Arc::clone(&shared);
+                    thread::spawn(move || {
+                        let _guard = lock.lock().unwrap();
+                        increment(&shared);
+                    })
+                })
+                .collect::<Vec<_>>();
+            for thd in thds {
+                thd.join().unwrap();
+            }
+            assert_eq!(read(&shared), 2);
+        },
+        None,
+    );
+}
+
+#[test]
+fn unsafe_cell_concurrent_reads_dont_race() {
+    check_dfs(
+        || {
+            let shared = Arc::new(Shared(UnsafeCell::new(0)));
+            increment(&shared);
+            let thds = (0..2)
+                .map(|_| {
+                    let shared = Arc::clone(&shared);
+                    thread::spawn(move || read(&shared))
+                })
+                .collect::<Vec<_>>();
+            for thd in thds {
+                assert_eq!(thd.join().unwrap(), 1);
+            }
+            // Joining the readers orders their reads before this write
+            increment(&shared);
+        },
+        None,
+    );
+}
+
+#[test]
+#[should_panic(expected = "data race on UnsafeCell")]
+fn unsafe_cell_read_races_with_write() {
+    check_dfs(
+        || {
+            let shared = Arc::new(Shared(UnsafeCell::new(0)));
+            let thd = {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || read(&shared))
+            };
+            increment(&shared);
+            // Propagate the spawned thread's panic, in case it's the one that saw the race
+            if let Err(e) = thd.join() {
+                panic::resume_unwind(e);
+            }
+        },
+        None,
+    );
+}
@@ -0,0 +1,143 @@
+use shuttle::scheduler::{TaskId, WeightedRandomScheduler};
+use shuttle::sync::Mutex;
+use shuttle::{thread, Config, Runner};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use test_env_log::test;
+
+// Spawn two threads that race to record their number, and return which thread went first in each
+// execution
+fn first_runner(scheduler: WeightedRandomScheduler) -> Vec<usize> {
+    let first = Arc::new(std::sync::Mutex::new(Vec::new()));
+    {
+        let first = Arc::clone(&first);
+        let runner = Runner::new(scheduler);
+        runner.run(move || {
+            let order = Arc::new(Mutex::new(Vec::new()));
+            let threads = (1..=2)
+                .map(|i| {
+                    let order = Arc::clone(&order);
+                    thread::spawn(move || order.lock().unwrap().push(i))
+                })
+                .collect::<Vec<_>>();
+            for thd in threads {
+                thd.join().unwrap();
+            }
+            first.lock().unwrap().push(order.lock().unwrap()[0]);
+        });
+    }
+    Arc::try_unwrap(first).unwrap().into_inner().unwrap()
+}
+
+#[test]
+fn weighted_random_prefers_heavy_task() {
+    // Only the first spawned thread is lightly weighted, so the main thread usually spawns the second
+    // thread before the first one gets to run
+    let light = TaskId::from(1);
+    let scheduler = WeightedRandomScheduler::new_from_seed(0, 1000, move |tid| if tid == light { 10 } else { 100 });
+    let first = first_runner(scheduler);
+    assert_eq!(first.len(), 1000);
+
+    // The heavy thread usually goes first, but not always
+    let heavy_first = first.iter().filter(|i| **i == 2).count();
+    assert!(heavy_first > 900, "heavy thread went first {} times", heavy_first);
+    assert!(heavy_first < 1000);
+}
+
+#[test]
+fn weighted_random_zero_weights() {
+    // With every weight zero, the choice is uniform, so both threads sometimes go first
+    let first = first_runner(WeightedRandomScheduler::new_from_seed(0, 100, |_| 0));
+    assert!(first.contains(&1));
+    assert!(first.contains(&2));
+}
+
+// The heavily weighted main thread blocks on the join, so the scheduler must run the other thread
+#[test]
+fn weighted_random_only_chooses_runnable() {
+    let main = TaskId::from(0);
+    let scheduler = WeightedRandomScheduler::new_from_seed(0, 100, move |tid| if tid == main { 1000 } else { 0 });
+    let runner = Runner::new(scheduler);
+    runner.run(|| {
+        let lock = Arc::new(Mutex::new(0));
+        let thd = {
+            let lock = Arc::clone(&lock);
+            thread::spawn(move || *lock.lock().unwrap() += 1)
+        };
+        thd.join().unwrap();
+        assert_eq!(*lock.lock().unwrap(), 1);
+    });
+}
+
+// The lightly weighted main thread holds a lock that a heavy thread is blocked on, while a medium
+// thread does some work. Return how many executions the medium thread finished its work before the
+// heavy thread acquired the lock.
+fn priority_inversions(priority_inheritance: bool) -> usize {
+    const ITERATIONS: usize = 100;
+    const STEPS: usize = 20;
+
+    let (high, medium) = (TaskId::from(1), TaskId::from(2));
+    let scheduler = WeightedRandomScheduler::new_from_seed(0, ITERATIONS, move |tid| {
+        if tid == high {
+            1000
+        } else if tid == medium {
+            100
+        } else {
+            1
+        }
+    });
+    let mut config = Config::new();
+    config.priority_inheritance = priority_inheritance;
+
+    let inversions = Arc::new(AtomicUsize::new(0));
+    {
+        let inversions = Arc::clone(&inversions);
+        let runner = Runner::new(scheduler).with_config(config);
+        runner.run(move || {
+            let lock = Arc::new(Mutex::new(()));
+            let medium_done = Arc::new(AtomicBool::new(false));
+            let guard = lock.lock().unwrap();
+
+            let high = {
+                let lock = Arc::clone(&lock);
+                let medium_done = Arc::clone(&medium_done);
+                let inversions = Arc::clone(&inversions);
+                thread::spawn(move || {
+                    let _guard = lock.lock().unwrap();
+                    if medium_done.load(Ordering::SeqCst) {
+                        inversions.fetch_add(1, Ordering::SeqCst);
+                    }
+                })
+            };
+            let medium = {
+                let medium_done = Arc::clone(&medium_done);
+                thread::spawn(move || {
+                    for _ in 0..STEPS {
+                        thread::yield_now();
+                    }
+                    medium_done.store(true, Ordering::SeqCst);
+                })
+            };
+
+            for _ in 0..STEPS {
+                thread::yield_now();
+            }
+            drop(guard);
+            high.join().unwrap();
+            medium.join().unwrap();
+        });
+    }
+    inversions.load(Ordering::SeqCst)
+}
+
+#[test]
+fn weighted_random_priority_inversion() {
+    let inversions = priority_inversions(false);
+    assert!(inversions > 90, "priority inversion happened {} times", inversions);
+}
+
+#[test]
+fn weighted_random_priority_inheritance() {
+    let inversions = priority_inversions(true);
+    assert!(inversions < 10, "priority inversion happened {} times", inversions);
+}
@@ -53,8 +53,11 @@ fn thread_rng_decorrelated() {
         })
         .collect::<Vec<_>>();
 
+    // Propagate a panic from either thread as is, so that tests can check its message
     for thd in thds {
-        thd.join().unwrap();
+        if let Err(payload) = thd.join() {
+            std::panic::resume_unwind(payload);
+        }
     }
 
     let arr = lock.lock().unwrap();
@@ -161,14 +164,14 @@ fn broken_atomic_counter_stress_roundtrip() {
 #[should_panic(expected = "requested random data from DFS scheduler")]
 fn dfs_thread_rng_decorrelated_disabled() {
     let scheduler = DfsScheduler::new(None, false);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(thread_rng_decorrelated);
 }
 
 #[test]
 fn dfs_threads_decorrelated_enabled() {
     let scheduler = DfsScheduler::new(None, true);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(thread_rng_decorrelated);
 }
 
@@ -179,7 +182,7 @@ fn dfs_does_not_reseed_across_executions() {
     let pair_clone = pair.clone();
 
     let scheduler = DfsScheduler::new(None, true);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(move || {
         thread::spawn(|| {
             for _ in 0..3 {
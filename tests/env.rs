@@ -0,0 +1,115 @@
+#![deny(warnings)]
+
+// These tests set environment variables, which are process-wide state, so they live in their own
+// test binary rather than in `tests/basic` where they could change the behavior of concurrently
+// running tests. They also hold `ENV_LOCK` so they don't interfere with each other.
+
+use regex::Regex;
+use shuttle::sync::Mutex;
+use shuttle::{check_dfs, replay_from_file, thread};
+use std::panic;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+fn concurrent_increment_buggy() {
+    let lock = Arc::new(Mutex::new(0usize));
+
+    let threads = (0..2)
+        .map(|_| {
+            let lock = Arc::clone(&lock);
+            thread::spawn(move || {
+                let curr = *lock.lock().unwrap();
+                *lock.lock().unwrap() = curr + 1;
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for thd in threads {
+        thd.join().unwrap();
+    }
+
+    assert_eq!(*lock.lock().unwrap(), 2, "counter is wrong");
+}
+
+// Returns the panic message and the contents of the file the failing schedule was persisted to
+fn persisted_failure(output: &str) -> (String, String) {
+    let file_regex = Regex::new("persisted to file: (.*)").unwrap();
+    let path = PathBuf::from(file_regex.captures(output).unwrap().get(1).unwrap().as_str());
+    let message = output.lines().next().unwrap().to_string();
+    (message, std::fs::read_to_string(path).unwrap())
+}
+
+#[test]
+fn failure_persistence_dir_from_env() {
+    let _guard = ENV_LOCK.lock().unwrap();
+    let tempdir = tempfile::tempdir().expect("could not create tempdir");
+    std::env::set_var("SHUTTLE_FAILURE_PERSISTENCE_DIR", tempdir.path());
+
+    let result = panic::catch_unwind(|| check_dfs(concurrent_increment_buggy, None)).expect_err("test should panic");
+    let output = result.downcast::<String>().unwrap();
+    let (message, schedule) = persisted_failure(&output);
+    assert!(message.contains("test panicked in task"));
+
+    // Replaying the persisted schedule should fail the same way, and persist the same schedule again
+    let schedule_file = std::fs::read_dir(tempdir.path())
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let result = panic::catch_unwind(|| replay_from_file(concurrent_increment_buggy, schedule_file))
+        .expect_err("replay should panic");
+    let new_output = result.downcast::<String>().unwrap();
+    let (new_message, new_schedule) = persisted_failure(&new_output);
+    assert_eq!(new_message, message);
+    assert_eq!(new_schedule, schedule);
+    assert_eq!(std::fs::read_dir(tempdir.path()).unwrap().count(), 2);
+
+    std::env::remove_var("SHUTTLE_FAILURE_PERSISTENCE_DIR");
+}
+
+#[test]
+fn replay_from_env() {
+    let _guard = ENV_LOCK.lock().unwrap();
+
+    // Count the executions each run takes to fail
+    let run = || {
+        let executions = Arc::new(AtomicUsize::new(0));
+        let result = {
+            let executions = Arc::clone(&executions);
+            panic::catch_unwind(move || {
+                check_dfs(
+                    move || {
+                        executions.fetch_add(1, Ordering::SeqCst);
+                        concurrent_increment_buggy();
+                    },
+                    None,
+                )
+            })
+            .expect_err("test should panic")
+        };
+        (*result.downcast::<String>().unwrap(), executions.load(Ordering::SeqCst))
+    };
+
+    let (output, executions) = run();
+    assert!(executions > 1);
+    let string_regex = Regex::new("failing schedule: \"([0-9a-f]+)\"").unwrap();
+    let schedule = string_regex
+        .captures(&output)
+        .unwrap()
+        .get(1)
+        .unwrap()
+        .as_str()
+        .to_string();
+
+    // With the schedule in the environment, the same test replays only the failing schedule, rather
+    // than starting its search from scratch
+    std::env::set_var("SHUTTLE_REPLAY", &schedule);
+    let (new_output, new_executions) = run();
+    std::env::remove_var("SHUTTLE_REPLAY");
+    assert_eq!(new_executions, 1);
+    assert_eq!(new_output, output);
+}
@@ -1,6 +1,7 @@
 use futures::{try_join, Future};
 use shuttle::sync::Mutex;
-use shuttle::{asynch, check_dfs, check_random, scheduler::PctScheduler, thread, Runner};
+use shuttle::{check_dfs, check_random, future, scheduler::PctScheduler, thread, Runner};
+use std::collections::HashSet;
 use std::pin::Pin;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
@@ -16,7 +17,7 @@ fn async_fncall() {
     check_dfs(
         move || {
             let sum = add(3, 5);
-            asynch::spawn(async move {
+            future::spawn(async move {
                 let r = sum.await;
                 assert_eq!(r, 8u32);
             });
@@ -30,9 +31,9 @@ fn async_with_join() {
     check_dfs(
         move || {
             thread::spawn(|| {
-                let join = asynch::spawn(async move { add(10, 32).await });
+                let join = future::spawn(async move { add(10, 32).await });
 
-                asynch::spawn(async move {
+                future::spawn(async move {
                     assert_eq!(join.await.unwrap(), 42u32);
                 });
             });
@@ -48,14 +49,14 @@ fn async_with_threads() {
             thread::spawn(|| {
                 let v1 = async { 3u32 };
                 let v2 = async { 2u32 };
-                asynch::spawn(async move {
+                future::spawn(async move {
                     assert_eq!(5u32, v1.await + v2.await);
                 });
             });
             thread::spawn(|| {
                 let v1 = async { 5u32 };
                 let v2 = async { 6u32 };
-                asynch::spawn(async move {
+                future::spawn(async move {
                     assert_eq!(11u32, v1.await + v2.await);
                 });
             });
@@ -68,7 +69,7 @@ fn async_with_threads() {
 fn async_block_on() {
     check_dfs(
         || {
-            let v = asynch::block_on(async { 42u32 });
+            let v = future::block_on(async { 42u32 });
             assert_eq!(v, 42u32);
         },
         None,
@@ -79,8 +80,8 @@ fn async_block_on() {
 fn async_spawn() {
     check_dfs(
         || {
-            let t = asynch::spawn(async { 42u32 });
-            let v = asynch::block_on(async { t.await.unwrap() });
+            let t = future::spawn(async { 42u32 });
+            let v = future::block_on(async { t.await.unwrap() });
             assert_eq!(v, 42u32);
         },
         None,
@@ -91,9 +92,9 @@ fn async_spawn() {
 fn async_spawn_chain() {
     check_dfs(
         || {
-            let t1 = asynch::spawn(async { 1u32 });
-            let t2 = asynch::spawn(async move { t1.await.unwrap() });
-            let v = asynch::block_on(async move { t2.await.unwrap() });
+            let t1 = future::spawn(async { 1u32 });
+            let t2 = future::spawn(async move { t1.await.unwrap() });
+            let v = future::block_on(async move { t2.await.unwrap() });
             assert_eq!(v, 1u32);
         },
         None,
@@ -105,10 +106,10 @@ fn async_thread_yield() {
     // This tests if thread::yield_now can be called from within an async block
     check_dfs(
         || {
-            asynch::spawn(async move {
+            future::spawn(async move {
                 thread::yield_now();
             });
-            asynch::spawn(async move {});
+            future::spawn(async move {});
         },
         None,
     )
@@ -124,12 +125,12 @@ fn async_atomic() {
         || {
             let r = Arc::new(AtomicUsize::new(0));
             let r1 = r.clone();
-            asynch::spawn(async move {
+            future::spawn(async move {
                 r1.store(1, Ordering::SeqCst);
                 thread::yield_now();
                 r1.store(0, Ordering::SeqCst);
             });
-            asynch::spawn(async move {
+            future::spawn(async move {
                 assert_eq!(r.load(Ordering::SeqCst), 0, "DFS should find a schedule where r=1 here");
             });
         },
@@ -148,13 +149,13 @@ fn async_mutex() {
 
             let t1 = {
                 let lock = Arc::clone(&lock);
-                asynch::spawn(async move {
+                future::spawn(async move {
                     let mut l = lock.lock().unwrap();
                     *l += 1;
                 })
             };
 
-            let t2 = asynch::block_on(async move {
+            let t2 = future::block_on(async move {
                 t1.await.unwrap();
                 *lock.lock().unwrap()
             });
@@ -169,8 +170,8 @@ fn async_mutex() {
 fn async_yield() {
     check_dfs(
         || {
-            let v = asynch::block_on(async {
-                asynch::yield_now().await;
+            let v = future::block_on(async {
+                future::yield_now().await;
                 42u32
             });
             assert_eq!(v, 42u32);
@@ -179,21 +180,115 @@ fn async_yield() {
     )
 }
 
+// Two spawned tasks each log twice with an await point in between, so DFS should see their steps
+// interleave in every possible order
+#[test]
+fn async_spawn_interleaving() {
+    let interleavings = Arc::new(std::sync::Mutex::new(HashSet::new()));
+    {
+        let interleavings = Arc::clone(&interleavings);
+        check_dfs(
+            move || {
+                let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+                let tasks = ["a", "b"]
+                    .iter()
+                    .map(|name| {
+                        let log = Arc::clone(&log);
+                        future::spawn(async move {
+                            log.lock().unwrap().push(format!("{}1", name));
+                            future::yield_now().await;
+                            log.lock().unwrap().push(format!("{}2", name));
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                future::block_on(async move {
+                    for t in tasks {
+                        t.await.unwrap();
+                    }
+                });
+                interleavings.lock().unwrap().insert(log.lock().unwrap().join(","));
+            },
+            None,
+        );
+    }
+    let interleavings = interleavings.lock().unwrap();
+    // Each task's steps stay in order, so there are (4 choose 2) = 6 possible interleavings
+    assert_eq!(interleavings.len(), 6, "interleavings: {:?}", interleavings);
+    assert!(interleavings.contains("a1,b1,a2,b2"));
+    assert!(interleavings.contains("b1,b2,a1,a2"));
+}
+
+// Two tasks do a non-atomic increment of a shared atomic, with an await point between the load and
+// the store, so DFS should find the schedule where one increment is lost
+#[test]
+#[should_panic(expected = "lost an increment")]
+fn async_spawn_shuttle_atomic_race() {
+    check_dfs(
+        || {
+            let counter = Arc::new(shuttle::sync::atomic::AtomicUsize::new(0));
+            let tasks = (0..2)
+                .map(|_| {
+                    let counter = Arc::clone(&counter);
+                    future::spawn(async move {
+                        let c = counter.load(Ordering::SeqCst);
+                        future::yield_now().await;
+                        counter.store(c + 1, Ordering::SeqCst);
+                    })
+                })
+                .collect::<Vec<_>>();
+            future::block_on(async move {
+                for t in tasks {
+                    t.await.unwrap();
+                }
+            });
+            assert_eq!(counter.load(Ordering::SeqCst), 2, "lost an increment");
+        },
+        None,
+    );
+}
+
+// Dropping a `JoinHandle` detaches the task, so the execution can end before the task finishes
+#[test]
+fn async_spawn_detached() {
+    let outcomes = Arc::new(std::sync::Mutex::new(HashSet::new()));
+    {
+        let outcomes = Arc::clone(&outcomes);
+        check_dfs(
+            move || {
+                let done = Arc::new(shuttle::sync::atomic::AtomicBool::new(false));
+                {
+                    let done = Arc::clone(&done);
+                    future::spawn(async move {
+                        future::yield_now().await;
+                        done.store(true, Ordering::SeqCst);
+                    });
+                }
+                thread::yield_now();
+                outcomes.lock().unwrap().insert(done.load(Ordering::SeqCst));
+            },
+            None,
+        );
+    }
+    let outcomes = outcomes.lock().unwrap();
+    assert!(outcomes.contains(&true));
+    assert!(outcomes.contains(&false));
+}
+
 fn async_counter() {
     let counter = Arc::new(AtomicUsize::new(0));
 
     let tasks: Vec<_> = (0..10)
         .map(|_| {
             let counter = Arc::clone(&counter);
-            asynch::spawn(async move {
+            future::spawn(async move {
                 let c = counter.load(Ordering::SeqCst);
-                asynch::yield_now().await;
+                future::yield_now().await;
                 counter.fetch_add(c, Ordering::SeqCst);
             })
         })
         .collect();
 
-    asynch::block_on(async move {
+    future::block_on(async move {
         for t in tasks {
             t.await.unwrap();
         }
@@ -208,7 +303,7 @@ fn async_counter_random() {
 #[test]
 fn async_counter_pct() {
     let scheduler = PctScheduler::new(2, 5000);
-    let runner = Runner::new(scheduler, Default::default());
+    let runner = Runner::new(scheduler);
     runner.run(async_counter);
 }
 
@@ -227,7 +322,7 @@ fn test_try_join() {
         || {
             let f2 = do_err(true);
             let f1 = do_err(false);
-            let res = asynch::block_on(async { try_join!(f1, f2) });
+            let res = future::block_on(async { try_join!(f1, f2) });
             assert!(res.is_err());
         },
         None,
@@ -246,7 +341,7 @@ fn drop_shuttle_future() {
         move || {
             orderings.fetch_add(1, Ordering::SeqCst);
             let async_accesses = async_accesses.clone();
-            asynch::spawn(async move {
+            future::spawn(async move {
                 async_accesses.fetch_add(1, Ordering::SeqCst);
             });
         },
@@ -272,9 +367,9 @@ fn drop_shuttle_yield_future() {
             orderings.fetch_add(1, Ordering::SeqCst);
             let async_accesses = async_accesses.clone();
             let post_yield_accesses = post_yield_accesses.clone();
-            asynch::spawn(async move {
+            future::spawn(async move {
                 async_accesses.fetch_add(1, Ordering::SeqCst);
-                asynch::yield_now().await;
+                future::yield_now().await;
                 post_yield_accesses.fetch_add(1, Ordering::SeqCst);
             });
         },
@@ -297,8 +392,8 @@ fn drop_shuttle_yield_future() {
 fn wake_self_on_join_handle() {
     check_dfs(
         || {
-            let yielder = asynch::spawn(async move {
-                asynch::yield_now().await;
+            let yielder = future::spawn(async move {
+                future::yield_now().await;
             });
 
             struct Timeout<F: Future> {
@@ -329,7 +424,7 @@ fn wake_self_on_join_handle() {
                 }
             }
 
-            let wait_on_yield = asynch::spawn(async move {
+            let wait_on_yield = future::spawn(async move {
                 Timeout {
                     inner: Box::pin(yielder),
                     counter: 2,
@@ -4,5 +4,7 @@
 mod basic;
 mod channel;
 mod countdown_timer;
+mod mutex;
 mod pct;
+mod select;
 mod waker;
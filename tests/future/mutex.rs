@@ -0,0 +1,135 @@
+use futures::future::join_all;
+use shuttle::future::{self, Mutex};
+use shuttle::{check_dfs, check_no_deadlock, check_random, thread};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use test_env_log::test;
+
+// Each task holds the lock across a yield point, so without mutual exclusion the other task could
+// observe the lock held
+#[test]
+fn async_mutex_mutual_exclusion() {
+    check_dfs(
+        || {
+            let lock = Arc::new(Mutex::new(false));
+            let tasks = (0..2)
+                .map(|_| {
+                    let lock = Arc::clone(&lock);
+                    future::spawn(async move {
+                        let mut held = lock.lock().await;
+                        assert!(!*held, "two tasks held the async mutex at once");
+                        *held = true;
+                        future::yield_now().await;
+                        *held = false;
+                    })
+                })
+                .collect::<Vec<_>>();
+            future::block_on(join_all(tasks));
+        },
+        None,
+    );
+}
+
+// DFS explores both orders in which contending tasks can acquire the lock
+#[test]
+fn async_mutex_explores_both_orders() {
+    let orders = Arc::new(std::sync::Mutex::new(HashSet::new()));
+    {
+        let orders = Arc::clone(&orders);
+        check_dfs(
+            move || {
+                let lock = Arc::new(Mutex::new(Vec::new()));
+                let tasks = (0..2)
+                    .map(|i| {
+                        let lock = Arc::clone(&lock);
+                        future::spawn(async move { lock.lock().await.push(i) })
+                    })
+                    .collect::<Vec<_>>();
+                future::block_on(join_all(tasks));
+                let order = future::block_on(lock.lock()).clone();
+                orders.lock().unwrap().insert(order);
+            },
+            None,
+        );
+    }
+    let orders = orders.lock().unwrap();
+    assert!(orders.contains(&vec![0, 1]));
+    assert!(orders.contains(&vec![1, 0]));
+}
+
+#[test]
+#[should_panic(expected = "lost an increment")]
+fn async_mutex_lost_update() {
+    check_random(
+        || {
+            let lock = Arc::new(Mutex::new(0usize));
+            let tasks = (0..2)
+                .map(|_| {
+                    let lock = Arc::clone(&lock);
+                    future::spawn(async move {
+                        let curr = *lock.lock().await;
+                        *lock.lock().await = curr + 1;
+                    })
+                })
+                .collect::<Vec<_>>();
+            future::block_on(join_all(tasks));
+            assert_eq!(*future::block_on(lock.lock()), 2, "lost an increment");
+        },
+        1000,
+    );
+}
+
+#[test]
+fn async_mutex_try_lock() {
+    check_dfs(
+        || {
+            let lock = Mutex::new(0usize);
+            future::block_on(async {
+                let guard = lock.lock().await;
+                assert!(lock.try_lock().is_none());
+                drop(guard);
+                *lock.try_lock().unwrap() += 1;
+            });
+            assert_eq!(lock.into_inner(), 1);
+        },
+        None,
+    );
+}
+
+// Two detached tasks take the same pair of locks in opposite orders. Once the main thread finishes,
+// nothing waits for them, so their deadlock is only reported by `check_no_deadlock`.
+fn detached_lock_inversion() {
+    let first = Arc::new(Mutex::new(0usize));
+    let second = Arc::new(Mutex::new(0usize));
+    let started = Arc::new(AtomicUsize::new(0));
+    for (outer, inner) in [
+        (Arc::clone(&first), Arc::clone(&second)),
+        (Arc::clone(&second), Arc::clone(&first)),
+    ] {
+        let started = Arc::clone(&started);
+        drop(future::spawn(async move {
+            let _outer = outer.lock().await;
+            started.fetch_add(1, Ordering::SeqCst);
+            let _inner = inner.lock().await;
+        }));
+    }
+    // Give the tasks a chance to block on their second lock before the main thread finishes
+    while started.load(Ordering::SeqCst) < 2 {
+        thread::yield_now();
+    }
+    for _ in 0..5 {
+        thread::yield_now();
+    }
+}
+
+#[test]
+fn async_mutex_detached_deadlock_ignored_by_default() {
+    check_random(detached_lock_inversion, 100);
+}
+
+#[test]
+#[should_panic(expected = "lock cycle: <unknown> (task 1) holds L0 waiting L1; <unknown> (task 2) holds L1 waiting L0")]
+fn async_mutex_detached_deadlock_reported() {
+    check_no_deadlock(detached_lock_inversion, 100);
+}
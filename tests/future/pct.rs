@@ -5,9 +5,9 @@ use std::task::{Context, Poll};
 
 use shuttle::scheduler::PctScheduler;
 use shuttle::sync::Arc;
-use shuttle::{asynch, Config, MaxSteps, Runner};
+use shuttle::{future, Config, MaxSteps, Runner};
 
-/// Like [`shuttle::asynch::yield_now`] but doesn't request a yield from the scheduler
+/// Like [`shuttle::future::yield_now`] but doesn't request a yield from the scheduler
 struct UnfairYieldNow {
     yielded: bool,
 }
@@ -35,23 +35,23 @@ fn yield_spin_loop(use_yield: bool) {
     let scheduler = PctScheduler::new(1, 100);
     let mut config = Config::new();
     config.max_steps = MaxSteps::FailAfter(50);
-    let runner = Runner::new(scheduler, config);
+    let runner = Runner::new(scheduler).with_config(config);
     runner.run(move || {
         let count = Arc::new(AtomicUsize::new(0usize));
 
         let _thds = (0..NUM_TASKS)
             .map(|_| {
                 let count = count.clone();
-                asynch::spawn(async move {
+                future::spawn(async move {
                     count.fetch_add(1, Ordering::SeqCst);
                 })
             })
             .collect::<Vec<_>>();
 
-        asynch::block_on(async move {
+        future::block_on(async move {
             while count.load(Ordering::SeqCst) < NUM_TASKS {
                 if use_yield {
-                    asynch::yield_now().await;
+                    future::yield_now().await;
                 } else {
                     let yielder = UnfairYieldNow { yielded: false };
                     yielder.await;
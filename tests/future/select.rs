@@ -0,0 +1,93 @@
+use futures::future::{pending, ready};
+use shuttle::future::{self, select};
+use shuttle::sync::atomic::{AtomicBool, Ordering};
+use shuttle::{check_dfs, thread};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use test_env_log::test;
+
+// Both branches are always ready, so DFS should explore each of them being chosen
+#[test]
+fn select_explores_both_ready_branches() {
+    let winners = Arc::new(Mutex::new(HashSet::new()));
+    {
+        let winners = Arc::clone(&winners);
+        check_dfs(
+            move || {
+                let winner = future::block_on(async {
+                    select! {
+                        a = ready(1) => a,
+                        b = async { 2 } => b,
+                    }
+                });
+                winners.lock().unwrap().insert(winner);
+            },
+            None,
+        );
+    }
+    assert_eq!(*winners.lock().unwrap(), [1, 2].iter().copied().collect());
+}
+
+#[test]
+fn select_skips_pending_branch() {
+    check_dfs(
+        || {
+            let value = future::block_on(async {
+                select! {
+                    _ = pending::<()>() => unreachable!("pending branch can't be chosen"),
+                    (a, b) = ready((1, 2)) => a + b,
+                    _ = pending::<()>() => unreachable!("pending branch can't be chosen"),
+                }
+            });
+            assert_eq!(value, 3);
+        },
+        None,
+    );
+}
+
+// The branch futures become ready only after another task runs, so the select has to wait for them
+#[test]
+fn select_waits_for_spawned_task() {
+    check_dfs(
+        || {
+            let flag = Arc::new(AtomicBool::new(false));
+            let task = {
+                let flag = Arc::clone(&flag);
+                future::spawn(async move {
+                    flag.store(true, Ordering::SeqCst);
+                    7
+                })
+            };
+            let value = future::block_on(async move {
+                select! {
+                    v = task => v.unwrap(),
+                    _ = pending::<()>() => unreachable!("pending branch can't be chosen"),
+                }
+            });
+            assert_eq!(value, 7);
+            assert!(flag.load(Ordering::SeqCst));
+        },
+        None,
+    );
+}
+
+#[test]
+fn select_in_spawned_task() {
+    check_dfs(
+        || {
+            let task = future::spawn(async {
+                let mut hits = 0;
+                for _ in 0..2 {
+                    select! {
+                        _ = future::yield_now() => hits += 1,
+                        _ = ready(()) => hits += 1,
+                    }
+                }
+                hits
+            });
+            thread::yield_now();
+            assert_eq!(future::block_on(task).unwrap(), 2);
+        },
+        None,
+    );
+}
@@ -0,0 +1,163 @@
+//! Loom-style tests that run unchanged under Shuttle with the `loom-compat` feature, apart from the
+//! `loom` import.
+
+use shuttle::loom;
+use shuttle::loom::cell::UnsafeCell;
+use shuttle::loom::sync::atomic::{AtomicUsize, Ordering};
+use shuttle::loom::sync::{Arc, Mutex};
+use shuttle::loom::thread;
+use test_env_log::test;
+
+#[test]
+fn loom_model_concurrent_increments() {
+    loom::model(|| {
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let ths: Vec<_> = (0..2)
+            .map(|_| {
+                let counter = counter.clone();
+                thread::spawn(move || {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+
+        for th in ths {
+            th.join().unwrap();
+        }
+
+        assert_eq!(2, counter.load(Ordering::SeqCst));
+    });
+}
+
+#[test]
+#[should_panic(expected = "lost an increment")]
+fn loom_model_finds_lost_update() {
+    loom::model(|| {
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let ths: Vec<_> = (0..2)
+            .map(|_| {
+                let counter = counter.clone();
+                thread::spawn(move || {
+                    let curr = counter.load(Ordering::SeqCst);
+                    counter.store(curr + 1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+
+        for th in ths {
+            th.join().unwrap();
+        }
+
+        assert_eq!(2, counter.load(Ordering::SeqCst), "lost an increment");
+    });
+}
+
+#[test]
+fn loom_builder_preemption_bound() {
+    let mut builder = loom::model::Builder::new();
+    builder.preemption_bound = Some(1);
+    builder.check(|| {
+        let lock = Arc::new(Mutex::new(0));
+        let th = {
+            let lock = lock.clone();
+            thread::spawn(move || *lock.lock().unwrap() += 1)
+        };
+        *lock.lock().unwrap() += 1;
+        th.join().unwrap();
+        assert_eq!(*lock.lock().unwrap(), 2);
+    });
+}
+
+#[test]
+fn loom_builder_max_permutations() {
+    let executions = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let mut builder = loom::model::Builder::new();
+    builder.max_permutations = Some(3);
+    {
+        let executions = executions.clone();
+        builder.check(move || {
+            executions.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let th = thread::spawn(thread::yield_now);
+            thread::yield_now();
+            th.join().unwrap();
+        });
+    }
+    assert_eq!(executions.load(std::sync::atomic::Ordering::SeqCst), 3);
+}
+
+struct Shared(UnsafeCell<usize>);
+
+unsafe impl Sync for Shared {}
+
+// Accesses ordered by a lock don't race
+#[test]
+fn loom_unsafe_cell_synchronized() {
+    loom::model(|| {
+        let shared = Arc::new((Mutex::new(()), Shared(UnsafeCell::new(0))));
+        let th = {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                let _guard = shared.0.lock().unwrap();
+                shared.1 .0.with_mut(|ptr| unsafe { *ptr += 1 });
+            })
+        };
+        {
+            let _guard = shared.0.lock().unwrap();
+            shared.1 .0.with_mut(|ptr| unsafe { *ptr += 1 });
+        }
+        th.join().unwrap();
+        assert_eq!(shared.1 .0.with(|ptr| unsafe { *ptr }), 2);
+    });
+}
+
+#[test]
+#[should_panic(expected = "data race")]
+fn loom_unsafe_cell_race() {
+    loom::model(|| {
+        let shared = Arc::new(Shared(UnsafeCell::new(0)));
+        let th = {
+            let shared = shared.clone();
+            thread::spawn(move || shared.0.with_mut(|ptr| unsafe { *ptr += 1 }))
+        };
+        shared.0.with(|ptr| unsafe { *ptr });
+        // Propagate the spawned thread's panic, in case it's the one that saw the race
+        if let Err(e) = th.join() {
+            std::panic::resume_unwind(e);
+        }
+    });
+}
+
+// An atomic flag with release/acquire ordering orders the write before the read
+#[test]
+fn loom_unsafe_cell_atomic_flag() {
+    loom::model(|| {
+        let shared = Arc::new((AtomicUsize::new(0), Shared(UnsafeCell::new(0))));
+        let th = {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                shared.1 .0.with_mut(|ptr| unsafe { *ptr = 42 });
+                shared.0.store(1, Ordering::Release);
+            })
+        };
+        if shared.0.load(Ordering::Acquire) == 1 {
+            assert_eq!(shared.1 .0.with(|ptr| unsafe { *ptr }), 42);
+        }
+        th.join().unwrap();
+    });
+}
+
+#[test]
+#[should_panic(expected = "compare_exchange_weak failed spuriously")]
+fn loom_model_explores_spurious_compare_exchange_weak() {
+    loom::model(|| {
+        // Nothing else touches `value`, so the exchange can only fail spuriously, which happens if
+        // the other thread runs in the middle of it
+        let value = AtomicUsize::new(0);
+        let th = thread::spawn(|| ());
+        let result = value.compare_exchange_weak(0, 1, Ordering::SeqCst, Ordering::SeqCst);
+        assert!(result.is_ok(), "compare_exchange_weak failed spuriously");
+        th.join().unwrap();
+    });
+}
@@ -1,9 +1,10 @@
 #![deny(warnings)]
 
-mod asynch;
 mod basic;
 mod data;
 mod demo;
+mod future;
+mod tokio;
 
 use shuttle::scheduler::{ReplayScheduler, Scheduler};
 use shuttle::{replay_from_file, Config, FailurePersistence, Runner};
@@ -25,7 +26,7 @@ where
         panic::catch_unwind(move || {
             let mut config = Config::new();
             config.failure_persistence = FailurePersistence::Print;
-            let runner = Runner::new(scheduler, config);
+            let runner = Runner::new(scheduler).with_config(config);
             runner.run(move || test_func())
         })
         .expect_err("test should panic")
@@ -40,7 +41,7 @@ where
             let mut config = Config::new();
             config.failure_persistence = FailurePersistence::Print;
             let scheduler = ReplayScheduler::new_from_encoded(&schedule);
-            let runner = Runner::new(scheduler, config);
+            let runner = Runner::new(scheduler).with_config(config);
             runner.run(move || test_func());
         })
         .expect_err("replay should panic")
@@ -70,7 +71,7 @@ where
         panic::catch_unwind(move || {
             let mut config = Config::new();
             config.failure_persistence = FailurePersistence::File(Some(tempdir_path));
-            let runner = Runner::new(scheduler, config);
+            let runner = Runner::new(scheduler).with_config(config);
             runner.run(move || test_func())
         })
         .expect_err("test should panic")
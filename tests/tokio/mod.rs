@@ -0,0 +1,2 @@
+mod notify;
+mod semaphore;
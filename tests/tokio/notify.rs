@@ -0,0 +1,190 @@
+use futures::FutureExt;
+use shuttle::sync::Barrier;
+use shuttle::tokio::sync::Notify;
+use shuttle::{check_dfs, future};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use test_env_log::test;
+
+#[test]
+fn notify_one_stores_permit() {
+    check_dfs(
+        || {
+            let notify = Notify::new();
+            // Nobody is waiting yet, so the notification is stored for the next waiter
+            notify.notify_one();
+            future::block_on(notify.notified());
+        },
+        None,
+    );
+}
+
+#[test]
+fn notify_one_stores_at_most_one_permit() {
+    check_dfs(
+        || {
+            let notify = Notify::new();
+            notify.notify_one();
+            notify.notify_one();
+            assert!(notify.notified().now_or_never().is_some());
+            assert!(notify.notified().now_or_never().is_none());
+        },
+        None,
+    );
+}
+
+#[test]
+fn notify_waiters_stores_no_permit() {
+    check_dfs(
+        || {
+            let notify = Notify::new();
+            notify.notify_waiters();
+            assert!(notify.notified().now_or_never().is_none());
+        },
+        None,
+    );
+}
+
+#[test]
+fn notify_waiters_wakes_unpolled_futures() {
+    check_dfs(
+        || {
+            let notify = Notify::new();
+            // Creating the future registers it for `notify_waiters`, even before it's polled
+            let notified = notify.notified();
+            notify.notify_waiters();
+            assert!(notified.now_or_never().is_some());
+        },
+        None,
+    );
+}
+
+// The waiting task might not have started waiting when notify_one is called, but the stored permit
+// means it never misses the notification
+#[test]
+fn notify_one_never_missed() {
+    check_dfs(
+        || {
+            let notify = Arc::new(Notify::new());
+            let waiter = {
+                let notify = Arc::clone(&notify);
+                future::spawn(async move { notify.notified().await })
+            };
+            notify.notify_one();
+            future::block_on(waiter).unwrap();
+        },
+        None,
+    );
+}
+
+// notify_waiters stores no permit, so if it runs before the task starts waiting, the notification is
+// missed and the task waits forever
+#[test]
+#[should_panic(expected = "deadlock")]
+fn notify_waiters_missed_notification() {
+    check_dfs(
+        || {
+            let notify = Arc::new(Notify::new());
+            let waiter = {
+                let notify = Arc::clone(&notify);
+                future::spawn(async move { notify.notified().await })
+            };
+            notify.notify_waiters();
+            future::block_on(waiter).unwrap();
+        },
+        None,
+    );
+}
+
+// Every task waiting when notify_waiters is called is woken
+#[test]
+fn notify_waiters_wakes_all() {
+    check_dfs(
+        || {
+            let notify = Arc::new(Notify::new());
+            let barrier = Arc::new(Barrier::new(3));
+            let woken = Arc::new(AtomicUsize::new(0));
+            let waiters = (0..2)
+                .map(|_| {
+                    let notify = Arc::clone(&notify);
+                    let barrier = Arc::clone(&barrier);
+                    let woken = Arc::clone(&woken);
+                    future::spawn(async move {
+                        // Start waiting before the main thread notifies
+                        let mut notified = Box::pin(notify.notified());
+                        assert!(notified.as_mut().now_or_never().is_none());
+                        barrier.wait();
+                        notified.await;
+                        woken.fetch_add(1, Ordering::SeqCst);
+                    })
+                })
+                .collect::<Vec<_>>();
+            barrier.wait();
+            notify.notify_waiters();
+            future::block_on(async move {
+                for waiter in waiters {
+                    waiter.await.unwrap();
+                }
+            });
+            assert_eq!(woken.load(Ordering::SeqCst), 2);
+        },
+        None,
+    );
+}
+
+// Each notify_one wakes a single waiting task, so two waiting tasks need two notifications. If the
+// tasks weren't both waiting already, the notifications could coalesce into one stored permit.
+#[test]
+fn notify_one_wakes_one_waiter() {
+    check_dfs(
+        || {
+            let notify = Arc::new(Notify::new());
+            let barrier = Arc::new(Barrier::new(3));
+            let woken = Arc::new(AtomicUsize::new(0));
+            let waiters = (0..2)
+                .map(|_| {
+                    let notify = Arc::clone(&notify);
+                    let barrier = Arc::clone(&barrier);
+                    let woken = Arc::clone(&woken);
+                    future::spawn(async move {
+                        let mut notified = Box::pin(notify.notified());
+                        assert!(notified.as_mut().now_or_never().is_none());
+                        barrier.wait();
+                        notified.await;
+                        woken.fetch_add(1, Ordering::SeqCst);
+                    })
+                })
+                .collect::<Vec<_>>();
+            barrier.wait();
+            notify.notify_one();
+            assert!(woken.load(Ordering::SeqCst) <= 1);
+            notify.notify_one();
+            future::block_on(async move {
+                for waiter in waiters {
+                    waiter.await.unwrap();
+                }
+            });
+            assert_eq!(woken.load(Ordering::SeqCst), 2);
+        },
+        None,
+    );
+}
+
+// A future that's chosen by notify_one but dropped before it sees the notification passes the
+// notification on, so it isn't lost
+#[test]
+fn notify_one_forwarded_on_drop() {
+    check_dfs(
+        || {
+            let notify = Notify::new();
+            let mut first = Box::pin(notify.notified());
+            let mut second = Box::pin(notify.notified());
+            assert!(first.as_mut().now_or_never().is_none());
+            assert!(second.as_mut().now_or_never().is_none());
+            notify.notify_one();
+            drop(first);
+            assert!(second.now_or_never().is_some());
+        },
+        None,
+    );
+}
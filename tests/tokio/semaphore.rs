@@ -0,0 +1,149 @@
+use futures::FutureExt;
+use shuttle::tokio::sync::{Semaphore, TryAcquireError};
+use shuttle::{check_dfs, check_random, future};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use test_env_log::test;
+
+#[test]
+fn tokio_semaphore_bounds_holders() {
+    check_random(
+        || {
+            let semaphore = Arc::new(Semaphore::new(2));
+            let holders = Arc::new(AtomicUsize::new(0));
+
+            let tasks = (0..4)
+                .map(|_| {
+                    let semaphore = Arc::clone(&semaphore);
+                    let holders = Arc::clone(&holders);
+                    future::spawn(async move {
+                        let _permit = semaphore.acquire().await.unwrap();
+                        let now_holding = holders.fetch_add(1, Ordering::SeqCst) + 1;
+                        assert!(now_holding <= 2, "{} tasks hold a permit", now_holding);
+                        future::yield_now().await;
+                        holders.fetch_sub(1, Ordering::SeqCst);
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            future::block_on(async move {
+                for task in tasks {
+                    task.await.unwrap();
+                }
+            });
+            assert_eq!(semaphore.available_permits(), 2);
+        },
+        1000,
+    );
+}
+
+#[test]
+fn tokio_semaphore_try_acquire() {
+    check_dfs(
+        || {
+            let semaphore = Semaphore::new(2);
+            let permit = semaphore.try_acquire_many(2).unwrap();
+            assert_eq!(permit.num_permits(), 2);
+            assert_eq!(semaphore.try_acquire().unwrap_err(), TryAcquireError::NoPermits);
+            drop(permit);
+            assert!(semaphore.try_acquire().is_ok());
+            semaphore.close();
+            assert_eq!(semaphore.try_acquire().unwrap_err(), TryAcquireError::Closed);
+        },
+        None,
+    );
+}
+
+// Permits are handed out in the order they're requested, so a waiter that wants more permits than
+// are available holds up later requests for fewer permits
+#[test]
+fn tokio_semaphore_fair() {
+    check_dfs(
+        || {
+            let semaphore = Semaphore::new(1);
+            let mut big = Box::pin(semaphore.acquire_many(2));
+            assert!(big.as_mut().now_or_never().is_none());
+            // The waiter at the front of the queue already claimed the only permit
+            assert_eq!(semaphore.available_permits(), 0);
+            assert_eq!(semaphore.try_acquire().unwrap_err(), TryAcquireError::NoPermits);
+
+            semaphore.add_permits(2);
+            let big = big.now_or_never().unwrap().unwrap();
+            assert_eq!(big.num_permits(), 2);
+            assert_eq!(semaphore.available_permits(), 1);
+        },
+        None,
+    );
+}
+
+// Dropping a waiter gives back the permits it had already claimed
+#[test]
+fn tokio_semaphore_cancel_waiter() {
+    check_dfs(
+        || {
+            let semaphore = Semaphore::new(1);
+            let mut big = Box::pin(semaphore.acquire_many(2));
+            assert!(big.as_mut().now_or_never().is_none());
+            drop(big);
+            assert_eq!(semaphore.available_permits(), 1);
+            assert!(semaphore.try_acquire().is_ok());
+        },
+        None,
+    );
+}
+
+#[test]
+fn tokio_semaphore_close_wakes_waiters() {
+    check_dfs(
+        || {
+            let semaphore = Arc::new(Semaphore::new(0));
+            let waiter = {
+                let semaphore = Arc::clone(&semaphore);
+                future::spawn(async move { semaphore.acquire().await.map(|_| ()) })
+            };
+            semaphore.close();
+            assert!(semaphore.is_closed());
+            assert!(future::block_on(waiter).unwrap().is_err());
+        },
+        None,
+    );
+}
+
+#[test]
+fn tokio_semaphore_forget() {
+    check_dfs(
+        || {
+            let semaphore = Semaphore::new(2);
+            let permit = future::block_on(semaphore.acquire()).unwrap();
+            permit.forget();
+            assert_eq!(semaphore.available_permits(), 1);
+        },
+        None,
+    );
+}
+
+// A task that waits for a permit is woken when another task releases one
+#[test]
+fn tokio_semaphore_handoff() {
+    check_dfs(
+        || {
+            let semaphore = Arc::new(Semaphore::new(1));
+            let tasks = (0..2)
+                .map(|_| {
+                    let semaphore = Arc::clone(&semaphore);
+                    future::spawn(async move {
+                        let _permit = semaphore.acquire().await.unwrap();
+                        future::yield_now().await;
+                    })
+                })
+                .collect::<Vec<_>>();
+            future::block_on(async move {
+                for task in tasks {
+                    task.await.unwrap();
+                }
+            });
+            assert_eq!(semaphore.available_permits(), 1);
+        },
+        None,
+    );
+}